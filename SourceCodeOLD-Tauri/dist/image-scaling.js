@@ -15,7 +15,12 @@ class ImageScaler {
 
     // Set the scaling mode for future elements
     setScalingMode(mode) {
-        const validModes = ['contain', 'fill', 'cover'];
+        // 'stretch' is an alias for 'fill'
+        if (mode === 'stretch') {
+            mode = 'fill';
+        }
+        
+        const validModes = ['contain', 'fill', 'cover', 'none'];
         if (!validModes.includes(mode)) {
             mode = 'contain';
         }
@@ -28,7 +33,7 @@ class ImageScaler {
         const mode = scalingMode || this.currentScalingMode;
         
         // Remove all existing scaling classes
-        element.classList.remove('scaling-contain', 'scaling-fill', 'scaling-cover');
+        element.classList.remove('scaling-contain', 'scaling-fill', 'scaling-cover', 'scaling-none');
         
         // Add the appropriate scaling class
         element.classList.add(`scaling-${mode}`);
@@ -62,6 +67,15 @@ class ImageScaler {
                     element.style.maxHeight = 'none';
                     element.style.objectFit = 'cover';
                     break;
+                    
+                case 'none':
+                    // Original pixel size, centered and cropped if larger than the screen
+                    element.style.width = 'auto';
+                    element.style.height = 'auto';
+                    element.style.maxWidth = 'none';
+                    element.style.maxHeight = 'none';
+                    element.style.objectFit = 'none';
+                    break;
             }
         } else if (element.tagName === 'VIDEO') {
             // Apply same logic to videos
@@ -75,6 +89,9 @@ class ImageScaler {
                 case 'cover':
                     element.style.objectFit = 'cover';
                     break;
+                case 'none':
+                    element.style.objectFit = 'none';
+                    break;
             }
         }
     }
@@ -109,4 +126,4 @@ window.imageScaler = new ImageScaler();
 // Export for use in other files
 if (typeof module !== 'undefined' && module.exports) {
     module.exports = ImageScaler;
-}
\ No newline at end of file
+}
\ No newline at end of file
@@ -1,19 +1,136 @@
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::net::UdpSocket;
 use anyhow::Result;
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use tracing::{error, info, warn};
+
+/// DNS-SD service type devices advertise themselves under
+const MDNS_SERVICE_TYPE: &str = "_signage._tcp.local.";
+
+/// Link-local all-nodes group, the IPv6 stand-in for 255.255.255.255
+const IPV6_ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// Shortest allowed gap between announcements, so a large fleet can't flood the network
+pub const MIN_DISCOVERY_INTERVAL_SECS: u64 = 1;
+
+/// Shortest time a quiet peer is kept, so a few lost announcements don't drop it
+pub const MIN_PEER_TTL_SECS: u64 = 10;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Peer {
     pub id: String,
     pub name: String,
-    pub ip: String,
+    pub ip: IpAddr,
     pub port: u16,
     pub manual: bool,
     pub online: bool,
     pub last_seen: Option<i64>,
+    /// Admin-chosen label, shown instead of the announced name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Admin-chosen position in peer lists; unordered peers come last
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<u32>,
+}
+
+impl Peer {
+    /// The alias if one is set, otherwise the announced name
+    pub fn display_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+    
+    /// Root URL of the peer's web server; IPv6 addresses go in brackets
+    pub fn base_url(&self) -> String {
+        format!("http://{}", SocketAddr::new(self.ip, self.port))
+    }
+}
+
+/// Auto-discovered peers, kept in memory so announcements never rewrite the config file
+pub type PeerTable = Arc<Mutex<Vec<Peer>>>;
+
+/// Manual peers from the config followed by discovered ones not already listed
+pub fn merge_peers(saved: &[Peer], live: &[Peer]) -> Vec<Peer> {
+    let mut peers = saved.to_vec();
+    
+    for peer in live {
+        if !peers.iter().any(|p| p.id == peer.id) {
+            peers.push(peer.clone());
+        }
+    }
+    
+    peers
+}
+
+/// Sort peers by their `order`, then by display name
+pub fn sort_peers(peers: &mut [Peer]) {
+    peers.sort_by_cached_key(|p| (p.order.unwrap_or(u32::MAX), p.display_name().to_lowercase()));
+}
+
+/// Set a peer's alias and order, returning false if no such peer is known.
+/// A labelled discovered peer moves into the saved config so the label survives restarts.
+pub fn set_peer_label(
+    config: &mut crate::config::Config,
+    live_peers: &Mutex<Vec<Peer>>,
+    peer_id: &str,
+    alias: Option<String>,
+    order: Option<u32>,
+) -> bool {
+    let alias = alias.map(|a| a.trim().to_string()).filter(|a| !a.is_empty());
+    
+    if let Some(peer) = config.peers.iter_mut().find(|p| p.id == peer_id) {
+        peer.alias = alias;
+        peer.order = order;
+        return true;
+    }
+    
+    let mut live = live_peers.lock().unwrap();
+    let Some(index) = live.iter().position(|p| p.id == peer_id) else {
+        return false;
+    };
+    
+    let mut peer = live.remove(index);
+    peer.alias = alias;
+    peer.order = order;
+    config.peers.push(peer);
+    true
+}
+
+/// Update a peer's status wherever it lives. Saved peers are written back to the
+/// config when they go on- or offline, so the flag survives a restart.
+pub fn set_peer_online(
+    config: &Mutex<crate::config::Config>,
+    live_peers: &Mutex<Vec<Peer>>,
+    peer_id: &str,
+    online: bool,
+) {
+    let now = chrono::Utc::now().timestamp();
+    let update = |peer: &mut Peer| {
+        peer.online = online;
+        if online {
+            peer.last_seen = Some(now);
+        }
+    };
+    
+    let mut cfg = config.lock().unwrap();
+    if let Some(peer) = cfg.peers.iter_mut().find(|p| p.id == peer_id) {
+        let changed = peer.online != online;
+        update(peer);
+        if changed {
+            if let Err(e) = crate::config::save_shared(&mut cfg) {
+                warn!("Failed to save status of peer {}: {}", peer_id, e);
+            }
+        }
+        return;
+    }
+    drop(cfg);
+    
+    if let Some(peer) = live_peers.lock().unwrap().iter_mut().find(|p| p.id == peer_id) {
+        update(peer);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,28 +143,92 @@ enum DiscoveryMessage {
     },
 }
 
-/// Start the UDP discovery service
-pub async fn start_discovery(config: Arc<Mutex<crate::config::Config>>) -> Result<()> {
-    let cfg = config.lock().await;
-    let discovery_port = cfg.discovery_port;
-    let device_name = cfg.display_name.clone();
-    let app_port = cfg.port;
-    drop(cfg);
+/// Which discovery mechanisms a `discovery_mode` setting turns on, as (udp, mdns)
+fn discovery_modes(mode: &str) -> (bool, bool) {
+    match mode {
+        "udp" => (true, false),
+        "mdns" => (false, true),
+        _ => (true, true),
+    }
+}
+
+/// Start peer discovery (UDP broadcast, mDNS, or both, per the config)
+pub async fn start_discovery(config: Arc<Mutex<crate::config::Config>>, live_peers: PeerTable) -> Result<()> {
+    let (discovery_port, device_name, app_port, mode) = {
+        let mut cfg = config.lock().unwrap();
+        // Older versions saved discovered peers into the config
+        cfg.peers.retain(|p| p.manual);
+        // Announce where the server really is, in case the configured port was taken
+        let app_port = crate::server_port().unwrap_or(cfg.port);
+        (cfg.discovery_port, cfg.display_name.clone(), app_port, cfg.discovery_mode.clone())
+    };
+    let (use_udp, use_mdns) = discovery_modes(&mode);
+    
+    // Held for as long as discovery runs; dropping the daemon stops advertising
+    let _mdns = if use_mdns {
+        match start_mdns(config.clone(), live_peers.clone(), &device_name, app_port) {
+            Ok(daemon) => Some(daemon),
+            Err(e) => {
+                error!("mDNS discovery failed to start: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    
+    if !use_udp {
+        std::future::pending::<()>().await;
+    }
     
     // Bind to the discovery port
-    let socket = UdpSocket::bind(format!("0.0.0.0:{}", discovery_port)).await?;
+    let socket = Arc::new(UdpSocket::bind(format!("0.0.0.0:{}", discovery_port)).await?);
     socket.set_broadcast(true)?;
     
-    println!("Discovery service listening on port {}", discovery_port);
+    // IPv6 runs alongside; hosts without it still discover over IPv4
+    let socket_v6 = match bind_discovery_v6(discovery_port) {
+        Ok(socket_v6) => {
+            let socket_v6 = Arc::new(socket_v6);
+            tokio::spawn(listen_for_announcements(socket_v6.clone(), config.clone(), live_peers.clone()));
+            Some(socket_v6)
+        }
+        Err(e) => {
+            warn!("IPv6 discovery unavailable: {}", e);
+            None
+        }
+    };
+    
+    info!("Discovery service listening on port {}", discovery_port);
     
     // Spawn announcement task
-    let announce_socket = socket.try_clone()?;
+    let announce_socket = socket.clone();
     let announce_config = config.clone();
     tokio::spawn(async move {
-        announce_periodically(announce_socket, announce_config, device_name, app_port, discovery_port).await;
+        announce_periodically(announce_socket, socket_v6, announce_config, device_name, app_port, discovery_port).await;
     });
     
     // Listen for announcements from other devices
+    listen_for_announcements(socket, config, live_peers).await;
+    Ok(())
+}
+
+/// A UDP socket on [::]:port that has joined the all-nodes group. It is IPv6-only,
+/// so it can share the port with the IPv4 socket.
+fn bind_discovery_v6(port: u16) -> std::io::Result<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    
+    let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_only_v6(true)?;
+    socket.bind(&SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port).into())?;
+    socket.join_multicast_v6(&IPV6_ALL_NODES, 0)?;
+    socket.set_multicast_loop_v6(true)?;
+    socket.set_nonblocking(true)?;
+    
+    UdpSocket::from_std(socket.into())
+}
+
+/// Record every announcement that arrives on `socket`
+async fn listen_for_announcements(socket: Arc<UdpSocket>, config: Arc<Mutex<crate::config::Config>>, live_peers: PeerTable) {
     let mut buf = [0u8; 1024];
     loop {
         match socket.recv_from(&mut buf).await {
@@ -56,59 +237,150 @@ pub async fn start_discovery(config: Arc<Mutex<crate::config::Config>>) -> Resul
                     if let Ok(msg) = serde_json::from_str::<DiscoveryMessage>(msg_str) {
                         match msg {
                             DiscoveryMessage::Announce { id, name, port } => {
-                                // Check if this is from ourselves
-                                let cfg = config.lock().await;
-                                if id == cfg.display_name {
-                                    continue;
-                                }
-                                drop(cfg);
-                                
-                                // Update or add peer
-                                let mut cfg = config.lock().await;
-                                let peer_id = format!("{}:{}", addr.ip(), port);
-                                
-                                if let Some(peer) = cfg.peers.iter_mut().find(|p| p.id == peer_id) {
-                                    peer.name = name;
-                                    peer.online = true;
-                                    peer.last_seen = Some(chrono::Utc::now().timestamp());
-                                } else {
-                                    // Add new peer
-                                    cfg.peers.push(Peer {
-                                        id: peer_id,
-                                        name,
-                                        ip: addr.ip().to_string(),
-                                        port,
-                                        manual: false,
-                                        online: true,
-                                        last_seen: Some(chrono::Utc::now().timestamp()),
-                                    });
-                                    println!("Discovered new peer: {} at {}:{}", name, addr.ip(), port);
-                                }
+                                record_announcement(&config, &live_peers, id, name, addr.ip(), port);
                             }
                         }
                     }
                 }
             }
             Err(e) => {
-                eprintln!("Discovery receive error: {}", e);
+                warn!("Discovery receive error: {}", e);
             }
         }
     }
 }
 
+/// Advertise this device as a DNS-SD service and add devices found the same way to the peer table
+pub fn start_mdns(
+    config: Arc<Mutex<crate::config::Config>>,
+    live_peers: PeerTable,
+    device_name: &str,
+    port: u16,
+) -> Result<ServiceDaemon> {
+    let host = hostname::get()
+        .ok()
+        .and_then(|h| h.into_string().ok())
+        .unwrap_or_else(|| "signage".to_string());
+    let properties = [("id", device_name), ("name", device_name)];
+    
+    let service = ServiceInfo::new(
+        MDNS_SERVICE_TYPE,
+        device_name,
+        &format!("{}.local.", host),
+        (),
+        port,
+        &properties[..],
+    )?.enable_addr_auto();
+    
+    let daemon = ServiceDaemon::new()?;
+    run_mdns(&daemon, service, config, live_peers)?;
+    
+    info!("mDNS discovery advertising {} as {}", device_name, MDNS_SERVICE_TYPE);
+    Ok(daemon)
+}
+
+fn run_mdns(
+    daemon: &ServiceDaemon,
+    service: ServiceInfo,
+    config: Arc<Mutex<crate::config::Config>>,
+    live_peers: PeerTable,
+) -> Result<()> {
+    daemon.register(service)?;
+    let events = daemon.browse(MDNS_SERVICE_TYPE)?;
+    
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv_async().await {
+            if let ServiceEvent::ServiceResolved(info) = event {
+                record_mdns_service(&config, &live_peers, &info);
+            }
+        }
+    });
+    
+    Ok(())
+}
+
+/// Feed a resolved mDNS service through the same path as a UDP announcement,
+/// so a device seen both ways shares one ip:port entry
+fn record_mdns_service(
+    config: &Mutex<crate::config::Config>,
+    live_peers: &Mutex<Vec<Peer>>,
+    info: &ServiceInfo,
+) {
+    let addresses = info.get_addresses();
+    let Some(ip) = addresses.iter().find(|ip| ip.is_ipv4()).or_else(|| addresses.iter().next()) else {
+        return;
+    };
+    
+    let fallback = info.get_fullname().trim_end_matches(MDNS_SERVICE_TYPE).trim_end_matches('.');
+    let id = info.get_property_val_str("id").unwrap_or(fallback).to_string();
+    let name = info.get_property_val_str("name").unwrap_or(fallback).to_string();
+    
+    record_announcement(config, live_peers, id, name, *ip, info.get_port());
+}
+
+/// Update or add the peer behind a discovery announcement
+fn record_announcement(
+    config: &Mutex<crate::config::Config>,
+    live_peers: &Mutex<Vec<Peer>>,
+    id: String,
+    name: String,
+    ip: IpAddr,
+    port: u16,
+) {
+    // An IPv4-mapped source is really an IPv4 peer, so it keeps the same id either way
+    let ip = ip.to_canonical();
+    let peer_id = SocketAddr::new(ip, port).to_string();
+    
+    {
+        let mut cfg = config.lock().unwrap();
+        
+        // Check if this is from ourselves
+        if id == cfg.display_name {
+            return;
+        }
+        
+        // A saved peer (manual or labelled) just gets its status refreshed
+        if let Some(peer) = cfg.peers.iter_mut().find(|p| p.id == peer_id) {
+            peer.online = true;
+            peer.last_seen = Some(chrono::Utc::now().timestamp());
+            return;
+        }
+    }
+    
+    let mut peers = live_peers.lock().unwrap();
+    
+    // Only the announced name changes; an admin's alias and order stay as set
+    if let Some(peer) = peers.iter_mut().find(|p| p.id == peer_id) {
+        peer.name = name;
+        peer.online = true;
+        peer.last_seen = Some(chrono::Utc::now().timestamp());
+    } else {
+        // Add new peer
+        info!("Discovered new peer: {} at {}:{}", name, ip, port);
+        peers.push(Peer {
+            id: peer_id,
+            name,
+            ip,
+            port,
+            manual: false,
+            online: true,
+            last_seen: Some(chrono::Utc::now().timestamp()),
+            alias: None,
+            order: None,
+        });
+    }
+}
+
 /// Periodically announce this device's presence
 async fn announce_periodically(
-    socket: UdpSocket,
+    socket: Arc<UdpSocket>,
+    socket_v6: Option<Arc<UdpSocket>>,
     config: Arc<Mutex<crate::config::Config>>,
     device_name: String,
     port: u16,
     discovery_port: u16,
 ) {
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
-    
     loop {
-        interval.tick().await;
-        
         let announcement = DiscoveryMessage::Announce {
             id: device_name.clone(),
             name: device_name.clone(),
@@ -125,29 +397,37 @@ async fn announce_periodically(
             let _ = socket.send_to(msg_bytes, format!("127.0.0.1:{}", discovery_port)).await;
             
             // Send to static IP subnet if configured
-            let cfg = config.lock().await;
-            if !cfg.static_ip.is_empty() {
-                if let Some(subnet) = get_subnet_broadcast(&cfg.static_ip) {
-                    let _ = socket.send_to(msg_bytes, format!("{}:{}", subnet, discovery_port)).await;
-                }
+            let static_ip = config.lock().unwrap().static_ip.clone();
+            if let Some(subnet) = get_subnet_broadcast(&static_ip) {
+                let _ = socket.send_to(msg_bytes, (subnet, discovery_port)).await;
+            }
+            
+            if let Some(socket_v6) = &socket_v6 {
+                announce_v6(socket_v6, msg_bytes, discovery_port).await;
             }
         }
+        
+        // Read every time, so a changed interval applies from the next announcement
+        let interval = config.lock().unwrap().discovery_interval_secs.max(MIN_DISCOVERY_INTERVAL_SECS);
+        tokio::time::sleep(Duration::from_secs(interval)).await;
     }
 }
 
-/// Get broadcast address for a subnet
-fn get_subnet_broadcast(ip: &str) -> Option<String> {
-    let parts: Vec<&str> = ip.split('.').collect();
-    if parts.len() == 4 {
-        Some(format!("{}.{}.{}.255", parts[0], parts[1], parts[2]))
-    } else {
-        None
-    }
+/// Send an announcement to every IPv6 node on the link, and to ::1 for instances on this machine
+async fn announce_v6(socket: &UdpSocket, msg: &[u8], discovery_port: u16) {
+    let _ = socket.send_to(msg, (IPV6_ALL_NODES, discovery_port)).await;
+    let _ = socket.send_to(msg, (Ipv6Addr::LOCALHOST, discovery_port)).await;
+}
+
+/// Get the /24 broadcast address for an IPv4 address; IPv6 has no broadcast
+fn get_subnet_broadcast(ip: &str) -> Option<Ipv4Addr> {
+    let [a, b, c, _] = ip.trim().parse::<Ipv4Addr>().ok()?.octets();
+    Some(Ipv4Addr::new(a, b, c, 255))
 }
 
 /// Check if a peer is online
 pub async fn check_peer_status(peer: &Peer) -> bool {
-    let url = format!("http://{}:{}/api/config", peer.ip, peer.port);
+    let url = format!("{}/api/config", peer.base_url());
     
     match reqwest::Client::new()
         .get(&url)
@@ -161,33 +441,328 @@ pub async fn check_peer_status(peer: &Peer) -> bool {
 }
 
 /// Periodically check all peer statuses
-pub async fn check_all_peers(config: Arc<Mutex<crate::config::Config>>) {
+pub async fn check_all_peers(config: Arc<Mutex<crate::config::Config>>, live_peers: PeerTable) {
     let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(10));
     
     loop {
         interval.tick().await;
         
-        let mut cfg = config.lock().await;
-        let peers = cfg.peers.clone();
-        drop(cfg);
+        let peers = merge_peers(&config.lock().unwrap().peers, &live_peers.lock().unwrap());
         
         for peer in peers.iter() {
             let online = check_peer_status(peer).await;
-            
-            let mut cfg = config.lock().await;
-            if let Some(p) = cfg.peers.iter_mut().find(|p| p.id == peer.id) {
-                p.online = online;
-                if online {
-                    p.last_seen = Some(chrono::Utc::now().timestamp());
+            set_peer_online(&config, &live_peers, &peer.id, online);
+        }
+        
+        // Forget discovered peers that have gone quiet
+        let ttl = config.lock().unwrap().peer_ttl_secs.max(MIN_PEER_TTL_SECS);
+        reap_stale_peers(&mut live_peers.lock().unwrap(), chrono::Utc::now().timestamp(), ttl);
+    }
+}
+
+/// Drop peers not heard from in the last `ttl_secs`. Manual peers stay however long they are quiet.
+pub fn reap_stale_peers(peers: &mut Vec<Peer>, now: i64, ttl_secs: u64) {
+    let ttl = i64::try_from(ttl_secs).unwrap_or(i64::MAX);
+    peers.retain(|p| p.manual || p.last_seen.is_some_and(|last| now.saturating_sub(last) < ttl));
+}
+
+/// Result of pushing media files to a peer
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    pub succeeded: usize,
+    pub failed: Vec<SyncFailure>,
+    /// Set when the peer stopped accepting connections partway through
+    pub peer_offline: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncFailure {
+    pub file: String,
+    pub error: String,
+}
+
+/// Upload media files to a peer's /api/media/upload endpoint, one request per file.
+/// Password-protected peers need `password` to accept the uploads.
+pub async fn sync_media_to_peer(peer: &Peer, files: &[String], password: Option<&str>) -> Result<SyncReport, String> {
+    let media_dir = crate::media::get_media_dir()?;
+    let url = format!("{}/api/media/upload", peer.base_url());
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|e| e.to_string())?;
+    
+    let mut report = SyncReport::default();
+    
+    for filename in files {
+        // Once the peer is gone every remaining upload would fail the same way
+        if report.peer_offline {
+            report.failed.push(SyncFailure {
+                file: filename.clone(),
+                error: "Peer is offline".to_string(),
+            });
+            continue;
+        }
+        
+        let data = match tokio::fs::read(media_dir.join(filename)).await {
+            Ok(data) => data,
+            Err(e) => {
+                report.failed.push(SyncFailure {
+                    file: filename.clone(),
+                    error: format!("Failed to read file: {}", e),
+                });
+                continue;
+            }
+        };
+        
+        let form = reqwest::multipart::Form::new()
+            .part("files", reqwest::multipart::Part::bytes(data).file_name(filename.clone()));
+        
+        let mut request = client.post(&url).multipart(form);
+        if let Some(password) = password {
+            request = request.bearer_auth(password);
+        }
+        
+        match request.send().await {
+            Ok(response) => match upload_error(response).await {
+                None => report.succeeded += 1,
+                Some(error) => report.failed.push(SyncFailure {
+                    file: filename.clone(),
+                    error,
+                }),
+            },
+            Err(e) => {
+                if e.is_connect() {
+                    warn!("Peer {} went offline during sync", peer.id);
+                    report.peer_offline = true;
                 }
+                report.failed.push(SyncFailure {
+                    file: filename.clone(),
+                    error: e.to_string(),
+                });
             }
         }
+    }
+    
+    info!(
+        "Synced {} of {} files to peer {}",
+        report.succeeded,
+        files.len(),
+        peer.id
+    );
+    
+    Ok(report)
+}
+
+/// Pull the rejection reason out of a peer's upload response, if any
+async fn upload_error(response: reqwest::Response) -> Option<String> {
+    let status = response.status();
+    
+    if !status.is_success() {
+        return Some(format!("Peer responded with {}", status));
+    }
+    
+    let body: serde_json::Value = match response.json().await {
+        Ok(body) => body,
+        Err(e) => return Some(format!("Invalid response from peer: {}", e)),
+    };
+    
+    if body["success"].as_bool().unwrap_or(false) {
+        return None;
+    }
+    
+    Some(
+        body["errors"][0]["error"]
+            .as_str()
+            .unwrap_or("Upload rejected by peer")
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_discovered_peer_not_saved() {
+        let config = Mutex::new(crate::config::Config::default());
+        let live_peers = Mutex::new(Vec::new());
         
-        // Clean up old auto-discovered peers (not manual)
-        let mut cfg = config.lock().await;
-        let now = chrono::Utc::now().timestamp();
-        cfg.peers.retain(|p| {
-            p.manual || p.last_seen.map_or(false, |last| now - last < 30)
-        });
+        record_announcement(
+            &config,
+            &live_peers,
+            "lobby".to_string(),
+            "Lobby Screen".to_string(),
+            "192.168.1.20".parse().unwrap(),
+            3006,
+        );
+        
+        let merged = merge_peers(&config.lock().unwrap().peers, &live_peers.lock().unwrap());
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].id, "192.168.1.20:3006");
+        assert!(!merged[0].manual);
+        
+        let saved = serde_json::to_value(&*config.lock().unwrap()).unwrap();
+        assert_eq!(saved["peers"], serde_json::json!([]));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_alias_survives_announcement() {
+        let config = Mutex::new(crate::config::Config::default());
+        let live_peers = Mutex::new(Vec::new());
+        let ip: std::net::IpAddr = "192.168.1.20".parse().unwrap();
+        
+        record_announcement(&config, &live_peers, "lobby".to_string(), "Lobby Screen".to_string(), ip, 3006);
+        assert!(set_peer_label(&mut config.lock().unwrap(), &live_peers, "192.168.1.20:3006", Some(" Front Desk ".to_string()), Some(2)));
+        assert!(!set_peer_label(&mut config.lock().unwrap(), &live_peers, "10.0.0.1:3006", None, None));
+        
+        // The device announces itself again under a new name
+        record_announcement(&config, &live_peers, "lobby".to_string(), "Renamed Screen".to_string(), ip, 3006);
+        
+        let merged = merge_peers(&config.lock().unwrap().peers, &live_peers.lock().unwrap());
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].display_name(), "Front Desk");
+        assert_eq!(merged[0].order, Some(2));
+        assert!(merged[0].online);
+    }
+    
+    #[test]
+    fn test_reap_stale_peers() {
+        let peer = |id: &str, manual: bool, last_seen: Option<i64>| Peer {
+            id: id.to_string(),
+            name: id.to_string(),
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3000,
+            manual,
+            online: false,
+            last_seen,
+            alias: None,
+            order: None,
+        };
+        let now = 1_000_000;
+        
+        let mut peers = vec![
+            peer("fresh", false, Some(now - 50)),
+            peer("stale", false, Some(now - 61)),
+            peer("never", false, None),
+            peer("manual", true, Some(now - 3600)),
+        ];
+        reap_stale_peers(&mut peers, now, 60);
+        
+        let ids: Vec<&str> = peers.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["fresh", "manual"]);
+        
+        // A shorter TTL drops the fresh one too
+        reap_stale_peers(&mut peers, now, 30);
+        assert_eq!(peers.len(), 1);
+        assert!(peers[0].manual);
+    }
+    
+    #[test]
+    fn test_sort_peers() {
+        let peer = |id: &str, name: &str, alias: Option<&str>, order: Option<u32>| Peer {
+            id: id.to_string(),
+            name: name.to_string(),
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3000,
+            manual: true,
+            online: false,
+            last_seen: None,
+            alias: alias.map(str::to_string),
+            order,
+        };
+        
+        let mut peers = vec![
+            peer("a", "zeta", None, None),
+            peer("b", "beta", None, Some(1)),
+            peer("c", "omega", Some("Alpha"), None),
+            peer("d", "gamma", None, Some(0)),
+        ];
+        sort_peers(&mut peers);
+        
+        let ids: Vec<&str> = peers.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["d", "b", "c", "a"]);
+    }
+    
+    #[tokio::test]
+    async fn test_mdns_discovers_loopback_service() {
+        let config = Arc::new(Mutex::new(crate::config::Config {
+            display_name: "This Screen".to_string(),
+            ..crate::config::Config::default()
+        }));
+        let live_peers: PeerTable = Arc::new(Mutex::new(Vec::new()));
+        
+        let daemon = ServiceDaemon::new().unwrap();
+        daemon.enable_interface(mdns_sd::IfKind::LoopbackV4).unwrap();
+        
+        let properties = [("id", "lobby"), ("name", "Lobby Screen")];
+        let service = ServiceInfo::new(
+            MDNS_SERVICE_TYPE,
+            "Lobby Screen",
+            "lobby-test.local.",
+            "127.0.0.1",
+            47006,
+            &properties[..],
+        ).unwrap();
+        run_mdns(&daemon, service, config.clone(), live_peers.clone()).unwrap();
+        
+        let mut found = None;
+        for _ in 0..50 {
+            found = live_peers.lock().unwrap().iter().find(|p| p.port == 47006).cloned();
+            if found.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let _ = daemon.shutdown();
+        
+        let peer = found.expect("service was not discovered");
+        assert_eq!(peer.id, "127.0.0.1:47006");
+        assert_eq!(peer.name, "Lobby Screen");
+        assert!(config.lock().unwrap().peers.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_discovers_peer_over_ipv6_multicast() {
+        let config = Arc::new(Mutex::new(crate::config::Config {
+            display_name: "This Screen".to_string(),
+            ..crate::config::Config::default()
+        }));
+        let live_peers: PeerTable = Arc::new(Mutex::new(Vec::new()));
+        
+        let listener = Arc::new(bind_discovery_v6(0).unwrap());
+        let discovery_port = listener.local_addr().unwrap().port();
+        let listening = tokio::spawn(listen_for_announcements(listener, config.clone(), live_peers.clone()));
+        
+        let announcement = serde_json::to_string(&DiscoveryMessage::Announce {
+            id: "lobby".to_string(),
+            name: "Lobby Screen".to_string(),
+            port: 47007,
+        }).unwrap();
+        let sender = bind_discovery_v6(0).unwrap();
+        announce_v6(&sender, announcement.as_bytes(), discovery_port).await;
+        
+        let mut found = None;
+        for _ in 0..50 {
+            found = live_peers.lock().unwrap().iter().find(|p| p.port == 47007).cloned();
+            if found.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        listening.abort();
+        
+        let peer = found.expect("announcement was not received");
+        assert!(peer.ip.is_ipv6());
+        assert_eq!(peer.id, SocketAddr::new(peer.ip, 47007).to_string());
+        assert!(peer.base_url().starts_with("http://["));
+        assert_eq!(peer.name, "Lobby Screen");
+    }
+    
+    #[test]
+    fn test_subnet_broadcast() {
+        assert_eq!(get_subnet_broadcast("192.168.1.20"), Some(Ipv4Addr::new(192, 168, 1, 255)));
+        assert_eq!(get_subnet_broadcast("fd00::2"), None);
+        assert_eq!(get_subnet_broadcast(""), None);
+    }
+}
@@ -1,10 +1,136 @@
 use anyhow::{Context, Result};
-use std::path::Path;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
+/// Hex-encoded ed25519 key that update signatures must come from, set when building a release
+const UPDATE_PUBLIC_KEY: Option<&str> = option_env!("UPDATE_PUBLIC_KEY");
+
+/// Largest update package accepted for staging
+pub const MAX_UPDATE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Where an uploaded update waits until an admin confirms it
+pub fn staged_update_path() -> Result<PathBuf, String> {
+    Ok(crate::paths::get_app_dir()?.join("updates").join("pending.asar"))
+}
+
+/// Why an update upload was not staged
+#[derive(Debug)]
+pub enum StageRejection {
+    /// An earlier upload is still waiting to be confirmed or discarded
+    AlreadyStaged,
+    TooLarge,
+    Invalid(String),
+}
+
+/// The staged package, as reported back to the admin for confirmation
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StagedUpdate {
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// An update being streamed to the staging area
+pub struct PendingStage {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: fs::File,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl PendingStage {
+    pub async fn begin(filename: &str) -> Result<Self, StageRejection> {
+        if Path::new(filename).extension().and_then(|e| e.to_str()) != Some("asar") {
+            return Err(StageRejection::Invalid("Invalid update file format. Expected .asar file".to_string()));
+        }
+        
+        let path = staged_update_path().map_err(StageRejection::Invalid)?;
+        if path.exists() {
+            return Err(StageRejection::AlreadyStaged);
+        }
+        
+        let dir = path.parent().ok_or(StageRejection::Invalid("Invalid staging path".to_string()))?;
+        fs::create_dir_all(dir).await
+            .map_err(|e| StageRejection::Invalid(format!("Failed to create updates folder: {}", e)))?;
+        
+        // create_new also stops two uploads racing each other into the same file
+        let temp_path = path.with_extension("asar.part");
+        let file = fs::OpenOptions::new().write(true).create_new(true).open(&temp_path).await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::AlreadyExists => StageRejection::AlreadyStaged,
+                _ => StageRejection::Invalid(format!("Failed to create staging file: {}", e)),
+            })?;
+        
+        Ok(Self { path, temp_path, file, hasher: Sha256::new(), written: 0 })
+    }
+    
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), StageRejection> {
+        if self.written + chunk.len() as u64 > MAX_UPDATE_BYTES {
+            return Err(StageRejection::TooLarge);
+        }
+        
+        self.file.write_all(chunk).await
+            .map_err(|e| StageRejection::Invalid(format!("Failed to write update: {}", e)))?;
+        self.hasher.update(chunk);
+        self.written += chunk.len() as u64;
+        Ok(())
+    }
+    
+    pub async fn abort(self) {
+        drop(self.file);
+        let _ = fs::remove_file(&self.temp_path).await;
+    }
+    
+    pub async fn finish(mut self) -> Result<StagedUpdate, StageRejection> {
+        if self.written == 0 {
+            self.abort().await;
+            return Err(StageRejection::Invalid("Update file is empty".to_string()));
+        }
+        
+        if let Err(e) = self.file.flush().await {
+            self.abort().await;
+            return Err(StageRejection::Invalid(format!("Failed to write update: {}", e)));
+        }
+        
+        let staged = StagedUpdate {
+            size: self.written,
+            sha256: to_hex(&self.hasher.finalize_reset()),
+        };
+        
+        drop(self.file);
+        fs::rename(&self.temp_path, &self.path).await
+            .map_err(|e| StageRejection::Invalid(format!("Failed to stage update: {}", e)))?;
+        
+        Ok(staged)
+    }
+}
+
+/// Apply the staged update once the admin confirms its checksum
+pub async fn apply_staged_update(expected_sha256: &str, signature: Option<&str>) -> Result<()> {
+    let path = staged_update_path().map_err(|e| anyhow::anyhow!(e))?;
+    if !path.exists() {
+        anyhow::bail!("No update is staged");
+    }
+    
+    process_update(&path.to_string_lossy(), expected_sha256, signature).await
+}
+
+/// Throw away a staged update so a different one can be uploaded
+pub async fn discard_staged_update() -> Result<bool, String> {
+    let path = staged_update_path()?;
+    match fs::remove_file(&path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!("Failed to remove staged update: {}", e)),
+    }
+}
+
 /// Process and apply an update
-pub async fn process_update(update_path: &str) -> Result<()> {
+pub async fn process_update(update_path: &str, expected_sha256: &str, signature: Option<&str>) -> Result<()> {
     let update_file = Path::new(update_path);
     
     if !update_file.exists() {
@@ -16,6 +142,14 @@ pub async fn process_update(update_path: &str) -> Result<()> {
         anyhow::bail!("Invalid update file format. Expected .asar file");
     }
     
+    // Never hand a corrupted or tampered file to the update script
+    let path = update_file.to_path_buf();
+    let expected_sha256 = expected_sha256.to_string();
+    let signature = signature.map(str::to_string);
+    tokio::task::spawn_blocking(move || verify_update(&path, &expected_sha256, signature.as_deref()))
+        .await?
+        .map_err(|e| anyhow::anyhow!(e))?;
+    
     // Create update script based on platform
     #[cfg(target_os = "windows")]
     {
@@ -35,6 +169,56 @@ pub async fn process_update(update_path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Check an update file against its published SHA-256 and, if given, its ed25519 signature
+pub fn verify_update(path: &Path, expected_sha256: &str, signature: Option<&str>) -> Result<(), String> {
+    let data = std::fs::read(path)
+        .map_err(|e| format!("Failed to read update: {}", e))?;
+    
+    let actual = to_hex(&Sha256::digest(&data));
+    if !actual.eq_ignore_ascii_case(expected_sha256.trim()) {
+        return Err(format!("Update checksum mismatch: expected {}, got {}", expected_sha256, actual));
+    }
+    
+    if let Some(signature) = signature {
+        let public_key = UPDATE_PUBLIC_KEY
+            .ok_or("This build has no update signing key to check the signature with")?;
+        verify_signature(public_key, &data, signature)?;
+    }
+    
+    Ok(())
+}
+
+fn verify_signature(public_key_hex: &str, data: &[u8], signature_hex: &str) -> Result<(), String> {
+    let key: [u8; 32] = from_hex(public_key_hex)?
+        .try_into()
+        .map_err(|_| "Update public key must be 32 bytes".to_string())?;
+    let key = VerifyingKey::from_bytes(&key)
+        .map_err(|e| format!("Invalid update public key: {}", e))?;
+    
+    let signature: [u8; 64] = from_hex(signature_hex)?
+        .try_into()
+        .map_err(|_| "Update signature must be 64 bytes".to_string())?;
+    
+    key.verify(data, &Signature::from_bytes(&signature))
+        .map_err(|_| "Update signature is not valid".to_string())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err("Invalid hex string".to_string());
+    }
+    
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| "Invalid hex string".to_string()))
+        .collect()
+}
+
 #[cfg(target_os = "windows")]
 async fn create_windows_update_script(update_path: &str) -> Result<()> {
     let exe_path = std::env::current_exe()?;
@@ -74,7 +258,9 @@ exit
 }
 
 #[cfg(target_os = "linux")]
-async fn create_linux_update_script(update_path: &str) -> Result<()> {
+async fn create_linux_update_script(_update_path: &str) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    
     let exe_path = std::env::current_exe()?;
     let app_dir = exe_path.parent()
         .context("Failed to get app directory")?;
@@ -130,4 +316,49 @@ async fn create_macos_update_script(update_path: &str) -> Result<()> {
 //   ],
 //   "dialog": true,
 //   "pubkey": "YOUR_PUBLIC_KEY"
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    
+    fn write_update(name: &str, data: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("update-test-{}-{}.asar", name, std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+    
+    #[test]
+    fn test_verify_update_checksum() {
+        let path = write_update("checksum", b"update contents");
+        let sha256 = to_hex(&Sha256::digest(b"update contents"));
+        
+        assert!(verify_update(&path, &sha256, None).is_ok());
+        assert!(verify_update(&path, &sha256.to_uppercase(), None).is_ok());
+        
+        let wrong = to_hex(&Sha256::digest(b"other contents"));
+        assert!(verify_update(&path, &wrong, None).unwrap_err().contains("mismatch"));
+        
+        let _ = std::fs::remove_file(&path);
+    }
+    
+    #[test]
+    fn test_verify_signature() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = to_hex(signing_key.verifying_key().as_bytes());
+        let data = b"update contents";
+        
+        let signature = to_hex(&signing_key.sign(data).to_bytes());
+        assert!(verify_signature(&public_key, data, &signature).is_ok());
+        
+        // Signed by a different key
+        let other = to_hex(&SigningKey::from_bytes(&[8u8; 32]).sign(data).to_bytes());
+        assert!(verify_signature(&public_key, data, &other).is_err());
+        
+        // Right key, tampered data
+        assert!(verify_signature(&public_key, b"update c0ntents", &signature).is_err());
+        
+        assert!(verify_signature(&public_key, data, "not hex").is_err());
+    }
+}
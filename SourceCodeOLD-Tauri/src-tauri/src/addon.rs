@@ -1,8 +1,37 @@
+use crate::error::AppError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::fs;
 use mlua::prelude::*;
+use tracing::{debug, info, warn};
+
+/// Longest a single backend.lua run may take before it is aborted
+pub const LUA_TIME_LIMIT: Duration = Duration::from_secs(10);
+
+/// How many Lua instructions run between checks on a script's time and instruction budget
+const LUA_HOOK_INTERVAL: u32 = 10_000;
+
+pub mod install;
+#[cfg(feature = "addon-watcher")]
+pub mod watcher;
+
+/// Permission an addon must declare in addon.toml to spawn processes
+pub const PERMISSION_EXECUTE_COMMAND: &str = "execute_command";
+
+/// Permission an addon must declare in addon.toml to make HTTP requests
+pub const PERMISSION_NETWORK: &str = "network";
+
+/// Permission an addon must declare in addon.toml to list the device's media
+pub const PERMISSION_MEDIA_READ: &str = "media_read";
+
+/// Setting types the settings UI knows how to render
+pub const SETTING_TYPES: &[&str] = &["boolean", "text", "color", "range", "select", "file"];
+
+/// Longest addon.http_get waits for a response
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddonInfo {
@@ -11,6 +40,15 @@ pub struct AddonInfo {
     pub author: Option<String>,
     pub description: Option<String>,
     pub category: Option<String>,
+    /// Lower values load first among addons that don't depend on each other
+    #[serde(default)]
+    pub load_order: Option<i32>,
+    /// Folder names of addons that must load before this one
+    #[serde(default)]
+    pub requires: Option<Vec<String>>,
+    /// Sections settings are shown in, in display order; settings without a group go in a default one
+    #[serde(default)]
+    pub groups: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,12 +64,20 @@ pub struct AddonSetting {
     pub max: Option<i64>,
     pub unit: Option<String>,
     pub options: Option<Vec<serde_json::Value>>,
+    /// Id of another setting that decides whether this one is shown
+    pub depends_on: Option<String>,
+    /// Value `depends_on` must have for this setting to show; any truthy value if unset
+    pub depends_value: Option<serde_json::Value>,
+    /// One of `info.groups` to show this setting under
+    pub group: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddonManifest {
     pub info: AddonInfo,
     pub settings: Vec<AddonSetting>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,26 +90,63 @@ pub struct Addon {
     pub config: HashMap<String, serde_json::Value>,
     pub has_backend: bool,
     pub has_frontend: bool,
+    /// Problems the addon loads despite, e.g. undeclared config keys or dangling `depends_on`
+    pub config_key_warnings: Vec<String>,
+    pub permissions: Vec<String>,
+    /// Why the addon can't run (missing or circular dependencies); such addons stay disabled
+    #[serde(default)]
+    pub load_error: Option<String>,
+    /// Why the manifest or backend init failed on the last scan; the addon is still listed
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Functions backend.lua asked, via `addon.schedule`, to have called periodically
+    #[serde(skip)]
+    pub schedules: Vec<crate::scheduler::ScheduledCall>,
 }
 
-pub fn get_addons_dir() -> Result<PathBuf, String> {
+pub fn get_addons_dir() -> Result<PathBuf, AppError> {
     crate::paths::get_addons_dir()
 }
 
-pub async fn scan_addons() -> Result<Vec<Addon>, String> {
-    let addons_dir = get_addons_dir()?;
-    
+/// Files a scan reads; the addon is only scanned again once one of them changes
+const SCANNED_FILES: &[&str] = &["addon.toml", "backend.lua", "frontend.js"];
+
+/// Size and modification time of each of `SCANNED_FILES`, or None if it doesn't exist
+type FileStamp = Vec<Option<(SystemTime, u64)>>;
+
+fn file_stamp(addon_dir: &Path) -> FileStamp {
+    SCANNED_FILES.iter()
+        .map(|name| {
+            let metadata = std::fs::metadata(addon_dir.join(name)).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+/// Last successfully loaded version of each addon folder. It is reused while the folder's
+/// files are unchanged, and kept in case an edit breaks the manifest.
+static LOADED_ADDONS: OnceLock<Mutex<HashMap<PathBuf, (FileStamp, Addon)>>> = OnceLock::new();
+
+fn loaded_addons() -> &'static Mutex<HashMap<PathBuf, (FileStamp, Addon)>> {
+    LOADED_ADDONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// List installed addons. Unchanged addons come from the cache without re-running their
+/// backend init; `force` rescans every addon from disk.
+pub async fn scan_addons(force: bool) -> Result<Vec<Addon>, AppError> {
+    scan_addons_in(&get_addons_dir()?, force).await
+}
+
+async fn scan_addons_in(addons_dir: &Path, force: bool) -> Result<Vec<Addon>, AppError> {
     if !addons_dir.exists() {
-        fs::create_dir_all(&addons_dir).await
-            .map_err(|e| e.to_string())?;
+        fs::create_dir_all(addons_dir).await?;
         return Ok(Vec::new());
     }
     
-    let mut entries = fs::read_dir(&addons_dir).await
-        .map_err(|e| e.to_string())?;
+    let mut entries = fs::read_dir(addons_dir).await?;
     let mut addons = Vec::new();
     
-    while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+    while let Some(entry) = entries.next_entry().await? {
         let path = entry.path();
         
         if !path.is_dir() {
@@ -72,36 +155,99 @@ pub async fn scan_addons() -> Result<Vec<Addon>, String> {
         
         let folder_name = path.file_name()
             .and_then(|n| n.to_str())
-            .ok_or("Invalid folder name")?
+            .ok_or_else(|| AppError::Validation("Invalid folder name".to_string()))?
             .to_string();
         
+        // Hidden folders are installs still being unpacked
+        if folder_name.starts_with('.') {
+            continue;
+        }
+        
         // Check for addon.toml
         let manifest_path = path.join("addon.toml");
         if !manifest_path.exists() {
-            println!("Skipping {}: no addon.toml found", folder_name);
+            info!("Skipping {}: no addon.toml found", folder_name);
             continue;
         }
         
+        // Callers merge config into what they get back, so hand out copies of the cached addon
+        let stamp = file_stamp(&path);
+        if !force {
+            let cached = loaded_addons().lock().unwrap().get(&path)
+                .filter(|(cached_stamp, _)| *cached_stamp == stamp)
+                .map(|(_, addon)| addon.clone());
+            if let Some(addon) = cached {
+                addons.push(addon);
+                continue;
+            }
+        }
+        
         // Read manifest
-        let manifest_content = fs::read_to_string(&manifest_path).await
-            .map_err(|e| format!("Failed to read manifest for {}: {}", folder_name, e))?;
+        let manifest = match fs::read_to_string(&manifest_path).await {
+            Ok(content) => toml::from_str::<AddonManifest>(&content)
+                .map_err(|e| format!("Failed to parse manifest for {}: {}", folder_name, e)),
+            Err(e) => Err(format!("Failed to read manifest for {}: {}", folder_name, e)),
+        };
         
-        let mut manifest: AddonManifest = toml::from_str(&manifest_content)
-            .map_err(|e| format!("Failed to parse manifest for {}: {}", folder_name, e))?;
+        let mut manifest = match manifest {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                // A half-saved or broken edit shouldn't make the addon vanish
+                let previous = loaded_addons().lock().unwrap().get(&path).map(|(_, addon)| addon.clone());
+                let addon = match previous {
+                    Some(previous) => {
+                        warn!("{}; keeping previously loaded version", e);
+                        previous
+                    }
+                    None => {
+                        warn!("{}; listing it without settings", e);
+                        broken_addon(&folder_name)
+                    }
+                };
+                addons.push(Addon { last_error: Some(e), ..addon });
+                continue;
+            }
+        };
         
         // Check for backend.lua
         let backend_path = path.join("backend.lua");
         let has_backend = backend_path.exists();
         
         // If backend exists, run its init function to modify settings
+        let mut last_error = None;
+        let mut schedules = Vec::new();
         if has_backend {
-            if let Err(e) = execute_lua_backend_init(&backend_path, &mut manifest.settings, &folder_name).await {
-                println!("Warning: Failed to execute backend init for {}: {}", folder_name, e);
+            match execute_lua_backend_init(&backend_path, &mut manifest.settings, &folder_name, &manifest.permissions).await {
+                Ok(calls) => schedules = calls,
+                Err(e) => {
+                    warn!("Failed to execute backend init for {}: {}", folder_name, e);
+                    last_error = Some(format!("Backend init failed: {}", e));
+                }
             }
         }
         
         // Check for frontend.js
-        let has_frontend = path.join("frontend.js").exists();
+        let frontend_path = path.join("frontend.js");
+        let has_frontend = frontend_path.exists();
+        
+        // Warn about config keys the frontend reads that no setting declares
+        let mut config_key_warnings = if has_frontend {
+            match fs::read_to_string(&frontend_path).await {
+                Ok(script) => check_config_keys(&script, &manifest.settings),
+                Err(e) => {
+                    warn!("Failed to read frontend.js for {}: {}", folder_name, e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        config_key_warnings.extend(check_setting_dependencies(&manifest.settings));
+        config_key_warnings.extend(check_setting_groups(&manifest.info.groups, &manifest.settings));
+        
+        for warning in &config_key_warnings {
+            warn!("{}: {}", folder_name, warning);
+        }
         
         // Use folder name as ID
         let addon = Addon {
@@ -113,74 +259,197 @@ pub async fn scan_addons() -> Result<Vec<Addon>, String> {
             config: HashMap::new(), // Will be loaded from config
             has_backend,
             has_frontend,
+            config_key_warnings,
+            permissions: manifest.permissions,
+            load_error: None,
+            last_error,
+            schedules,
         };
         
+        loaded_addons().lock().unwrap().insert(path, (stamp, addon.clone()));
         addons.push(addon);
     }
     
-    Ok(addons)
+    Ok(sort_by_dependencies(addons))
+}
+
+/// Placeholder listed for an addon whose manifest can't be read, so its error can be shown
+fn broken_addon(folder_name: &str) -> Addon {
+    Addon {
+        id: folder_name.to_string(),
+        folder: folder_name.to_string(),
+        info: AddonInfo {
+            name: folder_name.to_string(),
+            version: String::new(),
+            author: None,
+            description: None,
+            category: None,
+            load_order: None,
+            requires: None,
+            groups: Vec::new(),
+        },
+        settings: Vec::new(),
+        enabled: false,
+        config: HashMap::new(),
+        has_backend: false,
+        has_frontend: false,
+        config_key_warnings: Vec::new(),
+        permissions: Vec::new(),
+        load_error: None,
+        last_error: None,
+        schedules: Vec::new(),
+    }
+}
+
+/// Order addons so every addon comes after the ones it requires, breaking ties by
+/// `load_order` then id. Addons with missing or circular dependencies get a `load_error`.
+fn sort_by_dependencies(addons: Vec<Addon>) -> Vec<Addon> {
+    use std::collections::BTreeSet;
+    
+    let requires = |addon: &Addon| {
+        let mut ids = addon.info.requires.clone().unwrap_or_default();
+        ids.sort();
+        ids.dedup();
+        ids
+    };
+    let sort_key = |addon: &Addon| (addon.info.load_order.unwrap_or(0), addon.id.clone());
+    
+    let mut pending: HashMap<String, Addon> = addons.into_iter()
+        .map(|addon| (addon.id.clone(), addon))
+        .collect();
+    
+    // A previously loaded copy may carry an error from an earlier scan
+    for addon in pending.values_mut() {
+        addon.load_error = None;
+    }
+    
+    // Count the requirements each addon is still waiting on
+    let ids: Vec<String> = pending.keys().cloned().collect();
+    let mut waiting: HashMap<String, usize> = HashMap::new();
+    for id in &ids {
+        let addon = &pending[id];
+        let missing: Vec<String> = requires(addon).into_iter()
+            .filter(|dep| !pending.contains_key(dep))
+            .collect();
+        let present = requires(addon).len() - missing.len();
+        waiting.insert(id.clone(), present);
+        
+        if !missing.is_empty() {
+            let error = format!("requires missing addon(s): {}", missing.join(", "));
+            warn!("Disabling addon {}: {}", id, error);
+            pending.get_mut(id).unwrap().load_error = Some(error);
+        }
+    }
+    
+    let mut ready: BTreeSet<(i32, String)> = pending.values()
+        .filter(|addon| waiting[&addon.id] == 0)
+        .map(sort_key)
+        .collect();
+    let mut sorted = Vec::with_capacity(pending.len());
+    
+    while let Some(key) = ready.pop_first() {
+        let mut addon = pending.remove(&key.1).unwrap();
+        
+        // Anything built on a disabled addon can't run either
+        if addon.load_error.is_none() {
+            let deps = requires(&addon);
+            let broken = sorted.iter().find(|done: &&Addon| done.load_error.is_some() && deps.contains(&done.id));
+            if let Some(dep) = broken {
+                let error = format!("requires disabled addon {}", dep.id);
+                warn!("Disabling addon {}: {}", addon.id, error);
+                addon.load_error = Some(error);
+            }
+        }
+        
+        for dependent in pending.values() {
+            if requires(dependent).contains(&addon.id) {
+                let count = waiting.get_mut(&dependent.id).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(sort_key(dependent));
+                }
+            }
+        }
+        
+        sorted.push(addon);
+    }
+    
+    // Whatever is left is in, or depends on, a dependency cycle
+    let mut stuck: Vec<Addon> = pending.into_values().collect();
+    stuck.sort_by_key(sort_key);
+    for mut addon in stuck {
+        let error = "is part of, or depends on, a dependency cycle".to_string();
+        warn!("Disabling addon {}: {}", addon.id, error);
+        addon.load_error = Some(error);
+        sorted.push(addon);
+    }
+    
+    sorted
 }
 
-/// Execute Lua backend initialization to modify settings dynamically
+/// Execute Lua backend initialization to modify settings dynamically.
+/// Returns the functions the script scheduled while loading.
 async fn execute_lua_backend_init(
     backend_path: &PathBuf,
     settings: &mut Vec<AddonSetting>,
     addon_id: &str,
-) -> Result<(), String> {
-    println!("=== EXECUTING LUA BACKEND FOR {} ===", addon_id);
+    permissions: &[String],
+) -> Result<Vec<crate::scheduler::ScheduledCall>, AppError> {
+    debug!("Executing Lua backend for {}", addon_id);
     
     // Read the Lua script
     let lua_script = fs::read_to_string(backend_path).await
-        .map_err(|e| format!("Failed to read backend.lua: {}", e))?;
+        .map_err(|e| AppError::Io(format!("Failed to read backend.lua: {}", e)))?;
     
     // Create Lua instance
     let lua = Lua::new();
     
     // Setup Lua API for addon to use
-    setup_lua_api(&lua, addon_id)?;
+    setup_lua_api(&lua, addon_id, permissions)?;
+    limit_execution(&lua, LuaLimits::load())?;
     
     // Execute the Lua script
     lua.load(&lua_script).exec()
-        .map_err(|e| format!("Failed to execute Lua script: {}", e))?;
+        .map_err(|e| AppError::Lua(format!("Failed to execute Lua script: {}", e)))?;
     
     // Call the init function if it exists
     let globals = lua.globals();
     if let Ok(init_fn) = globals.get::<_, LuaFunction>("init") {
         // Convert settings to Lua table
         let settings_table = lua.create_table()
-            .map_err(|e| format!("Failed to create settings table: {}", e))?;
+            .map_err(|e| AppError::Lua(format!("Failed to create settings table: {}", e)))?;
         
         for (i, setting) in settings.iter().enumerate() {
             let setting_table = lua.create_table()
-                .map_err(|e| format!("Failed to create setting table: {}", e))?;
+                .map_err(|e| AppError::Lua(format!("Failed to create setting table: {}", e)))?;
             
             setting_table.set("id", setting.id.clone())
-                .map_err(|e| format!("Failed to set id: {}", e))?;
+                .map_err(|e| AppError::Lua(format!("Failed to set id: {}", e)))?;
             setting_table.set("name", setting.name.clone())
-                .map_err(|e| format!("Failed to set name: {}", e))?;
+                .map_err(|e| AppError::Lua(format!("Failed to set name: {}", e)))?;
             setting_table.set("type", setting.setting_type.clone())
-                .map_err(|e| format!("Failed to set type: {}", e))?;
+                .map_err(|e| AppError::Lua(format!("Failed to set type: {}", e)))?;
             
             if let Some(options) = &setting.options {
                 let options_table = lua.create_table()
-                    .map_err(|e| format!("Failed to create options table: {}", e))?;
+                    .map_err(|e| AppError::Lua(format!("Failed to create options table: {}", e)))?;
                 
                 for (j, opt) in options.iter().enumerate() {
                     options_table.set(j + 1, serde_json::to_string(opt).unwrap_or_default())
-                        .map_err(|e| format!("Failed to set option: {}", e))?;
+                        .map_err(|e| AppError::Lua(format!("Failed to set option: {}", e)))?;
                 }
                 
                 setting_table.set("options", options_table)
-                    .map_err(|e| format!("Failed to set options: {}", e))?;
+                    .map_err(|e| AppError::Lua(format!("Failed to set options: {}", e)))?;
             }
             
             settings_table.set(i + 1, setting_table)
-                .map_err(|e| format!("Failed to set setting: {}", e))?;
+                .map_err(|e| AppError::Lua(format!("Failed to set setting: {}", e)))?;
         }
         
         // Call init with settings
         let result: LuaTable = init_fn.call(settings_table)
-            .map_err(|e| format!("Failed to call init function: {}", e))?;
+            .map_err(|e| AppError::Lua(format!("Failed to call init function: {}", e)))?;
         
         // Convert result back to settings
         for i in 1..=result.len().unwrap_or(0) {
@@ -203,7 +472,7 @@ async fn execute_lua_backend_init(
                             if !new_options.is_empty() {
                                 let count = new_options.len();
                                 setting.options = Some(new_options);
-                                println!("Updated {} options for setting '{}'", count, id);
+                                info!("Updated {} options for setting '{}'", count, id);
                             }
                         }
                     }
@@ -212,42 +481,39 @@ async fn execute_lua_backend_init(
         }
     }
     
-    println!("=== LUA BACKEND EXECUTION COMPLETE ===");
-    Ok(())
+    debug!("Lua backend execution complete");
+    Ok(crate::scheduler::registered_calls(&lua))
 }
 
 /// Setup Lua API functions that addons can use
-fn setup_lua_api(lua: &Lua, addon_id: &str) -> Result<(), String> {
+fn setup_lua_api(lua: &Lua, addon_id: &str, permissions: &[String]) -> Result<(), AppError> {
     let globals = lua.globals();
     
     // Create addon API table
     let addon_api = lua.create_table()
-        .map_err(|e| format!("Failed to create addon API: {}", e))?;
+        .map_err(|e| AppError::Lua(format!("Failed to create addon API: {}", e)))?;
     
     // Add get_fonts_dir function
-    let fonts_dir_path = crate::paths::get_fonts_dir()
-        .map_err(|e| format!("Failed to get fonts dir: {}", e))?;
+    let fonts_dir_path = crate::paths::get_fonts_dir()?;
     let fonts_dir_str = fonts_dir_path.to_string_lossy().to_string();
     
     let get_fonts_dir_fn = lua.create_function(move |_, ()| {
         Ok(fonts_dir_str.clone())
-    }).map_err(|e| format!("Failed to create get_fonts_dir function: {}", e))?;
+    }).map_err(|e| AppError::Lua(format!("Failed to create get_fonts_dir function: {}", e)))?;
     
     addon_api.set("get_fonts_dir", get_fonts_dir_fn)
-        .map_err(|e| format!("Failed to set get_fonts_dir: {}", e))?;
+        .map_err(|e| AppError::Lua(format!("Failed to set get_fonts_dir: {}", e)))?;
     
     // Add get_addon_dir function
-    let addon_dir_path = get_addons_dir()
-        .map_err(|e| format!("Failed to get addons dir: {}", e))?
-        .join(addon_id);
+    let addon_dir_path = get_addons_dir()?.join(addon_id);
     let addon_dir_str = addon_dir_path.to_string_lossy().to_string();
     
     let get_addon_dir_fn = lua.create_function(move |_, ()| {
         Ok(addon_dir_str.clone())
-    }).map_err(|e| format!("Failed to create get_addon_dir function: {}", e))?;
+    }).map_err(|e| AppError::Lua(format!("Failed to create get_addon_dir function: {}", e)))?;
     
     addon_api.set("get_addon_dir", get_addon_dir_fn)
-        .map_err(|e| format!("Failed to set get_addon_dir: {}", e))?;
+        .map_err(|e| AppError::Lua(format!("Failed to set get_addon_dir: {}", e)))?;
     
     // Add list_directory function for cross-platform directory listing
     let list_directory_fn = lua.create_function(|_, path: String| {
@@ -281,7 +547,7 @@ fn setup_lua_api(lua: &Lua, addon_id: &str) -> Result<(), String> {
                             files.push(file_name.to_string_lossy().to_string());
                         }
                         Err(e) => {
-                            eprintln!("Error reading directory entry: {}", e);
+                            warn!("Error reading directory entry: {}", e);
                         }
                     }
                 }
@@ -292,45 +558,614 @@ fn setup_lua_api(lua: &Lua, addon_id: &str) -> Result<(), String> {
                 format!("Failed to read directory: {}", e)
             ))
         }
-    }).map_err(|e| format!("Failed to create list_directory function: {}", e))?;
+    }).map_err(|e| AppError::Lua(format!("Failed to create list_directory function: {}", e)))?;
     
     addon_api.set("list_directory", list_directory_fn)
-        .map_err(|e| format!("Failed to set list_directory: {}", e))?;
+        .map_err(|e| AppError::Lua(format!("Failed to set list_directory: {}", e)))?;
+    
+    // Add read_file/write_file for storage inside the addon's own folder
+    register_file_api(lua, &addon_api, &addon_dir_path)?;
+    
+    // Add json_decode/json_encode
+    register_json_api(lua, &addon_api)?;
     
     // Add print function that logs to console
     let addon_id_for_print = addon_id.to_string();
     let print_fn = lua.create_function(move |_, msg: String| {
-        println!("[Addon: {}] {}", addon_id_for_print, msg);
+        info!("[Addon: {}] {}", addon_id_for_print, msg);
         Ok(())
-    }).map_err(|e| format!("Failed to create print function: {}", e))?;
+    }).map_err(|e| AppError::Lua(format!("Failed to create print function: {}", e)))?;
     
     addon_api.set("print", print_fn)
-        .map_err(|e| format!("Failed to set print: {}", e))?;
+        .map_err(|e| AppError::Lua(format!("Failed to set print: {}", e)))?;
+    
+    // Add schedule function for periodic backend tasks
+    crate::scheduler::register_lua_api(lua, &addon_api).map_err(AppError::Lua)?;
+    
+    // Add http_get, gated on the network permission
+    register_http_get(lua, &addon_api, addon_id, permissions)?;
+    
+    // Add list_media, gated on the media_read permission
+    register_list_media(lua, &addon_api, addon_id, permissions)?;
     
     // Set the API in globals
     globals.set("addon", addon_api)
-        .map_err(|e| format!("Failed to set addon API: {}", e))?;
+        .map_err(|e| AppError::Lua(format!("Failed to set addon API: {}", e)))?;
+    
+    Ok(())
+}
+
+/// Best-effort scan of a frontend script for addonConfig keys that aren't declared settings
+pub fn check_config_keys(script: &str, settings: &[AddonSetting]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    
+    for key in find_config_key_refs(script) {
+        if !settings.iter().any(|s| s.id == key) {
+            warnings.push(format!(
+                "frontend.js reads addonConfig.{} but no setting with id '{}' is declared",
+                key, key
+            ));
+        }
+    }
+    
+    warnings
+}
+
+/// Settings whose `depends_on` names no other setting; the UI shows those unconditionally
+pub fn check_setting_dependencies(settings: &[AddonSetting]) -> Vec<String> {
+    settings.iter()
+        .filter_map(|setting| {
+            let target = setting.depends_on.as_deref()?;
+            let exists = settings.iter().any(|s| s.id == target && s.id != setting.id);
+            (!exists).then(|| format!(
+                "setting '{}' depends on '{}', which is not another declared setting",
+                setting.id, target
+            ))
+        })
+        .collect()
+}
+
+/// Settings placed in a group `info.groups` doesn't list; the UI puts those in the default section
+pub fn check_setting_groups(groups: &[String], settings: &[AddonSetting]) -> Vec<String> {
+    settings.iter()
+        .filter_map(|setting| {
+            let group = setting.group.as_deref()?;
+            (!groups.iter().any(|g| g == group)).then(|| format!(
+                "setting '{}' is in group '{}', which is not listed in info.groups",
+                setting.id, group
+            ))
+        })
+        .collect()
+}
+
+/// Find keys read from window.addonConfig, including through simple aliases like
+/// `const config = window.addonConfig || {...}`
+fn find_config_key_refs(script: &str) -> Vec<String> {
+    let mut names = vec!["addonConfig".to_string()];
+    
+    for line in script.lines() {
+        let line = line.trim();
+        let rest = match ["const ", "let ", "var "].iter().find_map(|kw| line.strip_prefix(kw)) {
+            Some(rest) => rest,
+            None => continue,
+        };
+        
+        if let Some((name, value)) = rest.split_once('=') {
+            let name = name.trim();
+            let value = value.trim_start();
+            
+            if is_identifier(name)
+                && (value.starts_with("window.addonConfig") || value.starts_with("addonConfig"))
+                && !names.iter().any(|n| n == name)
+            {
+                names.push(name.to_string());
+            }
+        }
+    }
+    
+    let mut keys: Vec<String> = Vec::new();
+    
+    for name in &names {
+        for (pos, _) in script.match_indices(name.as_str()) {
+            // Must be a whole identifier, not the tail of a longer one
+            let before = script[..pos].chars().next_back();
+            if before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$') {
+                continue;
+            }
+            
+            let after = &script[pos + name.len()..];
+            let key = if let Some(rest) = after.strip_prefix('.') {
+                let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                    .unwrap_or(rest.len());
+                
+                // Skip method calls like config.hasOwnProperty(...)
+                if rest[end..].trim_start().starts_with('(') {
+                    continue;
+                }
+                &rest[..end]
+            } else if let Some(rest) = after.strip_prefix("['").or_else(|| after.strip_prefix("[\"")) {
+                match rest.find(['\'', '"']) {
+                    Some(end) => &rest[..end],
+                    None => continue,
+                }
+            } else {
+                continue;
+            };
+            
+            if is_identifier(key) && !keys.iter().any(|k| k == key) {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    
+    keys
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == '$' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// What a single backend.lua run may use before it is aborted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LuaLimits {
+    pub time: Duration,
+    /// 0 is unlimited
+    pub max_instructions: u64,
+    /// Bytes the Lua state may allocate; 0 is unlimited
+    pub memory_bytes: u64,
+}
+
+impl LuaLimits {
+    pub fn from_config(cfg: &crate::config::Config) -> Self {
+        Self {
+            time: LUA_TIME_LIMIT,
+            max_instructions: cfg.lua_max_instructions,
+            memory_bytes: cfg.lua_memory_limit_bytes,
+        }
+    }
+    
+    /// The limits in the saved config, or the defaults if it can't be read
+    pub fn load() -> Self {
+        let cfg = crate::config::load_config().unwrap_or_default();
+        Self::from_config(&cfg)
+    }
+}
+
+/// Abort a Lua script with an error once it runs too long, runs too many instructions
+/// or allocates too much memory
+pub fn limit_execution(lua: &Lua, limits: LuaLimits) -> Result<(), AppError> {
+    if limits.memory_bytes > 0 {
+        let bytes = usize::try_from(limits.memory_bytes).unwrap_or(usize::MAX);
+        lua.set_memory_limit(bytes)
+            .map_err(|e| AppError::Lua(format!("Failed to set memory limit: {}", e)))?;
+    }
+    
+    let started = Instant::now();
+    let instructions = std::sync::atomic::AtomicU64::new(0);
+    
+    lua.set_hook(LuaHookTriggers::new().every_nth_instruction(LUA_HOOK_INTERVAL), move |_, _| {
+        let instructions = instructions.fetch_add(u64::from(LUA_HOOK_INTERVAL), std::sync::atomic::Ordering::Relaxed)
+            + u64::from(LUA_HOOK_INTERVAL);
+        
+        if started.elapsed() > limits.time {
+            Err(LuaError::RuntimeError(format!(
+                "Script exceeded time limit of {}s",
+                limits.time.as_secs()
+            )))
+        } else if limits.max_instructions > 0 && instructions > limits.max_instructions {
+            Err(LuaError::RuntimeError(format!(
+                "Script exceeded instruction limit of {}",
+                limits.max_instructions
+            )))
+        } else {
+            Ok(())
+        }
+    });
     
     Ok(())
 }
 
+/// Check an addon.toml without installing it, reporting every problem found
+pub fn validate_manifest(toml_str: &str) -> Result<AddonManifest, Vec<String>> {
+    let value: toml::Value = toml::from_str(toml_str)
+        .map_err(|e| vec![format!("Invalid TOML: {}", e)])?;
+    
+    let mut errors = Vec::new();
+    
+    match value.get("info") {
+        Some(info) => {
+            let name = info.get("name").and_then(|v| v.as_str()).unwrap_or("");
+            if name.trim().is_empty() {
+                errors.push("info.name is missing".to_string());
+            }
+            if info.get("version").and_then(|v| v.as_str()).is_none() {
+                errors.push("info.version is missing".to_string());
+            }
+        }
+        None => errors.push("[info] section is missing".to_string()),
+    }
+    
+    let settings = match value.get("settings") {
+        Some(toml::Value::Array(settings)) => settings.as_slice(),
+        Some(_) => {
+            errors.push("settings must be an array of [[settings]] tables".to_string());
+            &[]
+        }
+        None => {
+            errors.push("settings is missing".to_string());
+            &[]
+        }
+    };
+    
+    let mut seen_ids = std::collections::HashSet::new();
+    
+    for (index, setting) in settings.iter().enumerate() {
+        let id = setting.get("id").and_then(|v| v.as_str());
+        let label = id.map_or_else(|| format!("settings[{}]", index), |id| format!("setting '{}'", id));
+        
+        match id {
+            Some(id) => {
+                if !seen_ids.insert(id) {
+                    errors.push(format!("Duplicate setting id '{}'", id));
+                }
+            }
+            None => errors.push(format!("{} is missing an id", label)),
+        }
+        
+        if setting.get("name").and_then(|v| v.as_str()).is_none() {
+            errors.push(format!("{} is missing a name", label));
+        }
+        
+        match setting.get("type").and_then(|v| v.as_str()) {
+            Some(setting_type) if SETTING_TYPES.contains(&setting_type) => {}
+            Some(setting_type) => errors.push(format!(
+                "{} has unknown type '{}' (expected one of: {})",
+                label, setting_type, SETTING_TYPES.join(", ")
+            )),
+            None => errors.push(format!("{} is missing a type", label)),
+        }
+        
+        if setting.get("default").is_none() {
+            errors.push(format!("{} is missing a default", label));
+        }
+        
+        let min = setting.get("min").and_then(|v| v.as_integer());
+        let max = setting.get("max").and_then(|v| v.as_integer());
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                errors.push(format!("{} has min {} greater than max {}", label, min, max));
+            }
+        }
+    }
+    
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    
+    // Catch anything the checks above don't cover, like wrongly typed optional fields
+    value.try_into::<AddonManifest>()
+        .map_err(|e| vec![e.to_string()])
+}
+
+/// Whether an addon with this folder name is installed
+pub fn addon_exists(addon_id: &str) -> Result<bool, AppError> {
+    if addon_id.is_empty() || addon_id.contains(['/', '\\']) || addon_id.contains("..") {
+        return Ok(false);
+    }
+    
+    Ok(get_addons_dir()?.join(addon_id).join("addon.toml").is_file())
+}
+
+/// Read the permissions an addon declares, denying everything if the manifest can't be read
+pub fn load_permissions(addon_dir: &Path) -> Vec<String> {
+    std::fs::read_to_string(addon_dir.join("addon.toml"))
+        .ok()
+        .and_then(|content| toml::from_str::<AddonManifest>(&content).ok())
+        .map(|manifest| manifest.permissions)
+        .unwrap_or_default()
+}
+
+/// Register addon.execute_command, which only runs processes if the addon declared the permission
+pub fn register_execute_command(
+    lua: &Lua,
+    addon_api: &LuaTable,
+    addon_id: &str,
+    permissions: &[String],
+) -> Result<(), AppError> {
+    let addon_id = addon_id.to_string();
+    
+    let execute_command_fn = if permissions.iter().any(|p| p == PERMISSION_EXECUTE_COMMAND) {
+        lua.create_function(move |_, (command, args): (String, Option<Vec<String>>)| {
+            info!("[Addon: {}] Executing command: {} {:?}", addon_id, command, args);
+            
+            let mut cmd = std::process::Command::new(&command);
+            
+            if let Some(args_vec) = args {
+                cmd.args(&args_vec);
+            }
+            
+            match cmd.output() {
+                Ok(output) => {
+                    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+                    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+                    let success = output.status.success();
+                    
+                    Ok((success, stdout, stderr))
+                }
+                Err(e) => {
+                    Err(LuaError::RuntimeError(format!("Failed to execute command: {}", e)))
+                }
+            }
+        })
+    } else {
+        lua.create_function(move |_, _: LuaMultiValue| -> LuaResult<()> {
+            warn!("[Addon: {}] Blocked execute_command: permission not declared", addon_id);
+            Err(LuaError::RuntimeError(format!(
+                "Addon '{}' is missing the \"{}\" permission in addon.toml",
+                addon_id, PERMISSION_EXECUTE_COMMAND
+            )))
+        })
+    }.map_err(|e| AppError::Lua(format!("Failed to create execute_command function: {}", e)))?;
+    
+    addon_api.set("execute_command", execute_command_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set execute_command: {}", e)))
+}
+
+/// Register addon.http_get(url), which returns (status_code, body) if the addon declared network access
+pub fn register_http_get(
+    lua: &Lua,
+    addon_api: &LuaTable,
+    addon_id: &str,
+    permissions: &[String],
+) -> Result<(), AppError> {
+    let addon_id = addon_id.to_string();
+    let allowed = permissions.iter().any(|p| p == PERMISSION_NETWORK);
+    
+    let http_get_fn = lua.create_function(move |_, url: String| {
+        if !allowed {
+            return Err(LuaError::RuntimeError(format!(
+                "Addon '{}' is missing the \"{}\" permission in addon.toml",
+                addon_id, PERMISSION_NETWORK
+            )));
+        }
+        
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(LuaError::RuntimeError(format!("Only http(s) URLs are allowed: {}", url)));
+        }
+        
+        info!("[Addon: {}] HTTP GET {}", addon_id, url);
+        
+        // The blocking client panics if used on a Tokio worker, so give it its own thread
+        std::thread::spawn(move || http_get(&url))
+            .join()
+            .map_err(|_| LuaError::RuntimeError("HTTP request thread panicked".to_string()))?
+            .map_err(LuaError::RuntimeError)
+    }).map_err(|e| AppError::Lua(format!("Failed to create http_get function: {}", e)))?;
+    
+    addon_api.set("http_get", http_get_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set http_get: {}", e)))
+}
+
+/// Register addon.list_media(), which returns the playlist as an array of {name, type, size}
+/// tables if the addon declared media access
+pub fn register_list_media(
+    lua: &Lua,
+    addon_api: &LuaTable,
+    addon_id: &str,
+    permissions: &[String],
+) -> Result<(), AppError> {
+    let addon_id = addon_id.to_string();
+    let allowed = permissions.iter().any(|p| p == PERMISSION_MEDIA_READ);
+    
+    let list_media_fn = lua.create_function(move |lua, ()| {
+        if !allowed {
+            return Err(LuaError::RuntimeError(format!(
+                "Addon '{}' is missing the \"{}\" permission in addon.toml",
+                addon_id, PERMISSION_MEDIA_READ
+            )));
+        }
+        
+        // Lua is called from async code, and blocking a Tokio worker on the runtime it
+        // belongs to panics, so the listing runs on its own thread and runtime
+        let files = std::thread::spawn(list_media)
+            .join()
+            .map_err(|_| LuaError::RuntimeError("Media listing thread panicked".to_string()))?
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to list media: {}", e)))?;
+        
+        let list = lua.create_table_with_capacity(files.len(), 0)?;
+        for file in files {
+            let entry = lua.create_table()?;
+            entry.set("name", file.name)?;
+            entry.set("type", file.file_type)?;
+            entry.set("size", file.size)?;
+            list.push(entry)?;
+        }
+        Ok(list)
+    }).map_err(|e| AppError::Lua(format!("Failed to create list_media function: {}", e)))?;
+    
+    addon_api.set("list_media", list_media_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set list_media: {}", e)))
+}
+
+/// The default collection in playlist order
+fn list_media() -> Result<Vec<crate::media::MediaFile>, AppError> {
+    let order = crate::config::load_config()?.media_order;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(crate::media::get_files(None, &order))
+}
+
+/// Receives the `addon-event` payloads raised by addon.emit
+pub type EventSink = Arc<dyn Fn(serde_json::Value) + Send + Sync>;
+
+/// Register addon.emit(event_name, json_string), which forwards the event to the display window
+pub fn register_emit(lua: &Lua, addon_api: &LuaTable, addon_id: &str, sink: EventSink) -> Result<(), AppError> {
+    let addon_id = addon_id.to_string();
+    
+    let emit_fn = lua.create_function(move |_, (event_name, payload): (String, String)| {
+        let payload: serde_json::Value = serde_json::from_str(&payload)
+            .map_err(|e| LuaError::RuntimeError(format!("Payload for '{}' is not valid JSON: {}", event_name, e)))?;
+        
+        debug!("[Addon: {}] Emitting {}", addon_id, event_name);
+        sink(serde_json::json!({
+            "addon_id": addon_id,
+            "event_name": event_name,
+            "payload": payload,
+        }));
+        Ok(())
+    }).map_err(|e| AppError::Lua(format!("Failed to create emit function: {}", e)))?;
+    
+    addon_api.set("emit", emit_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set emit: {}", e)))
+}
+
+/// Register addon.json_decode(string) and addon.json_encode(value). JSON null decodes to nil,
+/// and Lua sequences encode as arrays.
+pub fn register_json_api(lua: &Lua, addon_api: &LuaTable) -> Result<(), AppError> {
+    let decode_fn = lua.create_function(|lua, text: String| {
+        let value: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid JSON: {}", e)))?;
+        
+        let options = mlua::SerializeOptions::new()
+            .serialize_none_to_null(false)
+            .serialize_unit_to_null(false);
+        lua.to_value_with(&value, options)
+    }).map_err(|e| AppError::Lua(format!("Failed to create json_decode function: {}", e)))?;
+    
+    addon_api.set("json_decode", decode_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set json_decode: {}", e)))?;
+    
+    let encode_fn = lua.create_function(|lua, value: LuaValue| {
+        let value: serde_json::Value = lua.from_value(value)?;
+        serde_json::to_string(&value)
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to encode JSON: {}", e)))
+    }).map_err(|e| AppError::Lua(format!("Failed to create json_encode function: {}", e)))?;
+    
+    addon_api.set("json_encode", encode_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set json_encode: {}", e)))
+}
+
+/// Register addon.get_config(key) and addon.set_config(key, value), which read and write the
+/// addon's own entry in config.json. `enabled` stays under the user's control.
+pub fn register_config_api(lua: &Lua, addon_api: &LuaTable, addon_id: &str) -> Result<(), AppError> {
+    let get_id = addon_id.to_string();
+    let get_config_fn = lua.create_function(move |lua, key: String| {
+        let config = crate::config::load_config().map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+        match config.addons.get(&get_id).and_then(|values| values.get(&key)) {
+            Some(value) => lua.to_value(value),
+            None => Ok(LuaValue::Nil),
+        }
+    }).map_err(|e| AppError::Lua(format!("Failed to create get_config function: {}", e)))?;
+    
+    addon_api.set("get_config", get_config_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set get_config: {}", e)))?;
+    
+    let set_id = addon_id.to_string();
+    let set_config_fn = lua.create_function(move |lua, (key, value): (String, LuaValue)| {
+        if key.is_empty() || key == "enabled" || key == "password" {
+            return Err(LuaError::RuntimeError(format!("Addons can't set '{}'", key)));
+        }
+        let value: serde_json::Value = lua.from_value(value)?;
+        
+        let mut config = crate::config::load_config().map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+        config.addons.entry(set_id.clone()).or_default().insert(key.clone(), value);
+        crate::config::save_config(&config).map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+        
+        debug!("[Addon: {}] Saved config value {}", set_id, key);
+        Ok(())
+    }).map_err(|e| AppError::Lua(format!("Failed to create set_config function: {}", e)))?;
+    
+    addon_api.set("set_config", set_config_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set set_config: {}", e)))
+}
+
+/// Resolve a path an addon passed in against its own folder, refusing anything that would leave it
+pub fn resolve_addon_path(addon_dir: &Path, relative: &str) -> Result<PathBuf, AppError> {
+    use std::path::Component;
+    
+    let relative = Path::new(relative);
+    if relative.as_os_str().is_empty() {
+        return Err(AppError::Validation("Path is empty".to_string()));
+    }
+    
+    for component in relative.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {}
+            _ => return Err(AppError::Validation(format!("Path must stay inside the addon folder: {}", relative.display()))),
+        }
+    }
+    
+    Ok(addon_dir.join(relative))
+}
+
+/// Register addon.read_file(path) and addon.write_file(path, contents), relative to the addon's folder
+pub fn register_file_api(lua: &Lua, addon_api: &LuaTable, addon_dir: &Path) -> Result<(), AppError> {
+    let read_dir = addon_dir.to_path_buf();
+    let read_file_fn = lua.create_function(move |_, path: String| {
+        let full_path = resolve_addon_path(&read_dir, &path).map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+        
+        std::fs::read_to_string(&full_path)
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to read {}: {}", path, e)))
+    }).map_err(|e| AppError::Lua(format!("Failed to create read_file function: {}", e)))?;
+    
+    addon_api.set("read_file", read_file_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set read_file: {}", e)))?;
+    
+    let write_dir = addon_dir.to_path_buf();
+    let write_file_fn = lua.create_function(move |_, (path, contents): (String, String)| {
+        let full_path = resolve_addon_path(&write_dir, &path).map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+        
+        if let Some(parent) = full_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| LuaError::RuntimeError(format!("Failed to create folder for {}: {}", path, e)))?;
+        }
+        
+        std::fs::write(&full_path, contents)
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to write {}: {}", path, e)))
+    }).map_err(|e| AppError::Lua(format!("Failed to create write_file function: {}", e)))?;
+    
+    addon_api.set("write_file", write_file_fn)
+        .map_err(|e| AppError::Lua(format!("Failed to set write_file: {}", e)))
+}
+
+fn http_get(url: &str) -> Result<(u16, String), String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    
+    let response = client.get(url).send()
+        .map_err(|e| format!("HTTP request failed: {}", e))?;
+    
+    let status = response.status().as_u16();
+    let body = response.text()
+        .map_err(|e| format!("Failed to read response body: {}", e))?;
+    
+    Ok((status, body))
+}
+
 pub async fn get_frontend_script_with_config(
     addon_id: &str,
     addon_config: &HashMap<String, serde_json::Value>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let addons_dir = get_addons_dir()?;
     let frontend_path = addons_dir.join(addon_id).join("frontend.js");
     
     if !frontend_path.exists() {
-        return Err("Frontend script not found".to_string());
+        return Err(AppError::NotFound("Frontend script not found".to_string()));
     }
     
-    let script = fs::read_to_string(&frontend_path).await
-        .map_err(|e| e.to_string())?;
+    let script = fs::read_to_string(&frontend_path).await?;
     
     // Inject config before the script
     let config_json = serde_json::to_string(addon_config)
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::Validation(e.to_string()))?;
     
     let wrapped_script = format!(
         "window.addonConfig = {};\n{}",
@@ -341,26 +1176,140 @@ pub async fn get_frontend_script_with_config(
     Ok(wrapped_script)
 }
 
-pub async fn get_frontend_script(addon_id: &str) -> Result<String, String> {
+/// ETag for an addon's config-injected frontend.js: changes when the file or the config does
+pub fn frontend_etag(addon_id: &str, addon_config: &HashMap<String, serde_json::Value>) -> Result<String, AppError> {
+    use std::hash::{Hash, Hasher};
+    
+    let modified = std::fs::metadata(get_addons_dir()?.join(addon_id).join("frontend.js"))
+        .and_then(|meta| meta.modified())?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    
+    // Going through Value sorts the keys, so the same config always hashes the same
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    serde_json::to_value(addon_config)
+        .map(|v| v.to_string())
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    
+    Ok(format!("{:x}-{:016x}", modified.as_nanos(), hasher.finish()))
+}
+
+pub async fn get_frontend_script(addon_id: &str) -> Result<String, AppError> {
     let addons_dir = get_addons_dir()?;
     let frontend_path = addons_dir.join(addon_id).join("frontend.js");
     
     if !frontend_path.exists() {
-        return Err("Frontend script not found".to_string());
+        return Err(AppError::NotFound("Frontend script not found".to_string()));
     }
     
-    let script = fs::read_to_string(&frontend_path).await
-        .map_err(|e| e.to_string())?;
+    let script = fs::read_to_string(&frontend_path).await?;
     
     Ok(script)
 }
 
+/// Settings an installed addon declares; empty if its manifest doesn't parse
+pub async fn declared_settings(addon_id: &str) -> Result<Vec<AddonSetting>, AppError> {
+    Ok(scan_addons(false).await?
+        .into_iter()
+        .find(|addon| addon.id == addon_id)
+        .map(|addon| addon.settings)
+        .unwrap_or_default())
+}
+
+/// Check values saved for an addon against its declared settings, clamping numbers into
+/// min/max. Keys no setting declares pass through unchanged, apart from `enabled`.
+pub fn validate_config_values(
+    settings: &[AddonSetting],
+    addon_dir: &Path,
+    updates: &serde_json::Map<String, serde_json::Value>,
+) -> Result<HashMap<String, serde_json::Value>, String> {
+    let mut values = HashMap::new();
+    
+    for (key, value) in updates {
+        if key == "password" {
+            continue;
+        }
+        
+        let value = match settings.iter().find(|s| &s.id == key) {
+            Some(setting) => validate_setting_value(setting, addon_dir, value)?,
+            None if key == "enabled" && !value.is_boolean() => {
+                return Err(format!("Setting 'enabled' expects true or false, got {}", value));
+            }
+            None => value.clone(),
+        };
+        values.insert(key.clone(), value);
+    }
+    
+    Ok(values)
+}
+
+fn validate_setting_value(
+    setting: &AddonSetting,
+    addon_dir: &Path,
+    value: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let mismatch = |expected: &str| {
+        Err(format!("Setting '{}' expects {}, got {}", setting.id, expected, value))
+    };
+    
+    match setting.setting_type.as_str() {
+        "boolean" if !value.is_boolean() => mismatch("true or false"),
+        "text" if !value.is_string() => mismatch("text"),
+        "color" => match value.as_str() {
+            Some(color) if crate::config::is_hex_color(color) => Ok(value.clone()),
+            _ => mismatch("a color like #RRGGBB"),
+        },
+        "range" => {
+            if let Some(n) = value.as_i64() {
+                let n = setting.min.map_or(n, |min| n.max(min));
+                let n = setting.max.map_or(n, |max| n.min(max));
+                Ok(serde_json::json!(n))
+            } else if let Some(n) = value.as_f64() {
+                let n = setting.min.map_or(n, |min| n.max(min as f64));
+                let n = setting.max.map_or(n, |max| n.min(max as f64));
+                Ok(serde_json::json!(n))
+            } else {
+                mismatch("a number")
+            }
+        }
+        "select" => {
+            // Options are plain values or {value, label}; the settings form sends them back as strings
+            let as_text = |v: &serde_json::Value| v.as_str().map_or_else(|| v.to_string(), str::to_string);
+            let options = setting.options.as_deref().unwrap_or_default();
+            let allowed: Vec<String> = options.iter()
+                .map(|opt| as_text(opt.get("value").unwrap_or(opt)))
+                .collect();
+            
+            if allowed.is_empty() || allowed.contains(&as_text(value)) {
+                Ok(value.clone())
+            } else {
+                mismatch(&format!("one of {}", allowed.join(", ")))
+            }
+        }
+        "file" => match value.as_str() {
+            // Empty means no file chosen
+            Some("") => Ok(value.clone()),
+            Some(path) => {
+                let full_path = resolve_addon_path(addon_dir, path)?;
+                if full_path.is_file() {
+                    Ok(value.clone())
+                } else {
+                    Err(format!("Setting '{}': file {} not found in the addon folder", setting.id, path))
+                }
+            }
+            None => mismatch("a file path"),
+        },
+        _ => Ok(value.clone()),
+    }
+}
+
 pub fn merge_addon_config(
     addon: &mut Addon,
     saved_config: Option<&HashMap<String, serde_json::Value>>,
 ) {
     if let Some(saved) = saved_config {
-        addon.enabled = saved.get("enabled")
+        addon.enabled = addon.load_error.is_none() && saved.get("enabled")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
         
@@ -379,4 +1328,590 @@ pub fn merge_addon_config(
             addon.config.insert(setting.id.clone(), setting.default.clone());
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn setting(id: &str) -> AddonSetting {
+        AddonSetting {
+            id: id.to_string(),
+            name: id.to_string(),
+            setting_type: "text".to_string(),
+            default: serde_json::Value::Null,
+            description: None,
+            placeholder: None,
+            min: None,
+            max: None,
+            unit: None,
+            options: None,
+            depends_on: None,
+            depends_value: None,
+            group: None,
+        }
+    }
+    
+    #[test]
+    fn test_validate_config_values() {
+        let dir = std::env::temp_dir().join(format!("addon-values-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("images")).unwrap();
+        std::fs::write(dir.join("images").join("logo.png"), b"png").unwrap();
+        
+        let settings = vec![
+            AddonSetting { setting_type: "color".to_string(), ..setting("accent") },
+            AddonSetting { setting_type: "range".to_string(), min: Some(8), max: Some(96), ..setting("size") },
+            AddonSetting { setting_type: "boolean".to_string(), ..setting("seconds") },
+            AddonSetting { setting_type: "file".to_string(), ..setting("logo") },
+        ];
+        let validate = |updates: serde_json::Value| {
+            validate_config_values(&settings, &dir, updates.as_object().unwrap())
+        };
+        
+        let values = validate(serde_json::json!({
+            "accent": "#1a2B3c",
+            "size": 500,
+            "logo": "images/logo.png",
+            "enabled": true,
+            "password": "secret",
+        })).unwrap();
+        assert_eq!(values["accent"], "#1a2B3c");
+        assert_eq!(values["size"], 96);
+        assert_eq!(values["logo"], "images/logo.png");
+        assert!(!values.contains_key("password"));
+        
+        let err = validate(serde_json::json!({ "seconds": "yes" })).unwrap_err();
+        assert!(err.contains("'seconds' expects true or false"), "{}", err);
+        assert!(validate(serde_json::json!({ "size": true })).is_err());
+        assert!(validate(serde_json::json!({ "accent": "red" })).is_err());
+        assert!(validate(serde_json::json!({ "logo": "images/missing.png" })).is_err());
+        assert!(validate(serde_json::json!({ "logo": "../outside.png" })).is_err());
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_config_key_warnings() {
+        let script = r#"
+            const config = window.addonConfig || { fontSize: 12 };
+            el.style.fontSize = config.fontSize + 'px';
+            el.style.color = window.addonConfig.font_color;
+            if (config.hasOwnProperty('x')) {}
+            const mode = addonConfig['mode'];
+        "#;
+        let settings = vec![setting("fontSize"), setting("mode")];
+        
+        let warnings = check_config_keys(script, &settings);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("font_color"));
+    }
+    
+    fn run_execute_command(permissions: &[String]) -> LuaResult<()> {
+        let lua = Lua::new();
+        let addon_api = lua.create_table().unwrap();
+        register_execute_command(&lua, &addon_api, "test-addon", permissions).unwrap();
+        lua.globals().set("addon", addon_api).unwrap();
+        
+        lua.load(r#"addon.execute_command("image-presenter-no-such-command")"#).exec()
+    }
+    
+    #[test]
+    fn test_execute_command_requires_permission() {
+        let err = run_execute_command(&[]).unwrap_err().to_string();
+        assert!(err.contains("missing the \"execute_command\" permission"), "{}", err);
+    }
+    
+    #[test]
+    fn test_execute_command_with_permission() {
+        // The command itself doesn't exist, but it gets as far as trying to spawn it
+        let err = run_execute_command(&[PERMISSION_EXECUTE_COMMAND.to_string()])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Failed to execute command"), "{}", err);
+    }
+    
+    #[test]
+    fn test_manifest_permissions() {
+        let manifest: AddonManifest = toml::from_str(r#"
+            permissions = ["execute_command"]
+            
+            [info]
+            name = "Test"
+            version = "1.0.0"
+            
+            [[settings]]
+            id = "enabled"
+            name = "Enabled"
+            type = "boolean"
+            default = false
+        "#).unwrap();
+        assert_eq!(manifest.permissions, vec!["execute_command".to_string()]);
+        
+        let manifest: AddonManifest = toml::from_str(r#"
+            settings = []
+            
+            [info]
+            name = "Test"
+            version = "1.0.0"
+        "#).unwrap();
+        assert!(manifest.permissions.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_malformed_manifest_keeps_previous_addon() {
+        let dir = std::env::temp_dir().join(format!("addons-reload-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let addon_dir = dir.join("reload-test-addon");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        
+        std::fs::write(addon_dir.join("addon.toml"), r#"
+            settings = []
+            
+            [info]
+            name = "Reload Test"
+            version = "1.0.0"
+        "#).unwrap();
+        
+        let addons = scan_addons_in(&dir, false).await.unwrap();
+        assert_eq!(addons.len(), 1);
+        
+        // Simulate an editor saving a broken manifest
+        std::fs::write(addon_dir.join("addon.toml"), "[info\nname = ").unwrap();
+        
+        let addons = scan_addons_in(&dir, false).await.unwrap();
+        assert_eq!(addons.len(), 1);
+        assert_eq!(addons[0].info.name, "Reload Test");
+        assert!(addons[0].last_error.as_deref().unwrap().contains("Failed to parse manifest"));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[tokio::test]
+    async fn test_dangling_setting_dependency_warns() {
+        let dir = std::env::temp_dir().join(format!("addons-depends-on-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let addon_dir = dir.join("clock");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        
+        std::fs::write(addon_dir.join("addon.toml"), r#"
+            [info]
+            name = "Clock"
+            version = "1.0.0"
+            
+            [[settings]]
+            id = "showDate"
+            name = "Show date"
+            type = "boolean"
+            default = false
+            
+            [[settings]]
+            id = "dateFormat"
+            name = "Date format"
+            type = "text"
+            default = "YYYY-MM-DD"
+            depends_on = "showDate"
+            depends_value = true
+            
+            [[settings]]
+            id = "seconds"
+            name = "Seconds"
+            type = "boolean"
+            default = false
+            depends_on = "showTime"
+        "#).unwrap();
+        
+        let addons = scan_addons_in(&dir, true).await.unwrap();
+        assert_eq!(addons.len(), 1);
+        assert!(addons[0].last_error.is_none());
+        assert_eq!(addons[0].settings.len(), 3);
+        assert_eq!(addons[0].settings[1].depends_on.as_deref(), Some("showDate"));
+        assert_eq!(addons[0].settings[1].depends_value, Some(serde_json::json!(true)));
+        assert_eq!(addons[0].config_key_warnings.len(), 1);
+        assert!(addons[0].config_key_warnings[0].contains("'seconds' depends on 'showTime'"));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[tokio::test]
+    async fn test_failing_backend_init_is_reported() {
+        let dir = std::env::temp_dir().join(format!("addons-init-error-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let addon_dir = dir.join("throws");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        
+        std::fs::write(addon_dir.join("addon.toml"), r#"
+            [[settings]]
+            id = "label"
+            type = "text"
+            name = "Label"
+            default = "Hello"
+            
+            [info]
+            name = "Throws"
+            version = "1.0.0"
+        "#).unwrap();
+        std::fs::write(addon_dir.join("backend.lua"), r#"
+            function init(settings) error("boom") end
+        "#).unwrap();
+        
+        let addons = scan_addons_in(&dir, false).await.unwrap();
+        assert_eq!(addons.len(), 1);
+        assert!(addons[0].last_error.as_deref().unwrap().contains("boom"));
+        assert_eq!(addons[0].settings.len(), 1);
+        
+        // An addon that never had a readable manifest is still listed
+        std::fs::create_dir_all(dir.join("broken")).unwrap();
+        std::fs::write(dir.join("broken").join("addon.toml"), "[info\nname = ").unwrap();
+        
+        let addons = scan_addons_in(&dir, false).await.unwrap();
+        let broken = addons.iter().find(|a| a.id == "broken").unwrap();
+        assert!(broken.last_error.is_some());
+        assert!(broken.settings.is_empty());
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[tokio::test]
+    async fn test_unchanged_addon_is_not_rescanned() {
+        let dir = std::env::temp_dir().join(format!("addons-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        write_manifest(&dir, "counter", "");
+        
+        // Each run of backend.lua appends a line, so the file counts init runs
+        let runs = dir.join("runs.txt");
+        let backend = format!(r#"
+            local f = io.open({:?}, "a")
+            f:write("run\n")
+            f:close()
+            addon.schedule(60, "refresh")
+            function init(settings) return settings end
+        "#, runs.to_string_lossy());
+        std::fs::write(dir.join("counter").join("backend.lua"), &backend).unwrap();
+        let run_count = || std::fs::read_to_string(&runs).unwrap_or_default().lines().count();
+        
+        scan_addons_in(&dir, false).await.unwrap();
+        let mut addons = scan_addons_in(&dir, false).await.unwrap();
+        assert_eq!(run_count(), 1);
+        // The scheduler relies on the registrations made during that one run
+        assert_eq!(addons[0].schedules, vec![crate::scheduler::ScheduledCall {
+            interval: std::time::Duration::from_secs(60),
+            function_name: "refresh".to_string(),
+        }]);
+        
+        // Changes to a returned addon must not leak into the cache
+        addons[0].enabled = true;
+        assert!(!scan_addons_in(&dir, false).await.unwrap()[0].enabled);
+        
+        std::fs::write(dir.join("counter").join("backend.lua"), format!("{}\n-- edited", backend)).unwrap();
+        scan_addons_in(&dir, false).await.unwrap();
+        assert_eq!(run_count(), 2);
+        
+        scan_addons_in(&dir, true).await.unwrap();
+        assert_eq!(run_count(), 3);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    fn write_manifest(dir: &Path, id: &str, extra: &str) {
+        std::fs::create_dir_all(dir.join(id)).unwrap();
+        std::fs::write(dir.join(id).join("addon.toml"), format!(r#"
+            settings = []
+            
+            [info]
+            name = "{}"
+            version = "1.0.0"
+            {}
+        "#, id, extra)).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_dependency_chain_loads_in_order() {
+        let dir = std::env::temp_dir().join(format!("addons-deps-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        
+        // Load orders alone would put these in reverse
+        write_manifest(&dir, "chain-base", "load_order = 3");
+        write_manifest(&dir, "chain-middle", "load_order = 2\nrequires = [\"chain-base\"]");
+        write_manifest(&dir, "chain-top", "load_order = 1\nrequires = [\"chain-middle\"]");
+        write_manifest(&dir, "chain-early", "load_order = -1");
+        write_manifest(&dir, "chain-orphan", "requires = [\"not-installed\"]");
+        
+        let addons = scan_addons_in(&dir, false).await.unwrap();
+        let ids: Vec<&str> = addons.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["chain-early", "chain-orphan", "chain-base", "chain-middle", "chain-top"]);
+        
+        let orphan = addons.iter().find(|a| a.id == "chain-orphan").unwrap();
+        assert!(orphan.load_error.as_deref().unwrap().contains("not-installed"));
+        assert!(addons.iter().filter(|a| a.id != "chain-orphan").all(|a| a.load_error.is_none()));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[tokio::test]
+    async fn test_dependency_cycle_disables_addons() {
+        let dir = std::env::temp_dir().join(format!("addons-cycle-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        
+        write_manifest(&dir, "cycle-a", "requires = [\"cycle-b\"]");
+        write_manifest(&dir, "cycle-b", "requires = [\"cycle-a\"]");
+        write_manifest(&dir, "cycle-user", "requires = [\"cycle-a\"]");
+        write_manifest(&dir, "cycle-free", "");
+        
+        let mut addons = scan_addons_in(&dir, false).await.unwrap();
+        assert_eq!(addons[0].id, "cycle-free");
+        assert!(addons[0].load_error.is_none());
+        for addon in &addons[1..] {
+            assert!(addon.load_error.as_deref().unwrap().contains("cycle"), "{}", addon.id);
+        }
+        
+        // Even if saved as enabled, a broken addon stays off
+        let saved: HashMap<String, serde_json::Value> = [("enabled".to_string(), true.into())].into();
+        for addon in &mut addons {
+            merge_addon_config(addon, Some(&saved));
+        }
+        let enabled: Vec<&str> = addons.iter().filter(|a| a.enabled).map(|a| a.id.as_str()).collect();
+        assert_eq!(enabled, ["cycle-free"]);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    /// Serve a single canned HTTP response on a local port
+    fn mock_server(body: &'static str) -> String {
+        use std::io::{Read, Write};
+        
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        
+        format!("http://{}/weather", addr)
+    }
+    
+    fn http_get_lua(permissions: &[String]) -> Lua {
+        let lua = Lua::new();
+        let addon_api = lua.create_table().unwrap();
+        register_http_get(&lua, &addon_api, "test-addon", permissions).unwrap();
+        lua.globals().set("addon", addon_api).unwrap();
+        lua
+    }
+    
+    #[test]
+    fn test_http_get() {
+        let url = mock_server(r#"{"temp": 21}"#);
+        let lua = http_get_lua(&[PERMISSION_NETWORK.to_string()]);
+        
+        let (status, body): (u16, String) = lua.load(format!("return addon.http_get({:?})", url))
+            .eval()
+            .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"temp": 21}"#);
+        
+        let err = lua.load(r#"addon.http_get("file:///etc/passwd")"#).exec().unwrap_err();
+        assert!(err.to_string().contains("Only http(s) URLs"), "{}", err);
+    }
+    
+    #[test]
+    fn test_http_get_requires_permission() {
+        let lua = http_get_lua(&[]);
+        let err = lua.load(r#"addon.http_get("http://127.0.0.1:1/")"#).exec().unwrap_err();
+        assert!(err.to_string().contains("missing the \"network\" permission"), "{}", err);
+    }
+    
+    #[test]
+    fn test_validate_manifest() {
+        let manifest = validate_manifest(r#"
+            [info]
+            name = "Clock"
+            version = "1.0.0"
+            
+            [[settings]]
+            id = "size"
+            name = "Size"
+            type = "range"
+            default = 24
+            min = 8
+            max = 96
+        "#).unwrap();
+        assert_eq!(manifest.info.name, "Clock");
+        assert_eq!(manifest.settings.len(), 1);
+        
+        let errors = validate_manifest(r#"
+            [info]
+            version = "1.0.0"
+            
+            [[settings]]
+            id = "size"
+            name = "Size"
+            type = "range"
+            default = 24
+            
+            [[settings]]
+            id = "size"
+            name = "Size again"
+            type = "slider"
+            default = 24
+        "#).unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("info.name")));
+        assert!(errors.iter().any(|e| e.contains("Duplicate setting id 'size'")));
+        assert!(errors.iter().any(|e| e.contains("unknown type 'slider'")));
+        
+        let errors = validate_manifest(r#"
+            settings = [{ id = "size", name = "Size", type = "range", default = 24, min = 96, max = 8 }]
+            
+            [info]
+            name = "Clock"
+            version = "1.0.0"
+        "#).unwrap_err();
+        assert_eq!(errors, vec!["setting 'size' has min 96 greater than max 8".to_string()]);
+    }
+    
+    #[test]
+    fn test_read_write_file() {
+        let dir = std::env::temp_dir().join(format!("addon-files-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        let lua = Lua::new();
+        let addon_api = lua.create_table().unwrap();
+        register_file_api(&lua, &addon_api, &dir).unwrap();
+        lua.globals().set("addon", addon_api).unwrap();
+        
+        let count: String = lua.load(r#"
+            addon.write_file("state/count.txt", "41")
+            local count = tonumber(addon.read_file("state/count.txt")) + 1
+            addon.write_file("state/count.txt", tostring(count))
+            return addon.read_file("./state/count.txt")
+        "#).eval().unwrap();
+        assert_eq!(count, "42");
+        assert_eq!(std::fs::read_to_string(dir.join("state").join("count.txt")).unwrap(), "42");
+        
+        assert!(lua.load(r#"addon.read_file("missing.txt")"#).exec().is_err());
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_file_api_rejects_traversal() {
+        let dir = std::env::temp_dir().join(format!("addon-traversal-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        let lua = Lua::new();
+        let addon_api = lua.create_table().unwrap();
+        register_file_api(&lua, &addon_api, &dir).unwrap();
+        lua.globals().set("addon", addon_api).unwrap();
+        
+        let escape = lua.load(r#"addon.write_file("../escaped.txt", "x")"#).exec().unwrap_err();
+        assert!(escape.to_string().contains("inside the addon folder"));
+        assert!(!dir.parent().unwrap().join("escaped.txt").exists());
+        
+        assert!(lua.load(r#"addon.read_file("state/../../config.json")"#).exec().is_err());
+        assert!(lua.load(r#"addon.read_file("/etc/hostname")"#).exec().is_err());
+        assert!(lua.load(r#"addon.read_file("")"#).exec().is_err());
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_emit_forwards_event() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink: EventSink = {
+            let events = events.clone();
+            Arc::new(move |event| events.lock().unwrap().push(event))
+        };
+        
+        let lua = Lua::new();
+        let addon_api = lua.create_table().unwrap();
+        register_emit(&lua, &addon_api, "ticker", sink).unwrap();
+        lua.globals().set("addon", addon_api).unwrap();
+        
+        lua.load(r#"
+            function refresh()
+                addon.emit("headline", '{"text": "Hello"}')
+            end
+        "#).exec().unwrap();
+        lua.globals().get::<_, LuaFunction>("refresh").unwrap().call::<_, ()>(()).unwrap();
+        
+        assert_eq!(*events.lock().unwrap(), vec![serde_json::json!({
+            "addon_id": "ticker",
+            "event_name": "headline",
+            "payload": {"text": "Hello"},
+        })]);
+        
+        let invalid = lua.load(r#"addon.emit("headline", "not json")"#).exec().unwrap_err();
+        assert!(invalid.to_string().contains("not valid JSON"));
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+    
+    #[test]
+    fn test_json_round_trip() {
+        let lua = Lua::new();
+        let addon_api = lua.create_table().unwrap();
+        register_json_api(&lua, &addon_api).unwrap();
+        lua.globals().set("addon", addon_api).unwrap();
+        
+        let original = serde_json::json!({
+            "title": "Weather",
+            "count": 3,
+            "ratio": 0.5,
+            "live": true,
+            "missing": null,
+            "days": [{"name": "Mon", "temps": [12, 18]}, {"name": "Tue", "temps": []}],
+        });
+        lua.globals().set("input", original.to_string()).unwrap();
+        
+        let (encoded, first_day, high, missing_is_nil): (String, String, i64, bool) = lua.load(r#"
+            local data = addon.json_decode(input)
+            return addon.json_encode(data), data.days[1].name, data.days[1].temps[2], data.missing == nil
+        "#).eval().unwrap();
+        assert_eq!(first_day, "Mon");
+        assert_eq!(high, 18);
+        assert!(missing_is_nil);
+        
+        // null has no Lua equivalent, so it comes back as an absent key
+        let mut expected = original.clone();
+        expected.as_object_mut().unwrap().remove("missing");
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&encoded).unwrap(), expected);
+        
+        let built: String = lua.load(r#"return addon.json_encode({ tags = { "a", "b" }, size = 2 })"#).eval().unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&built).unwrap(), serde_json::json!({ "tags": ["a", "b"], "size": 2 }));
+        
+        let invalid = lua.load(r#"addon.json_decode("{not json")"#).exec().unwrap_err();
+        assert!(invalid.to_string().contains("Invalid JSON"));
+    }
+    
+    #[test]
+    fn test_runaway_scripts_are_stopped() {
+        let limits = LuaLimits {
+            time: LUA_TIME_LIMIT,
+            max_instructions: 1_000_000,
+            memory_bytes: 4 * 1024 * 1024,
+        };
+        
+        let lua = Lua::new();
+        limit_execution(&lua, limits).unwrap();
+        let looped = lua.load("while true do end").exec().unwrap_err();
+        assert!(looped.to_string().contains("instruction limit"), "{}", looped);
+        
+        let lua = Lua::new();
+        limit_execution(&lua, LuaLimits { max_instructions: 0, ..limits }).unwrap();
+        let hoarded = lua.load("local t = {} while true do t[#t + 1] = string.rep('x', 1024) .. #t end").exec().unwrap_err();
+        assert!(matches!(hoarded, LuaError::MemoryError(_)), "{}", hoarded);
+        
+        // Well-behaved scripts are unaffected
+        let lua = Lua::new();
+        limit_execution(&lua, limits).unwrap();
+        let sum: i64 = lua.load("local n = 0 for i = 1, 1000 do n = n + i end return n").eval().unwrap();
+        assert_eq!(sum, 500_500);
+    }
+}
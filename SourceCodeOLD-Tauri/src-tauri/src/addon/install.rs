@@ -0,0 +1,233 @@
+use crate::error::AppError;
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+use tracing::{info, warn};
+use zip::ZipArchive;
+
+/// Refuse addon archives that would expand to more than this, to guard against zip bombs
+const MAX_ADDON_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Why an addon archive was not installed
+#[derive(Debug)]
+pub enum InstallRejection {
+    /// An addon with this folder name exists and overwriting wasn't asked for
+    AlreadyInstalled(String),
+    Failed(String),
+}
+
+/// An addon archive that passed validation and is ready to be written out
+#[derive(Debug)]
+pub struct AddonArchive {
+    pub id: String,
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// Read an uploaded zip holding exactly one addon folder with a valid addon.toml.
+/// Any entry that would land outside that folder rejects the whole archive.
+pub fn read_addon_zip(data: &[u8]) -> Result<AddonArchive, Vec<String>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| vec![format!("Not a valid zip file: {}", e)])?;
+    
+    let mut errors = Vec::new();
+    let mut folders = std::collections::BTreeSet::new();
+    let mut files = Vec::new();
+    let mut total_bytes = 0u64;
+    
+    for i in 0..archive.len() {
+        let mut file = match archive.by_index(i) {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push(format!("Failed to read entry {}: {}", i, e));
+                continue;
+            }
+        };
+        
+        let name = file.name().to_string();
+        
+        // enclosed_name rejects absolute paths and anything climbing out with ..
+        let path = match file.enclosed_name() {
+            Some(path) if path.components().all(|c| matches!(c, Component::Normal(_))) => path,
+            _ => {
+                errors.push(format!("{}: unsafe path", name));
+                continue;
+            }
+        };
+        
+        if let Some(Component::Normal(folder)) = path.components().next() {
+            folders.insert(folder.to_string_lossy().to_string());
+        }
+        
+        if file.is_dir() {
+            continue;
+        }
+        
+        if path.components().count() < 2 {
+            errors.push(format!("{}: files must be inside the addon folder", name));
+            continue;
+        }
+        
+        total_bytes += file.size();
+        if total_bytes > MAX_ADDON_BYTES {
+            return Err(vec![format!(
+                "Addon expands to more than {} MB",
+                MAX_ADDON_BYTES / (1024 * 1024)
+            )]);
+        }
+        
+        let mut contents = Vec::new();
+        if let Err(e) = file.read_to_end(&mut contents) {
+            errors.push(format!("{}: failed to extract: {}", name, e));
+            continue;
+        }
+        
+        files.push((path, contents));
+    }
+    
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    
+    let id = match folders.len() {
+        1 => folders.into_iter().next().unwrap_or_default(),
+        0 => return Err(vec!["Archive is empty".to_string()]),
+        _ => return Err(vec![format!(
+            "Archive must contain exactly one addon folder, found: {}",
+            folders.into_iter().collect::<Vec<_>>().join(", ")
+        )]),
+    };
+    
+    if let Err(e) = crate::media::sanitize_filename(&id) {
+        return Err(vec![format!("Invalid addon folder name: {}", e)]);
+    }
+    
+    let manifest_path = Path::new(&id).join("addon.toml");
+    let manifest = files.iter().find(|(path, _)| *path == manifest_path);
+    match manifest {
+        Some((_, contents)) => {
+            crate::addon::validate_manifest(&String::from_utf8_lossy(contents))
+                .map_err(|problems| problems.into_iter()
+                    .map(|p| format!("{}/addon.toml: {}", id, p))
+                    .collect::<Vec<_>>())?;
+        }
+        None => return Err(vec![format!("{}: missing addon.toml", id)]),
+    }
+    
+    Ok(AddonArchive { id, files })
+}
+
+/// Write a validated addon into `addons_dir`. It is unpacked into a hidden folder first
+/// and moved into place whole, so a reload never sees half an addon.
+pub fn install_addon(addons_dir: &Path, archive: AddonArchive, overwrite: bool) -> Result<String, InstallRejection> {
+    let target = addons_dir.join(&archive.id);
+    if target.exists() && !overwrite {
+        return Err(InstallRejection::AlreadyInstalled(archive.id));
+    }
+    
+    let staging = addons_dir.join(format!(".install-{}", archive.id));
+    let _ = std::fs::remove_dir_all(&staging);
+    
+    if let Err(e) = unpack(&staging, &archive) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(InstallRejection::Failed(e));
+    }
+    
+    if target.exists() {
+        if let Err(e) = std::fs::remove_dir_all(&target) {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(InstallRejection::Failed(format!("Failed to remove old version: {}", e)));
+        }
+        warn!("Replacing installed addon {}", archive.id);
+    }
+    
+    std::fs::rename(&staging, &target)
+        .map_err(|e| InstallRejection::Failed(format!("Failed to move addon into place: {}", e)))?;
+    
+    info!("Installed addon {} ({} files)", archive.id, archive.files.len());
+    Ok(archive.id)
+}
+
+/// Delete an installed addon's folder, returning false if there is no such folder.
+/// Only a real directory directly inside `addons_dir` is removed, so a crafted id or a
+/// symlink can't take anything else with it.
+pub fn uninstall_addon(addons_dir: &Path, addon_id: &str) -> Result<bool, AppError> {
+    if crate::media::sanitize_filename(addon_id).is_err() {
+        return Ok(false);
+    }
+    
+    let target = addons_dir.join(addon_id);
+    let is_folder = std::fs::symlink_metadata(&target).is_ok_and(|m| m.is_dir());
+    if !is_folder {
+        return Ok(false);
+    }
+    
+    std::fs::remove_dir_all(&target)
+        .map_err(|e| AppError::Io(format!("Failed to remove addon: {}", e)))?;
+    
+    info!("Uninstalled addon {}", addon_id);
+    Ok(true)
+}
+
+fn unpack(staging: &Path, archive: &AddonArchive) -> Result<(), String> {
+    for (path, contents) in &archive.files {
+        // Drop the addon folder; staging stands in for it
+        let relative: PathBuf = path.components().skip(1).collect();
+        let dest = staging.join(relative);
+        
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        std::fs::write(&dest, contents)
+            .map_err(|e| format!("Failed to write {:?}: {}", dest, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+    
+    const MANIFEST: &[u8] = b"settings = []\n[info]\nname = \"Clock\"\nversion = \"1.0\"\n";
+    
+    fn make_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+    
+    #[test]
+    fn test_read_addon_zip() {
+        let archive = read_addon_zip(&make_zip(&[
+            ("clock/addon.toml", MANIFEST),
+            ("clock/assets/face.svg", b"<svg></svg>"),
+        ])).unwrap();
+        assert_eq!(archive.id, "clock");
+        assert_eq!(archive.files.len(), 2);
+        
+        // Zip-slip: nothing may land outside the addon folder
+        let errors = read_addon_zip(&make_zip(&[
+            ("clock/addon.toml", MANIFEST),
+            ("clock/../../evil.sh", b"rm -rf /"),
+        ])).unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("unsafe path")), "{:?}", errors);
+        assert!(read_addon_zip(&make_zip(&[("/etc/cron.d/evil", b"")])).is_err());
+        
+        let errors = read_addon_zip(&make_zip(&[("clock/frontend.js", b"")])).unwrap_err();
+        assert_eq!(errors, vec!["clock: missing addon.toml"]);
+        
+        let errors = read_addon_zip(&make_zip(&[
+            ("clock/addon.toml", MANIFEST),
+            ("weather/addon.toml", MANIFEST),
+        ])).unwrap_err();
+        assert!(errors[0].contains("exactly one addon folder"));
+        
+        assert!(read_addon_zip(&make_zip(&[("clock/addon.toml", b"[info]\n")])).is_err());
+        assert!(read_addon_zip(b"not a zip").is_err());
+    }
+}
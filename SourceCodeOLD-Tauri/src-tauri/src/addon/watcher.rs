@@ -0,0 +1,73 @@
+use notify::{RecursiveMode, Watcher};
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// How long the Addons folder must stay quiet before a reload, so one save triggers one reload
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Files that make up an addon; anything else (e.g. written by addon.write_file) is just data
+const SOURCE_EXTENSIONS: &[&str] = &["toml", "lua", "js", "css", "html"];
+
+/// Whether a change to this path could change how an addon loads or runs
+fn is_addon_source(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => SOURCE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()),
+        // Folders appearing or disappearing add or remove whole addons
+        None => true,
+    }
+}
+
+/// Watch the Addons directory and run `on_change` once edits settle down
+pub async fn watch_addons<F, Fut>(on_change: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let addons_dir = match super::get_addons_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            warn!("Addon watcher disabled: {}", e);
+            return;
+        }
+    };
+    
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    
+    let mut watcher = match notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if !event.kind.is_access() && event.paths.iter().any(|p| is_addon_source(p)) {
+                let _ = tx.send(());
+            }
+        }
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Addon watcher disabled: {}", e);
+            return;
+        }
+    };
+    
+    if let Err(e) = watcher.watch(&addons_dir, RecursiveMode::Recursive) {
+        warn!("Addon watcher disabled: {}", e);
+        return;
+    }
+    
+    info!("Watching {:?} for addon changes", addons_dir);
+    
+    while rx.recv().await.is_some() {
+        // Keep waiting until no new events arrive for a full debounce period
+        loop {
+            match tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                Ok(Some(())) => continue,
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+        
+        info!("Addons changed on disk, reloading");
+        on_change().await;
+    }
+}
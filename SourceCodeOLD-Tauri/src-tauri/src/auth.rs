@@ -0,0 +1,229 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Prefix marking a password that has already been salted and hashed
+const HASH_PREFIX: &str = "sha256:";
+
+/// Name of the cookie holding a web UI session token
+pub const SESSION_COOKIE: &str = "session";
+
+/// Routes that must stay reachable without credentials
+const PUBLIC_ROUTES: &[&str] = &["/api/login"];
+
+static SESSIONS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn sessions() -> &'static Mutex<HashSet<String>> {
+    SESSIONS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+fn digest(salt: &str, password: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(password.as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+/// Salt and hash a password for storage in the config
+pub fn hash_password(password: &str) -> String {
+    let salt = random_hex(16);
+    format!("{}{}:{}", HASH_PREFIX, salt, digest(&salt, password))
+}
+
+pub fn is_hashed(stored: &str) -> bool {
+    stored.starts_with(HASH_PREFIX)
+}
+
+/// Check a candidate password against a stored hash
+pub fn verify_password(stored: &str, candidate: &str) -> bool {
+    let Some((salt, expected)) = stored
+        .strip_prefix(HASH_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+    else {
+        return false;
+    };
+    
+    // Compare every byte so the timing doesn't leak how much matched
+    let actual = digest(salt, candidate);
+    actual.len() == expected.len()
+        && actual.bytes().zip(expected.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Replace a plaintext password (from an old config or the settings window) with its hash
+pub fn hash_if_plaintext(config: &mut crate::config::Config) -> bool {
+    if config.password.is_empty() || is_hashed(&config.password) {
+        return false;
+    }
+    
+    config.password = hash_password(&config.password);
+    true
+}
+
+/// Start a session for the web UI, returning its token
+pub fn create_session() -> String {
+    let token = random_hex(32);
+    sessions().lock().unwrap().insert(token.clone());
+    token
+}
+
+/// Forget every session, e.g. after the password changes
+pub fn clear_sessions() {
+    sessions().lock().unwrap().clear();
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION)?
+        .to_str().ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+fn session_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers.get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+/// Whether the request carries the password or a valid session
+pub fn is_authorized(stored_password: &str, headers: &HeaderMap) -> bool {
+    if stored_password.is_empty() {
+        return true;
+    }
+    
+    if let Some(password) = bearer_token(headers) {
+        if verify_password(stored_password, password) {
+            return true;
+        }
+    }
+    
+    session_cookie(headers)
+        .is_some_and(|token| sessions().lock().unwrap().contains(token))
+}
+
+/// Middleware requiring the password on mutating /api routes when one is set
+pub async fn require_auth(
+    config: Arc<Mutex<crate::config::Config>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mutating = matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+    let path = request.uri().path();
+    let protected = mutating && path.starts_with("/api/") && !PUBLIC_ROUTES.contains(&path);
+    
+    if protected {
+        let authorized = {
+            let cfg = config.lock().unwrap();
+            is_authorized(&cfg.password, request.headers())
+        };
+        
+        if !authorized {
+            return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({
+                "error": "Invalid or missing password"
+            }))).into_response();
+        }
+    }
+    
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    
+    #[test]
+    fn test_password_hashing() {
+        let stored = hash_password("hunter2");
+        assert!(is_hashed(&stored));
+        assert!(!stored.contains("hunter2"));
+        assert!(verify_password(&stored, "hunter2"));
+        assert!(!verify_password(&stored, "hunter3"));
+        
+        // Salted, so the same password never hashes the same way twice
+        assert_ne!(stored, hash_password("hunter2"));
+    }
+    
+    async fn serve(password: &str) -> String {
+        let config = Arc::new(Mutex::new(crate::config::Config {
+            password: if password.is_empty() { String::new() } else { hash_password(password) },
+            ..crate::config::Config::default()
+        }));
+        
+        let app = Router::new()
+            .route("/api/media/order", post(|| async { "ok" }))
+            .route("/api/login", post(|| async { "login" }))
+            .layer(middleware::from_fn(move |request, next| {
+                require_auth(config.clone(), request, next)
+            }));
+        
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        
+        format!("http://{}", addr)
+    }
+    
+    async fn post_status(url: &str, token: Option<&str>) -> u16 {
+        let mut request = reqwest::Client::new().post(url);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.unwrap().status().as_u16()
+    }
+    
+    #[tokio::test]
+    async fn test_correct_password() {
+        let base = serve("secret").await;
+        assert_eq!(post_status(&format!("{}/api/media/order", base), Some("secret")).await, 200);
+        
+        let token = create_session();
+        let status = reqwest::Client::new()
+            .post(format!("{}/api/media/order", base))
+            .header(header::COOKIE, format!("{}={}", SESSION_COOKIE, token))
+            .send().await.unwrap()
+            .status();
+        assert_eq!(status.as_u16(), 200);
+    }
+    
+    #[tokio::test]
+    async fn test_wrong_password() {
+        let base = serve("secret").await;
+        assert_eq!(post_status(&format!("{}/api/media/order", base), Some("guess")).await, 401);
+        assert_eq!(post_status(&format!("{}/api/media/order", base), None).await, 401);
+        
+        // Logging in must not require being logged in
+        assert_eq!(post_status(&format!("{}/api/login", base), None).await, 200);
+    }
+    
+    #[tokio::test]
+    async fn test_empty_password_is_open() {
+        let base = serve("").await;
+        assert_eq!(post_status(&format!("{}/api/media/order", base), None).await, 200);
+    }
+}
@@ -0,0 +1,541 @@
+use std::collections::BTreeSet;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::fs;
+use tokio::io::{AsyncRead, ReadBuf};
+use tracing::warn;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Refuse bundles that would expand to more than this, to guard against zip bombs
+const MAX_BUNDLE_BYTES: u64 = 512 * 1024 * 1024;
+
+const FONT_EXTENSIONS: &[&str] = &["ttf", "otf", "woff", "woff2"];
+
+/// Hidden folder in the data dir where an import is assembled before anything is replaced
+const STAGING_DIR: &str = ".import";
+
+/// What a successful import brought in
+#[derive(Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportManifest {
+    pub config: bool,
+    pub media: Vec<String>,
+    pub addons: Vec<String>,
+    pub fonts: Vec<String>,
+}
+
+/// A bundle that passed validation and is ready to be written out
+pub struct Bundle {
+    config: Option<crate::config::Config>,
+    /// Addon and font files, by path relative to the data dir
+    files: Vec<(PathBuf, Vec<u8>)>,
+    media: Vec<MediaEntry>,
+    manifest: ImportManifest,
+}
+
+/// A media file from the bundle, stored like an upload to its collection
+struct MediaEntry {
+    collection: Option<String>,
+    name: String,
+    contents: Vec<u8>,
+}
+
+/// A finished export in a hidden file in the data dir, deleted once it has been read
+pub struct ExportedBundle {
+    file: Option<fs::File>,
+    path: PathBuf,
+    pub len: u64,
+}
+
+impl AsyncRead for ExportedBundle {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        match self.file.as_mut() {
+            Some(file) => Pin::new(file).poll_read(cx, buf),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl Drop for ExportedBundle {
+    fn drop(&mut self) {
+        // Close it first; Windows won't delete an open file
+        drop(self.file.take());
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Zip up config.json (without passwords), Media/, Addons/ and Fonts/. The zip is
+/// written to disk rather than memory, since it holds the whole media library.
+pub fn export_bundle(config: &crate::config::Config) -> Result<ExportedBundle, String> {
+    let data_dir = crate::paths::get_data_dir()?;
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let path = data_dir.join(format!(".export-{}.zip", nanos));
+    
+    // Read back once written, to send it
+    let file = std::fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path)
+        .map_err(|e| format!("Failed to create bundle: {}", e))?;
+    let mut bundle = ExportedBundle { file: None, path, len: 0 };
+    
+    let mut file = export_from(&data_dir, config, file)?;
+    bundle.len = file.metadata()
+        .map_err(|e| format!("Failed to finish bundle: {}", e))?
+        .len();
+    file.rewind()
+        .map_err(|e| format!("Failed to finish bundle: {}", e))?;
+    bundle.file = Some(fs::File::from_std(file));
+    
+    Ok(bundle)
+}
+
+fn export_from<W: Write + Seek>(data_dir: &Path, config: &crate::config::Config, out: W) -> Result<W, String> {
+    let mut zip = ZipWriter::new(out);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // Media and fonts are already compressed
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    
+    let mut config = config.clone();
+    config.password = String::new();
+    config.peer_passwords.clear();
+    
+    let config_json = serde_json::to_string_pretty(&config)
+        .map_err(|e| e.to_string())?;
+    
+    zip.start_file("config.json", deflated)
+        .map_err(|e| format!("Failed to write config.json: {}", e))?;
+    zip.write_all(config_json.as_bytes())
+        .map_err(|e| format!("Failed to write config.json: {}", e))?;
+    
+    let dirs = [
+        ("Media", stored),
+        ("Addons", deflated),
+        ("Fonts", stored),
+    ];
+    
+    for (prefix, options) in dirs {
+        let dir = data_dir.join(prefix);
+        if !dir.exists() {
+            continue;
+        }
+        
+        for path in list_files_recursive(&dir)? {
+            let relative = path.strip_prefix(&dir)
+                .map_err(|e| e.to_string())?;
+            
+            // Skip temp uploads and other hidden files
+            if relative.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.')) {
+                continue;
+            }
+            
+            let name = format!("{}/{}", prefix, relative.to_string_lossy().replace('\\', "/"));
+            let mut file = std::fs::File::open(&path)
+                .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            
+            zip.start_file(name.as_str(), options)
+                .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+            std::io::copy(&mut file, &mut zip)
+                .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        }
+    }
+    
+    zip.finish()
+        .map_err(|e| format!("Failed to finish bundle: {}", e))
+}
+
+fn list_files_recursive(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    
+    for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        
+        if path.is_dir() {
+            files.extend(list_files_recursive(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    
+    files.sort();
+    Ok(files)
+}
+
+/// Read and validate every part of a bundle, collecting all problems found
+pub fn parse_bundle(data: &[u8]) -> Result<Bundle, Vec<String>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| vec![format!("Not a valid zip file: {}", e)])?;
+    
+    let mut errors = Vec::new();
+    let mut bundle = Bundle {
+        config: None,
+        files: Vec::new(),
+        media: Vec::new(),
+        manifest: ImportManifest::default(),
+    };
+    let mut addon_ids = BTreeSet::new();
+    let mut total_bytes = 0u64;
+    
+    for i in 0..archive.len() {
+        let mut file = match archive.by_index(i) {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push(format!("Failed to read entry {}: {}", i, e));
+                continue;
+            }
+        };
+        
+        if file.is_dir() {
+            continue;
+        }
+        
+        let name = file.name().to_string();
+        
+        // enclosed_name rejects absolute paths and anything climbing out with ..
+        let path = match file.enclosed_name() {
+            Some(path) if path.components().all(|c| matches!(c, Component::Normal(_))) => path,
+            _ => {
+                errors.push(format!("{}: unsafe path", name));
+                continue;
+            }
+        };
+        
+        total_bytes += file.size();
+        if total_bytes > MAX_BUNDLE_BYTES {
+            return Err(vec![format!(
+                "Bundle expands to more than {} MB",
+                MAX_BUNDLE_BYTES / (1024 * 1024)
+            )]);
+        }
+        
+        let mut contents = Vec::new();
+        if let Err(e) = file.read_to_end(&mut contents) {
+            errors.push(format!("{}: failed to extract: {}", name, e));
+            continue;
+        }
+        
+        let parts: Vec<String> = path.components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        
+        match validate_entry(&parts, &contents, &mut bundle, &mut addon_ids) {
+            Ok(Some(media)) => bundle.media.push(MediaEntry { contents, ..media }),
+            Ok(None) if path != Path::new("config.json") => bundle.files.push((path, contents)),
+            Ok(None) => {}
+            Err(e) => errors.push(format!("{}: {}", name, e)),
+        }
+    }
+    
+    // Every addon folder needs a manifest that parses
+    for addon_id in &addon_ids {
+        let manifest_path = Path::new("Addons").join(addon_id).join("addon.toml");
+        let manifest = bundle.files.iter().find(|(path, _)| *path == manifest_path);
+        
+        match manifest {
+            Some((_, contents)) => {
+                let text = String::from_utf8_lossy(contents);
+                if let Err(e) = toml::from_str::<crate::addon::AddonManifest>(&text) {
+                    errors.push(format!("Addons/{}/addon.toml: invalid manifest: {}", addon_id, e));
+                }
+            }
+            None => errors.push(format!("Addons/{}: missing addon.toml", addon_id)),
+        }
+    }
+    
+    bundle.manifest.addons = addon_ids.into_iter().collect();
+    
+    if errors.is_empty() {
+        Ok(bundle)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Check one entry, returning where it goes if it is a media file.
+/// The media's contents are left for the caller to fill in.
+fn validate_entry(
+    parts: &[String],
+    contents: &[u8],
+    bundle: &mut Bundle,
+    addon_ids: &mut BTreeSet<String>,
+) -> Result<Option<MediaEntry>, String> {
+    if parts.iter().any(|p| p.starts_with('.')) {
+        return Err("hidden files are not allowed".to_string());
+    }
+    
+    match parts {
+        [name] if name == "config.json" => {
+            let config = crate::config::parse_config(contents)
+                .map_err(|e| format!("invalid config: {}", e))?;
+            bundle.config = Some(config);
+            bundle.manifest.config = true;
+        }
+        [dir, name] if dir == "Media" => return media_entry(None, name, contents).map(Some),
+        [dir, collection, name] if dir == "Media" => {
+            return media_entry(Some(collection), name, contents).map(Some);
+        }
+        [dir, name] if dir == "Fonts" => {
+            let ext = Path::new(name).extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !FONT_EXTENSIONS.contains(&ext.as_str()) {
+                return Err("not a supported font type".to_string());
+            }
+            bundle.manifest.fonts.push(name.clone());
+        }
+        [dir, addon_id, _, ..] if dir == "Addons" => {
+            addon_ids.insert(addon_id.clone());
+        }
+        _ => return Err("unexpected file in bundle".to_string()),
+    }
+    
+    Ok(None)
+}
+
+/// Check a media file the way an upload to its collection would be checked
+fn media_entry(collection: Option<&str>, name: &str, contents: &[u8]) -> Result<MediaEntry, String> {
+    crate::paths::collection_dir(Path::new("Media"), collection)?;
+    let name = crate::media::check_upload_name(name)?;
+    
+    let ext = Path::new(&name).extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+    crate::media::validate_media_bytes(&ext, contents)
+        .map_err(|e| format!("corrupt media: {}", e))?;
+    
+    Ok(MediaEntry {
+        collection: collection.map(str::to_string),
+        name,
+        contents: Vec::new(),
+    })
+}
+
+/// Write a validated bundle into place. Device-specific settings (password and
+/// network bindings) are kept from the current config rather than cloned.
+/// Media goes through the same checks as an upload; if any file is refused, the
+/// import is undone and nothing else is written.
+pub async fn apply_bundle(
+    bundle: Bundle,
+    current: &crate::config::Config,
+) -> Result<(Option<crate::config::Config>, ImportManifest), String> {
+    if let Some(locked) = bundle.media.iter().find(|m| m.collection.is_none() && current.locked_media.contains(&m.name)) {
+        return Err(format!("{} is locked; unlock it before importing over it", locked.name));
+    }
+    
+    let staging = crate::paths::get_data_dir()?.join(STAGING_DIR);
+    let _ = fs::remove_dir_all(&staging).await;
+    
+    let applied = apply_staged(&bundle, current.max_total_media_bytes, &staging).await;
+    let _ = fs::remove_dir_all(&staging).await;
+    // Files were set aside and put back behind the upload code's back
+    crate::media::quota::invalidate();
+    
+    let mut manifest = bundle.manifest;
+    manifest.media = applied?;
+    
+    let config = bundle.config.map(|mut config| {
+        config.password = current.password.clone();
+        config.static_ip = current.static_ip.clone();
+        config.localhost_only = current.localhost_only;
+        config.allowed_ips = current.allowed_ips.clone();
+        config.allowed_origins = current.allowed_origins.clone();
+        config.bind_addresses = current.bind_addresses.clone();
+        config.port = current.port;
+        config.ws_port = current.ws_port;
+        config.discovery_port = current.discovery_port;
+        config.peers = current.peers.clone();
+        config.peer_passwords = current.peer_passwords.clone();
+        config
+    });
+    
+    if let Some(config) = &config {
+        crate::config::save_config(config)?;
+    }
+    
+    Ok((config, manifest))
+}
+
+/// Write addon and font files into `staging`, store the media, then move the staged
+/// files into place. Returns the names the media was stored under.
+async fn apply_staged(bundle: &Bundle, max_total_bytes: u64, staging: &Path) -> Result<Vec<String>, String> {
+    for (path, contents) in &bundle.files {
+        let target = staging.join(path);
+        create_parent(&target).await?;
+        fs::write(&target, contents).await
+            .map_err(|e| format!("Failed to write {:?}: {}", target, e))?;
+    }
+    
+    let media = import_media(&bundle.media, max_total_bytes, &staging.join("Replaced")).await?;
+    
+    let data_dir = crate::paths::get_data_dir()?;
+    for (path, _) in &bundle.files {
+        let target = data_dir.join(path);
+        create_parent(&target).await?;
+        fs::rename(staging.join(path), &target).await
+            .map_err(|e| format!("Failed to move {:?} into place: {}", target, e))?;
+    }
+    
+    Ok(media)
+}
+
+/// Store each media file like an upload. Files about to be replaced are copied into
+/// `backup_dir` first, so that when one is refused everything can be put back.
+async fn import_media(entries: &[MediaEntry], max_total_bytes: u64, backup_dir: &Path) -> Result<Vec<String>, String> {
+    let mut created = Vec::new();
+    let mut replaced = Vec::new();
+    let mut names = Vec::new();
+    
+    for entry in entries {
+        let label = match &entry.collection {
+            Some(collection) => format!("{}/{}", collection, entry.name),
+            None => entry.name.clone(),
+        };
+        
+        let stored = async {
+            let target = crate::paths::get_media_dir(entry.collection.as_deref())?.join(&entry.name);
+            if target.is_file() {
+                let backup = backup_dir.join(replaced.len().to_string());
+                create_parent(&backup).await?;
+                fs::copy(&target, &backup).await
+                    .map_err(|e| format!("Failed to back up {:?}: {}", target, e))?;
+                replaced.push((target.clone(), backup));
+            }
+            
+            let saved = crate::media::save_file(
+                entry.collection.as_deref(),
+                &entry.name,
+                &entry.contents,
+                None,
+                None,
+                max_total_bytes,
+            ).await?;
+            
+            if !saved.deduplicated && !replaced.iter().any(|(path, _)| *path == target) {
+                created.push(target.with_file_name(&saved.filename));
+            }
+            Ok::<_, String>(saved.filename)
+        }.await;
+        
+        match stored {
+            Ok(filename) => names.push(match &entry.collection {
+                Some(collection) => format!("{}/{}", collection, filename),
+                None => filename,
+            }),
+            Err(e) => {
+                roll_back(&created, &replaced).await;
+                return Err(format!("Media/{}: {}", label, e));
+            }
+        }
+    }
+    
+    Ok(names)
+}
+
+/// Undo a partial import: remove the files it added and restore the ones it replaced
+async fn roll_back(created: &[PathBuf], replaced: &[(PathBuf, PathBuf)]) {
+    for path in created {
+        let _ = fs::remove_file(path).await;
+        forget_media(path).await;
+    }
+    
+    for (target, backup) in replaced {
+        if let Err(e) = fs::rename(backup, target).await {
+            warn!("Failed to restore {:?} after a failed import: {}", target, e);
+        }
+        forget_media(target).await;
+    }
+}
+
+/// Drop the thumbnail and dedup entry of a media file whose contents changed under them
+async fn forget_media(path: &Path) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let name = name.to_string_lossy();
+    
+    let _ = fs::remove_file(crate::media::thumbnail::thumbnail_path(dir, &name)).await;
+    if let Err(e) = crate::media::index::forget(dir, &name).await {
+        warn!("{}", e);
+    }
+}
+
+async fn create_parent(path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await
+            .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn make_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+    
+    #[test]
+    fn test_parse_bundle() {
+        let good = make_zip(&[
+            ("Media/slide.svg", b"<svg></svg>"),
+            ("Fonts/Roboto.ttf", b"font"),
+            ("Addons/clock/addon.toml", b"settings = []\n[info]\nname = \"Clock\"\nversion = \"1.0\"\n"),
+            ("Addons/clock/frontend.js", b""),
+        ]);
+        let bundle = parse_bundle(&good).unwrap();
+        assert_eq!(bundle.media.len(), 1);
+        assert_eq!(bundle.media[0].name, "slide.svg");
+        assert_eq!(bundle.manifest.addons, vec!["clock"]);
+        assert!(!bundle.manifest.config);
+        
+        let bad = make_zip(&[
+            ("../evil.sh", b"rm -rf /"),
+            ("Media/broken.png", b"\x89PNG"),
+            ("Addons/nomanifest/frontend.js", b""),
+        ]);
+        let problems = parse_bundle(&bad).err().unwrap();
+        assert_eq!(problems.len(), 3);
+    }
+    
+    #[test]
+    fn test_exported_bundle_imports() {
+        let data_dir = std::env::temp_dir().join(format!("bundle-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        for dir in ["Media/Events", "Media/.thumbnails", "Fonts"] {
+            std::fs::create_dir_all(data_dir.join(dir)).unwrap();
+        }
+        std::fs::write(data_dir.join("Media/slide.svg"), b"<svg></svg>").unwrap();
+        std::fs::write(data_dir.join("Media/Events/poster.svg"), b"<svg><rect/></svg>").unwrap();
+        std::fs::write(data_dir.join("Media/.thumbnails/slide.svg.jpg"), b"cache").unwrap();
+        std::fs::write(data_dir.join("Fonts/Roboto.ttf"), b"font").unwrap();
+        
+        let exported = export_from(&data_dir, &crate::config::Config::default(), Cursor::new(Vec::new()))
+            .unwrap()
+            .into_inner();
+        let _ = std::fs::remove_dir_all(&data_dir);
+        let bundle = parse_bundle(&exported).unwrap();
+        
+        assert!(bundle.manifest.config);
+        let media: Vec<_> = bundle.media.iter()
+            .map(|m| (m.collection.as_deref(), m.name.as_str(), m.contents.as_slice()))
+            .collect();
+        assert_eq!(media, vec![
+            (Some("Events"), "poster.svg", &b"<svg><rect/></svg>"[..]),
+            (None, "slide.svg", &b"<svg></svg>"[..]),
+        ]);
+        assert_eq!(bundle.files, vec![(PathBuf::from("Fonts/Roboto.ttf"), b"font".to_vec())]);
+        
+        // Collections are a single level, and can't climb out of Media
+        assert!(parse_bundle(&make_zip(&[("Media/a/b/slide.svg", b"<svg></svg>")])).is_err());
+        assert!(parse_bundle(&make_zip(&[("Media/../slide.svg", b"<svg></svg>")])).is_err());
+    }
+}
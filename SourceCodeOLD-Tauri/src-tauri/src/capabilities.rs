@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use tracing::info;
+
+/// Optional features available in this build and on this device
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// HTTPS termination (not built into this version)
+    pub tls: bool,
+    /// Image transcoding to WebP or AVIF, built in
+    pub transcoding: bool,
+    /// Video transcoding to MP4, backed by ffmpeg
+    pub video_transcoding: bool,
+    /// HEIC/HEIF image support, backed by libheif
+    pub heic: bool,
+    /// Remote update upload, staged and then applied by a platform update script
+    pub updater: bool,
+    /// Lua backends for addons
+    pub lua_addons: bool,
+    /// Bundled assets served through the custom protocol
+    pub custom_protocol: bool,
+    pub tools: Tools,
+}
+
+/// External tools found on the device at runtime
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tools {
+    pub ffmpeg: bool,
+    pub libheif: bool,
+}
+
+static TOOLS: OnceLock<Tools> = OnceLock::new();
+
+/// The ffmpeg found by the probe, if any
+static FFMPEG: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// The ffprobe found on first use, if any
+static FFPROBE: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Get the capabilities of this build, probing runtime tools on first use
+pub fn get_capabilities() -> Capabilities {
+    let tools = TOOLS.get_or_init(probe_tools).clone();
+    
+    Capabilities {
+        tls: false,
+        transcoding: true,
+        video_transcoding: tools.ffmpeg,
+        heic: tools.libheif,
+        updater: cfg!(any(target_os = "windows", target_os = "linux", target_os = "macos")),
+        lua_addons: true,
+        custom_protocol: cfg!(feature = "custom-protocol"),
+        tools,
+    }
+}
+
+/// The ffmpeg to run for video transcoding, probing for it on first use
+pub fn ffmpeg_path() -> Option<PathBuf> {
+    FFMPEG.get_or_init(|| find_tool("ffmpeg")).clone()
+}
+
+/// The ffprobe to read video details with, probing for it on first use
+pub fn ffprobe_path() -> Option<PathBuf> {
+    FFPROBE.get_or_init(|| find_tool("ffprobe")).clone()
+}
+
+/// Prefer a tool shipped next to the app, then fall back to one on PATH
+fn find_tool(name: &str) -> Option<PathBuf> {
+    let bundled = crate::paths::get_app_dir().ok()
+        .map(|dir| dir.join(if cfg!(windows) { format!("{}.exe", name) } else { name.to_string() }))
+        .filter(|path| path.is_file());
+    
+    bundled.into_iter()
+        .chain(std::iter::once(PathBuf::from(name)))
+        .find(|program| tool_available(program.as_os_str(), "-version"))
+}
+
+fn probe_tools() -> Tools {
+    let tools = Tools {
+        ffmpeg: ffmpeg_path().is_some(),
+        libheif: tool_available("heif-convert", "--version"),
+    };
+    
+    info!("Detected tools: ffmpeg={}, libheif={}", tools.ffmpeg, tools.libheif);
+    tools
+}
+
+/// Check whether an executable can be launched, by path or from PATH
+fn tool_available(program: impl AsRef<std::ffi::OsStr>, version_arg: &str) -> bool {
+    Command::new(program)
+        .arg(version_arg)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
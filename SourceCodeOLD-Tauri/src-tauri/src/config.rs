@@ -1,11 +1,22 @@
+use crate::error::AppError;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Bump this and add a step to `migrate` whenever the config's shape changes
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
+    /// Configs written before versioning existed deserialize as 0
+    #[serde(default)]
+    pub schema_version: u32,
     pub display_name: String,
     pub image_duration: u64,
     pub video_position: String,
@@ -15,18 +26,118 @@ pub struct Config {
     pub manual_height: Option<u32>,
     pub password: String,
     pub static_ip: String,
+    /// IPs the web server listens on, one listener each; empty listens on every interface
+    #[serde(default)]
+    pub bind_addresses: Vec<String>,
+    /// Origins allowed to call the API from a browser; empty allows any
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Client addresses or CIDR ranges allowed to reach the web server; empty allows any
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
     pub localhost_only: bool,
     pub port: u16,
     pub ws_port: u16,
     pub discovery_port: u16,
+    /// How to find other devices: "udp", "mdns" or "both"
+    #[serde(default = "default_discovery_mode")]
+    pub discovery_mode: String,
+    /// Seconds between this device's discovery announcements
+    #[serde(default = "default_discovery_interval_secs")]
+    pub discovery_interval_secs: u64,
+    /// Seconds a discovered peer is kept after it was last heard from
+    #[serde(default = "default_peer_ttl_secs")]
+    pub peer_ttl_secs: u64,
     pub rotation: i32,
+    #[serde(default = "default_preview_interval_ms")]
+    pub preview_interval_ms: u64,
     #[serde(default)]
     pub addons: HashMap<String, HashMap<String, serde_json::Value>>,
+    #[serde(default)]
+    pub peers: Vec<crate::network::Peer>,
+    /// Passwords of password-protected peers, by peer id, sent when syncing to them
+    #[serde(default)]
+    pub peer_passwords: HashMap<String, String>,
+    #[serde(default)]
+    pub media_order: Vec<String>,
+    /// Per-file image duration overrides in milliseconds
+    #[serde(default)]
+    pub durations: HashMap<String, u64>,
+    /// Tags per media file, e.g. "promos" or "menus", for grouping and filtering the library
+    #[serde(default)]
+    pub tags: HashMap<String, Vec<String>>,
+    /// Media files that can't be deleted and stay in the playlist order until unlocked,
+    /// e.g. a standing emergency notice. Sorted, so equal configs save and hash the same.
+    #[serde(default)]
+    pub locked_media: BTreeSet<String>,
+    /// Largest single file accepted by the upload endpoint
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_upload_bytes: u64,
+    /// Most bytes of media kept across Media and its collections; uploads past it are refused. 0 is unlimited
+    #[serde(default)]
+    pub max_total_media_bytes: u64,
+    /// Lua instructions one addon backend run may execute before it is aborted. 0 is unlimited
+    #[serde(default = "default_lua_max_instructions")]
+    pub lua_max_instructions: u64,
+    /// Memory one addon backend's Lua state may allocate. 0 is unlimited
+    #[serde(default = "default_lua_memory_limit_bytes")]
+    pub lua_memory_limit_bytes: u64,
+    /// Re-encode uploaded JPEG and PNG images to `transcode_format` to save space
+    #[serde(default)]
+    pub auto_transcode: bool,
+    /// "webp", "avif" or "none"
+    #[serde(default = "default_transcode_format")]
+    pub transcode_format: String,
+    /// Encoder quality, 1 to 100, for transcoded uploads and thumbnails
+    #[serde(default = "default_image_quality", alias = "transcode_quality")]
+    pub image_quality: u8,
+    /// Convert uploaded MOV and MKV videos to H.264 MP4 with ffmpeg, when it is available
+    #[serde(default)]
+    pub transcode_video: bool,
+    /// Mutating API requests allowed per client IP each minute; 0 means unlimited
+    #[serde(default)]
+    pub rate_limit_per_min: u32,
+    /// Effect between slides: "none", "fade", "slide" or "zoom"
+    #[serde(default = "default_transition")]
+    pub transition: String,
+    #[serde(default = "default_transition_duration_ms")]
+    pub transition_duration_ms: u64,
+    /// Shown on screen while there is no media to play
+    #[serde(default = "default_fallback_message")]
+    pub fallback_message: String,
+    /// Background behind the fallback message, as #RRGGBB
+    #[serde(default = "default_fallback_background")]
+    pub fallback_background: String,
+    /// Hours the display should be on; None means always
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    /// Most verbose log level shown: "trace", "debug", "info", "warn", "error" or "off"
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Slide the display was on when last saved, so a restart resumes there
+    #[serde(default)]
+    pub last_slide_index: usize,
+    /// When `last_slide_index` was saved, as RFC 3339
+    #[serde(default)]
+    pub last_slide_timestamp: Option<String>,
+    /// Monitor the presentation window is shown on, counted from 0 in the order the OS lists them
+    #[serde(default)]
+    pub display_index: u32,
+    #[serde(default = "default_fullscreen")]
+    pub fullscreen: bool,
+    /// Play the media in a random order that only repeats once every file has been shown
+    #[serde(default)]
+    pub shuffle: bool,
+    /// Seed of the current shuffled cycle. Devices with the same seed and files play the same
+    /// sequence, and each cycle's seed follows from the last so they stay in step.
+    #[serde(default)]
+    pub shuffle_seed: u64,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            schema_version: CURRENT_SCHEMA_VERSION,
             display_name: hostname::get()
                 .ok()
                 .and_then(|h| h.into_string().ok())
@@ -39,50 +150,774 @@ impl Default for Config {
             manual_height: None,
             password: String::new(),
             static_ip: String::new(),
+            bind_addresses: Vec::new(),
+            allowed_origins: Vec::new(),
+            allowed_ips: Vec::new(),
             localhost_only: false,
             port: 3006,
             ws_port: 3001,
             discovery_port: 3002,
+            discovery_mode: default_discovery_mode(),
+            discovery_interval_secs: default_discovery_interval_secs(),
+            peer_ttl_secs: default_peer_ttl_secs(),
             rotation: 0,
+            preview_interval_ms: default_preview_interval_ms(),
             addons: HashMap::new(),
+            peers: Vec::new(),
+            peer_passwords: HashMap::new(),
+            media_order: Vec::new(),
+            durations: HashMap::new(),
+            tags: HashMap::new(),
+            locked_media: BTreeSet::new(),
+            max_upload_bytes: default_max_upload_bytes(),
+            max_total_media_bytes: 0,
+            lua_max_instructions: default_lua_max_instructions(),
+            lua_memory_limit_bytes: default_lua_memory_limit_bytes(),
+            auto_transcode: false,
+            transcode_format: default_transcode_format(),
+            image_quality: default_image_quality(),
+            transcode_video: false,
+            rate_limit_per_min: 0,
+            transition: default_transition(),
+            transition_duration_ms: default_transition_duration_ms(),
+            fallback_message: default_fallback_message(),
+            fallback_background: default_fallback_background(),
+            schedule: None,
+            log_level: default_log_level(),
+            last_slide_index: 0,
+            last_slide_timestamp: None,
+            display_index: 0,
+            fullscreen: default_fullscreen(),
+            shuffle: false,
+            shuffle_seed: 0,
+        }
+    }
+}
+
+/// Weekly window during which the display should show media
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    pub enabled: bool,
+    /// "HH:MM" the display turns on
+    pub start_time: String,
+    /// "HH:MM" the display turns off, later the same day
+    pub end_time: String,
+    /// Days the window applies to, 0 (Sunday) to 6 (Saturday)
+    pub days: Vec<u8>,
+}
+
+fn parse_schedule_time(time: &str) -> Result<chrono::NaiveTime, AppError> {
+    chrono::NaiveTime::parse_from_str(time, "%H:%M")
+        .ok()
+        .filter(|_| time.len() == 5)
+        .ok_or_else(|| AppError::Validation(format!("{:?} is not a time in HH:MM form", time)))
+}
+
+impl Schedule {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let start = parse_schedule_time(&self.start_time)?;
+        let end = parse_schedule_time(&self.end_time)?;
+        
+        if start >= end {
+            return Err(AppError::Validation("startTime must be before endTime".to_string()));
         }
+        
+        if let Some(day) = self.days.iter().find(|day| **day > 6) {
+            return Err(AppError::Validation(format!(
+                "day {} is out of range; days run from 0 (Sunday) to 6 (Saturday)",
+                day
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    /// Whether the display should be on at the given local time
+    pub fn is_active_at(&self, now: chrono::NaiveDateTime) -> bool {
+        use chrono::Datelike;
+        
+        if !self.enabled {
+            return true;
+        }
+        
+        // A schedule that somehow got saved broken shouldn't blank the screen
+        let (Ok(start), Ok(end)) = (parse_schedule_time(&self.start_time), parse_schedule_time(&self.end_time)) else {
+            return true;
+        };
+        
+        let day = now.weekday().num_days_from_sunday() as u8;
+        self.days.contains(&day) && (start..end).contains(&now.time())
     }
 }
 
-pub fn get_config_path() -> Result<PathBuf, String> {
+impl Config {
+    /// Whether the display should be showing media at the given local time
+    pub fn display_active_at(&self, now: chrono::NaiveDateTime) -> bool {
+        self.schedule.as_ref().is_none_or(|schedule| schedule.is_active_at(now))
+    }
+    
+    /// Slide to resume on in a playlist of `total`, which may have shrunk since it was saved
+    pub fn resume_slide_index(&self, total: usize) -> usize {
+        self.last_slide_index.min(total.saturating_sub(1))
+    }
+    
+    /// How long an image stays on screen, honoring its override if it has one
+    pub fn duration_for(&self, filename: &str) -> u64 {
+        self.durations.get(filename).copied().unwrap_or(self.image_duration)
+    }
+    
+    /// Drop per-file settings for a media file that no longer exists
+    pub fn forget_media(&mut self, filename: &str) -> bool {
+        let had_duration = self.durations.remove(filename).is_some();
+        let had_tags = self.tags.remove(filename).is_some();
+        let was_locked = self.locked_media.remove(filename);
+        let order_len = self.media_order.len();
+        self.media_order.retain(|name| name != filename);
+        
+        had_duration || had_tags || was_locked || self.media_order.len() != order_len
+    }
+    
+    /// Refuse to delete a locked media file
+    pub fn check_unlocked(&self, filename: &str) -> Result<(), AppError> {
+        if self.locked_media.contains(filename) {
+            return Err(AppError::Conflict(format!("{} is locked; unlock it before deleting", filename)));
+        }
+        Ok(())
+    }
+    
+    /// Keep locked files that `order` leaves out at the place they had in the current order
+    pub fn keep_locked_in_order(&self, order: &mut Vec<String>) {
+        for (index, name) in self.media_order.iter().enumerate() {
+            if self.locked_media.contains(name) && !order.contains(name) {
+                order.insert(index.min(order.len()), name.clone());
+            }
+        }
+    }
+    
+    /// Replace a media file's tags; an empty list removes its entry
+    pub fn set_tags(&mut self, filename: &str, tags: Vec<String>) {
+        if tags.is_empty() {
+            self.tags.remove(filename);
+        } else {
+            self.tags.insert(filename.to_string(), tags);
+        }
+    }
+    
+    /// Move per-file settings over to a media file's new name
+    pub fn rename_media(&mut self, old_name: &str, new_name: &str) -> bool {
+        let mut changed = false;
+        
+        if let Some(duration) = self.durations.remove(old_name) {
+            self.durations.insert(new_name.to_string(), duration);
+            changed = true;
+        }
+        
+        if let Some(tags) = self.tags.remove(old_name) {
+            self.tags.insert(new_name.to_string(), tags);
+            changed = true;
+        }
+        
+        if self.locked_media.remove(old_name) {
+            self.locked_media.insert(new_name.to_string());
+            changed = true;
+        }
+        
+        for name in self.media_order.iter_mut().filter(|name| *name == old_name) {
+            *name = new_name.to_string();
+            changed = true;
+        }
+        
+        changed
+    }
+}
+
+/// Longest tag accepted, in characters
+pub const MAX_TAG_LEN: usize = 64;
+
+/// Trim tags, dropping blanks and duplicates, and sort them so the saved list is stable
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, AppError> {
+    let mut normalized: Vec<String> = tags.iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    
+    if let Some(tag) = normalized.iter().find(|tag| tag.chars().count() > MAX_TAG_LEN) {
+        return Err(AppError::Validation(format!("Tag '{}' is longer than {} characters", tag, MAX_TAG_LEN)));
+    }
+    
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
+/// Values the display understands for `image_scaling`
+pub const IMAGE_SCALING_MODES: &[&str] = &["contain", "cover", "fill", "stretch", "none"];
+
+/// Values the playlist understands for `video_position`
+pub const VIDEO_POSITIONS: &[&str] = &["before", "after", "interleave"];
+
+/// Screen rotations in degrees
+pub const ROTATIONS: &[i64] = &[0, 90, 180, 270];
+
+/// Effects the display can play between slides
+pub const TRANSITIONS: &[&str] = &["none", "fade", "slide", "zoom"];
+
+/// Formats uploaded images can be transcoded to
+pub const TRANSCODE_FORMATS: &[&str] = &["webp", "avif", "none"];
+
+/// Whether `color` is written as #RRGGBB
+pub fn is_hex_color(color: &str) -> bool {
+    color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Longest transition accepted, so a typo can't leave slides permanently mid-animation
+pub const MAX_TRANSITION_DURATION_MS: u64 = 5000;
+
+fn default_preview_interval_ms() -> u64 {
+    1000
+}
+
+fn default_discovery_mode() -> String {
+    "both".to_string()
+}
+
+fn default_discovery_interval_secs() -> u64 {
+    5
+}
+
+fn default_peer_ttl_secs() -> u64 {
+    30
+}
+
+fn default_max_upload_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_lua_max_instructions() -> u64 {
+    100_000_000
+}
+
+fn default_lua_memory_limit_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_transcode_format() -> String {
+    "webp".to_string()
+}
+
+fn default_image_quality() -> u8 {
+    80
+}
+
+fn default_transition() -> String {
+    "none".to_string()
+}
+
+fn default_transition_duration_ms() -> u64 {
+    300
+}
+
+fn default_fallback_message() -> String {
+    "No media uploaded".to_string()
+}
+
+fn default_fallback_background() -> String {
+    "#000000".to_string()
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_fullscreen() -> bool {
+    true
+}
+
+/// Short hash of the config contents, used as an ETag to detect stale saves
+pub fn config_version(config: &Config) -> String {
+    use std::hash::{Hash, Hasher};
+    
+    // Going through Value sorts map keys, so equal configs always hash the same
+    let mut value = serde_json::to_value(config).unwrap_or_default();
+    
+    // The slide position is saved as the show runs; it shouldn't make every edit look stale
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("lastSlideIndex");
+        obj.remove("lastSlideTimestamp");
+    }
+    let canonical = value.to_string();
+    
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    canonical.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// The config as served to the display, the web UI and WebSocket clients.
+/// Everything except the password, plus version and capability details.
+pub fn to_api_json(cfg: &Config) -> serde_json::Value {
+    serde_json::json!({
+        "schemaVersion": cfg.schema_version,
+        "displayName": cfg.display_name,
+        "imageDuration": cfg.image_duration,
+        "videoPosition": cfg.video_position,
+        "imageScaling": cfg.image_scaling,
+        "manualResolution": cfg.manual_resolution,
+        "manualWidth": cfg.manual_width,
+        "manualHeight": cfg.manual_height,
+        "port": cfg.port,
+        "rotation": cfg.rotation,
+        "hasPassword": !cfg.password.is_empty(),
+        "staticIp": cfg.static_ip,
+        "bindAddresses": cfg.bind_addresses,
+        "allowedOrigins": cfg.allowed_origins,
+        "allowedIps": cfg.allowed_ips,
+        "localhostOnly": cfg.localhost_only,
+        "wsPort": cfg.ws_port,
+        "discoveryPort": cfg.discovery_port,
+        "discoveryMode": cfg.discovery_mode,
+        "discoveryIntervalSecs": cfg.discovery_interval_secs,
+        "peerTtlSecs": cfg.peer_ttl_secs,
+        "logLevel": cfg.log_level,
+        "displayIndex": cfg.display_index,
+        "fullscreen": cfg.fullscreen,
+        "shuffle": cfg.shuffle,
+        "shuffleSeed": cfg.shuffle_seed,
+        "transition": cfg.transition,
+        "transitionDurationMs": cfg.transition_duration_ms,
+        "fallbackMessage": cfg.fallback_message,
+        "fallbackBackground": cfg.fallback_background,
+        "schedule": cfg.schedule,
+        "previewIntervalMs": cfg.preview_interval_ms,
+        "rateLimitPerMin": cfg.rate_limit_per_min,
+        "maxUploadBytes": cfg.max_upload_bytes,
+        "maxTotalMediaBytes": cfg.max_total_media_bytes,
+        "luaMaxInstructions": cfg.lua_max_instructions,
+        "luaMemoryLimitBytes": cfg.lua_memory_limit_bytes,
+        "autoTranscode": cfg.auto_transcode,
+        "transcodeFormat": cfg.transcode_format,
+        "imageQuality": cfg.image_quality,
+        "transcodeVideo": cfg.transcode_video,
+        "addons": cfg.addons,
+        "mediaOrder": cfg.media_order,
+        "durations": cfg.durations,
+        "tags": cfg.tags,
+        "lockedMedia": cfg.locked_media,
+        "version": env!("CARGO_PKG_VERSION"),
+        "configVersion": config_version(cfg),
+        "peers": cfg.peers,
+        "capabilities": crate::capabilities::get_capabilities(),
+    })
+}
+
+pub fn get_config_path() -> Result<PathBuf, AppError> {
     crate::paths::get_config_path()
 }
 
-pub fn load_config() -> Result<Config, String> {
+pub fn load_config() -> Result<Config, AppError> {
     let config_path = get_config_path()?;
+    let mut config = load_config_from(&config_path)?;
+    
+    // Older configs stored the password as plaintext
+    if crate::auth::hash_if_plaintext(&mut config) {
+        save_config_to(&config_path, &config)?;
+    }
+    
+    Ok(config)
+}
+
+pub fn save_config(config: &Config) -> Result<(), AppError> {
+    save_config_to(&get_config_path()?, config)
+}
+
+/// Take the addon settings from config.json into the shared config. Addon backends write
+/// their settings straight to the file, so the shared copy can fall behind it.
+pub fn refresh_addons(config: &mut Config) -> Result<(), AppError> {
+    config.addons = load_config()?.addons;
+    Ok(())
+}
+
+/// Save the shared config without rolling back addon settings written to the file meanwhile
+pub fn save_shared(config: &mut Config) -> Result<(), AppError> {
+    refresh_addons(config)?;
+    save_config(config)
+}
+
+/// How long config changes from the API are gathered before they are saved
+pub const SAVE_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Saves the shared config once changes stop arriving for a moment, so a burst of updates
+/// (a slider being dragged, say) costs one write and one notification instead of dozens
+#[derive(Clone)]
+pub struct DebouncedSaver {
+    changed: mpsc::UnboundedSender<()>,
+}
+
+impl DebouncedSaver {
+    /// Start the background task. `on_saved` runs after each save, with the config as written.
+    pub fn spawn<F>(config: Arc<Mutex<Config>>, window: Duration, on_saved: F) -> Self
+    where
+        F: Fn(&Config) + Send + 'static,
+    {
+        let (changed, mut rx) = mpsc::unbounded_channel();
+        
+        tokio::spawn(async move {
+            while rx.recv().await.is_some() {
+                // Keep waiting while changes keep coming; a closed channel still gets its final save
+                while let Ok(Some(())) = tokio::time::timeout(window, rx.recv()).await {}
+                
+                let saved = {
+                    let mut cfg = config.lock().unwrap();
+                    save_shared(&mut cfg).map(|()| cfg.clone())
+                };
+                match saved {
+                    Ok(snapshot) => on_saved(&snapshot),
+                    Err(e) => warn!("Failed to save config: {}", e),
+                }
+            }
+        });
+        
+        Self { changed }
+    }
+    
+    /// Note that the shared config changed and should be saved
+    pub fn request_save(&self) {
+        let _ = self.changed.send(());
+    }
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn schema_version_of(value: &serde_json::Value) -> u64 {
+    value.get("schemaVersion").and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+/// Upgrade a config written by an older version to the current shape
+fn migrate(mut value: serde_json::Value) -> serde_json::Value {
+    let version = schema_version_of(&value);
+    let Some(obj) = value.as_object_mut() else {
+        return value;
+    };
+    
+    if version < 1 {
+        // Unversioned configs predate several settings; take defaults for whatever is missing
+        if let Ok(serde_json::Value::Object(defaults)) = serde_json::to_value(Config::default()) {
+            for (key, default) in defaults {
+                obj.entry(key).or_insert(default);
+            }
+        }
+    }
+    
+    obj.insert("schemaVersion".to_string(), CURRENT_SCHEMA_VERSION.into());
+    value
+}
+
+/// Parse config JSON from any source (file, bundle), migrating older shapes first
+pub fn parse_config(content: &[u8]) -> Result<Config, AppError> {
+    parse_and_migrate(content).map(|(config, _)| config)
+}
+
+/// Parse a config exported from another device, refusing ones from a newer app version
+pub fn parse_imported_config(content: &[u8]) -> Result<Config, AppError> {
+    let value: serde_json::Value = serde_json::from_slice(content)
+        .map_err(|e| AppError::Validation(format!("Invalid config JSON: {}", e)))?;
+    
+    let version = schema_version_of(&value);
+    if version > CURRENT_SCHEMA_VERSION as u64 {
+        return Err(AppError::Validation(format!(
+            "Config schema version {} is newer than this app supports ({})",
+            version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+    
+    parse_config(content)
+}
+
+/// Read and migrate a config file, also reporting whether migration changed anything
+fn read_config_file(path: &Path) -> Result<(Config, bool), AppError> {
+    let content = fs::read(path)?;
+    
+    parse_and_migrate(&content)
+}
+
+fn parse_and_migrate(content: &[u8]) -> Result<(Config, bool), AppError> {
+    let value: serde_json::Value = serde_json::from_slice(content)
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+    
+    let version = schema_version_of(&value);
+    if version > CURRENT_SCHEMA_VERSION as u64 {
+        warn!("Config schema version {} is newer than this app ({})", version, CURRENT_SCHEMA_VERSION);
+    }
+    
+    let migrated = version < CURRENT_SCHEMA_VERSION as u64;
+    let value = if migrated { migrate(value) } else { value };
     
+    let config = serde_json::from_value(value)
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+    
+    Ok((config, migrated))
+}
+
+fn load_config_from(config_path: &Path) -> Result<Config, AppError> {
     if !config_path.exists() {
         let default_config = Config::default();
-        save_config(&default_config)?;
+        save_config_to(config_path, &default_config)?;
         return Ok(default_config);
     }
     
-    let content = fs::read_to_string(&config_path)
-        .map_err(|e| e.to_string())?;
+    match read_config_file(config_path) {
+        Ok((config, migrated)) => {
+            if migrated {
+                info!("Migrated config to schema version {}", CURRENT_SCHEMA_VERSION);
+                save_config_to(config_path, &config)?;
+            }
+            Ok(config)
+        }
+        Err(e) => {
+            // A damaged config would otherwise wipe every addon setting
+            let backup_path = sibling_path(config_path, ".bak");
+            warn!("Failed to load config ({}), trying backup {:?}", e, backup_path);
+            
+            read_config_file(&backup_path)
+                .map(|(config, _)| config)
+                .map_err(|backup_err| AppError::Io(format!("{} (backup: {})", e, backup_err)))
+        }
+    }
+}
+
+fn save_config_to(config_path: &Path, config: &Config) -> Result<(), AppError> {
+    debug!("Saving config to: {:?}", config_path);
     
-    let config: Config = serde_json::from_str(&content)
-        .map_err(|e| e.to_string())?;
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| AppError::Io(e.to_string()))?;
     
-    Ok(config)
+    // Keep the last good config around, but never rotate a broken one over it
+    if read_config_file(config_path).is_ok() {
+        let backup_path = sibling_path(config_path, ".bak");
+        if let Err(e) = fs::copy(config_path, &backup_path) {
+            warn!("Failed to back up config: {}", e);
+        }
+    }
+    
+    // Write beside the target and rename over it so a crash never leaves a truncated config
+    let temp_path = sibling_path(config_path, ".tmp");
+    
+    fs::write(&temp_path, content)?;
+    
+    fs::rename(&temp_path, config_path)
+        .map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            AppError::from(e)
+        })?;
+    
+    debug!("Config saved");
+    
+    Ok(())
 }
 
-pub fn save_config(config: &Config) -> Result<(), String> {
-    let config_path = get_config_path()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
     
-    println!("Saving config to: {:?}", config_path);
+    #[test]
+    fn test_api_json() {
+        let cfg = Config {
+            manual_resolution: true,
+            manual_width: Some(1920),
+            manual_height: Some(1080),
+            password: crate::auth::hash_password("secret"),
+            ..Config::default()
+        };
+        
+        let json = to_api_json(&cfg);
+        assert_eq!(json["manualResolution"], true);
+        assert_eq!(json["manualWidth"], 1920);
+        assert_eq!(json["manualHeight"], 1080);
+        assert!(json["peers"].is_array());
+        assert_eq!(json["hasPassword"], true);
+        assert!(json.get("password").is_none());
+        assert_eq!(json["configVersion"], config_version(&cfg));
+        assert_eq!(json["fallbackMessage"], "No media uploaded");
+        assert_eq!(json["fallbackBackground"], "#000000");
+    }
     
-    let content = serde_json::to_string_pretty(config)
-        .map_err(|e| e.to_string())?;
+    #[test]
+    fn test_schedule_window() {
+        let schedule = Schedule {
+            enabled: true,
+            start_time: "09:00".to_string(),
+            end_time: "17:30".to_string(),
+            days: vec![1, 2, 3, 4, 5],
+        };
+        assert!(schedule.validate().is_ok());
+        
+        let at = |date: &str, time: &str| {
+            chrono::NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+        };
+        
+        // 2024-06-03 is a Monday, 2024-06-08 a Saturday
+        assert!(schedule.is_active_at(at("2024-06-03", "09:00")));
+        assert!(schedule.is_active_at(at("2024-06-03", "12:15")));
+        assert!(!schedule.is_active_at(at("2024-06-03", "08:59")));
+        assert!(!schedule.is_active_at(at("2024-06-03", "17:30")));
+        assert!(!schedule.is_active_at(at("2024-06-08", "12:15")));
+        
+        let disabled = Schedule { enabled: false, ..schedule.clone() };
+        assert!(disabled.is_active_at(at("2024-06-08", "12:15")));
+        
+        let config = Config { schedule: Some(schedule.clone()), ..Config::default() };
+        assert!(!config.display_active_at(at("2024-06-08", "12:15")));
+        assert!(Config::default().display_active_at(at("2024-06-08", "12:15")));
+        
+        for broken in [
+            Schedule { start_time: "17:30".to_string(), end_time: "09:00".to_string(), ..schedule.clone() },
+            Schedule { start_time: "9:00".to_string(), ..schedule.clone() },
+            Schedule { end_time: "24:00".to_string(), ..schedule.clone() },
+            Schedule { days: vec![0, 7], ..schedule.clone() },
+        ] {
+            assert!(broken.validate().is_err(), "{:?}", broken);
+        }
+    }
     
-    fs::write(&config_path, content)
-        .map_err(|e| e.to_string())?;
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("config-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
     
-    println!("Config saved successfully");
+    #[test]
+    fn test_partial_temp_file_leaves_config_untouched() {
+        let dir = test_dir("partial");
+        let config_path = dir.join("config.json");
+        
+        let mut config = Config {
+            display_name: "Lobby".to_string(),
+            ..Config::default()
+        };
+        save_config_to(&config_path, &config).unwrap();
+        
+        // Simulate a crash halfway through writing the temp file
+        fs::write(dir.join("config.json.tmp"), "{\"displayName\": \"Lob").unwrap();
+        
+        let loaded = load_config_from(&config_path).unwrap();
+        assert_eq!(loaded.display_name, "Lobby");
+        
+        // The next save replaces the stale temp file
+        config.display_name = "Foyer".to_string();
+        save_config_to(&config_path, &config).unwrap();
+        assert_eq!(load_config_from(&config_path).unwrap().display_name, "Foyer");
+        assert!(!dir.join("config.json.tmp").exists());
+        
+        let _ = fs::remove_dir_all(&dir);
+    }
     
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn test_slide_position_survives_reload() {
+        let dir = test_dir("slide");
+        let config_path = dir.join("config.json");
+        
+        let config = Config {
+            last_slide_index: 7,
+            last_slide_timestamp: Some("2024-01-02T15:04:05Z".to_string()),
+            ..Config::default()
+        };
+        save_config_to(&config_path, &config).unwrap();
+        
+        let loaded = load_config_from(&config_path).unwrap();
+        assert_eq!(loaded.last_slide_index, 7);
+        assert_eq!(loaded.last_slide_timestamp.as_deref(), Some("2024-01-02T15:04:05Z"));
+        assert_eq!(loaded.resume_slide_index(10), 7);
+        
+        // Media removed while the app was off
+        assert_eq!(loaded.resume_slide_index(3), 2);
+        assert_eq!(loaded.resume_slide_index(0), 0);
+        
+        assert_eq!(config_version(&loaded), config_version(&Config { last_slide_index: 0, ..loaded.clone() }));
+        
+        let _ = fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_duration_overrides() {
+        let mut config = Config {
+            image_duration: 5000,
+            ..Config::default()
+        };
+        assert_eq!(config.duration_for("slide.png"), 5000);
+        
+        config.durations.insert("slide.png".to_string(), 12000);
+        assert_eq!(config.duration_for("slide.png"), 12000);
+        assert_eq!(config.duration_for("other.png"), 5000);
+        
+        config.durations.insert("slide.png".to_string(), 3000);
+        assert_eq!(config.duration_for("slide.png"), 3000);
+        
+        assert!(config.forget_media("slide.png"));
+        assert!(config.durations.is_empty());
+        assert_eq!(config.duration_for("slide.png"), 5000);
+        assert!(!config.forget_media("slide.png"));
+    }
+    
+    #[test]
+    fn test_load_falls_back_to_backup() {
+        let dir = test_dir("backup");
+        let config_path = dir.join("config.json");
+        
+        let mut config = Config {
+            display_name: "First".to_string(),
+            ..Config::default()
+        };
+        save_config_to(&config_path, &config).unwrap();
+        config.display_name = "Second".to_string();
+        save_config_to(&config_path, &config).unwrap();
+        
+        fs::write(&config_path, "{\"displayName\": ").unwrap();
+        
+        let loaded = load_config_from(&config_path).unwrap();
+        assert_eq!(loaded.display_name, "First");
+        
+        let _ = fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_migrates_unversioned_config() {
+        let dir = test_dir("migrate");
+        let config_path = dir.join("config.json");
+        
+        // Shape of a config from before schema versions, addons or peers existed
+        fs::write(&config_path, r#"{
+            "displayName": "Reception",
+            "imageDuration": 8000,
+            "videoPosition": "after",
+            "imageScaling": "cover",
+            "manualResolution": false,
+            "password": "",
+            "staticIp": "",
+            "localhostOnly": false,
+            "port": 3006,
+            "wsPort": 3001,
+            "discoveryPort": 3002,
+            "rotation": 0
+        }"#).unwrap();
+        
+        let loaded = load_config_from(&config_path).unwrap();
+        assert_eq!(loaded.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(loaded.display_name, "Reception");
+        assert_eq!(loaded.image_duration, 8000);
+        assert!(loaded.addons.is_empty());
+        
+        // The migrated config is written back with its new version
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&config_path).unwrap()).unwrap();
+        assert_eq!(saved["schemaVersion"], serde_json::json!(CURRENT_SCHEMA_VERSION));
+        assert!(saved["addons"].is_object());
+        
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,67 @@
+use tauri::{Monitor, WebviewWindow};
+use tracing::{info, warn};
+
+/// Label of the presentation window in tauri.conf.json
+pub const WINDOW_LABEL: &str = "main";
+
+/// Which of `count` monitors shows monitor `index`: that one if it is connected, otherwise
+/// the primary monitor, or the first when the primary one isn't known
+pub fn choose_monitor(index: u32, count: usize, primary: Option<usize>) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+    
+    match usize::try_from(index) {
+        Ok(index) if index < count => Some(index),
+        _ => Some(primary.filter(|&p| p < count).unwrap_or(0)),
+    }
+}
+
+/// Move the window onto monitor `index` and make it fullscreen or not, returning the monitor used
+pub fn apply(window: &WebviewWindow, index: u32, fullscreen: bool) -> Result<u32, String> {
+    let monitors = window.available_monitors()
+        .map_err(|e| format!("Failed to list monitors: {}", e))?;
+    let primary = window.primary_monitor().ok().flatten()
+        .and_then(|primary| monitors.iter().position(|m| same_monitor(m, &primary)));
+    
+    let chosen = choose_monitor(index, monitors.len(), primary)
+        .ok_or("No monitors found")?;
+    if chosen != index as usize {
+        warn!("Monitor {} is not connected; presenting on monitor {} instead", index, chosen);
+    }
+    let monitor = &monitors[chosen];
+    
+    // A fullscreen window stays on its monitor, so it has to leave fullscreen to move
+    window.set_fullscreen(false)
+        .map_err(|e| format!("Failed to leave fullscreen: {}", e))?;
+    window.set_position(*monitor.position())
+        .map_err(|e| format!("Failed to move window: {}", e))?;
+    window.set_fullscreen(fullscreen)
+        .map_err(|e| format!("Failed to set fullscreen: {}", e))?;
+    
+    info!(
+        "Presenting on monitor {} ({})",
+        chosen,
+        monitor.name().map(String::as_str).unwrap_or("unnamed")
+    );
+    Ok(chosen as u32)
+}
+
+fn same_monitor(a: &Monitor, b: &Monitor) -> bool {
+    a.position() == b.position() && a.size() == b.size()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_choose_monitor() {
+        assert_eq!(choose_monitor(1, 3, Some(0)), Some(1));
+        
+        // A monitor that was unplugged falls back to the primary one
+        assert_eq!(choose_monitor(5, 3, Some(2)), Some(2));
+        assert_eq!(choose_monitor(u32::MAX, 2, None), Some(0));
+        assert_eq!(choose_monitor(0, 0, None), None);
+    }
+}
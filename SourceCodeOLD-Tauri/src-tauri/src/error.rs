@@ -0,0 +1,62 @@
+use std::fmt;
+
+/// Error from the media, config, fonts and addon modules. The variant says what kind of
+/// failure it was, so the web API can pick a status code without parsing the message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    /// The file, font or addon asked for doesn't exist
+    NotFound(String),
+    /// The input is unusable: an unsafe name, an unsupported type, corrupt content
+    Validation(String),
+    /// The request clashes with what is already there, e.g. renaming onto an existing file
+    Conflict(String),
+    /// Reading or writing on disk failed
+    Io(String),
+    /// An addon's Lua backend failed
+    Lua(String),
+    /// Storing this would take the Media folder past its configured size limit
+    QuotaExceeded(String),
+}
+
+impl AppError {
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Validation(message)
+            | AppError::Conflict(message)
+            | AppError::Io(message)
+            | AppError::Lua(message)
+            | AppError::QuotaExceeded(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::NotFound(e.to_string()),
+            _ => AppError::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<mlua::Error> for AppError {
+    fn from(e: mlua::Error) -> Self {
+        AppError::Lua(e.to_string())
+    }
+}
+
+/// Tauri commands and older callers still deal in plain messages
+impl From<AppError> for String {
+    fn from(e: AppError) -> Self {
+        e.to_string()
+    }
+}
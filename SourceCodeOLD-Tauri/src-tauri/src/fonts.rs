@@ -1,100 +1,101 @@
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 use tokio::fs;
 
-pub fn get_fonts_dir() -> Result<std::path::PathBuf, String> {
+/// What the UI shows for a font instead of its file name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontMeta {
+    pub filename: String,
+    pub family: String,
+    /// CSS font-style: "normal", "italic" or "oblique"
+    pub style: String,
+    /// CSS font-weight, e.g. 400 or 700
+    pub weight: u16,
+}
+
+pub fn get_fonts_dir() -> Result<std::path::PathBuf, AppError> {
     crate::paths::get_fonts_dir()
 }
 
-pub async fn ensure_fonts_dir() -> Result<(), String> {
-    let fonts_dir = get_fonts_dir()?;
+/// Path of a font in the Fonts folder, refusing names that would leave it
+pub fn font_path(font_name: &str) -> Result<std::path::PathBuf, AppError> {
+    let font_name = crate::media::sanitize_filename(font_name)?;
+    let font_path = get_fonts_dir()?.join(&font_name);
     
-    if !fonts_dir.exists() {
-        fs::create_dir_all(&fonts_dir).await
-            .map_err(|e| e.to_string())?;
-        println!("Created Fonts directory: {:?}", fonts_dir);
+    if !font_path.is_file() {
+        return Err(AppError::NotFound(format!("Font not found: {}", font_name)));
     }
     
-    Ok(())
+    Ok(font_path)
 }
 
-pub async fn get_font_as_base64(font_name: &str) -> Result<String, String> {
-    let fonts_dir = get_fonts_dir()?;
-    let font_path = fonts_dir.join(font_name);
+/// MIME type of a font, from its extension
+pub fn font_content_type(font_name: &str) -> &'static str {
+    let ext = Path::new(font_name).extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
     
-    if !font_path.exists() {
-        return Err(format!("Font not found: {}", font_name));
+    match ext.as_deref() {
+        Some("ttf") => "font/ttf",
+        Some("otf") => "font/otf",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
     }
+}
+
+pub async fn get_font_as_base64(font_name: &str) -> Result<String, AppError> {
+    let font_data = fs::read(font_path(font_name)?).await?;
+    let mime_type = font_content_type(font_name);
     
-    let font_data = fs::read(&font_path).await
-        .map_err(|e| e.to_string())?;
-    
-    let base64 = base64_encode(&font_data);
-    
-    // Determine MIME type based on extension
-    let mime_type = if font_name.ends_with(".ttf") {
-        "font/ttf"
-    } else if font_name.ends_with(".otf") {
-        "font/otf"
-    } else if font_name.ends_with(".woff") {
-        "font/woff"
-    } else if font_name.ends_with(".woff2") {
-        "font/woff2"
-    } else {
-        "application/octet-stream"
-    };
+    // Encode straight into the data URI instead of building a second copy
+    let prefix = format!("data:{};base64,", mime_type);
+    let mut data_uri = String::with_capacity(prefix.len() + font_data.len() * 4 / 3 + 4);
+    data_uri.push_str(&prefix);
+    base64_encode_into(&font_data, &mut data_uri);
     
-    Ok(format!("data:{};base64,{}", mime_type, base64))
+    Ok(data_uri)
 }
 
-fn base64_encode(data: &[u8]) -> String {
-    use std::fmt::Write;
-    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// Append the standard (padded) base64 encoding of `data` to `out`
+fn base64_encode_into(data: &[u8], out: &mut String) {
+    const CHARSET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    // Encode in chunks so the intermediate buffer stays small for large fonts
+    const CHUNK_BYTES: usize = 3 * 1024;
     
-    let mut result = String::new();
-    let mut i = 0;
+    let mut buf = Vec::with_capacity(CHUNK_BYTES / 3 * 4);
     
-    while i < data.len() {
-        let b1 = data[i];
-        let b2 = if i + 1 < data.len() { data[i + 1] } else { 0 };
-        let b3 = if i + 2 < data.len() { data[i + 2] } else { 0 };
-        
-        let enc1 = (b1 >> 2) as usize;
-        let enc2 = (((b1 & 0x3) << 4) | (b2 >> 4)) as usize;
-        let enc3 = (((b2 & 0xf) << 2) | (b3 >> 6)) as usize;
-        let enc4 = (b3 & 0x3f) as usize;
-        
-        write!(&mut result, "{}", CHARSET[enc1] as char).unwrap();
-        write!(&mut result, "{}", CHARSET[enc2] as char).unwrap();
-        
-        if i + 1 < data.len() {
-            write!(&mut result, "{}", CHARSET[enc3] as char).unwrap();
-        } else {
-            write!(&mut result, "=").unwrap();
-        }
+    for chunk in data.chunks(CHUNK_BYTES) {
+        buf.clear();
         
-        if i + 2 < data.len() {
-            write!(&mut result, "{}", CHARSET[enc4] as char).unwrap();
-        } else {
-            write!(&mut result, "=").unwrap();
+        for group in chunk.chunks(3) {
+            let b1 = group[0];
+            let b2 = group.get(1).copied().unwrap_or(0);
+            let b3 = group.get(2).copied().unwrap_or(0);
+            
+            buf.push(CHARSET[(b1 >> 2) as usize]);
+            buf.push(CHARSET[(((b1 & 0x3) << 4) | (b2 >> 4)) as usize]);
+            buf.push(if group.len() > 1 { CHARSET[(((b2 & 0xf) << 2) | (b3 >> 6)) as usize] } else { b'=' });
+            buf.push(if group.len() > 2 { CHARSET[(b3 & 0x3f) as usize] } else { b'=' });
         }
         
-        i += 3;
+        // Every byte pushed comes from CHARSET or '=', so this is always ASCII
+        out.push_str(std::str::from_utf8(&buf).expect("base64 output is ASCII"));
     }
-    
-    result
 }
 
-pub async fn list_fonts() -> Result<Vec<String>, String> {
+pub async fn list_fonts() -> Result<Vec<String>, AppError> {
     let fonts_dir = get_fonts_dir()?;
     
     if !fonts_dir.exists() {
         return Ok(Vec::new());
     }
     
-    let mut entries = fs::read_dir(&fonts_dir).await
-        .map_err(|e| e.to_string())?;
+    let mut entries = fs::read_dir(&fonts_dir).await?;
     let mut fonts = Vec::new();
     
-    while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+    while let Some(entry) = entries.next_entry().await? {
         let path = entry.path();
         
         if path.is_file() {
@@ -111,4 +112,110 @@ pub async fn list_fonts() -> Result<Vec<String>, String> {
     
     fonts.sort();
     Ok(fonts)
-}
\ No newline at end of file
+}
+
+/// Like `list_fonts`, but with the family, style and weight each font declares
+pub async fn list_fonts_detailed() -> Result<Vec<FontMeta>, AppError> {
+    let fonts_dir = get_fonts_dir()?;
+    let mut fonts = Vec::new();
+    
+    for filename in list_fonts().await? {
+        let data = fs::read(fonts_dir.join(&filename)).await?;
+        fonts.push(font_meta(&filename, &data));
+    }
+    
+    Ok(fonts)
+}
+
+/// Read a font's name and OS/2 tables, guessing from the file name when that fails (e.g. WOFF)
+pub fn font_meta(filename: &str, data: &[u8]) -> FontMeta {
+    let Ok(face) = ttf_parser::Face::parse(data, 0) else {
+        return meta_from_filename(filename);
+    };
+    
+    let name = |id: u16| {
+        face.names()
+            .into_iter()
+            .filter(|n| n.name_id == id)
+            .find_map(|n| n.to_string())
+            .filter(|n| !n.trim().is_empty())
+    };
+    
+    // The typographic family groups every weight under one name, where the legacy one may not
+    let Some(family) = name(ttf_parser::name_id::TYPOGRAPHIC_FAMILY)
+        .or_else(|| name(ttf_parser::name_id::FAMILY))
+    else {
+        return meta_from_filename(filename);
+    };
+    
+    let style = match face.style() {
+        ttf_parser::Style::Normal => "normal",
+        ttf_parser::Style::Italic => "italic",
+        ttf_parser::Style::Oblique => "oblique",
+    };
+    
+    FontMeta {
+        filename: filename.to_string(),
+        family,
+        style: style.to_string(),
+        weight: face.weight().to_number(),
+    }
+}
+
+/// Best guess from names like `Roboto-BoldItalic.ttf`
+fn meta_from_filename(filename: &str) -> FontMeta {
+    let stem = Path::new(filename)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (family, variant) = stem.split_once('-').unwrap_or((&stem, ""));
+    let variant = variant.to_lowercase();
+    
+    FontMeta {
+        filename: filename.to_string(),
+        family: family.replace('_', " "),
+        style: if variant.contains("italic") { "italic" } else { "normal" }.to_string(),
+        weight: if variant.contains("bold") { 700 } else { 400 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn base64_encode(data: &[u8]) -> String {
+        let mut result = String::new();
+        base64_encode_into(data, &mut result);
+        result
+    }
+    
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        
+        // Crosses a chunk boundary
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let encoded = base64_encode(&data);
+        assert_eq!(encoded.len(), data.len().div_ceil(3) * 4);
+        assert!(encoded.ends_with('='));
+    }
+    
+    #[test]
+    fn test_font_meta() {
+        let data = include_bytes!("../tests/fixtures/FixtureSans-Bold.ttf");
+        let meta = font_meta("FixtureSans-Bold.ttf", data);
+        assert_eq!(meta.family, "Fixture Sans");
+        assert_eq!(meta.weight, 700);
+        assert_eq!(meta.style, "normal");
+        
+        // Not a font we can read, so the file name is all there is
+        let meta = font_meta("Roboto-BoldItalic.woff2", b"wOF2");
+        assert_eq!(meta.family, "Roboto");
+        assert_eq!(meta.weight, 700);
+        assert_eq!(meta.style, "italic");
+    }
+}
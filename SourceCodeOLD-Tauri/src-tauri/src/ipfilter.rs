@@ -0,0 +1,113 @@
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Parse an `allowed_ips` entry: a CIDR range like `192.168.10.0/24`, or a single address
+pub fn parse_range(entry: &str) -> Result<IpNet, String> {
+    let entry = entry.trim();
+    entry.parse::<IpNet>()
+        .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("'{}' is not an IP address or CIDR range", entry))
+}
+
+/// Whether `client` may use the server under `allowed_ips`. An empty list allows everyone,
+/// and the device itself is always let in so a bad list can be fixed locally.
+pub fn is_allowed(client: IpAddr, allowed_ips: &[String]) -> bool {
+    // Dual-stack listeners report IPv4 clients as ::ffff:a.b.c.d
+    let client = client.to_canonical();
+    
+    if allowed_ips.is_empty() || client.is_loopback() {
+        return true;
+    }
+    
+    allowed_ips.iter()
+        .filter_map(|entry| parse_range(entry).ok())
+        .any(|range| range.contains(&client))
+}
+
+/// Middleware answering 403 to clients outside `allowed_ips`
+pub async fn restrict_clients(
+    config: Arc<Mutex<crate::config::Config>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = request.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    
+    let allowed = {
+        let cfg = config.lock().unwrap();
+        match client {
+            Some(client) => is_allowed(client, &cfg.allowed_ips),
+            None => cfg.allowed_ips.is_empty(),
+        }
+    };
+    
+    if !allowed {
+        warn!(
+            "Refused {} {} from {}: not in allowed IPs",
+            request.method(),
+            request.uri().path(),
+            client.map_or("unknown address".to_string(), |ip| ip.to_string())
+        );
+        return (StatusCode::FORBIDDEN, Json(serde_json::json!({
+            "error": "This address is not allowed to use the server"
+        }))).into_response();
+    }
+    
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::Service;
+    
+    #[tokio::test]
+    async fn test_clients_outside_allowed_ips_are_rejected() {
+        let config = Arc::new(Mutex::new(crate::config::Config {
+            allowed_ips: vec!["192.168.10.0/24".to_string(), "fd00::/8".to_string(), "10.0.0.7".to_string()],
+            ..crate::config::Config::default()
+        }));
+        let app = Router::new()
+            .route("/api/config", get(|| async { "ok" }))
+            .layer(middleware::from_fn({
+                let config = config.clone();
+                move |request, next| restrict_clients(config.clone(), request, next)
+            }));
+        
+        let status_from = |ip: &str| {
+            let mut request = Request::builder().uri("/api/config").body(Body::empty()).unwrap();
+            let addr = SocketAddr::new(ip.parse().unwrap(), 50000);
+            request.extensions_mut().insert(ConnectInfo(addr));
+            // A Router is always ready, so it can be called directly
+            let mut app = app.clone();
+            async move { app.call(request).await.unwrap().status() }
+        };
+        
+        assert_eq!(status_from("192.168.10.42").await, StatusCode::OK);
+        assert_eq!(status_from("::ffff:192.168.10.42").await, StatusCode::OK);
+        assert_eq!(status_from("fd12::1").await, StatusCode::OK);
+        assert_eq!(status_from("10.0.0.7").await, StatusCode::OK);
+        assert_eq!(status_from("127.0.0.1").await, StatusCode::OK);
+        
+        assert_eq!(status_from("192.168.11.1").await, StatusCode::FORBIDDEN);
+        assert_eq!(status_from("10.0.0.8").await, StatusCode::FORBIDDEN);
+        assert_eq!(status_from("2001:db8::1").await, StatusCode::FORBIDDEN);
+        
+        // An empty list leaves everyone in
+        config.lock().unwrap().allowed_ips.clear();
+        assert_eq!(status_from("192.168.11.1").await, StatusCode::OK);
+        
+        assert!(parse_range("192.168.1.0/33").is_err());
+        assert!(parse_range("example.com").is_err());
+    }
+}
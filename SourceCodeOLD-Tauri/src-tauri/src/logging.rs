@@ -0,0 +1,167 @@
+use crate::error::AppError;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::level_filters::LevelFilter;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, Registry};
+
+type LevelHandle = reload::Handle<LevelFilter, Registry>;
+
+static LEVEL: OnceLock<LevelHandle> = OnceLock::new();
+
+/// Log files are named `server.<date>.log`, one per day
+const LOG_FILE_PREFIX: &str = "server";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// Days of logs kept in the Logs folder; older files are deleted as new ones start
+const MAX_LOG_FILES: usize = 14;
+
+/// A log file as listed by the API
+#[derive(Debug, Clone, Serialize)]
+pub struct LogFile {
+    pub name: String,
+    pub size: u64,
+    pub modified: String,
+}
+
+/// Parse a `log_level` setting: "trace", "debug", "info", "warn", "error" or "off"
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.trim().parse::<LevelFilter>()
+        .map_err(|_| format!("Unknown log level '{}'", level))
+}
+
+/// Install the global subscriber at info level, writing to the console and the Logs folder;
+/// call `set_level` once the config is loaded
+pub fn init() {
+    let (filter, handle) = reload::Layer::new(LevelFilter::INFO);
+    let file_layer = file_appender().map(|appender| fmt::layer().with_writer(appender).with_ansi(false));
+    
+    if tracing_subscriber::registry().with(filter).with(fmt::layer()).with(file_layer).try_init().is_ok() {
+        let _ = LEVEL.set(handle);
+    }
+}
+
+/// Daily log files in the Logs folder, or None if it can't be written
+fn file_appender() -> Option<RollingFileAppender> {
+    let dir = crate::paths::get_logs_dir().ok()?;
+    
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(&dir);
+    
+    match appender {
+        Ok(appender) => Some(appender),
+        Err(e) => {
+            // Nothing is listening for tracing events yet
+            eprintln!("Logging to the console only: can't write logs to {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+/// The log files in `dir`, newest first
+pub async fn list_logs(dir: &Path) -> Result<Vec<LogFile>, AppError> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    
+    let mut logs = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        if !metadata.is_file() || !is_log_name(&name) {
+            continue;
+        }
+        
+        logs.push(LogFile {
+            name,
+            size: metadata.len(),
+            modified: metadata.modified().map(crate::media::format_modified).unwrap_or_default(),
+        });
+    }
+    
+    // Dated names sort chronologically
+    logs.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(logs)
+}
+
+/// Path of the log file `name` in `dir`, refusing anything that isn't a plain log file name
+pub fn log_file_path(dir: &Path, name: &str) -> Result<PathBuf, AppError> {
+    let name = crate::media::sanitize_filename(name)?;
+    if !is_log_name(&name) {
+        return Err(AppError::Validation(format!("Not a log file: {}", name)));
+    }
+    
+    Ok(dir.join(name))
+}
+
+fn is_log_name(name: &str) -> bool {
+    name.ends_with(&format!(".{}", LOG_FILE_SUFFIX))
+}
+
+/// Change the level of the running subscriber
+pub fn set_level(level: &str) -> Result<(), String> {
+    match LEVEL.get() {
+        Some(handle) => apply_level(handle, level),
+        None => Ok(()),
+    }
+}
+
+fn apply_level(handle: &LevelHandle, level: &str) -> Result<(), String> {
+    let filter = parse_level(level)?;
+    handle.reload(filter).map_err(|e| format!("Failed to set log level: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    
+    #[test]
+    fn test_level_filter() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let writer = {
+            let output = output.clone();
+            move || Capture(output.clone())
+        };
+        
+        let (filter, handle) = reload::Layer::new(LevelFilter::TRACE);
+        let subscriber = tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer().with_writer(writer).with_ansi(false));
+        
+        tracing::subscriber::with_default(subscriber, || {
+            apply_level(&handle, "INFO").unwrap();
+            tracing::debug!("hidden detail");
+            tracing::info!("visible message");
+        });
+        
+        let logged = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert!(logged.contains("visible message"));
+        assert!(!logged.contains("hidden detail"));
+        
+        assert!(apply_level(&handle, "loud").is_err());
+    }
+    
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+    
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -1,108 +1,290 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "console")]
+// serde_json::json! recurses once per key, and config::to_api_json has many
+#![recursion_limit = "256"]
 
 mod config;
+mod error;
 mod media;
 mod addon;
 mod fonts;
 mod paths;
+mod capabilities;
+mod preview;
+mod scheduler;
+mod system;
+mod logging;
+mod bundle;
+mod ws;
+mod auth;
+mod ratelimit;
+mod ipfilter;
+mod playback;
+mod display;
+#[path = "Network.rs"]
+mod network;
+#[path = "Update.rs"]
+mod update;
 
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
-use tauri::{State, AppHandle, Emitter};
+use tauri::{State, AppHandle, Emitter, Manager};
 use axum::{
-    extract::{Multipart, Path as AxumPath, DefaultBodyLimit},
-    response::{IntoResponse, Json},
+    extract::{rejection::JsonRejection, Multipart, Path as AxumPath, Query, DefaultBodyLimit},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
-use tower_http::{services::ServeDir, cors::CorsLayer};
+use tower_http::{
+    services::ServeDir,
+    cors::{AllowOrigin, CorsLayer},
+    compression::{
+        predicate::{DefaultPredicate, NotForContentType, Predicate},
+        CompressionLayer,
+    },
+};
 use std::net::SocketAddr;
+use tracing::{debug, error, info, warn};
+
+/// Body limit for small JSON endpoints (config, addon settings)
+const JSON_BODY_LIMIT: usize = 64 * 1024;
+
+/// Body limit for file uploads (media, fonts, updates)
+const UPLOAD_BODY_LIMIT: usize = 100 * 1024 * 1024;
+
+/// How far above the configured port to look when it is already taken
+const PORT_SEARCH_RANGE: u16 = 10;
+
+/// Port the web server ended up on, or why it couldn't start; set once at startup
+static SERVER_PORT: std::sync::OnceLock<Result<u16, String>> = std::sync::OnceLock::new();
+
+/// Data folders that failed the startup check; set once at startup
+static STARTUP_PROBLEMS: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+
+/// Port the web server is actually serving on, which may differ from the configured one
+fn server_port() -> Option<u16> {
+    SERVER_PORT.get().and_then(|status| status.as_ref().ok().copied())
+}
+
+/// Error response for the web API: a status code with the usual {"error": ...} body
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn api_error(status: StatusCode, message: impl std::fmt::Display) -> ApiError {
+    (status, Json(serde_json::json!({
+        "error": message.to_string()
+    })))
+}
+
+/// Lets handlers use `?` on module errors; the variant picks the status code
+impl From<error::AppError> for ApiError {
+    fn from(e: error::AppError) -> Self {
+        let status = match &e {
+            error::AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            error::AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            error::AppError::Conflict(_) => StatusCode::CONFLICT,
+            error::AppError::QuotaExceeded(_) => StatusCode::INSUFFICIENT_STORAGE,
+            error::AppError::Io(_) | error::AppError::Lua(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        api_error(status, e)
+    }
+}
+
+/// Unwrap a JSON body, turning malformed input into a 400 with a JSON error
+fn json_body<T>(body: Result<Json<T>, JsonRejection>) -> Result<T, ApiError> {
+    body.map(|Json(value)| value)
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.body_text()))
+}
 
 #[allow(dead_code)]
 struct AppState {
     config: Arc<Mutex<config::Config>>,
     app_handle: Arc<Mutex<Option<AppHandle>>>,
+    preview: Arc<preview::PreviewHub>,
+    playback: playback::SharedPlayback,
 }
 
 #[tauri::command]
 fn log_message(message: String) {
-    println!("[FRONTEND] {}", message);
+    info!("[FRONTEND] {}", message);
 }
 
 #[tauri::command]
-fn get_config(state: State<AppState>) -> Result<config::Config, String> {
+fn submit_preview_frame(state: State<AppState>, frame: Vec<u8>) {
+    state.preview.publish(frame);
+}
+
+/// Port the web server is serving on, or why it failed to start
+#[tauri::command]
+fn get_server_port() -> Result<u16, String> {
+    SERVER_PORT.get()
+        .cloned()
+        .unwrap_or_else(|| Err("Web server has not started".to_string()))
+}
+
+/// Data folders that couldn't be created or written to at startup
+#[tauri::command]
+fn get_startup_problems() -> Vec<String> {
+    STARTUP_PROBLEMS.get().cloned().unwrap_or_default()
+}
+
+/// Where the slideshow is; the display window starts from here
+#[tauri::command]
+fn get_playback_state(state: State<AppState>) -> playback::PlaybackState {
+    *state.playback.lock().unwrap()
+}
+
+/// The display window reports each slide it shows, so the API knows where it is
+#[tauri::command]
+fn report_playback_index(state: State<AppState>, index: usize) {
+    state.playback.lock().unwrap().index = index;
+}
+
+#[tauri::command]
+fn get_config(state: State<AppState>) -> Result<serde_json::Value, String> {
     let config = state.config.lock().unwrap();
-    Ok(config.clone())
+    Ok(config::to_api_json(&config))
 }
 
 #[tauri::command]
-fn save_config_command(state: State<AppState>, new_config: config::Config) -> Result<(), String> {
+fn save_config_command(state: State<AppState>, mut new_config: config::Config) -> Result<(), String> {
+    // The settings window sends a new password as plaintext
+    if auth::hash_if_plaintext(&mut new_config) {
+        auth::clear_sessions();
+    }
+    
     let mut config = state.config.lock().unwrap();
-    *config = new_config.clone();
-    config::save_config(&new_config)?;
+    *config = new_config;
+    config::save_shared(&mut config)?;
+    logging::set_level(&config.log_level)?;
     Ok(())
 }
 
+/// Move the presentation to another monitor and remember it for the next start.
+/// Returns the monitor actually used, which is the primary one if `index` isn't connected.
 #[tauri::command]
-async fn get_media_files() -> Result<Vec<media::MediaFile>, String> {
-    media::get_files().await
+fn set_display(app: AppHandle, state: State<AppState>, index: u32, fullscreen: Option<bool>) -> Result<u32, String> {
+    let window = app.get_webview_window(display::WINDOW_LABEL)
+        .ok_or("Display window not found")?;
+    
+    let mut cfg = state.config.lock().unwrap();
+    let fullscreen = fullscreen.unwrap_or(cfg.fullscreen);
+    let used = display::apply(&window, index, fullscreen)?;
+    
+    // Keep the monitor asked for, so it is used again once it is plugged back in
+    cfg.display_index = index;
+    cfg.fullscreen = fullscreen;
+    config::save_shared(&mut cfg)?;
+    
+    let _ = app.emit("config-update", config::to_api_json(&cfg));
+    ws::broadcast_config_update(&cfg);
+    Ok(used)
 }
 
 #[tauri::command]
-async fn delete_media_file(filename: String) -> Result<(), String> {
-    media::delete_file(&filename).await
+async fn get_media_files(state: State<'_, AppState>) -> Result<Vec<media::MediaFile>, String> {
+    playlist(&state.config).await
+        .map_err(String::from)
 }
 
+/// The display window calls this with the seed of the cycle it just finished, so a
+/// shuffled slideshow plays the next cycle in a new order. Returns the new cycle's seed.
 #[tauri::command]
-async fn get_addon_frontend_script(addon_id: String) -> Result<String, String> {
-    // Load addons and get the config for this addon
-    let mut addons = addon::scan_addons().await?;
-    let config = config::load_config()?;
+fn start_playback_cycle(state: State<AppState>, seed: u64) -> Result<u64, String> {
+    let handle = state.app_handle.lock().unwrap().clone();
+    reshuffle(&state.config, handle.as_ref(), seed)
+        .map_err(String::from)
+}
+
+#[tauri::command]
+async fn delete_media_file(state: State<'_, AppState>, filename: String) -> Result<(), String> {
+    state.config.lock().unwrap().check_unlocked(&filename)?;
+    media::delete_file(&filename).await?;
     
-    // Find the addon
-    let addon_item = addons.iter_mut()
-        .find(|a| a.id == addon_id)
-        .ok_or("Addon not found")?;
+    let mut cfg = state.config.lock().unwrap();
+    if cfg.forget_media(&filename) {
+        config::save_shared(&mut cfg)?;
+    }
     
-    // Merge config
-    let saved_config = config.addons.get(&addon_item.id);
-    addon::merge_addon_config(addon_item, saved_config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_addon_frontend_script(addon_id: String) -> Result<String, String> {
+    let addon_item = find_addon_with_config(&addon_id).await?
+        .ok_or("Addon not found")?;
     
     // Get frontend script with injected config
     addon::get_frontend_script_with_config(&addon_id, &addon_item.config).await
+        .map_err(String::from)
+}
+
+/// Look up an installed addon with its saved config merged in
+async fn find_addon_with_config(addon_id: &str) -> Result<Option<addon::Addon>, String> {
+    let addons = addon::scan_addons(false).await?;
+    let config = config::load_config()?;
+    
+    Ok(addons.into_iter()
+        .find(|a| a.id == addon_id)
+        .map(|mut addon_item| {
+            addon::merge_addon_config(&mut addon_item, config.addons.get(addon_id));
+            addon_item
+        }))
 }
 
 #[tauri::command]
-async fn save_addon_config(addon_id: String, new_config: HashMap<String, serde_json::Value>) -> Result<(), String> {
-    let mut config = config::load_config()?;
+async fn save_addon_config(state: State<'_, AppState>, addon_id: String, new_config: HashMap<String, serde_json::Value>) -> Result<(), String> {
+    let settings = addon::declared_settings(&addon_id).await?;
+    let addon_dir = addon::get_addons_dir()?.join(&addon_id);
+    let new_config = addon::validate_config_values(&settings, &addon_dir, &new_config.into_iter().collect())?;
     
-    // Update addon config in main config
-    config.addons.insert(addon_id, new_config);
+    {
+        let mut config = state.config.lock().unwrap();
+        config::refresh_addons(&mut config)?;
+        
+        // Update addon config in main config
+        config.addons.insert(addon_id, new_config);
+        
+        config::save_config(&config)?;
+    }
     
-    config::save_config(&config)?;
+    // Enabling or disabling an addon starts or stops its scheduled tasks
+    tokio::spawn(scheduler::sync_schedules());
     
     Ok(())
 }
 
 #[tauri::command]
-async fn reload_addons() -> Result<(), String> {
-    // Just a placeholder for now - actual reload will happen when frontend calls get_addons again
+async fn reload_addons(state: State<'_, AppState>) -> Result<(), String> {
+    reload_addons_and_notify(&state.app_handle).await?;
     Ok(())
 }
 
+/// Re-scan addons from disk, then let the display and scheduler pick up the changes
+async fn reload_addons_and_notify(app_handle: &Arc<Mutex<Option<AppHandle>>>) -> Result<serde_json::Value, String> {
+    let addons = get_addons_internal(true).await?;
+    
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("addons-update", ());
+        debug!("Emitted addons-update event");
+    }
+    
+    scheduler::sync_schedules().await;
+    
+    Ok(addons)
+}
+
 #[tauri::command]
 async fn get_font_data(font_name: String) -> Result<String, String> {
-    println!("=== get_font_data called ===");
-    println!("Font name requested: {}", font_name);
+    debug!("Font data requested: {}", font_name);
     
     match fonts::get_font_as_base64(&font_name).await {
         Ok(data) => {
-            println!("Font loaded successfully, data length: {}", data.len());
+            debug!("Font loaded, data length: {}", data.len());
             Ok(data)
         }
         Err(e) => {
-            println!("Failed to load font: {}", e);
-            Err(e)
+            warn!("Failed to load font: {}", e);
+            Err(e.into())
         }
     }
 }
@@ -110,11 +292,24 @@ async fn get_font_data(font_name: String) -> Result<String, String> {
 #[tauri::command]
 async fn list_fonts() -> Result<Vec<String>, String> {
     fonts::list_fonts().await
+        .map_err(String::from)
+}
+
+#[tauri::command]
+async fn apply_update(path: String, sha256: String, signature: Option<String>) -> Result<(), String> {
+    update::process_update(&path, &sha256, signature.as_deref()).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_fonts_detailed() -> Result<Vec<fonts::FontMeta>, String> {
+    fonts::list_fonts_detailed().await
+        .map_err(String::from)
 }
 
 #[tauri::command]
 async fn get_addons() -> Result<serde_json::Value, String> {
-    let mut addons = addon::scan_addons().await?;
+    let mut addons = addon::scan_addons(false).await?;
     
     // Load saved configs from main config
     let config = config::load_config()?;
@@ -126,7 +321,7 @@ async fn get_addons() -> Result<serde_json::Value, String> {
     
     // Convert to JSON object with addon IDs as keys
     let mut addons_map = serde_json::Map::new();
-    for addon_item in addons {
+    for (load_index, addon_item) in addons.into_iter().enumerate() {
         addons_map.insert(addon_item.id.clone(), serde_json::json!({
             "id": addon_item.id,
             "info": {
@@ -135,10 +330,17 @@ async fn get_addons() -> Result<serde_json::Value, String> {
                 "author": addon_item.info.author,
                 "description": addon_item.info.description,
                 "category": addon_item.info.category,
+                "groups": addon_item.info.groups,
             },
             "enabled": addon_item.enabled,
             "config": addon_item.config,
             "settings": addon_item.settings,
+            "configKeyWarnings": addon_item.config_key_warnings,
+            "permissions": addon_item.permissions,
+            "loadError": addon_item.load_error,
+            "lastError": addon_item.last_error,
+            // The map is keyed by id, so carry the dependency order explicitly
+            "loadIndex": load_index,
         }));
     }
     
@@ -151,61 +353,112 @@ fn get_addons_dir() -> Result<String, String> {
     Ok(path.to_string_lossy().to_string())
 }
 
+/// Call a function in an addon's backend.lua, e.g. to try it out with some inputs
 #[tauri::command]
-async fn call_addon_function(addon_id: String, function_name: String) -> Result<String, String> {
-    println!("=== CALLING ADDON FUNCTION: {} in {} ===", function_name, addon_id);
+async fn call_addon_function(
+    app: AppHandle,
+    addon_id: String,
+    function_name: String,
+    args: Option<Vec<serde_json::Value>>,
+) -> Result<serde_json::Value, String> {
+    run_addon_function(addon_id, function_name, args.unwrap_or_default(), app_event_sink(app)).await
+}
+
+/// Forward addon.emit events to the display window
+fn app_event_sink(app: AppHandle) -> addon::EventSink {
+    Arc::new(move |event| {
+        if let Err(e) = app.emit("addon-event", event) {
+            warn!("Failed to emit addon event: {}", e);
+        }
+    })
+}
+
+/// Call `function_name` in an addon's backend with `args` as its parameters, returning what
+/// it returns as JSON: nil becomes null and tables become objects or arrays
+async fn run_addon_function(
+    addon_id: String,
+    function_name: String,
+    args: Vec<serde_json::Value>,
+    sink: addon::EventSink,
+) -> Result<serde_json::Value, String> {
+    debug!("Calling addon function {} in {} with {} argument(s)", function_name, addon_id, args.len());
+    
+    use mlua::prelude::*;
+    let lua = load_addon_backend(&addon_id, sink).await?;
+    
+    let Ok(func) = lua.globals().get::<_, LuaFunction>(function_name.as_str()) else {
+        return Err(format!("Function '{}' not found in addon", function_name));
+    };
+    
+    // null arguments arrive as nil, as with addon.json_decode
+    let options = mlua::SerializeOptions::new()
+        .serialize_none_to_null(false)
+        .serialize_unit_to_null(false);
+    let lua_args = args.iter()
+        .map(|arg| lua.to_value_with(arg, options))
+        .collect::<Result<mlua::Variadic<mlua::Value>, _>>()
+        .map_err(|e| format!("Failed to convert arguments for Lua: {}", e))?;
     
-    // Load the addon's backend.lua
+    let result = func.call::<_, mlua::Value>(lua_args)
+        .map_err(|e| format!("Function call failed: {}", e))?;
+    
+    // Functions and other values JSON can't hold are left out rather than failing the call
+    lua.from_value_with(result, mlua::DeserializeOptions::new().deny_unsupported_types(false))
+        .map_err(|e| format!("Failed to convert result to JSON: {}", e))
+}
+
+/// Run an addon's backend.lua in a fresh Lua instance with the addon API set up
+async fn load_addon_backend(addon_id: &str, sink: addon::EventSink) -> Result<mlua::Lua, String> {
     let addons_dir = addon::get_addons_dir()?;
-    let backend_path = addons_dir.join(&addon_id).join("backend.lua");
+    let backend_path = addons_dir.join(addon_id).join("backend.lua");
     
     if !backend_path.exists() {
         return Err(format!("Addon {} has no backend.lua", addon_id));
     }
     
-    // Read the Lua script
     let lua_script = tokio::fs::read_to_string(&backend_path).await
         .map_err(|e| format!("Failed to read backend.lua: {}", e))?;
     
-    // Create Lua instance
-    use mlua::prelude::*;
-    let lua = Lua::new();
-    
-    // Setup addon API
-    setup_lua_api_for_runtime(&lua, &addon_id)?;
+    let lua = mlua::Lua::new();
+    setup_lua_api_for_runtime(&lua, addon_id, sink)?;
+    addon::limit_execution(&lua, addon::LuaLimits::load())?;
     
-    // Execute the Lua script
     lua.load(&lua_script).exec()
         .map_err(|e| format!("Failed to execute Lua script: {}", e))?;
     
-    // Call the requested function
-    let globals = lua.globals();
-    let result = match globals.get::<_, LuaFunction>(function_name.as_str()) {
-        Ok(func) => {
-            match func.call::<_, mlua::Value>(()) {
-                Ok(result) => {
-                    // Convert result to string
-                    let result_str = match result {
-                        mlua::Value::Boolean(b) => b.to_string(),
-                        mlua::Value::String(s) => s.to_str().unwrap_or("").to_string(),
-                        mlua::Value::Number(n) => n.to_string(),
-                        mlua::Value::Nil => "nil".to_string(),
-                        _ => "success".to_string(),
-                    };
-                    Ok(result_str)
-                }
-                Err(e) => Err(format!("Function call failed: {}", e))
-            }
-        }
-        Err(_) => Err(format!("Function '{}' not found in addon", function_name))
+    Ok(lua)
+}
+
+/// Call `on_config_change(config)` in an addon's backend.lua, if it defines one,
+/// with the addon's settings as they are after a save
+async fn notify_config_change(
+    addon_id: &str,
+    config: &HashMap<String, serde_json::Value>,
+    sink: addon::EventSink,
+) -> Result<(), String> {
+    use mlua::LuaSerdeExt;
+    
+    let lua = load_addon_backend(addon_id, sink).await?;
+    let Ok(hook) = lua.globals().get::<_, mlua::Function>("on_config_change") else {
+        return Ok(());
     };
     
-    result
+    // Unset values arrive as nil rather than a null placeholder, as with addon.json_decode
+    let options = mlua::SerializeOptions::new()
+        .serialize_none_to_null(false)
+        .serialize_unit_to_null(false);
+    let config_table = lua.to_value_with(config, options)
+        .map_err(|e| format!("Failed to convert config for Lua: {}", e))?;
+    
+    hook.call::<_, ()>(config_table)
+        .map_err(|e| format!("on_config_change failed: {}", e))?;
+    
+    debug!("Addon {} handled its config change", addon_id);
+    Ok(())
 }
 
 // Helper function to setup Lua API for runtime calls
-fn setup_lua_api_for_runtime(lua: &mlua::Lua, addon_id: &str) -> Result<(), String> {
-    use mlua::prelude::*;
+fn setup_lua_api_for_runtime(lua: &mlua::Lua, addon_id: &str, sink: addon::EventSink) -> Result<(), String> {
     let globals = lua.globals();
     
     let addon_api = lua.create_table()
@@ -239,40 +492,37 @@ fn setup_lua_api_for_runtime(lua: &mlua::Lua, addon_id: &str) -> Result<(), Stri
     // Add print
     let addon_id_for_print = addon_id.to_string();
     let print_fn = lua.create_function(move |_, msg: String| {
-        println!("[Addon: {}] {}", addon_id_for_print, msg);
+        info!("[Addon: {}] {}", addon_id_for_print, msg);
         Ok(())
     }).map_err(|e| format!("Failed to create print function: {}", e))?;
     
     addon_api.set("print", print_fn)
         .map_err(|e| format!("Failed to set print: {}", e))?;
     
-    // Add execute_command
-    let addon_id_for_cmd = addon_id.to_string();
-    let execute_command_fn = lua.create_function(move |_, (command, args): (String, Option<Vec<String>>)| {
-        println!("[Addon: {}] Executing command: {} {:?}", addon_id_for_cmd, command, args);
-        
-        let mut cmd = std::process::Command::new(&command);
-        
-        if let Some(args_vec) = args {
-            cmd.args(&args_vec);
-        }
-        
-        match cmd.output() {
-            Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let success = output.status.success();
-                
-                Ok((success, stdout, stderr))
-            }
-            Err(e) => {
-                Err(LuaError::RuntimeError(format!("Failed to execute command: {}", e)))
-            }
-        }
-    }).map_err(|e| format!("Failed to create execute_command function: {}", e))?;
+    // Add read_file/write_file
+    addon::register_file_api(lua, &addon_api, &addon_dir_path)?;
+    
+    // Add json_decode/json_encode
+    addon::register_json_api(lua, &addon_api)?;
+    
+    // Add execute_command, gated on the addon's declared permissions
+    let permissions = addon::load_permissions(&addon_dir_path);
+    addon::register_execute_command(lua, &addon_api, addon_id, &permissions)?;
+    
+    // Add http_get, gated on the network permission
+    addon::register_http_get(lua, &addon_api, addon_id, &permissions)?;
     
-    addon_api.set("execute_command", execute_command_fn)
-        .map_err(|e| format!("Failed to set execute_command: {}", e))?;
+    // Add list_media, gated on the media_read permission
+    addon::register_list_media(lua, &addon_api, addon_id, &permissions)?;
+    
+    // Add schedule
+    scheduler::register_lua_api(lua, &addon_api)?;
+    
+    // Add emit
+    addon::register_emit(lua, &addon_api, addon_id, sink)?;
+    
+    // Add get_config/set_config
+    addon::register_config_api(lua, &addon_api, addon_id)?;
     
     globals.set("addon", addon_api)
         .map_err(|e| format!("Failed to set addon API: {}", e))?;
@@ -282,29 +532,111 @@ fn setup_lua_api_for_runtime(lua: &mlua::Lua, addon_id: &str) -> Result<(), Stri
 
 #[tokio::main]
 async fn main() {
+    system::started_at();
+    logging::init();
     let config = config::load_config().unwrap_or_default();
+    if let Err(e) = logging::set_level(&config.log_level) {
+        warn!("{}; keeping info level", e);
+    }
     let config_arc = Arc::new(Mutex::new(config.clone()));
     let app_handle_arc = Arc::new(Mutex::new(None));
     
-    // Ensure Fonts directory exists
-    if let Err(e) = fonts::ensure_fonts_dir().await {
-        eprintln!("Failed to create Fonts directory: {}", e);
-    }
+    // Create the data folders now, so a permission problem is reported before anything needs them
+    let startup_problems = match paths::ensure_all_dirs() {
+        Ok(dirs) => {
+            debug!("Data folders ready: {:?}", dirs);
+            Vec::new()
+        }
+        Err(problems) => {
+            for problem in &problems {
+                error!("{}", problem);
+            }
+            problems
+        }
+    };
+    let _ = STARTUP_PROBLEMS.set(startup_problems);
+    
+    // Probe optional tools up front so the first config request doesn't wait on them
+    let capabilities = capabilities::get_capabilities();
+    info!("Capabilities: {:?}", capabilities);
+    
+    // Discovered peers live here rather than in the saved config
+    let live_peers: network::PeerTable = Arc::new(Mutex::new(Vec::new()));
     
     // Start Axum web server in background
-    let config_for_server = config_arc.clone();
-    let app_handle_for_server = app_handle_arc.clone();
+    // Pick up where the show was before the restart
+    let playlist_len = media::get_files(None, &config.media_order).await.map_or(0, |files| files.len());
+    let playback: playback::SharedPlayback = Arc::new(Mutex::new(playback::PlaybackState {
+        index: config.resume_slide_index(playlist_len),
+        paused: false,
+    }));
+    tokio::spawn(playback::persist_periodically(config_arc.clone(), playback.clone()));
+    
+    let web_server = match start_web_server(config_arc.clone(), app_handle_arc.clone(), live_peers.clone(), playback.clone()).await {
+        Ok((server, port)) => {
+            let _ = SERVER_PORT.set(Ok(port));
+            Some(server)
+        }
+        Err(e) => {
+            error!("Web server failed to start: {}", e);
+            let _ = SERVER_PORT.set(Err(e));
+            None
+        }
+    };
+    
+    // Start scheduled backend tasks for enabled addons
+    tokio::spawn(scheduler::sync_schedules());
+    
+    // Reload addons automatically when their files change
+    #[cfg(feature = "addon-watcher")]
+    {
+        let app_handle_for_watcher = app_handle_arc.clone();
+        tokio::spawn(async move {
+            addon::watcher::watch_addons(move || {
+                let app_handle = app_handle_for_watcher.clone();
+                async move {
+                    if let Err(e) = reload_addons_and_notify(&app_handle).await {
+                        error!("Failed to reload addons: {}", e);
+                    }
+                }
+            }).await;
+        });
+    }
+    
+    // Start peer discovery and status checks in background
+    let config_for_discovery = config_arc.clone();
+    let peers_for_discovery = live_peers.clone();
     tokio::spawn(async move {
-        start_web_server(config_for_server, app_handle_for_server).await;
+        if let Err(e) = network::start_discovery(config_for_discovery, peers_for_discovery).await {
+            error!("Discovery service failed: {}", e);
+        }
     });
+    tokio::spawn(network::check_all_peers(config_arc.clone(), live_peers));
+    
+    // Start WebSocket server (events and live preview) in background
+    let preview_hub = Arc::new(preview::PreviewHub::new());
+    tokio::spawn(ws::start_ws_server(
+        config_arc.clone(),
+        app_handle_arc.clone(),
+        preview_hub.clone(),
+    ));
     
     let app = tauri::Builder::default()
         .manage(AppState {
             config: config_arc,
             app_handle: app_handle_arc.clone(),
+            preview: preview_hub,
+            playback,
         })
         .invoke_handler(tauri::generate_handler![
             log_message,
+            submit_preview_frame,
+            get_server_port,
+            get_startup_problems,
+            get_playback_state,
+            report_playback_index,
+            start_playback_cycle,
+            set_display,
             get_config,
             save_config_command,
             get_media_files,
@@ -316,6 +648,8 @@ async fn main() {
             reload_addons,
             get_font_data,
             list_fonts,
+            list_fonts_detailed,
+            apply_update,
             call_addon_function,
         ])
         .build(tauri::generate_context!())
@@ -326,20 +660,225 @@ async fn main() {
         let mut handle = app_handle_arc.lock().unwrap();
         *handle = Some(app.handle().clone());
     }
+    scheduler::set_event_sink(app_event_sink(app.handle().clone()));
+    
+    // Put the presentation back on the monitor it was last moved to
+    if let Some(window) = app.get_webview_window(display::WINDOW_LABEL) {
+        if let Err(e) = display::apply(&window, config.display_index, config.fullscreen) {
+            warn!("Failed to restore display placement: {}", e);
+        }
+    }
+    
+    // The server bound before the app handle existed, so report the outcome now
+    match SERVER_PORT.get() {
+        Some(Ok(port)) => {
+            let _ = app.handle().emit("server-port", port);
+        }
+        Some(Err(e)) => {
+            let _ = app.handle().emit("server-error", e);
+        }
+        None => {}
+    }
+    if let Some(problems) = STARTUP_PROBLEMS.get().filter(|problems| !problems.is_empty()) {
+        let _ = app.handle().emit("startup-problems", problems);
+    }
+    
+    app.run(move |_app_handle, event| {
+        if let tauri::RunEvent::ExitRequested { code, api, .. } = event {
+            // Closing the last window keeps the app serving; only an explicit exit quits
+            if code.is_none() {
+                api.prevent_exit();
+            } else if let Some(server) = &web_server {
+                server.shutdown();
+            }
+        }
+    });
+}
+
+/// Gzip/deflate for API and static responses, negotiated via Accept-Encoding
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    // The default predicate already skips images (other than SVG) and tiny bodies;
+    // video, web fonts and bundles are compressed formats too
+    let predicate = DefaultPredicate::new()
+        .and(NotForContentType::const_new("video/"))
+        .and(NotForContentType::const_new("font/woff"))
+        .and(NotForContentType::const_new("application/zip"))
+        // A compressed slice of a file is no use to a client that asked for a byte range
+        .and(|status: StatusCode, _: axum::http::Version, _: &HeaderMap, _: &axum::http::Extensions| {
+            status != StatusCode::PARTIAL_CONTENT
+        });
+    
+    CompressionLayer::new()
+        .gzip(true)
+        .deflate(true)
+        .compress_when(predicate)
+}
+
+/// CORS for the API: any origin by default, or only the configured ones
+fn cors_layer(allowed_origins: &[String]) -> CorsLayer {
+    if allowed_origins.is_empty() {
+        return CorsLayer::permissive();
+    }
+    
+    let origins: Vec<HeaderValue> = allowed_origins.iter()
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                warn!("Ignoring invalid allowed origin: {}", origin);
+                None
+            }
+        })
+        .collect();
+    
+    CorsLayer::permissive().allow_origin(AllowOrigin::list(origins))
+}
+
+/// How long in-flight requests (e.g. uploads) get to finish when the app quits
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Handle for stopping a web server started with `serve_with_shutdown`
+struct ServerHandle {
+    shutdown: tokio::sync::watch::Sender<bool>,
+    stopped: std::sync::mpsc::Receiver<()>,
+}
+
+impl ServerHandle {
+    /// Stop accepting connections and wait up to `SHUTDOWN_GRACE` for open requests to drain.
+    /// Blocks the calling thread, so it can run from Tauri's (synchronous) exit handler.
+    fn shutdown(&self) -> bool {
+        let _ = self.shutdown.send(true);
+        
+        match self.stopped.recv_timeout(SHUTDOWN_GRACE) {
+            Ok(()) | Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                info!("Web server stopped");
+                true
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                warn!("Web server did not stop within {:?}", SHUTDOWN_GRACE);
+                false
+            }
+        }
+    }
+}
+
+/// First wait before restarting a web server that stopped on its own
+const RESTART_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The wait doubles with each quick failure up to this, so a server that keeps dying can't spin
+const MAX_RESTART_BACKOFF: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A server that stayed up this long counts as healthy again and is restarted straight away
+const HEALTHY_UPTIME: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Keep running the server `run` starts until a shutdown is requested. If it returns on its own,
+/// for whatever reason, it is restarted after a backoff instead of leaving the web interface dead.
+async fn supervise<F, Fut, E>(
+    name: &str,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+    initial_backoff: std::time::Duration,
+    mut run: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(), E>>,
+    E: std::fmt::Display,
+{
+    let mut backoff = initial_backoff;
+    
+    loop {
+        let started = std::time::Instant::now();
+        let result = run().await;
+        
+        if *shutdown_rx.borrow() {
+            return;
+        }
+        
+        if started.elapsed() >= HEALTHY_UPTIME {
+            backoff = initial_backoff;
+        }
+        match result {
+            Ok(()) => warn!("{} stopped unexpectedly; restarting in {:?}", name, backoff),
+            Err(e) => error!("{} failed: {}; restarting in {:?}", name, e, backoff),
+        }
+        
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown_rx.wait_for(|stop| *stop) => return,
+        }
+        backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+    }
+}
+
+/// Serve `app` on every listener in the background until the returned handle asks them to stop.
+/// A listener whose server stops unexpectedly is bound again and served anew.
+fn serve_with_shutdown(listeners: Vec<tokio::net::TcpListener>, app: Router) -> ServerHandle {
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let (stopped_tx, stopped_rx) = std::sync::mpsc::channel();
     
-    app.run(|_app_handle, event| {
-        if let tauri::RunEvent::ExitRequested { api, .. } = event {
-            api.prevent_exit();
+    tokio::spawn(async move {
+        let servers: Vec<_> = listeners.into_iter()
+            .map(|listener| {
+                let addr = listener.local_addr().ok();
+                let name = match addr {
+                    Some(addr) => format!("Web server on {}", addr),
+                    None => "Web server".to_string(),
+                };
+                let mut listener = Some(listener);
+                let app = app.clone();
+                let shutdown_rx = shutdown_rx.clone();
+                
+                tokio::spawn(async move {
+                    supervise(&name, shutdown_rx.clone(), RESTART_BACKOFF, || {
+                        let listener = listener.take();
+                        let mut shutdown_rx = shutdown_rx.clone();
+                        let signal = async move {
+                            let _ = shutdown_rx.wait_for(|stop| *stop).await;
+                        };
+                        let service = app.clone().into_make_service_with_connect_info::<SocketAddr>();
+                        
+                        async move {
+                            let listener = match (listener, addr) {
+                                (Some(listener), _) => listener,
+                                (None, Some(addr)) => tokio::net::TcpListener::bind(addr).await?,
+                                (None, None) => return Err(std::io::Error::other("listening address unknown")),
+                            };
+                            axum::serve(listener, service).with_graceful_shutdown(signal).await
+                        }
+                    }).await;
+                })
+            })
+            .collect();
+        
+        for server in servers {
+            let _ = server.await;
         }
+        let _ = stopped_tx.send(());
     });
+    
+    ServerHandle {
+        shutdown: shutdown_tx,
+        stopped: stopped_rx,
+    }
 }
 
-async fn start_web_server(config: Arc<Mutex<config::Config>>, app_handle: Arc<Mutex<Option<AppHandle>>>) {
-    let port = {
+async fn start_web_server(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    live_peers: network::PeerTable,
+    playback: playback::SharedPlayback,
+) -> Result<(ServerHandle, u16), String> {
+    let (preferred_port, bind_addresses, allowed_origins) = {
         let cfg = config.lock().unwrap();
-        cfg.port
+        (cfg.port, cfg.bind_addresses.clone(), cfg.allowed_origins.clone())
     };
     
+    let port = find_available_port(preferred_port).ok_or_else(|| format!(
+        "Port {} and the {} ports above it are all in use; choose another port in the network settings",
+        preferred_port, PORT_SEARCH_RANGE
+    ))?;
+    if port != preferred_port {
+        warn!("Port {} is in use; serving on port {} instead", preferred_port, port);
+    }
+    
     // Determine web directory path
     let web_dir = if cfg!(debug_assertions) {
         // Dev mode: look in parent of src-tauri
@@ -366,8 +905,20 @@ async fn start_web_server(config: Arc<Mutex<config::Config>>, app_handle: Arc<Mu
         }
     };
     
-    println!("Web directory: {:?}", web_dir);
-    println!("Web directory exists: {}", web_dir.exists());
+    debug!("Web directory: {:?}", web_dir);
+    debug!("Web directory exists: {}", web_dir.exists());
+    
+    // Saves from the settings page come in bursts; write and announce only the final state
+    let config_saver = config::DebouncedSaver::spawn(config.clone(), config::SAVE_DEBOUNCE, {
+        let app_handle = app_handle.clone();
+        move |cfg| {
+            if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+                let _ = handle.emit("config-update", config::to_api_json(cfg));
+                debug!("Emitted config-update event");
+            }
+            ws::broadcast_config_update(cfg);
+        }
+    });
     
     let app = Router::new()
         .route("/api/config", get({
@@ -375,247 +926,3962 @@ async fn start_web_server(config: Arc<Mutex<config::Config>>, app_handle: Arc<Mu
             move || get_config_handler(config)
         }))
         .route("/api/config", post({
+            let config = config.clone();
+            move |headers, body| post_config_handler(config, config_saver, headers, body)
+        }).layer(DefaultBodyLimit::max(JSON_BODY_LIMIT)))
+        .route("/api/config/export", get({
+            let config = config.clone();
+            move |headers, query| export_config_handler(config, headers, query)
+        }))
+        .route("/api/config/import", post({
             let config = config.clone();
             let app_handle = app_handle.clone();
-            move |body| post_config_handler(config, app_handle, body)
+            move |body| import_config_handler(config, app_handle, body)
+        }).layer(DefaultBodyLimit::max(JSON_BODY_LIMIT)))
+        .route("/api/login", post({
+            let config = config.clone();
+            move |body| login_handler(config, body)
         }))
-        .route("/api/media", get(get_media_handler))
-        .route("/api/media/upload", post({
+        .route("/api/password", post({
+            let config = config.clone();
+            move |body| change_password_handler(config, body)
+        }))
+        .route("/api/media", get({
+            let config = config.clone();
+            move |query, filter| get_media_handler(config, query, filter)
+        }))
+        .route("/api/collections", get(get_collections_handler))
+        .route("/api/system/info", get(get_system_info_handler))
+        .route("/api/logs", get({
+            let config = config.clone();
+            move |headers| list_logs_handler(config, headers)
+        }))
+        .route("/api/logs/:name", get({
+            let config = config.clone();
+            move |path, headers| get_log_handler(config, path, headers)
+        }))
+        .route("/api/health", get(health_handler))
+        .route("/api/ready", get(ready_handler))
+        .route("/api/schedule/active", get({
+            let config = config.clone();
+            move || schedule_active_handler(config.clone())
+        }))
+        .route("/api/playback", get({
+            let config = config.clone();
+            let playback = playback.clone();
+            move || get_playback_handler(config, playback)
+        }).post({
+            let config = config.clone();
             let app_handle = app_handle.clone();
-            move |multipart| upload_media_handler(app_handle, multipart)
+            let playback = playback.clone();
+            move |body| control_playback_handler(config, app_handle, playback, body)
+        }))
+        .route("/api/playback/next", get({
+            let config = config.clone();
+            let playback = playback.clone();
+            move || get_next_slide_handler(config, playback)
+        }))
+        .route("/api/playback/sequence", get({
+            let config = config.clone();
+            move || get_playback_sequence_handler(config)
         }))
-        .layer(DefaultBodyLimit::max(100 * 1024 * 1024)) // 100MB limit
+        .route("/api/media/order", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |body| set_media_order_handler(config, app_handle, body)
+        }).layer(DefaultBodyLimit::max(JSON_BODY_LIMIT)))
+        .route("/api/media/upload", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |query, multipart| upload_media_handler(config, app_handle, query, multipart)
+        }).layer(DefaultBodyLimit::max(UPLOAD_BODY_LIMIT)))
+        .route("/api/media/upload/init", post({
+            let config = config.clone();
+            move |query, body| init_resumable_upload_handler(config, query, body)
+        }).layer(DefaultBodyLimit::max(JSON_BODY_LIMIT)))
+        .route("/api/media/upload/:id", axum::routing::patch({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path, headers, body| append_resumable_upload_handler(config, app_handle, path, headers, body)
+        }).head(resumable_upload_offset_handler))
         .route("/api/media/:filename", axum::routing::delete({
+            let config = config.clone();
             let app_handle = app_handle.clone();
-            move |path| delete_media_handler(app_handle, path)
+            move |path| delete_media_handler(config, app_handle, path)
         }))
-        .route("/api/peers", get(get_peers_handler))
-        .route("/api/addons", get(get_addons_handler))
-        .route("/api/addons/reload", post(reload_addons_handler))
-        .route("/api/addons/:id/config", post({
+        .route("/api/media/bulk-delete", post({
+            let config = config.clone();
             let app_handle = app_handle.clone();
-            move |path, body| update_addon_config_handler(app_handle, path, body)
+            move |body| bulk_delete_media_handler(config, app_handle, body)
         }))
-        .nest_service("/", ServeDir::new(web_dir))
-        .layer(CorsLayer::permissive());
-    
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    println!("Web server started on http://0.0.0.0:{}", port);
-    
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
-}
-
-async fn get_config_handler(config: Arc<Mutex<config::Config>>) -> impl IntoResponse {
-    let cfg = config.lock().unwrap();
-    Json(serde_json::json!({
-        "displayName": cfg.display_name,
-        "imageDuration": cfg.image_duration,
-        "videoPosition": cfg.video_position,
-        "imageScaling": cfg.image_scaling,
-        "port": cfg.port,
-        "rotation": cfg.rotation,
-        "hasPassword": !cfg.password.is_empty(),
-        "staticIp": cfg.static_ip,
-        "localhostOnly": cfg.localhost_only,
-        "wsPort": cfg.ws_port,
-        "discoveryPort": cfg.discovery_port,
-        "version": env!("CARGO_PKG_VERSION"),
-        "peers": [],
-    }))
-}
-
-async fn post_config_handler(
-    config: Arc<Mutex<config::Config>>,
-    app_handle: Arc<Mutex<Option<AppHandle>>>,
-    Json(updates): Json<serde_json::Value>,
-) -> impl IntoResponse {
-    let mut cfg = config.lock().unwrap();
-    
-    if let Some(val) = updates.get("displayName").and_then(|v| v.as_str()) {
-        cfg.display_name = val.to_string();
-    }
-    if let Some(val) = updates.get("imageDuration").and_then(|v| v.as_u64()) {
-        cfg.image_duration = val;
-    }
-    if let Some(val) = updates.get("videoPosition").and_then(|v| v.as_str()) {
-        cfg.video_position = val.to_string();
-    }
-    if let Some(val) = updates.get("imageScaling").and_then(|v| v.as_str()) {
-        cfg.image_scaling = val.to_string();
-    }
-    if let Some(val) = updates.get("rotation").and_then(|v| v.as_i64()) {
-        cfg.rotation = val as i32;
-    }
+        .route("/api/media/:filename/duration", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path, body| set_media_duration_handler(config, app_handle, path, body)
+        }))
+        .route("/api/media/:filename/tags", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path, body| set_media_tags_handler(config, app_handle, path, body)
+        }))
+        .route("/api/media/:filename/lock", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path| set_media_lock_handler(config, app_handle, path, true)
+        }).delete({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path| set_media_lock_handler(config, app_handle, path, false)
+        }))
+        .route("/api/media/:filename/rename", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path, body| rename_media_handler(config, app_handle, path, body)
+        }))
+        .route("/api/media/:filename/thumbnail", get({
+            let config = config.clone();
+            move |path| get_thumbnail_handler(config, path)
+        }))
+        .route("/api/media/raw/:filename", get(get_raw_media_handler))
+        .route("/api/media/:filename/download", get(download_media_handler))
+        .route("/api/media/:filename/metadata", get(media_metadata_handler))
+        .route("/api/fonts/:name", get(get_font_handler))
+        .route("/api/peers", get({
+            let config = config.clone();
+            let live_peers = live_peers.clone();
+            move || get_peers_handler(config, live_peers)
+        }))
+        .route("/api/peers/:id", post({
+            let config = config.clone();
+            let live_peers = live_peers.clone();
+            move |path, body| set_peer_label_handler(config, live_peers, path, body)
+        }))
+        .route("/api/peers/:id/sync", post({
+            let config = config.clone();
+            let live_peers = live_peers.clone();
+            move |path| sync_peer_handler(config, live_peers, path)
+        }))
+        .route("/api/peers/:id/password", post({
+            let config = config.clone();
+            let live_peers = live_peers.clone();
+            move |path, body| set_peer_password_handler(config, live_peers, path, body)
+        }))
+        .route("/api/addons", get(get_addons_handler))
+        .route("/api/addons/validate", post(validate_addon_manifest_handler))
+        .route("/api/addons/install", post({
+            let app_handle = app_handle.clone();
+            move |query, multipart| install_addon_handler(app_handle, query, multipart)
+        }).layer(DefaultBodyLimit::max(UPLOAD_BODY_LIMIT)))
+        .route("/api/addons/reload", post({
+            let app_handle = app_handle.clone();
+            move || reload_addons_handler(app_handle)
+        }))
+        .route("/api/addons/:id", axum::routing::delete({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path| uninstall_addon_handler(config, app_handle, path)
+        }))
+        .route("/api/addons/:id/frontend.js", get(get_addon_frontend_handler))
+        .route("/api/addons/:id/config", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path, body| update_addon_config_handler(config, app_handle, path, body)
+        }).layer(DefaultBodyLimit::max(JSON_BODY_LIMIT)))
+        .route("/api/addons/:id/enabled", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |path, body| set_addon_enabled_handler(config, app_handle, path, body)
+        }))
+        .route("/api/bundle/export", get({
+            let config = config.clone();
+            move |headers| export_bundle_handler(config, headers)
+        }))
+        .route("/api/bundle/import", post({
+            let config = config.clone();
+            let app_handle = app_handle.clone();
+            move |multipart| import_bundle_handler(config, app_handle, multipart)
+        }).layer(DefaultBodyLimit::max(UPLOAD_BODY_LIMIT)))
+        .route("/api/update", post(upload_update_handler)
+            .layer(DefaultBodyLimit::max(UPLOAD_BODY_LIMIT))
+            .delete(discard_update_handler))
+        .route("/api/update/confirm", post(confirm_update_handler))
+        .nest_service("/", ServeDir::new(web_dir))
+        // Anything without its own limit gets the small JSON one
+        .layer(DefaultBodyLimit::max(JSON_BODY_LIMIT))
+        .layer(axum::middleware::from_fn({
+            let config = config.clone();
+            move |request, next| auth::require_auth(config.clone(), request, next)
+        }))
+        // Outside auth, so failed password attempts count too
+        .layer(axum::middleware::from_fn({
+            let config = config.clone();
+            let limiter = ratelimit::RateLimiter::new();
+            move |request, next| ratelimit::limit_mutations(config.clone(), limiter.clone(), request, next)
+        }))
+        // Outermost, so refused clients don't even count towards rate limits
+        .layer(axum::middleware::from_fn({
+            let config = config.clone();
+            move |request, next| ipfilter::restrict_clients(config.clone(), request, next)
+        }))
+        .layer(cors_layer(&allowed_origins))
+        .layer(compression_layer());
     
-    if let Err(e) = config::save_config(&cfg) {
-        return Json(serde_json::json!({
-            "error": e
-        }));
-    }
+    let addrs = bind_addrs(&bind_addresses, port)?;
+    let listeners = bind_listeners(&addrs).await?;
     
-    // Emit config update event - Tauri v2 uses emit() not emit_all()
-    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
-        let _ = handle.emit("config-update", cfg.clone());
-        println!("Emitted config-update event");
+    for listener in &listeners {
+        if let Ok(addr) = listener.local_addr() {
+            info!("Web server started on http://{}", addr);
+        }
     }
     
-    Json(serde_json::json!({
-        "success": true
-    }))
+    Ok((serve_with_shutdown(listeners, app), port))
 }
 
-async fn get_media_handler() -> impl IntoResponse {
-    match media::get_files().await {
-        Ok(files) => Json(serde_json::json!(files)),
-        Err(e) => Json(serde_json::json!({
-            "error": e
-        })),
-    }
+/// The preferred port if it is free, otherwise the first free one just above it
+fn find_available_port(preferred: u16) -> Option<u16> {
+    (0..=PORT_SEARCH_RANGE)
+        .filter_map(|offset| preferred.checked_add(offset))
+        .find(|&port| std::net::TcpListener::bind((std::net::Ipv4Addr::UNSPECIFIED, port)).is_ok())
 }
 
-async fn upload_media_handler(app_handle: Arc<Mutex<Option<AppHandle>>>, mut multipart: Multipart) -> impl IntoResponse {
-    let mut uploaded_count = 0;
-    
-    while let Ok(Some(field)) = multipart.next_field().await {
-        if let Some(filename) = field.file_name() {
-            let filename = filename.to_string();
-            
-            if let Ok(data) = field.bytes().await {
-                if let Ok(_) = media::save_file(&filename, &data).await {
-                    uploaded_count += 1;
-                    println!("Uploaded: {}", filename);
-                }
-            }
-        }
-    }
-    
-    // Emit media update event - Tauri v2 uses emit() not emit_all()
-    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
-        let _ = handle.emit("media-update", ());
-        println!("Emitted media-update event");
+/// Where the web and WebSocket servers listen: each configured address, or every interface if there are none
+fn bind_addrs(bind_addresses: &[String], port: u16) -> Result<Vec<SocketAddr>, String> {
+    if bind_addresses.is_empty() {
+        return Ok(vec![
+            SocketAddr::from(([0, 0, 0, 0], port)),
+            SocketAddr::from((std::net::Ipv6Addr::UNSPECIFIED, port)),
+        ]);
     }
     
-    Json(serde_json::json!({
-        "success": true,
-        "files": uploaded_count
-    }))
+    bind_addresses.iter()
+        .map(|address| {
+            address.trim().parse::<std::net::IpAddr>()
+                .map(|ip| SocketAddr::new(ip, port))
+                .map_err(|_| format!("Invalid bind address: {}", address))
+        })
+        .collect()
 }
 
-async fn delete_media_handler(app_handle: Arc<Mutex<Option<AppHandle>>>, AxumPath(filename): AxumPath<String>) -> impl IntoResponse {
-    match media::delete_file(&filename).await {
-        Ok(_) => {
-            // Emit media update event - Tauri v2 uses emit() not emit_all()
-            if let Some(handle) = app_handle.lock().unwrap().as_ref() {
-                let _ = handle.emit("media-update", ());
-                println!("Emitted media-update event");
+async fn bind_listeners(addrs: &[SocketAddr]) -> Result<Vec<tokio::net::TcpListener>, String> {
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        let listener = match bind_listener(*addr) {
+            Ok(listener) => listener,
+            // The default [::] is best-effort; hosts without IPv6 still serve over IPv4
+            Err(e) if addr.is_ipv6() && addr.ip().is_unspecified() => {
+                warn!("Not listening on {}: {}", addr, e);
+                continue;
             }
-            
-            Json(serde_json::json!({
-                "success": true
-            }))
-        },
-        Err(e) => Json(serde_json::json!({
-            "error": e
-        })),
+            Err(e) => return Err(format!("Failed to bind {}: {}", addr, e)),
+        };
+        listeners.push(listener);
     }
+    Ok(listeners)
 }
 
-async fn get_peers_handler() -> impl IntoResponse {
-    // For now, return empty array
-    // Network discovery will be implemented later
-    Json(serde_json::json!([]))
+/// IPv6 sockets are made IPv6-only so [::] and 0.0.0.0 can share a port
+fn bind_listener(addr: SocketAddr) -> std::io::Result<tokio::net::TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    
+    tokio::net::TcpListener::from_std(socket.into())
 }
 
-async fn get_addons_handler() -> impl IntoResponse {
-    match get_addons_internal().await {
-        Ok(addons) => Json(addons),
-        Err(e) => Json(serde_json::json!({
-            "error": e
-        })),
+async fn get_config_handler(config: Arc<Mutex<config::Config>>) -> impl IntoResponse {
+    let cfg = config.lock().unwrap();
+    let version = config::config_version(&cfg);
+    
+    (
+        [(header::ETAG, format!("\"{}\"", version))],
+        Json(config::to_api_json(&cfg)),
+    )
+}
+
+/// Reject config updates the display can't render, naming the offending field
+fn validate_config_updates(updates: &serde_json::Value) -> Result<(), ApiError> {
+    let invalid = |field: &str, allowed: String| {
+        (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!("Invalid {}: expected one of {}", field, allowed),
+            "field": field,
+        })))
+    };
+    
+    let one_of = |field: &str, allowed: &[&str]| match updates.get(field) {
+        Some(val) if !val.as_str().is_some_and(|v| allowed.contains(&v)) => {
+            Err(invalid(field, allowed.join(", ")))
+        }
+        _ => Ok(()),
+    };
+    
+    one_of("imageScaling", config::IMAGE_SCALING_MODES)?;
+    one_of("videoPosition", config::VIDEO_POSITIONS)?;
+    one_of("transition", config::TRANSITIONS)?;
+    
+    if let Some(val) = updates.get("transitionDurationMs") {
+        if val.as_u64().is_none_or(|v| v > config::MAX_TRANSITION_DURATION_MS) {
+            return Err(invalid("transitionDurationMs", format!("0 to {}", config::MAX_TRANSITION_DURATION_MS)));
+        }
+    }
+    
+    if let Some(val) = updates.get("rotation") {
+        if val.as_i64().is_none_or(|v| !config::ROTATIONS.contains(&v)) {
+            let allowed: Vec<String> = config::ROTATIONS.iter().map(|r| r.to_string()).collect();
+            return Err(invalid("rotation", allowed.join(", ")));
+        }
+    }
+    
+    if let Some(val) = updates.get("fallbackBackground") {
+        if val.as_str().is_none_or(|v| !config::is_hex_color(v)) {
+            return Err(invalid("fallbackBackground", "#RRGGBB colors".to_string()));
+        }
+    }
+    
+    if let Some(val) = updates.get("logLevel") {
+        if val.as_str().is_none_or(|v| logging::parse_level(v).is_err()) {
+            return Err(invalid("logLevel", "trace, debug, info, warn, error, off".to_string()));
+        }
+    }
+    
+    one_of("transcodeFormat", config::TRANSCODE_FORMATS)?;
+    
+    if let Some(val) = updates.get("maxTotalMediaBytes") {
+        if val.as_u64().is_none() {
+            return Err(invalid("maxTotalMediaBytes", "byte counts, 0 for unlimited".to_string()));
+        }
+    }
+    
+    if let Some(val) = updates.get("discoveryIntervalSecs") {
+        if val.as_u64().is_none_or(|v| v < network::MIN_DISCOVERY_INTERVAL_SECS) {
+            return Err(invalid("discoveryIntervalSecs", format!("{} seconds or more", network::MIN_DISCOVERY_INTERVAL_SECS)));
+        }
+    }
+    
+    if let Some(val) = updates.get("peerTtlSecs") {
+        if val.as_u64().is_none_or(|v| v < network::MIN_PEER_TTL_SECS) {
+            return Err(invalid("peerTtlSecs", format!("{} seconds or more", network::MIN_PEER_TTL_SECS)));
+        }
+    }
+    
+    if let Some(val) = updates.get("shuffleSeed") {
+        if val.as_u64().is_none_or(|v| v > playback::MAX_SEED) {
+            return Err(invalid("shuffleSeed", format!("whole numbers from 0 to {}", playback::MAX_SEED)));
+        }
+    }
+    
+    if let Some(val) = updates.get("luaMaxInstructions") {
+        if val.as_u64().is_none() {
+            return Err(invalid("luaMaxInstructions", "instruction counts, 0 for unlimited".to_string()));
+        }
+    }
+    
+    if let Some(val) = updates.get("luaMemoryLimitBytes") {
+        if val.as_u64().is_none() {
+            return Err(invalid("luaMemoryLimitBytes", "byte counts, 0 for unlimited".to_string()));
+        }
+    }
+    
+    if let Some(val) = updates.get("imageQuality") {
+        if val.as_u64().is_none_or(|v| !(1..=100).contains(&v)) {
+            return Err(invalid("imageQuality", "1 to 100".to_string()));
+        }
+    }
+    
+    if let Some(val) = updates.get("schedule") {
+        let schedule = serde_json::from_value::<Option<config::Schedule>>(val.clone())
+            .map_err(|e| e.to_string())
+            .and_then(|schedule| schedule.map_or(Ok(()), |s| s.validate().map_err(String::from)));
+        if let Err(e) = schedule {
+            return Err((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": format!("Invalid schedule: {}", e),
+                "field": "schedule",
+            }))));
+        }
+    }
+    
+    if let Some(val) = updates.get("bindAddresses") {
+        let addresses: Option<Vec<String>> = serde_json::from_value(val.clone()).ok();
+        if addresses.is_none_or(|addresses| bind_addrs(&addresses, 0).is_err()) {
+            return Err((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "Invalid bindAddresses: expected a list of IP addresses",
+                "field": "bindAddresses",
+            }))));
+        }
     }
+    
+    if let Some(val) = updates.get("allowedOrigins") {
+        let origins: Option<Vec<String>> = serde_json::from_value(val.clone()).ok();
+        let valid = origins.is_some_and(|origins| origins.iter()
+            .all(|origin| !origin.trim().is_empty() && HeaderValue::from_str(origin.trim()).is_ok()));
+        if !valid {
+            return Err((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "Invalid allowedOrigins: expected a list of origins like https://example.com",
+                "field": "allowedOrigins",
+            }))));
+        }
+    }
+    
+    if let Some(val) = updates.get("allowedIps") {
+        let ranges: Option<Vec<String>> = serde_json::from_value(val.clone()).ok();
+        if ranges.is_none_or(|ranges| ranges.iter().any(|range| ipfilter::parse_range(range).is_err())) {
+            return Err((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "Invalid allowedIps: expected a list of IP addresses or CIDR ranges like 192.168.1.0/24",
+                "field": "allowedIps",
+            }))));
+        }
+    }
+    
+    Ok(())
 }
 
-async fn get_addons_internal() -> Result<serde_json::Value, String> {
-    let mut addons = addon::scan_addons().await?;
+/// Apply a partial config update. The new state is live at once; saving it and notifying
+/// the display are left to `saver`, which coalesces rapid updates.
+async fn post_config_handler(
+    config: Arc<Mutex<config::Config>>,
+    saver: config::DebouncedSaver,
+    headers: HeaderMap,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Response {
+    let updates = match json_body(body) {
+        Ok(updates) => updates,
+        Err(e) => return e.into_response(),
+    };
     
-    // Load saved configs from main config
-    let config = config::load_config()?;
+    // Validate everything up front so a bad field doesn't leave a half-applied update
+    if let Err(e) = validate_config_updates(&updates) {
+        return e.into_response();
+    }
     
-    for mut addon_item in &mut addons {
-        let saved_config = config.addons.get(&addon_item.id);
-        addon::merge_addon_config(&mut addon_item, saved_config);
+    let mut cfg = config.lock().unwrap();
+    
+    // Optional optimistic concurrency: reject saves based on a stale copy
+    let current_version = config::config_version(&cfg);
+    if let Some(expected) = headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok()) {
+        let expected = expected.trim().trim_start_matches("W/").trim_matches('"');
+        
+        if expected != "*" && expected != current_version {
+            warn!("Rejected config save: version {} is stale (current {})", expected, current_version);
+            return (
+                StatusCode::CONFLICT,
+                [(header::ETAG, format!("\"{}\"", current_version))],
+                Json(serde_json::json!({
+                    "error": "Config was changed by someone else",
+                    "config": config::to_api_json(&cfg),
+                })),
+            ).into_response();
+        }
     }
     
-    // Convert to JSON object with addon IDs as keys
-    let mut addons_map = serde_json::Map::new();
-    for addon_item in addons {
-        addons_map.insert(addon_item.id.clone(), serde_json::json!({
-            "id": addon_item.id,
-            "info": {
-                "name": addon_item.info.name,
-                "version": addon_item.info.version,
-                "author": addon_item.info.author,
-                "description": addon_item.info.description,
-                "category": addon_item.info.category,
-            },
-            "enabled": addon_item.enabled,
-            "config": addon_item.config,
-            "settings": addon_item.settings,
-        }));
+    if let Some(val) = updates.get("displayName").and_then(|v| v.as_str()) {
+        cfg.display_name = val.to_string();
+    }
+    if let Some(val) = updates.get("imageDuration").and_then(|v| v.as_u64()) {
+        cfg.image_duration = val;
+    }
+    if let Some(val) = updates.get("videoPosition").and_then(|v| v.as_str()) {
+        cfg.video_position = val.to_string();
+    }
+    if let Some(val) = updates.get("imageScaling").and_then(|v| v.as_str()) {
+        cfg.image_scaling = val.to_string();
+    }
+    if let Some(val) = updates.get("rotation").and_then(|v| v.as_i64()) {
+        cfg.rotation = val as i32;
+    }
+    if let Some(val) = updates.get("transition").and_then(|v| v.as_str()) {
+        cfg.transition = val.to_string();
+    }
+    if let Some(val) = updates.get("transitionDurationMs").and_then(|v| v.as_u64()) {
+        cfg.transition_duration_ms = val;
+    }
+    if let Some(val) = updates.get("fallbackMessage").and_then(|v| v.as_str()) {
+        cfg.fallback_message = val.to_string();
+    }
+    if let Some(val) = updates.get("fallbackBackground").and_then(|v| v.as_str()) {
+        cfg.fallback_background = val.to_string();
+    }
+    if let Some(val) = updates.get("schedule") {
+        cfg.schedule = serde_json::from_value(val.clone()).unwrap_or_default();
+    }
+    if let Some(val) = updates.get("previewIntervalMs").and_then(|v| v.as_u64()) {
+        cfg.preview_interval_ms = val.max(preview::MIN_PREVIEW_INTERVAL_MS);
+    }
+    if let Some(val) = updates.get("autoTranscode").and_then(|v| v.as_bool()) {
+        cfg.auto_transcode = val;
+    }
+    if let Some(val) = updates.get("shuffle").and_then(|v| v.as_bool()) {
+        cfg.shuffle = val;
+    }
+    if let Some(val) = updates.get("shuffleSeed").and_then(|v| v.as_u64()) {
+        cfg.shuffle_seed = val;
+    }
+    if let Some(val) = updates.get("transcodeVideo").and_then(|v| v.as_bool()) {
+        cfg.transcode_video = val;
+    }
+    if let Some(val) = updates.get("transcodeFormat").and_then(|v| v.as_str()) {
+        cfg.transcode_format = val.to_string();
+    }
+    if let Some(val) = updates.get("imageQuality").and_then(|v| v.as_u64()) {
+        let quality = val.clamp(1, 100) as u8;
+        if quality != cfg.image_quality {
+            // Cached thumbnails were encoded at the old quality
+            tokio::spawn(media::thumbnail::clear_thumbnails());
+        }
+        cfg.image_quality = quality;
+    }
+    if let Some(val) = updates.get("maxTotalMediaBytes").and_then(|v| v.as_u64()) {
+        cfg.max_total_media_bytes = val;
+    }
+    if let Some(val) = updates.get("luaMaxInstructions").and_then(|v| v.as_u64()) {
+        cfg.lua_max_instructions = val;
+    }
+    if let Some(val) = updates.get("luaMemoryLimitBytes").and_then(|v| v.as_u64()) {
+        cfg.lua_memory_limit_bytes = val;
+    }
+    if let Some(val) = updates.get("rateLimitPerMin").and_then(|v| v.as_u64()) {
+        cfg.rate_limit_per_min = val.min(u32::MAX as u64) as u32;
+    }
+    // Takes effect on the next start, like the ports
+    if let Some(val) = updates.get("bindAddresses").and_then(|v| v.as_array()) {
+        cfg.bind_addresses = val.iter()
+            .filter_map(|v| v.as_str())
+            .map(|v| v.trim().to_string())
+            .collect();
+    }
+    // Also applied on the next start, when the router is built
+    if let Some(val) = updates.get("allowedOrigins").and_then(|v| v.as_array()) {
+        cfg.allowed_origins = val.iter()
+            .filter_map(|v| v.as_str())
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .collect();
+    }
+    // Checked on every request, so this applies straight away
+    if let Some(val) = updates.get("allowedIps").and_then(|v| v.as_array()) {
+        cfg.allowed_ips = val.iter()
+            .filter_map(|v| v.as_str())
+            .map(|v| v.trim().to_string())
+            .collect();
+    }
+    if let Some(val) = updates.get("discoveryMode").and_then(|v| v.as_str()) {
+        if matches!(val, "udp" | "mdns" | "both") {
+            cfg.discovery_mode = val.to_string();
+        }
+    }
+    // The discovery loops read these each round, so they apply without a restart
+    if let Some(val) = updates.get("discoveryIntervalSecs").and_then(|v| v.as_u64()) {
+        cfg.discovery_interval_secs = val.max(network::MIN_DISCOVERY_INTERVAL_SECS);
+    }
+    if let Some(val) = updates.get("peerTtlSecs").and_then(|v| v.as_u64()) {
+        cfg.peer_ttl_secs = val.max(network::MIN_PEER_TTL_SECS);
+    }
+    if let Some(val) = updates.get("logLevel").and_then(|v| v.as_str()) {
+        cfg.log_level = val.trim().to_lowercase();
+        if let Err(e) = logging::set_level(val) {
+            warn!("{}", e);
+        }
     }
     
-    Ok(serde_json::Value::Object(addons_map))
+    saver.request_save();
+    
+    let new_version = config::config_version(&cfg);
+    (
+        [(header::ETAG, format!("\"{}\"", new_version))],
+        Json(serde_json::json!({
+            "success": true,
+            "configVersion": new_version
+        })),
+    ).into_response()
 }
 
-async fn reload_addons_handler() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "success": true,
-        "message": "Addons reloaded successfully"
-    }))
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportQuery {
+    #[serde(default)]
+    include_secrets: bool,
 }
 
-async fn update_addon_config_handler(
+/// Download the complete config as JSON, with passwords left out unless asked for
+async fn export_config_handler(
+    config: Arc<Mutex<config::Config>>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    let mut cfg = config.lock().unwrap().clone();
+    
+    if query.include_secrets {
+        // GETs skip the auth middleware, so check here before handing out the hash
+        if !auth::is_authorized(&cfg.password, &headers) {
+            return Err(api_error(StatusCode::UNAUTHORIZED, "Invalid or missing password"));
+        }
+    } else {
+        cfg.password = String::new();
+        cfg.peer_passwords.clear();
+    }
+    
+    let body = serde_json::to_string_pretty(&cfg)
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"device-config.json\""),
+        ],
+        body,
+    ).into_response())
+}
+
+/// Replace the whole config with one exported from another device.
+/// A redacted (empty) password keeps this device's current one, as do redacted peer passwords.
+async fn import_config_handler(
+    config: Arc<Mutex<config::Config>>,
     app_handle: Arc<Mutex<Option<AppHandle>>>,
-    AxumPath(addon_id): AxumPath<String>,
-    Json(updates): Json<serde_json::Value>,
-) -> impl IntoResponse {
-    // Load config
-    let mut main_config = match config::load_config() {
-        Ok(c) => c,
-        Err(e) => return Json(serde_json::json!({
-            "error": e
-        })),
-    };
+    body: axum::body::Bytes,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut imported = config::parse_imported_config(&body)
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e))?;
     
-    // Get or create addon config
-    let addon_config = main_config.addons
-        .entry(addon_id.clone())
-        .or_insert_with(HashMap::new);
+    let as_updates = serde_json::to_value(&imported)
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    validate_config_updates(&as_updates)?;
     
-    // Update config values
-    if let Some(obj) = updates.as_object() {
-        for (key, value) in obj {
-            if key != "password" {
-                addon_config.insert(key.clone(), value.clone());
-            }
+    {
+        let mut cfg = config.lock().unwrap();
+        
+        if imported.password.is_empty() {
+            imported.password = cfg.password.clone();
+        } else if auth::hash_if_plaintext(&mut imported) || imported.password != cfg.password {
+            auth::clear_sessions();
+        }
+        
+        if imported.peer_passwords.is_empty() {
+            imported.peer_passwords = cfg.peer_passwords.clone();
         }
+        
+        config::save_config(&imported)?;
+        *cfg = imported.clone();
     }
     
-    // Save config
-    if let Err(e) = config::save_config(&main_config) {
-        return Json(serde_json::json!({
-            "error": e
-        }));
+    if let Err(e) = logging::set_level(&imported.log_level) {
+        warn!("{}", e);
     }
     
-    // Emit addons update event - Tauri v2 uses emit() not emit_all()
     if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("config-update", config::to_api_json(&imported));
         let _ = handle.emit("addons-update", ());
-        println!("Emitted addons-update event");
+        debug!("Emitted config-update and addons-update events");
     }
+    ws::broadcast_config_update(&imported);
+    tokio::spawn(scheduler::sync_schedules());
     
-    Json(serde_json::json!({
-        "success": true
-    }))
-}
\ No newline at end of file
+    info!("Imported config from another device");
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "configVersion": config::config_version(&imported)
+    })))
+}
+
+async fn login_handler(
+    config: Arc<Mutex<config::Config>>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Response {
+    let body = match json_body(body) {
+        Ok(body) => body,
+        Err(e) => return e.into_response(),
+    };
+    let password = body.get("password").and_then(|v| v.as_str()).unwrap_or("");
+    
+    let authorized = {
+        let cfg = config.lock().unwrap();
+        cfg.password.is_empty() || auth::verify_password(&cfg.password, password)
+    };
+    
+    if !authorized {
+        return api_error(StatusCode::UNAUTHORIZED, "Invalid password").into_response();
+    }
+    
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict",
+        auth::SESSION_COOKIE,
+        auth::create_session()
+    );
+    
+    (
+        [(header::SET_COOKIE, cookie)],
+        Json(serde_json::json!({
+            "success": true
+        })),
+    ).into_response()
+}
+
+async fn change_password_handler(
+    config: Arc<Mutex<config::Config>>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    let Some(new_password) = body.get("newPassword").and_then(|v| v.as_str()) else {
+        return Err(api_error(StatusCode::BAD_REQUEST, "Missing newPassword"));
+    };
+    
+    let mut cfg = config.lock().unwrap();
+    
+    // An empty password turns protection off
+    cfg.password = if new_password.is_empty() {
+        String::new()
+    } else {
+        auth::hash_password(new_password)
+    };
+    
+    config::save_shared(&mut cfg)?;
+    
+    auth::clear_sessions();
+    info!("Password {}", if new_password.is_empty() { "removed" } else { "changed" });
+    
+    Ok(Json(serde_json::json!({
+        "success": true
+    })))
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct CollectionQuery {
+    collection: Option<String>,
+}
+
+impl CollectionQuery {
+    /// The requested collection, or None for the default one; rejects names that leave Media
+    fn validated(&self) -> Result<Option<&str>, ApiError> {
+        match self.collection.as_deref().filter(|name| !name.is_empty()) {
+            Some(name) => {
+                media::sanitize_filename(name)
+                    .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("Invalid collection name: {}", e)))?;
+                Ok(Some(name))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// List media; any of type, tag, offset, limit, sort or order switches to a `{ total, items }` page
+async fn get_media_handler(
+    config: Arc<Mutex<config::Config>>,
+    Query(query): Query<CollectionQuery>,
+    Query(filter): Query<media::MediaFilter>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let collection = query.validated()?;
+    
+    // The saved playlist order and tags belong to the default collection
+    let (order, tags) = match collection {
+        None => {
+            let cfg = config.lock().unwrap();
+            (cfg.media_order.clone(), cfg.tags.clone())
+        }
+        Some(_) => (Vec::new(), HashMap::new()),
+    };
+    
+    if !filter.is_empty() {
+        filter.validate()?;
+        let page = media::get_files_filtered(collection, &order, &tags, &filter).await?;
+        return Ok(Json(serde_json::json!(page)));
+    }
+    
+    let mut files = media::get_files(collection, &order).await?;
+    media::attach_tags(&mut files, &tags);
+    
+    Ok(Json(serde_json::json!(files)))
+}
+
+async fn get_collections_handler() -> Result<Json<serde_json::Value>, ApiError> {
+    let collections = media::list_collections().await?;
+    
+    Ok(Json(serde_json::json!(collections)))
+}
+
+/// Whether the display should be on right now, in the device's local time
+async fn schedule_active_handler(config: Arc<Mutex<config::Config>>) -> Json<serde_json::Value> {
+    let now = chrono::Local::now();
+    let cfg = config.lock().unwrap();
+    
+    Json(serde_json::json!({
+        "active": cfg.display_active_at(now.naive_local()),
+        "schedule": cfg.schedule,
+        "now": now.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+    }))
+}
+
+/// Number of slides in the display's playlist
+async fn playlist_len(config: &Mutex<config::Config>) -> Result<usize, ApiError> {
+    let order = config.lock().unwrap().media_order.clone();
+    media::get_files(None, &order).await
+        .map(|files| files.len())
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+async fn get_playback_handler(
+    config: Arc<Mutex<config::Config>>,
+    playback: playback::SharedPlayback,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let total = playlist_len(&config).await?;
+    let state = *playback.lock().unwrap();
+    Ok(Json(state.to_api_json(total)))
+}
+
+/// Files in the order the slideshow plays them: the saved order, or this cycle's shuffle
+async fn playlist(config: &Mutex<config::Config>) -> Result<Vec<media::MediaFile>, error::AppError> {
+    let (order, shuffle, seed) = {
+        let cfg = config.lock().unwrap();
+        (cfg.media_order.clone(), cfg.shuffle, cfg.shuffle_seed)
+    };
+    let files = media::get_files(None, &order).await?;
+    Ok(playback::sequence(&files, shuffle, seed))
+}
+
+/// Move a shuffled slideshow on from the cycle played with `completed` to the next one,
+/// returning the seed now in use. Every client that reaches the end of the same cycle
+/// asks for this, so only the first request advances the seed; the rest find it moved on.
+fn reshuffle(
+    config: &Mutex<config::Config>,
+    app_handle: Option<&AppHandle>,
+    completed: u64,
+) -> Result<u64, error::AppError> {
+    let mut cfg = config.lock().unwrap();
+    if !cfg.shuffle || cfg.shuffle_seed != completed {
+        return Ok(cfg.shuffle_seed);
+    }
+    
+    cfg.shuffle_seed = playback::next_seed(cfg.shuffle_seed);
+    config::save_shared(&mut cfg)?;
+    debug!("Reshuffled playback with seed {}", cfg.shuffle_seed);
+    
+    if let Some(handle) = app_handle {
+        let _ = handle.emit("config-update", config::to_api_json(&cfg));
+    }
+    ws::broadcast_config_update(&cfg);
+    Ok(cfg.shuffle_seed)
+}
+
+/// The names of the current media in the order they play this cycle
+async fn get_playback_sequence_handler(
+    config: Arc<Mutex<config::Config>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let files = playlist(&config).await?;
+    let (shuffle, seed) = {
+        let cfg = config.lock().unwrap();
+        (cfg.shuffle, cfg.shuffle_seed)
+    };
+    
+    Ok(Json(serde_json::json!({
+        "shuffle": shuffle,
+        "seed": seed,
+        "sequence": files.iter().map(|file| &file.name).collect::<Vec<_>>(),
+    })))
+}
+
+/// The slide after the current one, so the display can load it before the transition starts.
+/// `next` is null while the playlist is empty.
+async fn get_next_slide_handler(
+    config: Arc<Mutex<config::Config>>,
+    playback: playback::SharedPlayback,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let tags = config.lock().unwrap().tags.clone();
+    let mut files = playlist(&config).await?;
+    media::attach_tags(&mut files, &tags);
+    
+    let state = *playback.lock().unwrap();
+    let Some(index) = state.next_index(files.len()) else {
+        return Ok(Json(serde_json::json!({ "index": null, "total": 0, "next": null })));
+    };
+    let next = &files[index];
+    
+    // Videos play to the end; images stay up for their own duration
+    let display_ms = (next.file_type == "image").then(|| config.lock().unwrap().duration_for(&next.name));
+    
+    Ok(Json(serde_json::json!({
+        "index": index,
+        "total": files.len(),
+        "next": next,
+        "displayMs": display_ms,
+    })))
+}
+
+/// Remote control: move to another slide, or pause and resume the slideshow
+async fn control_playback_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    playback: playback::SharedPlayback,
+    body: Result<Json<playback::PlaybackAction>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let action = json_body(body)?;
+    let total = playlist_len(&config).await?;
+    let seed = config.lock().unwrap().shuffle_seed;
+    
+    let (state, wrapped) = {
+        let mut state = playback.lock().unwrap();
+        let before = state.index;
+        state.apply(action, total);
+        (*state, matches!(action, playback::PlaybackAction::Next) && total > 1 && state.index < before)
+    };
+    let payload = state.to_api_json(total);
+    
+    // Skipping past the last slide ends the cycle just as playing through it does
+    if wrapped {
+        let handle = app_handle.lock().unwrap().clone();
+        reshuffle(&config, handle.as_ref(), seed)?;
+    }
+    
+    debug!("Playback {:?} -> {}", action, payload);
+    
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("playback-control", &payload);
+    }
+    ws::broadcast(serde_json::json!({ "event": "playback-control", "playback": payload }));
+    
+    Ok(Json(payload))
+}
+
+/// Liveness check for monitoring; touches neither the config nor the disk
+async fn health_handler() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION")
+    }))
+}
+
+/// Readiness check: 503 until the Media, Fonts and Addons folders are accessible
+async fn ready_handler() -> (StatusCode, Json<serde_json::Value>) {
+    let missing = match system::inaccessible_dirs() {
+        Ok(missing) if missing.is_empty() => {
+            return (StatusCode::OK, Json(serde_json::json!({
+                "status": "ready",
+                "version": env!("CARGO_PKG_VERSION")
+            })));
+        }
+        Ok(missing) => missing,
+        Err(e) => {
+            warn!("Readiness check failed: {}", e);
+            vec![e]
+        }
+    };
+    
+    (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
+        "status": "unavailable",
+        "missing": missing
+    })))
+}
+
+async fn get_system_info_handler() -> Result<Json<serde_json::Value>, ApiError> {
+    let info = system::get_info().await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    
+    Ok(Json(serde_json::json!(info)))
+}
+
+async fn set_media_order_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    body: Result<Json<Vec<String>>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let order = json_body(body)?;
+    let files = media::get_files(None, &[]).await?;
+    
+    // Drop stale and duplicate entries so the saved order only names real files
+    let mut media_order: Vec<String> = Vec::new();
+    for name in order {
+        if files.iter().any(|f| f.name == name) && !media_order.contains(&name) {
+            media_order.push(name);
+        }
+    }
+    
+    {
+        let mut cfg = config.lock().unwrap();
+        cfg.keep_locked_in_order(&mut media_order);
+        cfg.media_order = media_order.clone();
+        
+        config::save_shared(&mut cfg)?;
+    }
+    
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("media-update", ());
+        debug!("Emitted media-update event");
+    }
+    ws::broadcast_media_update();
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "order": media_order
+    })))
+}
+
+/// Why a single file in an upload was turned away
+enum UploadRejection {
+    TooLarge,
+    /// Storing it would pass the total media storage limit
+    OverQuota(String),
+    Invalid(String),
+}
+
+/// Stream one multipart field to disk, giving up as soon as it passes `max_bytes`.
+/// The stored name differs from `filename` if the upload was transcoded or deduplicated.
+async fn receive_upload(
+    collection: Option<&str>,
+    filename: &str,
+    field: &mut axum::extract::multipart::Field<'_>,
+    max_bytes: u64,
+    max_total_bytes: u64,
+    transcode: Option<media::Transcode>,
+    video: Option<media::video::VideoTranscode>,
+) -> Result<media::SavedUpload, UploadRejection> {
+    let mut upload = media::PendingUpload::begin(collection, filename).await
+        .map_err(|e| UploadRejection::Invalid(e.into()))?
+        .transcode(transcode)
+        .transcode_video(video)
+        .max_total_bytes(max_total_bytes);
+    
+    loop {
+        let chunk = match field.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                upload.abort().await;
+                return Err(UploadRejection::Invalid(e.body_text()));
+            }
+        };
+        
+        if upload.written() + chunk.len() as u64 > max_bytes {
+            upload.abort().await;
+            return Err(UploadRejection::TooLarge);
+        }
+        
+        if let Err(e) = upload.write(&chunk).await {
+            upload.abort().await;
+            return Err(UploadRejection::Invalid(e.into()));
+        }
+    }
+    
+    upload.finish().await
+        .map_err(|e| match e {
+            error::AppError::QuotaExceeded(e) => UploadRejection::OverQuota(e),
+            e => UploadRejection::Invalid(e.into()),
+        })
+}
+
+async fn upload_media_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    Query(query): Query<CollectionQuery>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<serde_json::Value>), ApiError> {
+    let collection = query.validated()?;
+    let (max_bytes, max_total_bytes, transcode, video) = {
+        let cfg = config.lock().unwrap();
+        (
+            cfg.max_upload_bytes,
+            cfg.max_total_media_bytes,
+            media::Transcode::from_config(&cfg),
+            media::video::VideoTranscode::from_config(&cfg),
+        )
+    };
+    let mut uploaded_count = 0;
+    let mut over_quota = false;
+    let mut errors = Vec::new();
+    let mut results = Vec::new();
+    
+    while let Ok(Some(mut field)) = multipart.next_field().await {
+        let Some(filename) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        
+        let received = receive_upload(
+            collection,
+            &filename,
+            &mut field,
+            max_bytes,
+            max_total_bytes,
+            transcode.clone(),
+            video.clone(),
+        ).await;
+        match received {
+            Ok(saved) => {
+                uploaded_count += 1;
+                info!("Uploaded: {}", saved.filename);
+                results.push(serde_json::json!({
+                    "file": filename,
+                    "storedAs": saved.filename,
+                    "deduplicated": saved.deduplicated,
+                    "success": true
+                }));
+            }
+            Err(rejection) => {
+                let (error, too_large) = match rejection {
+                    UploadRejection::TooLarge => (format!("File is larger than the {} byte limit", max_bytes), true),
+                    UploadRejection::OverQuota(e) => {
+                        over_quota = true;
+                        (e, false)
+                    }
+                    UploadRejection::Invalid(e) => (e, false),
+                };
+                
+                warn!("Rejected upload {}: {}", filename, error);
+                errors.push(serde_json::json!({
+                    "file": filename,
+                    "error": error
+                }));
+                results.push(serde_json::json!({
+                    "file": filename,
+                    "success": false,
+                    "tooLarge": too_large,
+                    "error": error
+                }));
+            }
+        }
+    }
+    
+    // Emit media update event - Tauri v2 uses emit() not emit_all()
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("media-update", ());
+        debug!("Emitted media-update event");
+    }
+    ws::broadcast_media_update();
+    
+    // Nothing could be stored because the disk budget is used up
+    let status = if uploaded_count == 0 && over_quota {
+        StatusCode::INSUFFICIENT_STORAGE
+    } else {
+        StatusCode::OK
+    };
+    
+    Ok((status, Json(serde_json::json!({
+        "success": errors.is_empty(),
+        "files": uploaded_count,
+        "errors": errors,
+        "results": results
+    }))))
+}
+
+/// Header carrying how many bytes of a resumable upload the server has
+const UPLOAD_OFFSET: &str = "upload-offset";
+
+#[derive(Debug, serde::Deserialize)]
+struct ResumableUploadRequest {
+    filename: String,
+    size: u64,
+}
+
+/// Start a resumable upload; the bytes follow in PATCH requests to the returned id
+async fn init_resumable_upload_handler(
+    config: Arc<Mutex<config::Config>>,
+    Query(query): Query<CollectionQuery>,
+    body: Result<Json<ResumableUploadRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ApiError> {
+    let collection = query.validated()?;
+    let request = json_body(body)?;
+    
+    let (max_bytes, max_total_bytes) = {
+        let cfg = config.lock().unwrap();
+        (cfg.max_upload_bytes, cfg.max_total_media_bytes)
+    };
+    if request.size > max_bytes {
+        return Err(api_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("File is larger than the {} byte limit", max_bytes),
+        ));
+    }
+    
+    let upload = media::resumable::create(collection, &request.filename, request.size, max_total_bytes).await?;
+    
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "id": upload.id,
+        "filename": upload.filename,
+        "size": upload.size,
+        "offset": 0
+    }))))
+}
+
+/// How much of a resumable upload has arrived, so the client knows where to carry on
+async fn resumable_upload_offset_handler(AxumPath(id): AxumPath<String>) -> Result<Response, ApiError> {
+    let (upload, offset) = media::resumable::find(&id).await?
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, "Upload not found"))?;
+    
+    Ok([
+        (header::HeaderName::from_static(UPLOAD_OFFSET), offset.to_string()),
+        (header::HeaderName::from_static("upload-length"), upload.size.to_string()),
+        (header::CACHE_CONTROL, "no-store".to_string()),
+    ].into_response())
+}
+
+/// Append the request body to a resumable upload at its `Upload-Offset`.
+/// The last piece finishes the upload and answers with where the file was stored.
+async fn append_resumable_upload_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(id): AxumPath<String>,
+    headers: HeaderMap,
+    body: axum::body::Body,
+) -> Result<Response, ApiError> {
+    let offset = headers.get(UPLOAD_OFFSET)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .ok_or_else(|| api_error(StatusCode::BAD_REQUEST, "Missing or invalid Upload-Offset header"))?;
+    
+    let (transcode, video, max_total_bytes) = {
+        let cfg = config.lock().unwrap();
+        (
+            media::Transcode::from_config(&cfg),
+            media::video::VideoTranscode::from_config(&cfg),
+            cfg.max_total_media_bytes,
+        )
+    };
+    
+    let appended = media::resumable::append(&id, offset, body.into_data_stream(), transcode, video, max_total_bytes).await
+        .map_err(|rejection| match rejection {
+            media::resumable::AppendRejection::NotFound => api_error(StatusCode::NOT_FOUND, "Upload not found"),
+            media::resumable::AppendRejection::Busy => {
+                api_error(StatusCode::CONFLICT, "Upload is already receiving data")
+            }
+            media::resumable::AppendRejection::OffsetMismatch(current) => (StatusCode::CONFLICT, Json(serde_json::json!({
+                "error": format!("Upload is at offset {}, not {}", current, offset),
+                "offset": current
+            }))),
+            media::resumable::AppendRejection::TooLarge => {
+                api_error(StatusCode::PAYLOAD_TOO_LARGE, "Data runs past the announced file size")
+            }
+            media::resumable::AppendRejection::Rejected(e) => {
+                warn!("Rejected resumable upload {}: {}", id, e);
+                match e {
+                    error::AppError::Validation(e) => api_error(StatusCode::UNPROCESSABLE_ENTITY, e),
+                    e => e.into(),
+                }
+            }
+            media::resumable::AppendRejection::Failed(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+        })?;
+    
+    let saved = match appended {
+        media::resumable::Appended::Partial(offset) => {
+            return Ok((
+                StatusCode::NO_CONTENT,
+                [(header::HeaderName::from_static(UPLOAD_OFFSET), offset.to_string())],
+            ).into_response());
+        }
+        media::resumable::Appended::Complete(saved) => saved,
+    };
+    
+    info!("Uploaded: {}", saved.filename);
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("media-update", ());
+        debug!("Emitted media-update event");
+    }
+    ws::broadcast_media_update();
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "complete": true,
+        "storedAs": saved.filename,
+        "deduplicated": saved.deduplicated
+    })).into_response())
+}
+
+async fn delete_media_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(filename): AxumPath<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let filename = media::sanitize_filename(&filename)?;
+    config.lock().unwrap().check_unlocked(&filename)?;
+    media::delete_file(&filename).await?;
+    
+    // Don't leave a duration override, tags or order entry behind for the deleted file
+    {
+        let mut cfg = config.lock().unwrap();
+        if cfg.forget_media(&filename) {
+            if let Err(e) = config::save_shared(&mut cfg) {
+                warn!("Failed to clean up settings for {}: {}", filename, e);
+            }
+        }
+    }
+    
+    // Emit media update event - Tauri v2 uses emit() not emit_all()
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("media-update", ());
+        debug!("Emitted media-update event");
+    }
+    ws::broadcast_media_update();
+    
+    Ok(Json(serde_json::json!({
+        "success": true
+    })))
+}
+
+#[derive(serde::Deserialize)]
+struct BulkDeleteRequest {
+    files: Vec<String>,
+}
+
+/// Delete several files, reporting each one and notifying the display only once
+async fn bulk_delete_media_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    body: Result<Json<BulkDeleteRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    
+    let mut results = Vec::new();
+    let mut deleted = Vec::new();
+    
+    for file in body.files {
+        let outcome = media::sanitize_filename(&file)
+            .and_then(|filename| config.lock().unwrap().check_unlocked(&filename).map(|()| filename));
+        let outcome = match outcome {
+            Ok(filename) => media::delete_file(&filename).await.map(|_| filename),
+            Err(e) => Err(e),
+        };
+        
+        match outcome {
+            Ok(filename) => {
+                results.push(serde_json::json!({
+                    "file": file,
+                    "success": true
+                }));
+                deleted.push(filename);
+            }
+            Err(error) => {
+                results.push(serde_json::json!({
+                    "file": file,
+                    "success": false,
+                    "error": error.to_string()
+                }));
+            }
+        }
+    }
+    
+    if !deleted.is_empty() {
+        {
+            let mut cfg = config.lock().unwrap();
+            let mut changed = false;
+            for filename in &deleted {
+                changed |= cfg.forget_media(filename);
+            }
+            if changed {
+                if let Err(e) = config::save_shared(&mut cfg) {
+                    warn!("Failed to clean up settings for deleted files: {}", e);
+                }
+            }
+        }
+        
+        info!("Deleted {} file(s)", deleted.len());
+        
+        if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+            let _ = handle.emit("media-update", ());
+            debug!("Emitted media-update event");
+        }
+        ws::broadcast_media_update();
+    }
+    
+    Ok(Json(serde_json::json!({
+        "success": deleted.len() == results.len(),
+        "deleted": deleted.len(),
+        "results": results
+    })))
+}
+
+async fn rename_media_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(filename): AxumPath<String>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    let Some(new_name) = body.get("newName").and_then(|v| v.as_str()) else {
+        return Err(api_error(StatusCode::BAD_REQUEST, "Missing newName"));
+    };
+    
+    let filename = media::sanitize_filename(&filename)?;
+    let new_name = media::sanitize_filename(new_name)?;
+    
+    // Missing files give a 404 and taken names a 409
+    media::rename_file(&filename, &new_name).await?;
+    
+    {
+        let mut cfg = config.lock().unwrap();
+        if cfg.rename_media(&filename, &new_name) {
+            if let Err(e) = config::save_shared(&mut cfg) {
+                warn!("Failed to update settings for {}: {}", new_name, e);
+            }
+        }
+    }
+    
+    info!("Renamed {} to {}", filename, new_name);
+    
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("media-update", ());
+        debug!("Emitted media-update event");
+    }
+    ws::broadcast_media_update();
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "name": new_name
+    })))
+}
+
+async fn set_media_duration_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(filename): AxumPath<String>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    let filename = media::sanitize_filename(&filename)?;
+    
+    // A null or missing duration clears the override
+    let duration = body.get("duration").and_then(|v| v.as_u64());
+    
+    if duration.is_some() {
+        let exists = media::get_media_dir()
+            .map(|dir| dir.join(&filename).is_file())
+            .unwrap_or(false);
+        
+        if !exists {
+            return Err(api_error(StatusCode::NOT_FOUND, "File not found"));
+        }
+    }
+    
+    let mut cfg = config.lock().unwrap();
+    
+    match duration {
+        Some(ms) => { cfg.durations.insert(filename.clone(), ms); }
+        None => { cfg.durations.remove(&filename); }
+    }
+    
+    config::save_shared(&mut cfg)?;
+    
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("config-update", config::to_api_json(&cfg));
+        debug!("Emitted config-update event");
+    }
+    ws::broadcast_config_update(&cfg);
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "duration": cfg.duration_for(&filename)
+    })))
+}
+
+/// Replace a file's tags with `{"tags": [...]}`; an empty list removes them
+async fn set_media_tags_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(filename): AxumPath<String>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    let filename = media::sanitize_filename(&filename)?;
+    
+    let tags: Vec<String> = body.get("tags")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .ok_or_else(|| api_error(StatusCode::BAD_REQUEST, "tags must be an array of strings"))?;
+    let tags = config::normalize_tags(&tags)?;
+    
+    if !tags.is_empty() {
+        let exists = media::get_media_dir()
+            .map(|dir| dir.join(&filename).is_file())
+            .unwrap_or(false);
+        
+        if !exists {
+            return Err(api_error(StatusCode::NOT_FOUND, "File not found"));
+        }
+    }
+    
+    let mut cfg = config.lock().unwrap();
+    cfg.set_tags(&filename, tags.clone());
+    config::save_shared(&mut cfg)?;
+    
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("config-update", config::to_api_json(&cfg));
+        debug!("Emitted config-update event");
+    }
+    ws::broadcast_config_update(&cfg);
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "tags": tags
+    })))
+}
+
+/// Lock a file (POST) so deletes refuse it and order changes keep it, or unlock it (DELETE)
+async fn set_media_lock_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(filename): AxumPath<String>,
+    locked: bool,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let filename = media::sanitize_filename(&filename)?;
+    
+    if locked {
+        let exists = media::get_media_dir()
+            .map(|dir| dir.join(&filename).is_file())
+            .unwrap_or(false);
+        
+        if !exists {
+            return Err(api_error(StatusCode::NOT_FOUND, "File not found"));
+        }
+    }
+    
+    let mut cfg = config.lock().unwrap();
+    let changed = if locked {
+        cfg.locked_media.insert(filename.clone())
+    } else {
+        cfg.locked_media.remove(&filename)
+    };
+    
+    if changed {
+        config::save_shared(&mut cfg)?;
+        info!("{} {}", if locked { "Locked" } else { "Unlocked" }, filename);
+        
+        if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+            let _ = handle.emit("config-update", config::to_api_json(&cfg));
+            debug!("Emitted config-update event");
+        }
+        ws::broadcast_config_update(&cfg);
+    }
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "locked": locked
+    })))
+}
+
+/// Serve a media file, honouring `Range` so the display's `<video>` can seek
+async fn get_raw_media_handler(
+    AxumPath(filename): AxumPath<String>,
+    Query(query): Query<CollectionQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let filename = media::sanitize_filename(&filename)?;
+    let media_dir = paths::get_media_dir(query.validated()?)?;
+    
+    let ext = std::path::Path::new(&filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    
+    serve_file(&media_dir.join(&filename), media::content_type(&ext), &headers).await
+}
+
+/// Serve a media file as a download under its own name rather than for display
+async fn download_media_handler(
+    AxumPath(filename): AxumPath<String>,
+    Query(query): Query<CollectionQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    use tokio::io::AsyncReadExt;
+    
+    let filename = media::sanitize_filename(&filename)?;
+    let path = paths::get_media_dir(query.validated()?)?.join(&filename);
+    
+    // Enough of the file to recognise it when the extension doesn't say
+    let mut head = Vec::with_capacity(64);
+    if let Ok(file) = tokio::fs::File::open(&path).await {
+        let _ = file.take(64).read_to_end(&mut head).await;
+    }
+    
+    let mut response = serve_file(&path, media::download_content_type(&filename, &head), &headers).await?;
+    let disposition = HeaderValue::from_str(&media::attachment_disposition(&filename))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    response.headers_mut().insert(header::CONTENT_DISPOSITION, disposition);
+    
+    Ok(response)
+}
+
+/// Detailed, probed-on-demand information about one media file: image format and color
+/// details, or video codecs, bit rate and frame rate when ffprobe is installed
+async fn media_metadata_handler(
+    AxumPath(filename): AxumPath<String>,
+    Query(query): Query<CollectionQuery>,
+) -> Result<Json<media::metadata::Metadata>, ApiError> {
+    let filename = media::sanitize_filename(&filename)?;
+    let path = paths::get_media_dir(query.validated()?)?.join(&filename);
+    if !path.is_file() {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("File not found: {}", filename)));
+    }
+    
+    Ok(Json(media::metadata::read(&path).await?))
+}
+
+/// Whether the request's If-None-Match lists `etag`, or None when it has no If-None-Match
+fn etag_matches(headers: &HeaderMap, etag: &str) -> Option<bool> {
+    let value = headers.get(header::IF_NONE_MATCH)?.to_str().ok()?;
+    Some(value.split(',').any(|tag| {
+        let tag = tag.trim();
+        tag == "*" || tag.trim_start_matches("W/") == etag
+    }))
+}
+
+/// Serve a font file so browsers can load it with @font-face and cache it. Fonts rarely
+/// change, so clients keep them for a day, then revalidate against the ETag or Last-Modified.
+async fn get_font_handler(
+    AxumPath(name): AxumPath<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    const MAX_AGE_SECS: u32 = 24 * 60 * 60;
+    
+    let path = fonts::font_path(&name)?;
+    let metadata = tokio::fs::metadata(&path).await
+        .map_err(error::AppError::from)?;
+    let modified: chrono::DateTime<chrono::Utc> = metadata.modified()
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .into();
+    
+    let etag = format!("\"{:x}-{:x}\"", modified.timestamp(), metadata.len());
+    let last_modified = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::LAST_MODIFIED, last_modified),
+        (header::CACHE_CONTROL, format!("public, max-age={}", MAX_AGE_SECS)),
+    ];
+    
+    // If-Modified-Since only counts when there is no If-None-Match, and HTTP dates have whole seconds
+    let unchanged = etag_matches(&headers, &etag).unwrap_or_else(|| {
+        headers.get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+            .is_some_and(|since| modified.timestamp() <= since.timestamp())
+    });
+    if unchanged {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    
+    let mut response = serve_file(&path, fonts::font_content_type(&name), &headers).await?;
+    for (name, value) in cache_headers {
+        let value = HeaderValue::from_str(&value)
+            .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        response.headers_mut().insert(name, value);
+    }
+    
+    Ok(response)
+}
+
+/// Stream a file, or the part of it asked for with `Range`
+async fn serve_file(path: &std::path::Path, content_type: &str, headers: &HeaderMap) -> Result<Response, ApiError> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+    
+    let mut file = match tokio::fs::File::open(path).await {
+        Ok(file) if path.is_file() => file,
+        _ => return Err(api_error(StatusCode::NOT_FOUND, "File not found")),
+    };
+    let len = file.metadata().await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .len();
+    
+    let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+    
+    let (status, start, count) = match media::parse_byte_range(range, len) {
+        media::ByteRange::Full => (StatusCode::OK, 0, len),
+        media::ByteRange::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+        media::ByteRange::Unsatisfiable => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+            ).into_response());
+        }
+    };
+    
+    file.seek(std::io::SeekFrom::Start(start)).await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let body = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file.take(count)));
+    
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, count)
+        .header(header::ACCEPT_RANGES, "bytes");
+    if status == StatusCode::PARTIAL_CONTENT {
+        response = response.header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, start + count - 1, len));
+    }
+    
+    response.body(body)
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// GETs skip the auth middleware, so handlers serving anything sensitive check here
+fn check_authorized(config: &Mutex<config::Config>, headers: &HeaderMap) -> Result<(), ApiError> {
+    if !auth::is_authorized(&config.lock().unwrap().password, headers) {
+        return Err(api_error(StatusCode::UNAUTHORIZED, "Invalid or missing password"));
+    }
+    Ok(())
+}
+
+/// List the server's log files with their sizes, newest first
+async fn list_logs_handler(
+    config: Arc<Mutex<config::Config>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_authorized(&config, &headers)?;
+    
+    let logs = logging::list_logs(&paths::get_logs_dir()?).await?;
+    Ok(Json(serde_json::json!(logs)))
+}
+
+/// Download a log file; `Range` fetches just the end of a large one
+async fn get_log_handler(
+    config: Arc<Mutex<config::Config>>,
+    AxumPath(name): AxumPath<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    check_authorized(&config, &headers)?;
+    
+    let path = logging::log_file_path(&paths::get_logs_dir()?, &name)?;
+    serve_file(&path, "text/plain; charset=utf-8", &headers).await
+}
+
+async fn get_thumbnail_handler(config: Arc<Mutex<config::Config>>, AxumPath(filename): AxumPath<String>) -> Response {
+    let quality = config.lock().unwrap().image_quality;
+    let thumb_path = match media::generate_thumbnail(&filename, media::thumbnail::DEFAULT_THUMBNAIL_SIZE, quality).await {
+        Ok(path) => path,
+        // The frontend falls back to the full file or an icon
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    
+    match tokio::fs::read(&thumb_path).await {
+        Ok(data) => ([(header::CONTENT_TYPE, "image/jpeg")], data).into_response(),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+fn all_peers(config: &Mutex<config::Config>, live_peers: &network::PeerTable) -> Vec<network::Peer> {
+    let cfg = config.lock().unwrap();
+    network::merge_peers(&cfg.peers, &live_peers.lock().unwrap())
+}
+
+async fn get_peers_handler(config: Arc<Mutex<config::Config>>, live_peers: network::PeerTable) -> impl IntoResponse {
+    let mut peers = all_peers(&config, &live_peers);
+    network::sort_peers(&mut peers);
+    Json(serde_json::json!(peers))
+}
+
+#[derive(serde::Deserialize)]
+struct PeerLabelRequest {
+    alias: Option<String>,
+    order: Option<u32>,
+}
+
+/// Set a peer's alias and order; omitted fields are cleared
+async fn set_peer_label_handler(
+    config: Arc<Mutex<config::Config>>,
+    live_peers: network::PeerTable,
+    AxumPath(peer_id): AxumPath<String>,
+    body: Result<Json<PeerLabelRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    
+    let mut cfg = config.lock().unwrap();
+    if !network::set_peer_label(&mut cfg, &live_peers, &peer_id, body.alias, body.order) {
+        return Err(api_error(StatusCode::NOT_FOUND, "Peer not found"));
+    }
+    
+    config::save_shared(&mut cfg)?;
+    
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+#[derive(serde::Deserialize)]
+struct PeerPasswordRequest {
+    password: String,
+}
+
+/// Store the password sent when syncing to a peer; an empty password forgets it
+async fn set_peer_password_handler(
+    config: Arc<Mutex<config::Config>>,
+    live_peers: network::PeerTable,
+    AxumPath(peer_id): AxumPath<String>,
+    body: Result<Json<PeerPasswordRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    
+    if !all_peers(&config, &live_peers).iter().any(|p| p.id == peer_id) {
+        return Err(api_error(StatusCode::NOT_FOUND, "Peer not found"));
+    }
+    
+    let mut cfg = config.lock().unwrap();
+    if body.password.is_empty() {
+        cfg.peer_passwords.remove(&peer_id);
+    } else {
+        cfg.peer_passwords.insert(peer_id, body.password);
+    }
+    
+    config::save_shared(&mut cfg)?;
+    
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+async fn sync_peer_handler(
+    config: Arc<Mutex<config::Config>>,
+    live_peers: network::PeerTable,
+    AxumPath(peer_id): AxumPath<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let peer = all_peers(&config, &live_peers).into_iter().find(|p| p.id == peer_id);
+    let Some(peer) = peer else {
+        return Err(api_error(StatusCode::NOT_FOUND, "Peer not found"));
+    };
+    
+    let (order, password) = {
+        let cfg = config.lock().unwrap();
+        (cfg.media_order.clone(), cfg.peer_passwords.get(&peer.id).cloned())
+    };
+    let files: Vec<String> = media::get_files(None, &order).await?
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    
+    let report = network::sync_media_to_peer(&peer, &files, password.as_deref()).await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    
+    if report.peer_offline {
+        network::set_peer_online(&config, &live_peers, &peer.id, false);
+    }
+    
+    Ok(Json(serde_json::json!({
+        "success": report.failed.is_empty(),
+        "report": report
+    })))
+}
+
+async fn get_addons_handler() -> Result<Json<serde_json::Value>, ApiError> {
+    get_addons_internal(false).await
+        .map(Json)
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// An addon's frontend.js with its config injected, so browsers can load addons too.
+/// Clients revalidate with If-None-Match and get a 304 while nothing has changed.
+async fn get_addon_frontend_handler(
+    AxumPath(addon_id): AxumPath<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let addon_item = find_addon_with_config(&addon_id).await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .filter(|addon_item| addon_item.has_frontend)
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, "Addon not found"))?;
+    
+    let etag = format!("\"{}\"", addon::frontend_etag(&addon_id, &addon_item.config)?);
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    
+    if etag_matches(&headers, &etag) == Some(true) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    
+    let script = addon::get_frontend_script_with_config(&addon_id, &addon_item.config).await?;
+    
+    Ok((
+        cache_headers,
+        [(header::CONTENT_TYPE, "application/javascript; charset=utf-8")],
+        script,
+    ).into_response())
+}
+
+async fn get_addons_internal(force: bool) -> Result<serde_json::Value, String> {
+    let mut addons = addon::scan_addons(force).await?;
+    
+    // Load saved configs from main config
+    let config = config::load_config()?;
+    
+    for mut addon_item in &mut addons {
+        let saved_config = config.addons.get(&addon_item.id);
+        addon::merge_addon_config(&mut addon_item, saved_config);
+    }
+    
+    // Convert to JSON object with addon IDs as keys
+    let mut addons_map = serde_json::Map::new();
+    for (load_index, addon_item) in addons.into_iter().enumerate() {
+        addons_map.insert(addon_item.id.clone(), serde_json::json!({
+            "id": addon_item.id,
+            "info": {
+                "name": addon_item.info.name,
+                "version": addon_item.info.version,
+                "author": addon_item.info.author,
+                "description": addon_item.info.description,
+                "category": addon_item.info.category,
+                "groups": addon_item.info.groups,
+            },
+            "enabled": addon_item.enabled,
+            "config": addon_item.config,
+            "settings": addon_item.settings,
+            "configKeyWarnings": addon_item.config_key_warnings,
+            "permissions": addon_item.permissions,
+            "loadError": addon_item.load_error,
+            "lastError": addon_item.last_error,
+            // The map is keyed by id, so carry the dependency order explicitly
+            "loadIndex": load_index,
+        }));
+    }
+    
+    Ok(serde_json::Value::Object(addons_map))
+}
+
+async fn validate_addon_manifest_handler(manifest: String) -> impl IntoResponse {
+    match addon::validate_manifest(&manifest) {
+        Ok(manifest) => Json(serde_json::json!({
+            "valid": true,
+            "manifest": manifest
+        })),
+        Err(errors) => Json(serde_json::json!({
+            "valid": false,
+            "errors": errors
+        })),
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct InstallQuery {
+    #[serde(default)]
+    overwrite: bool,
+}
+
+/// Install an addon from an uploaded zip holding its folder
+async fn install_addon_handler(
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    Query(query): Query<InstallQuery>,
+    mut multipart: Multipart,
+) -> Response {
+    let mut data = None;
+    
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.file_name().is_some() {
+            data = field.bytes().await.ok();
+            break;
+        }
+    }
+    
+    let Some(data) = data else {
+        return api_error(StatusCode::BAD_REQUEST, "No addon zip uploaded").into_response();
+    };
+    
+    let installed = tokio::task::spawn_blocking(move || {
+        let archive = addon::install::read_addon_zip(&data)?;
+        let addons_dir = addon::get_addons_dir().map_err(|e| vec![e.to_string()])?;
+        Ok::<_, Vec<String>>(addon::install::install_addon(&addons_dir, archive, query.overwrite))
+    }).await;
+    
+    let addon_id = match installed {
+        Ok(Ok(Ok(addon_id))) => addon_id,
+        Ok(Err(problems)) => {
+            warn!("Rejected addon install: {:?}", problems);
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "Addon failed validation",
+                "problems": problems
+            }))).into_response();
+        }
+        Ok(Ok(Err(addon::install::InstallRejection::AlreadyInstalled(addon_id)))) => {
+            return api_error(
+                StatusCode::CONFLICT,
+                format!("Addon '{}' is already installed; pass overwrite=true to replace it", addon_id),
+            ).into_response();
+        }
+        Ok(Ok(Err(addon::install::InstallRejection::Failed(e)))) => {
+            return api_error(StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+        Err(e) => return api_error(StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    
+    // Installed either way; a failed rescan shows up when the addon list is next loaded
+    if let Err(e) = reload_addons_and_notify(&app_handle).await {
+        warn!("Failed to reload addons after installing {}: {}", addon_id, e);
+    }
+    
+    Json(serde_json::json!({
+        "success": true,
+        "id": addon_id
+    })).into_response()
+}
+
+async fn reload_addons_handler(app_handle: Arc<Mutex<Option<AppHandle>>>) -> Result<Json<serde_json::Value>, ApiError> {
+    let addons = reload_addons_and_notify(&app_handle).await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Addons reloaded successfully",
+        "addons": addons
+    })))
+}
+
+async fn update_addon_config_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(addon_id): AxumPath<String>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let updates = json_body(body)?;
+    
+    let exists = addon::addon_exists(&addon_id)?;
+    if !exists {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("Addon '{}' not found", addon_id)));
+    }
+    
+    // Check values against the declared setting types before anything is saved
+    let values = match updates.as_object() {
+        Some(obj) => {
+            let settings = addon::declared_settings(&addon_id).await?;
+            let addon_dir = addon::get_addons_dir()?
+                .join(&addon_id);
+            addon::validate_config_values(&settings, &addon_dir, obj)
+                .map_err(|e| api_error(StatusCode::BAD_REQUEST, e))?
+        }
+        None => HashMap::new(),
+    };
+    
+    {
+        let mut main_config = config.lock().unwrap();
+        config::refresh_addons(&mut main_config)?;
+        
+        // Get or create addon config and update its values
+        main_config.addons
+            .entry(addon_id.clone())
+            .or_default()
+            .extend(values);
+        
+        // Save config
+        config::save_config(&main_config)?;
+    }
+    
+    // Give the backend a chance to react, e.g. by fetching data for the new settings
+    if let Ok(Some(addon_item)) = find_addon_with_config(&addon_id).await {
+        if addon_item.has_backend {
+            if let Err(e) = notify_config_change(&addon_id, &addon_item.config, scheduler::event_sink()).await {
+                warn!("Addon {} failed to handle its config change: {}", addon_id, e);
+            }
+        }
+    }
+    
+    // Emit addons update event - Tauri v2 uses emit() not emit_all()
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("addons-update", ());
+        debug!("Emitted addons-update event");
+    }
+    
+    // Enabling or disabling an addon starts or stops its scheduled tasks
+    tokio::spawn(scheduler::sync_schedules());
+    
+    Ok(Json(serde_json::json!({
+        "success": true
+    })))
+}
+
+/// Turn an addon on or off without touching the rest of its settings
+async fn set_addon_enabled_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(addon_id): AxumPath<String>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    let Some(enabled) = body.get("enabled").and_then(|v| v.as_bool()) else {
+        return Err(api_error(StatusCode::BAD_REQUEST, "Missing boolean enabled"));
+    };
+    
+    let exists = addon::addon_exists(&addon_id)?;
+    if !exists {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("Addon '{}' not found", addon_id)));
+    }
+    
+    // Update and save under one lock so rapid toggles can't interleave
+    {
+        let mut cfg = config.lock().unwrap();
+        config::refresh_addons(&mut cfg)?;
+        cfg.addons
+            .entry(addon_id.clone())
+            .or_default()
+            .insert("enabled".to_string(), enabled.into());
+        
+        config::save_config(&cfg)?;
+    }
+    
+    info!("Addon {} {}", addon_id, if enabled { "enabled" } else { "disabled" });
+    
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        let _ = handle.emit("addons-update", ());
+        debug!("Emitted addons-update event");
+    }
+    
+    tokio::spawn(scheduler::sync_schedules());
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "enabled": enabled
+    })))
+}
+
+/// Remove an addon's folder along with its saved settings
+async fn uninstall_addon_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    AxumPath(addon_id): AxumPath<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let addons_dir = addon::get_addons_dir()?;
+    
+    let removed = {
+        let addon_id = addon_id.clone();
+        tokio::task::spawn_blocking(move || addon::install::uninstall_addon(&addons_dir, &addon_id)).await
+            .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??
+    };
+    if !removed {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("Addon '{}' not found", addon_id)));
+    }
+    
+    {
+        let mut cfg = config.lock().unwrap();
+        config::refresh_addons(&mut cfg)?;
+        if cfg.addons.remove(&addon_id).is_some() {
+            config::save_config(&cfg)?;
+        }
+    }
+    
+    // Also stops the addon's scheduled tasks
+    if let Err(e) = reload_addons_and_notify(&app_handle).await {
+        warn!("Failed to reload addons after uninstalling {}: {}", addon_id, e);
+    }
+    
+    Ok(Json(serde_json::json!({
+        "success": true
+    })))
+}
+
+async fn export_bundle_handler(config: Arc<Mutex<config::Config>>, headers: HeaderMap) -> Result<Response, ApiError> {
+    // GETs skip the auth middleware, and the bundle holds every file and addon setting
+    check_authorized(&config, &headers)?;
+    
+    let cfg = config.lock().unwrap().clone();
+    let exported = tokio::task::spawn_blocking(move || bundle::export_bundle(&cfg)).await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    
+    info!("Exported device bundle ({} bytes)", exported.len);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"device-bundle.zip\"".to_string()),
+            (header::CONTENT_LENGTH, exported.len.to_string()),
+        ],
+        axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(exported)),
+    ).into_response())
+}
+
+async fn import_bundle_handler(
+    config: Arc<Mutex<config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    mut multipart: Multipart,
+) -> Response {
+    let mut data = None;
+    
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.file_name().is_some() {
+            data = field.bytes().await.ok();
+            break;
+        }
+    }
+    
+    let data = match data {
+        Some(data) => data,
+        None => return api_error(StatusCode::BAD_REQUEST, "No bundle file uploaded").into_response(),
+    };
+    
+    // Validate everything before touching the device
+    let parsed = tokio::task::spawn_blocking(move || bundle::parse_bundle(&data)).await;
+    let parsed = match parsed {
+        Ok(Ok(parsed)) => parsed,
+        Ok(Err(problems)) => {
+            warn!("Rejected bundle import: {:?}", problems);
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "Bundle failed validation",
+                "problems": problems
+            }))).into_response();
+        }
+        Err(e) => return api_error(StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    
+    let current = config.lock().unwrap().clone();
+    let (new_config, manifest) = match bundle::apply_bundle(parsed, &current).await {
+        Ok(applied) => applied,
+        Err(e) => {
+            warn!("Bundle import failed: {}", e);
+            return api_error(StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+    };
+    
+    if let Some(new_config) = &new_config {
+        *config.lock().unwrap() = new_config.clone();
+    }
+    
+    if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+        if let Some(new_config) = &new_config {
+            let _ = handle.emit("config-update", config::to_api_json(new_config));
+        }
+        if !manifest.media.is_empty() {
+            let _ = handle.emit("media-update", ());
+        }
+        if new_config.is_some() || !manifest.addons.is_empty() {
+            let _ = handle.emit("addons-update", ());
+        }
+        debug!("Emitted update events for bundle import");
+    }
+    
+    tokio::spawn(scheduler::sync_schedules());
+    
+    info!("Imported device bundle: {:?}", manifest);
+    
+    Json(serde_json::json!({
+        "success": true,
+        "imported": manifest
+    })).into_response()
+}
+
+fn stage_error(rejection: update::StageRejection) -> ApiError {
+    match rejection {
+        update::StageRejection::AlreadyStaged => api_error(
+            StatusCode::CONFLICT,
+            "An update is already staged; confirm or discard it first",
+        ),
+        update::StageRejection::TooLarge => api_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Update exceeds the {} byte limit", update::MAX_UPDATE_BYTES),
+        ),
+        update::StageRejection::Invalid(e) => api_error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+/// Stream an uploaded update into the staging area and report its checksum.
+/// Nothing is applied until the checksum is sent back to /api/update/confirm.
+async fn upload_update_handler(mut multipart: Multipart) -> Result<Json<serde_json::Value>, ApiError> {
+    while let Some(mut field) = multipart.next_field().await
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.body_text()))?
+    {
+        // Skip form fields like the password that older admin pages send along
+        let Some(filename) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        
+        let mut stage = update::PendingStage::begin(&filename).await
+            .map_err(stage_error)?;
+        
+        loop {
+            let chunk = match field.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    stage.abort().await;
+                    return Err(api_error(StatusCode::BAD_REQUEST, e.body_text()));
+                }
+            };
+            
+            if let Err(e) = stage.write(&chunk).await {
+                stage.abort().await;
+                return Err(stage_error(e));
+            }
+        }
+        
+        let staged = stage.finish().await.map_err(stage_error)?;
+        info!("Staged update {} ({} bytes, sha256 {})", filename, staged.size, staged.sha256);
+        
+        return Ok(Json(serde_json::json!({
+            "success": true,
+            "staged": staged
+        })));
+    }
+    
+    Err(api_error(StatusCode::BAD_REQUEST, "No update file uploaded"))
+}
+
+/// Apply the staged update, provided the admin echoes back the checksum it was staged with
+async fn confirm_update_handler(
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let body = json_body(body)?;
+    let Some(sha256) = body.get("sha256").and_then(|v| v.as_str()) else {
+        return Err(api_error(StatusCode::BAD_REQUEST, "Missing sha256"));
+    };
+    let signature = body.get("signature").and_then(|v| v.as_str());
+    
+    update::apply_staged_update(sha256, signature).await
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e))?;
+    
+    info!("Applying staged update {}", sha256);
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+async fn discard_update_handler() -> Result<Json<serde_json::Value>, ApiError> {
+    let removed = update::discard_staged_update().await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "removed": removed
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// App directory shared by every test here, since the environment is process-wide
+    fn test_app_dir() -> std::path::PathBuf {
+        static DIR: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+        DIR.get_or_init(|| {
+            let dir = std::env::temp_dir().join(format!("api-test-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::env::set_var("IMAGE_PRESENTER_DIR", &dir);
+            dir
+        }).clone()
+    }
+    
+    /// Held by tests that write config.json, so they don't overwrite each other's saves
+    static CONFIG_FILE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+    
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        base
+    }
+    
+    async fn post_addon_config(base: &str, addon_id: &str, body: &str) -> (u16, serde_json::Value) {
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/addons/{}/config", base, addon_id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send().await.unwrap();
+        let status = response.status().as_u16();
+        (status, response.json().await.unwrap())
+    }
+    
+    #[tokio::test]
+    async fn test_update_addon_config_status_codes() {
+        let _guard = CONFIG_FILE.lock().await;
+        let dir = test_app_dir();
+        std::fs::create_dir_all(dir.join("Addons").join("clock")).unwrap();
+        std::fs::write(
+            dir.join("Addons").join("clock").join("addon.toml"),
+            "id = \"clock\"\nname = \"Clock\"\nversion = \"1.0.0\"\n",
+        ).unwrap();
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        let base = serve(Router::new()
+            .route("/api/addons/:id/config", post(move |path, body| {
+                update_addon_config_handler(config.clone(), app_handle.clone(), path, body)
+            }))).await;
+        
+        let (status, body) = post_addon_config(&base, "missing", r#"{"enabled": true}"#).await;
+        assert_eq!(status, 404);
+        assert!(body["error"].as_str().unwrap().contains("missing"));
+        
+        let (status, body) = post_addon_config(&base, "clock", "{\"enabled\": ").await;
+        assert_eq!(status, 400);
+        assert!(body["error"].is_string());
+        
+        let (status, body) = post_addon_config(&base, "clock", r#"{"enabled": true}"#).await;
+        assert_eq!(status, 200);
+        assert_eq!(body, serde_json::json!({ "success": true }));
+        
+        let saved = config::load_config().unwrap();
+        assert_eq!(saved.addons["clock"]["enabled"], serde_json::json!(true));
+    }
+    
+    #[tokio::test]
+    async fn test_config_update_calls_on_config_change() {
+        let _guard = CONFIG_FILE.lock().await;
+        let addon_dir = test_app_dir().join("Addons").join("forecast");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        std::fs::write(addon_dir.join("addon.toml"), r#"
+            [info]
+            name = "Forecast"
+            version = "1.0"
+            
+            [[settings]]
+            id = "city"
+            name = "City"
+            type = "text"
+            default = "Oslo"
+            
+            [[settings]]
+            id = "units"
+            name = "Units"
+            type = "text"
+            default = "metric"
+        "#).unwrap();
+        std::fs::write(addon_dir.join("backend.lua"), r#"
+            function on_config_change(config)
+                addon.write_file("changed.txt", config.city .. " " .. config.units)
+            end
+        "#).unwrap();
+        let _ = std::fs::remove_file(addon_dir.join("changed.txt"));
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        let base = serve(Router::new()
+            .route("/api/addons/:id/config", post(move |path, body| {
+                update_addon_config_handler(config.clone(), app_handle.clone(), path, body)
+            }))).await;
+        
+        // The hook sees the saved value merged with the other setting's default
+        let (status, _) = post_addon_config(&base, "forecast", r#"{"units": "imperial"}"#).await;
+        assert_eq!(status, 200);
+        assert_eq!(std::fs::read_to_string(addon_dir.join("changed.txt")).unwrap(), "Oslo imperial");
+        
+        // A failing hook is only logged; the save still goes through
+        std::fs::write(addon_dir.join("backend.lua"), "function on_config_change(config) error(\"boom\") end").unwrap();
+        let (status, _) = post_addon_config(&base, "forecast", r#"{"city": "Bergen"}"#).await;
+        assert_eq!(status, 200);
+        assert_eq!(config::load_config().unwrap().addons["forecast"]["city"], serde_json::json!("Bergen"));
+        
+        let _ = std::fs::remove_dir_all(&addon_dir);
+    }
+    
+    #[tokio::test]
+    async fn test_addon_setting_groups_in_api() {
+        let _guard = CONFIG_FILE.lock().await;
+        let addon_dir = test_app_dir().join("Addons").join("ticker-groups");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        std::fs::write(addon_dir.join("addon.toml"), r##"
+            [info]
+            name = "Ticker"
+            version = "1.0"
+            groups = ["Content", "Appearance"]
+            
+            [[settings]]
+            id = "text"
+            name = "Text"
+            type = "text"
+            default = ""
+            group = "Content"
+            
+            [[settings]]
+            id = "color"
+            name = "Color"
+            type = "color"
+            default = "#ffffff"
+            group = "Appearance"
+            
+            [[settings]]
+            id = "speed"
+            name = "Speed"
+            type = "number"
+            default = 5
+            
+            [[settings]]
+            id = "font"
+            name = "Font"
+            type = "text"
+            default = ""
+            group = "Typography"
+        "##).unwrap();
+        
+        let addons = get_addons_internal(true).await.unwrap();
+        let ticker = &addons["ticker-groups"];
+        assert_eq!(ticker["info"]["groups"], serde_json::json!(["Content", "Appearance"]));
+        
+        let groups: Vec<(&str, &serde_json::Value)> = ticker["settings"].as_array().unwrap().iter()
+            .map(|s| (s["id"].as_str().unwrap(), &s["group"]))
+            .collect();
+        assert_eq!(groups, vec![
+            ("text", &serde_json::json!("Content")),
+            ("color", &serde_json::json!("Appearance")),
+            ("speed", &serde_json::Value::Null),
+            ("font", &serde_json::json!("Typography")),
+        ]);
+        
+        // An unlisted group is kept but warned about
+        let warnings = ticker["configKeyWarnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].as_str().unwrap().contains("'font' is in group 'Typography'"));
+        
+        let _ = std::fs::remove_dir_all(&addon_dir);
+    }
+    
+    #[tokio::test]
+    async fn test_set_addon_enabled_keeps_settings() {
+        let _guard = CONFIG_FILE.lock().await;
+        let dir = test_app_dir();
+        std::fs::create_dir_all(dir.join("Addons").join("ticker")).unwrap();
+        std::fs::write(
+            dir.join("Addons").join("ticker").join("addon.toml"),
+            "[info]\nname = \"Ticker\"\nversion = \"1.0.0\"\n\n[[settings]]\nid = \"text\"\nname = \"Text\"\ntype = \"text\"\n",
+        ).unwrap();
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        let base = serve(Router::new()
+            .route("/api/addons/:id/config", post({
+                let config = config.clone();
+                let app_handle = app_handle.clone();
+                move |path, body| update_addon_config_handler(config.clone(), app_handle.clone(), path, body)
+            }))
+            .route("/api/addons/:id/enabled", post({
+                let config = config.clone();
+                move |path, body| set_addon_enabled_handler(config.clone(), app_handle.clone(), path, body)
+            }))).await;
+        
+        let (status, _) = post_addon_config(&base, "ticker", r#"{"text": "Welcome", "enabled": false}"#).await;
+        assert_eq!(status, 200);
+        
+        // Backends save their own values straight to the file, as addon.set_config does
+        let mut saved = config::load_config().unwrap();
+        saved.addons.get_mut("ticker").unwrap().insert("lastFetch".to_string(), serde_json::json!(42));
+        config::save_config(&saved).unwrap();
+        
+        let post_enabled = |id: &str, body: &str| {
+            reqwest::Client::new()
+                .post(format!("{}/api/addons/{}/enabled", base, id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.to_string())
+                .send()
+        };
+        
+        let response = post_enabled("ticker", r#"{"enabled": true}"#).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        
+        let ticker = config.lock().unwrap().addons["ticker"].clone();
+        assert_eq!(ticker["enabled"], serde_json::json!(true));
+        assert_eq!(ticker["text"], serde_json::json!("Welcome"));
+        assert_eq!(ticker["lastFetch"], serde_json::json!(42));
+        
+        // The saved file matches what's in memory
+        let saved = config::load_config().unwrap();
+        assert_eq!(saved.addons["ticker"], ticker);
+        
+        let response = post_enabled("ticker", r#"{"enabled": "yes"}"#).await.unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+        
+        let response = post_enabled("missing", r#"{"enabled": true}"#).await.unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+    
+    #[tokio::test]
+    async fn test_post_config_validation() {
+        let _guard = CONFIG_FILE.lock().await;
+        test_app_dir();
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let saver = config::DebouncedSaver::spawn(config.clone(), std::time::Duration::from_millis(10), |_| {});
+        let base = serve(Router::new()
+            .route("/api/config", post({
+                let config = config.clone();
+                move |headers, body| post_config_handler(config.clone(), saver.clone(), headers, body)
+            }))).await;
+        
+        let post_config = |body: serde_json::Value| {
+            reqwest::Client::new()
+                .post(format!("{}/api/config", base))
+                .json(&body)
+                .send()
+        };
+        
+        for (field, value) in [
+            ("imageScaling", serde_json::json!("conatin")),
+            ("videoPosition", serde_json::json!("middle")),
+            ("rotation", serde_json::json!(45)),
+            ("rotation", serde_json::json!("90")),
+            ("transition", serde_json::json!("dissolve")),
+            ("transitionDurationMs", serde_json::json!(60000)),
+            ("fallbackBackground", serde_json::json!("black")),
+            ("transcodeFormat", serde_json::json!("heic")),
+            ("imageQuality", serde_json::json!(0)),
+            ("imageQuality", serde_json::json!(101)),
+            ("schedule", serde_json::json!({ "enabled": true, "startTime": "18:00", "endTime": "08:00", "days": [1] })),
+            ("schedule", serde_json::json!({ "enabled": true, "startTime": "08:00", "endTime": "18:00", "days": [9] })),
+        ] {
+            // Valid fields in the same update must not be applied either
+            let response = post_config(serde_json::json!({ "displayName": "Changed", field: value })).await.unwrap();
+            assert_eq!(response.status().as_u16(), 400, "{} = {}", field, value);
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(body["field"], field);
+            assert_ne!(config.lock().unwrap().display_name, "Changed");
+        }
+        
+        let response = post_config(serde_json::json!({
+            "imageScaling": "cover",
+            "videoPosition": "interleave",
+            "rotation": 270,
+            "transition": "fade",
+            "transitionDurationMs": 800,
+        })).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let saved = config::load_config().unwrap();
+        assert_eq!(saved.image_scaling, "cover");
+        assert_eq!(saved.video_position, "interleave");
+        assert_eq!(saved.rotation, 270);
+        assert_eq!((saved.transition.as_str(), saved.transition_duration_ms), ("fade", 800));
+    }
+    
+    #[tokio::test]
+    async fn test_rapid_config_updates_save_once() {
+        let _guard = CONFIG_FILE.lock().await;
+        let _broadcasts = ws::BROADCASTS.lock().await;
+        test_app_dir();
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        // Written by an addon backend after the shared config was loaded
+        let mut on_disk = config::load_config().unwrap();
+        on_disk.addons.insert("ticker".to_string(), HashMap::from([("text".to_string(), serde_json::json!("Welcome"))]));
+        config::save_config(&on_disk).unwrap();
+        let saves = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let saver = config::DebouncedSaver::spawn(config.clone(), std::time::Duration::from_millis(100), {
+            let saves = saves.clone();
+            move |cfg| {
+                saves.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                ws::broadcast_config_update(cfg);
+            }
+        });
+        let base = serve(Router::new()
+            .route("/api/config", post(move |headers, body| {
+                post_config_handler(config.clone(), saver.clone(), headers, body)
+            }))).await;
+        let client = reqwest::Client::new();
+        
+        let mut events = ws::subscribe();
+        for duration in 1..=10 {
+            let response = client.post(format!("{}/api/config", base))
+                .json(&serde_json::json!({ "imageDuration": duration * 1000 }))
+                .send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert_eq!(saves.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let saved = config::load_config().unwrap();
+        assert_eq!(saved.image_duration, 10_000);
+        assert_eq!(saved.addons["ticker"]["text"], serde_json::json!("Welcome"));
+        
+        let mut config_updates = 0;
+        while let Ok(event) = events.try_recv() {
+            if event.contains("config-update") {
+                config_updates += 1;
+            }
+        }
+        assert_eq!(config_updates, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_stage_update() {
+        use sha2::Digest;
+        
+        test_app_dir();
+        let base = serve(Router::new()
+            .route("/api/update", post(upload_update_handler).delete(discard_update_handler))).await;
+        
+        let upload = |name: &'static str, data: &'static [u8]| {
+            let part = reqwest::multipart::Part::bytes(data).file_name(name);
+            let form = reqwest::multipart::Form::new()
+                .text("password", "ignored")
+                .part("update", part);
+            reqwest::Client::new().post(format!("{}/api/update", base)).multipart(form).send()
+        };
+        
+        let response = upload("app.zip", b"not an update").await.unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["error"].as_str().unwrap().contains(".asar"));
+        assert!(!update::staged_update_path().unwrap().exists());
+        
+        let response = upload("app.asar", b"update contents").await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["staged"]["size"], 15);
+        let expected: String = sha2::Sha256::digest(b"update contents").iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(body["staged"]["sha256"], expected);
+        assert_eq!(std::fs::read(update::staged_update_path().unwrap()).unwrap(), b"update contents");
+        
+        // A second upload waits until the first is confirmed or discarded
+        let response = upload("other.asar", b"other contents").await.unwrap();
+        assert_eq!(response.status().as_u16(), 409);
+        
+        let response = reqwest::Client::new().delete(format!("{}/api/update", base)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert!(!update::staged_update_path().unwrap().exists());
+    }
+    
+    #[tokio::test]
+    async fn test_health_and_ready() {
+        let dir = test_app_dir();
+        for name in ["Media", "Fonts", "Addons"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        
+        // A password must not hide these from monitoring
+        let config = Arc::new(Mutex::new(config::Config {
+            password: auth::hash_password("secret"),
+            ..config::Config::default()
+        }));
+        let base = serve(Router::new()
+            .route("/api/health", get(health_handler))
+            .route("/api/ready", get(ready_handler))
+            .layer(axum::middleware::from_fn(move |request, next| {
+                auth::require_auth(config.clone(), request, next)
+            }))).await;
+        
+        let health = reqwest::get(format!("{}/api/health", base)).await.unwrap();
+        assert_eq!(health.status().as_u16(), 200);
+        let health: serde_json::Value = health.json().await.unwrap();
+        assert_eq!(health["status"], "ok");
+        assert_eq!(health["version"], env!("CARGO_PKG_VERSION"));
+        
+        let ready = reqwest::get(format!("{}/api/ready", base)).await.unwrap();
+        assert_eq!(ready.status().as_u16(), 200);
+    }
+    
+    #[tokio::test]
+    async fn test_scheduled_function_stops_when_disabled() {
+        let _guard = CONFIG_FILE.lock().await;
+        let addon_dir = test_app_dir().join("Addons").join("ticker");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        std::fs::write(addon_dir.join("addon.toml"), r#"
+            settings = []
+            
+            [info]
+            name = "Ticker"
+            version = "1.0.0"
+        "#).unwrap();
+        std::fs::write(addon_dir.join("backend.lua"), r#"
+            addon.schedule(1, "tick")
+            
+            function tick()
+                return "tock"
+            end
+        "#).unwrap();
+        
+        static TICKS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        scheduler::set_event_sink(Arc::new(|event| {
+            if event["addon_id"] == "ticker" && event["event_name"] == "tick" && event["payload"] == "tock" {
+                TICKS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }));
+        let ticks = || TICKS.load(std::sync::atomic::Ordering::SeqCst);
+        
+        let set_enabled = |enabled: bool| {
+            let mut cfg = config::load_config().unwrap();
+            cfg.addons.entry("ticker".to_string()).or_default().insert("enabled".to_string(), enabled.into());
+            config::save_config(&cfg).unwrap();
+        };
+        
+        set_enabled(true);
+        scheduler::sync_schedules().await;
+        tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+        assert!(ticks() >= 1, "timer never fired");
+        
+        set_enabled(false);
+        scheduler::sync_schedules().await;
+        let stopped_at = ticks();
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+        assert_eq!(ticks(), stopped_at);
+        
+        let _ = std::fs::remove_dir_all(&addon_dir);
+    }
+    
+    #[tokio::test]
+    async fn test_uninstall_addon_purges_config() {
+        let _guard = CONFIG_FILE.lock().await;
+        let addon_dir = test_app_dir().join("Addons").join("countdown");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        std::fs::write(addon_dir.join("addon.toml"), "settings = []\n[info]\nname = \"Countdown\"\nversion = \"1.0\"\n").unwrap();
+        
+        let mut cfg = config::Config::default();
+        cfg.addons.insert("countdown".to_string(), HashMap::from([("enabled".to_string(), serde_json::json!(true))]));
+        cfg.addons.insert("other".to_string(), HashMap::new());
+        let config = Arc::new(Mutex::new(cfg.clone()));
+        // Saved by another addon's backend, so only the file has it
+        cfg.addons.insert("other".to_string(), HashMap::from([("city".to_string(), serde_json::json!("Oslo"))]));
+        config::save_config(&cfg).unwrap();
+        
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        let base = serve(Router::new()
+            .route("/api/addons/:id", axum::routing::delete({
+                let config = config.clone();
+                move |path| uninstall_addon_handler(config.clone(), app_handle.clone(), path)
+            }))).await;
+        let client = reqwest::Client::new();
+        
+        let response = client.delete(format!("{}/api/addons/countdown", base)).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!addon_dir.exists());
+        assert!(!config.lock().unwrap().addons.contains_key("countdown"));
+        let saved = config::load_config().unwrap();
+        assert!(!saved.addons.contains_key("countdown"));
+        assert_eq!(saved.addons["other"]["city"], serde_json::json!("Oslo"));
+        
+        let again = client.delete(format!("{}/api/addons/countdown", base)).send().await.unwrap();
+        assert_eq!(again.status(), StatusCode::NOT_FOUND);
+        let escape = client.delete(format!("{}/api/addons/..%2FMedia", base)).send().await.unwrap();
+        assert_eq!(escape.status(), StatusCode::NOT_FOUND);
+        assert!(test_app_dir().join("Media").exists());
+    }
+    
+    #[tokio::test]
+    async fn test_install_addon_from_zip() {
+        use std::io::Write;
+        
+        let _guard = CONFIG_FILE.lock().await;
+        let addon_dir = test_app_dir().join("Addons").join("ticker");
+        let _ = std::fs::remove_dir_all(&addon_dir);
+        
+        let zip = |version: &str| {
+            let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+            let options = zip::write::SimpleFileOptions::default();
+            zip.start_file("ticker/addon.toml", options).unwrap();
+            write!(zip, "settings = []\n[info]\nname = \"Ticker\"\nversion = \"{}\"\n", version).unwrap();
+            zip.start_file("ticker/frontend.js", options).unwrap();
+            zip.write_all(b"console.log('ticker');").unwrap();
+            zip.finish().unwrap().into_inner()
+        };
+        
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        let base = serve(Router::new()
+            .route("/api/addons/install", post(move |query, multipart| {
+                install_addon_handler(app_handle.clone(), query, multipart)
+            }))).await;
+        let install = |data: Vec<u8>, query: &str| {
+            let form = reqwest::multipart::Form::new()
+                .part("file", reqwest::multipart::Part::bytes(data).file_name("ticker.zip"));
+            reqwest::Client::new()
+                .post(format!("{}/api/addons/install{}", base, query))
+                .multipart(form)
+                .send()
+        };
+        
+        let response = install(zip("1.0"), "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json::<serde_json::Value>().await.unwrap()["id"], "ticker");
+        assert!(addon_dir.join("frontend.js").is_file());
+        
+        assert_eq!(install(zip("2.0"), "").await.unwrap().status(), StatusCode::CONFLICT);
+        assert_eq!(install(zip("2.0"), "?overwrite=true").await.unwrap().status(), StatusCode::OK);
+        let manifest = std::fs::read_to_string(addon_dir.join("addon.toml")).unwrap();
+        assert!(manifest.contains("2.0"));
+        
+        let response = install(b"not a zip".to_vec(), "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.json::<serde_json::Value>().await.unwrap()["problems"].is_array());
+        
+        let _ = std::fs::remove_dir_all(&addon_dir);
+    }
+    
+    #[tokio::test]
+    async fn test_addon_frontend_etag() {
+        let _guard = CONFIG_FILE.lock().await;
+        let addon_dir = test_app_dir().join("Addons").join("banner");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        std::fs::write(addon_dir.join("addon.toml"), r#"
+            [[settings]]
+            id = "text"
+            type = "text"
+            name = "Text"
+            default = "Welcome"
+            
+            [info]
+            name = "Banner"
+            version = "1.0.0"
+        "#).unwrap();
+        std::fs::write(addon_dir.join("frontend.js"), "console.log(window.addonConfig.text);").unwrap();
+        
+        let base = serve(Router::new()
+            .route("/api/addons/:id/frontend.js", get(get_addon_frontend_handler))).await;
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/addons/banner/frontend.js", base);
+        
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("application/javascript"));
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert!(response.text().await.unwrap().contains("window.addonConfig"));
+        
+        let response = client.get(&url).header(header::IF_NONE_MATCH, &etag).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+        assert!(response.text().await.unwrap().is_empty());
+        
+        // A config change shows up in the script, so it has to change the ETag too
+        let mut cfg = config::load_config().unwrap();
+        cfg.addons.entry("banner".to_string()).or_default().insert("text".to_string(), "Hello".into());
+        config::save_config(&cfg).unwrap();
+        
+        let response = client.get(&url).header(header::IF_NONE_MATCH, &etag).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_ne!(response.headers()[header::ETAG].to_str().unwrap(), etag);
+        
+        let response = client.get(format!("{}/api/addons/not-installed/frontend.js", base)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        
+        let _ = std::fs::remove_dir_all(&addon_dir);
+    }
+    
+    #[tokio::test]
+    async fn test_addon_persists_config() {
+        let _guard = CONFIG_FILE.lock().await;
+        let addon_dir = test_app_dir().join("Addons").join("visitor-counter");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        std::fs::write(addon_dir.join("backend.lua"), r#"
+            function count_visit()
+                local visits = (addon.get_config("visits") or 0) + 1
+                addon.set_config("visits", visits)
+                return visits
+            end
+            
+            function enable_self()
+                addon.set_config("enabled", true)
+            end
+        "#).unwrap();
+        
+        let mut cfg = config::load_config().unwrap();
+        cfg.addons.entry("clock".to_string()).or_default().insert("format".to_string(), serde_json::json!("24h"));
+        cfg.addons.remove("visitor-counter");
+        config::save_config(&cfg).unwrap();
+        
+        let sink: addon::EventSink = Arc::new(|_| {});
+        for expected in [1, 2] {
+            let result = run_addon_function("visitor-counter".to_string(), "count_visit".to_string(), Vec::new(), sink.clone()).await;
+            assert_eq!(result.unwrap(), serde_json::json!(expected));
+        }
+        
+        let saved = config::load_config().unwrap();
+        assert_eq!(saved.addons["visitor-counter"]["visits"], serde_json::json!(2));
+        assert_eq!(saved.addons["clock"]["format"], serde_json::json!("24h"));
+        
+        assert!(run_addon_function("visitor-counter".to_string(), "enable_self".to_string(), Vec::new(), sink).await.is_err());
+        assert!(!config::load_config().unwrap().addons["visitor-counter"].contains_key("enabled"));
+    }
+    
+    #[tokio::test]
+    async fn test_addon_function_arguments() {
+        let addon_dir = test_app_dir().join("Addons").join("calculator");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        std::fs::write(addon_dir.join("backend.lua"), r#"
+            function add(a, b)
+                return a + b
+            end
+            
+            function describe(name, scores)
+                return { name = name, scores = scores, best = math.max(table.unpack(scores)), callback = print }
+            end
+            
+            function answer()
+                return 42
+            end
+        "#).unwrap();
+        
+        let sink: addon::EventSink = Arc::new(|_| {});
+        let call = |function: &str, args: Vec<serde_json::Value>| {
+            run_addon_function("calculator".to_string(), function.to_string(), args, sink.clone())
+        };
+        
+        assert_eq!(call("add", vec![serde_json::json!(2), serde_json::json!(3)]).await.unwrap(), serde_json::json!(5));
+        assert_eq!(call("add", vec![serde_json::json!(1.5), serde_json::json!(2)]).await.unwrap(), serde_json::json!(3.5));
+        assert_eq!(call("answer", Vec::new()).await.unwrap(), serde_json::json!(42));
+        
+        let described = call("describe", vec![serde_json::json!("Ada"), serde_json::json!([3, 9, 4])]).await.unwrap();
+        assert_eq!(described, serde_json::json!({ "name": "Ada", "scores": [3, 9, 4], "best": 9 }));
+        
+        assert!(call("add", vec![serde_json::json!("two"), serde_json::json!({})]).await.unwrap_err().contains("Function call failed"));
+        
+        let _ = std::fs::remove_dir_all(&addon_dir);
+    }
+    
+    #[tokio::test]
+    async fn test_addon_lists_media() {
+        let _guard = CONFIG_FILE.lock().await;
+        let dir = test_app_dir();
+        let media_dir = dir.join("Media");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("lua-list-a.png"), b"png").unwrap();
+        std::fs::write(media_dir.join("lua-list-b.mp4"), b"video").unwrap();
+        
+        let backend = r#"
+            function playlist()
+                local found = {}
+                for _, file in ipairs(addon.list_media()) do
+                    if file.name:find("^lua%-list%-") then
+                        table.insert(found, file)
+                    end
+                end
+                return found
+            end
+        "#;
+        for (id, permissions) in [("now-playing", r#"["media_read"]"#), ("nosy", "[]")] {
+            let addon_dir = dir.join("Addons").join(id);
+            std::fs::create_dir_all(&addon_dir).unwrap();
+            std::fs::write(addon_dir.join("addon.toml"), format!(
+                "permissions = {}\nsettings = []\n\n[info]\nname = \"{}\"\nversion = \"1.0\"\n",
+                permissions, id
+            )).unwrap();
+            std::fs::write(addon_dir.join("backend.lua"), backend).unwrap();
+        }
+        
+        let sink: addon::EventSink = Arc::new(|_| {});
+        let listed = run_addon_function("now-playing".to_string(), "playlist".to_string(), Vec::new(), sink.clone()).await.unwrap();
+        assert_eq!(listed, serde_json::json!([
+            { "name": "lua-list-a.png", "type": "image", "size": 3 },
+            { "name": "lua-list-b.mp4", "type": "video", "size": 5 },
+        ]));
+        
+        let err = run_addon_function("nosy".to_string(), "playlist".to_string(), Vec::new(), sink).await.unwrap_err();
+        assert!(err.contains("missing the \"media_read\" permission"), "{}", err);
+        
+        let _ = std::fs::remove_file(media_dir.join("lua-list-a.png"));
+        let _ = std::fs::remove_file(media_dir.join("lua-list-b.mp4"));
+        let _ = std::fs::remove_dir_all(dir.join("Addons").join("now-playing"));
+        let _ = std::fs::remove_dir_all(dir.join("Addons").join("nosy"));
+    }
+    
+    #[tokio::test]
+    async fn test_bulk_delete() {
+        let _guard = CONFIG_FILE.lock().await;
+        let _broadcasts = ws::BROADCASTS.lock().await;
+        let media_dir = test_app_dir().join("Media");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("bulk-a.png"), b"a").unwrap();
+        std::fs::write(media_dir.join("bulk-b.mp4"), b"b").unwrap();
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        let base = serve(Router::new()
+            .route("/api/media/bulk-delete", post(move |body| {
+                bulk_delete_media_handler(config.clone(), app_handle.clone(), body)
+            }))).await;
+        
+        let mut events = ws::subscribe();
+        let response: serde_json::Value = reqwest::Client::new()
+            .post(format!("{}/api/media/bulk-delete", base))
+            .json(&serde_json::json!({ "files": ["bulk-a.png", "missing.png", "bulk-b.mp4", "../config.json"] }))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        
+        assert_eq!(response["success"], false);
+        assert_eq!(response["deleted"], 2);
+        let outcomes: Vec<(&str, bool)> = response["results"].as_array().unwrap().iter()
+            .map(|r| (r["file"].as_str().unwrap(), r["success"].as_bool().unwrap()))
+            .collect();
+        assert_eq!(outcomes, vec![
+            ("bulk-a.png", true),
+            ("missing.png", false),
+            ("bulk-b.mp4", true),
+            ("../config.json", false),
+        ]);
+        assert!(!media_dir.join("bulk-a.png").exists());
+        assert!(!media_dir.join("bulk-b.mp4").exists());
+        
+        let mut media_updates = 0;
+        while let Ok(event) = events.try_recv() {
+            if event.contains("media-update") {
+                media_updates += 1;
+            }
+        }
+        assert_eq!(media_updates, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_locked_media_survives_deletes() {
+        let _guard = CONFIG_FILE.lock().await;
+        let _broadcasts = ws::BROADCASTS.lock().await;
+        let media_dir = test_app_dir().join("Media");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("lock-notice.png"), b"notice").unwrap();
+        std::fs::write(media_dir.join("lock-promo.png"), b"promo").unwrap();
+        
+        let config = Arc::new(Mutex::new(config::Config {
+            media_order: vec!["lock-promo.png".to_string(), "lock-notice.png".to_string()],
+            ..config::Config::default()
+        }));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        let base = serve(Router::new()
+            .route("/api/media/order", post({
+                let (config, app_handle) = (config.clone(), app_handle.clone());
+                move |body| set_media_order_handler(config, app_handle, body)
+            }))
+            .route("/api/media/bulk-delete", post({
+                let (config, app_handle) = (config.clone(), app_handle.clone());
+                move |body| bulk_delete_media_handler(config, app_handle, body)
+            }))
+            .route("/api/media/:filename", axum::routing::delete({
+                let (config, app_handle) = (config.clone(), app_handle.clone());
+                move |path| delete_media_handler(config, app_handle, path)
+            }))
+            .route("/api/media/:filename/lock", post({
+                let (config, app_handle) = (config.clone(), app_handle.clone());
+                move |path| set_media_lock_handler(config, app_handle, path, true)
+            }).delete({
+                let (config, app_handle) = (config.clone(), app_handle.clone());
+                move |path| set_media_lock_handler(config, app_handle, path, false)
+            }))).await;
+        let client = reqwest::Client::new();
+        
+        let locked = client.post(format!("{}/api/media/lock-notice.png/lock", base)).send().await.unwrap();
+        assert_eq!(locked.status(), StatusCode::OK);
+        let missing = client.post(format!("{}/api/media/nothing.png/lock", base)).send().await.unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        
+        // Reordering without the locked file keeps it where it was
+        let order: serde_json::Value = client.post(format!("{}/api/media/order", base))
+            .json(&serde_json::json!(["lock-promo.png"]))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        assert_eq!(order["order"], serde_json::json!(["lock-promo.png", "lock-notice.png"]));
+        
+        let response: serde_json::Value = client.post(format!("{}/api/media/bulk-delete", base))
+            .json(&serde_json::json!({ "files": ["lock-notice.png", "lock-promo.png"] }))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        assert_eq!(response["deleted"], 1);
+        assert_eq!(response["results"][0]["success"], false);
+        assert!(response["results"][0]["error"].as_str().unwrap().contains("is locked"));
+        assert!(media_dir.join("lock-notice.png").exists());
+        assert!(!media_dir.join("lock-promo.png").exists());
+        
+        let refused = client.delete(format!("{}/api/media/lock-notice.png", base)).send().await.unwrap();
+        assert_eq!(refused.status(), StatusCode::CONFLICT);
+        assert!(media_dir.join("lock-notice.png").exists());
+        
+        let unlocked = client.delete(format!("{}/api/media/lock-notice.png/lock", base)).send().await.unwrap();
+        assert_eq!(unlocked.status(), StatusCode::OK);
+        let deleted = client.delete(format!("{}/api/media/lock-notice.png", base)).send().await.unwrap();
+        assert_eq!(deleted.status(), StatusCode::OK);
+        assert!(!media_dir.join("lock-notice.png").exists());
+        assert!(config.lock().unwrap().locked_media.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_media_tags() {
+        let _guard = CONFIG_FILE.lock().await;
+        let _broadcasts = ws::BROADCASTS.lock().await;
+        let media_dir = test_app_dir().join("Media");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("tagged-a.png"), b"a").unwrap();
+        std::fs::write(media_dir.join("tagged-b.png"), b"b").unwrap();
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        let base = serve(Router::new()
+            .route("/api/media", get({
+                let config = config.clone();
+                move |query, filter| get_media_handler(config, query, filter)
+            }))
+            .route("/api/media/:filename", axum::routing::delete({
+                let config = config.clone();
+                let app_handle = app_handle.clone();
+                move |path| delete_media_handler(config, app_handle, path)
+            }))
+            .route("/api/media/:filename/tags", post({
+                let config = config.clone();
+                let app_handle = app_handle.clone();
+                move |path, body| set_media_tags_handler(config, app_handle, path, body)
+            }))).await;
+        let client = reqwest::Client::new();
+        
+        let response: serde_json::Value = client.post(format!("{}/api/media/tagged-a.png/tags", base))
+            .json(&serde_json::json!({ "tags": [" promos ", "menus", "promos", ""] }))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        assert_eq!(response["tags"], serde_json::json!(["menus", "promos"]));
+        
+        let missing = client.post(format!("{}/api/media/missing.png/tags", base))
+            .json(&serde_json::json!({ "tags": ["promos"] }))
+            .send().await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+        
+        let page: serde_json::Value = client.get(format!("{}/api/media?tag=promos", base))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        let names: Vec<&str> = page["items"].as_array().unwrap().iter()
+            .map(|f| f["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["tagged-a.png"]);
+        assert_eq!(page["items"][0]["tags"], serde_json::json!(["menus", "promos"]));
+        
+        client.delete(format!("{}/api/media/tagged-a.png", base)).send().await.unwrap();
+        assert!(config.lock().unwrap().tags.is_empty());
+        
+        let _ = std::fs::remove_file(media_dir.join("tagged-b.png"));
+    }
+    
+    #[tokio::test]
+    async fn test_config_export_import_round_trip() {
+        let _guard = CONFIG_FILE.lock().await;
+        test_app_dir();
+        
+        let mut settings = HashMap::new();
+        settings.insert("enabled".to_string(), serde_json::json!(true));
+        settings.insert("format".to_string(), serde_json::json!("24h"));
+        let mut source = config::Config {
+            display_name: "Lobby".to_string(),
+            image_scaling: "cover".to_string(),
+            rotation: 90,
+            password: auth::hash_password("hunter2"),
+            ..config::Config::default()
+        };
+        source.addons.insert("clock".to_string(), settings);
+        
+        let source = Arc::new(Mutex::new(source));
+        let target = Arc::new(Mutex::new(config::Config {
+            password: auth::hash_password("target-password"),
+            ..config::Config::default()
+        }));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        
+        let source_base = serve(Router::new()
+            .route("/api/config/export", get({
+                let source = source.clone();
+                move |headers, query| export_config_handler(source.clone(), headers, query)
+            }))).await;
+        let target_base = serve(Router::new()
+            .route("/api/config/import", post({
+                let target = target.clone();
+                move |body| import_config_handler(target.clone(), app_handle.clone(), body)
+            }))).await;
+        
+        let client = reqwest::Client::new();
+        let import = |body: String| client.post(format!("{}/api/config/import", target_base)).body(body).send();
+        
+        // Redacted by default
+        let redacted = client.get(format!("{}/api/config/export", source_base)).send().await.unwrap();
+        assert_eq!(redacted.status().as_u16(), 200);
+        let redacted = redacted.text().await.unwrap();
+        assert!(!redacted.contains(&source.lock().unwrap().password));
+        
+        // Secrets need the password
+        let response = client.get(format!("{}/api/config/export?includeSecrets=true", source_base)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+        let full = client.get(format!("{}/api/config/export?includeSecrets=true", source_base))
+            .bearer_auth("hunter2")
+            .send().await.unwrap()
+            .text().await.unwrap();
+        
+        assert_eq!(import(full).await.unwrap().status().as_u16(), 200);
+        assert_eq!(
+            serde_json::to_value(&*target.lock().unwrap()).unwrap(),
+            serde_json::to_value(&*source.lock().unwrap()).unwrap()
+        );
+        assert_eq!(config::load_config().unwrap().display_name, "Lobby");
+        
+        // Importing a redacted export keeps the target's own password
+        let before = target.lock().unwrap().password.clone();
+        assert_eq!(import(redacted).await.unwrap().status().as_u16(), 200);
+        assert_eq!(target.lock().unwrap().password, before);
+        
+        let mut newer: serde_json::Value = serde_json::to_value(config::Config::default()).unwrap();
+        newer["schemaVersion"] = serde_json::json!(config::CURRENT_SCHEMA_VERSION + 1);
+        let response = import(newer.to_string()).await.unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(target.lock().unwrap().display_name, "Lobby");
+    }
+    
+    #[tokio::test]
+    async fn test_media_list_is_compressed() {
+        let media_dir = test_app_dir().join("Media");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        for i in 0..20 {
+            std::fs::write(media_dir.join(format!("compression-test-slide-{:02}.png", i)), b"").unwrap();
+        }
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let base = serve(Router::new()
+            .route("/api/media", get(move |query, filter| get_media_handler(config.clone(), query, filter)))
+            .route("/photo.png", get(|| async {
+                ([(header::CONTENT_TYPE, "image/png")], vec![0u8; 4096])
+            }))
+            .layer(compression_layer())).await;
+        
+        // reqwest is built without gzip support, so the body comes back as sent
+        let client = reqwest::Client::new();
+        let response = client.get(format!("{}/api/media", base))
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .send().await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        
+        // Already-compressed formats go out untouched
+        let response = client.get(format!("{}/photo.png", base))
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .send().await.unwrap();
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_upload_rejects_oversized_file() {
+        let dir = test_app_dir();
+        let config = Arc::new(Mutex::new(config::Config {
+            max_upload_bytes: 1024,
+            ..config::Config::default()
+        }));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        
+        let base = serve(Router::new()
+            .route("/api/media/upload", post(move |query, multipart| {
+                upload_media_handler(config.clone(), app_handle.clone(), query, multipart)
+            }))).await;
+        
+        let small = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0\x1f\x15\xc4\x89\0\0\0\0IEND\xae\x42\x60\x82".to_vec();
+        let mut big = small.clone();
+        big.resize(4096, 0);
+        
+        let form = reqwest::multipart::Form::new()
+            .part("file", reqwest::multipart::Part::bytes(small).file_name("upload-small.png"))
+            .part("file", reqwest::multipart::Part::bytes(big).file_name("upload-big.png"));
+        let body: serde_json::Value = reqwest::Client::new()
+            .post(format!("{}/api/media/upload?collection=upload-test", base))
+            .multipart(form)
+            .send().await.unwrap()
+            .json().await.unwrap();
+        
+        assert_eq!(body["success"], serde_json::json!(false));
+        assert_eq!(body["files"], serde_json::json!(1));
+        
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["file"], "upload-small.png");
+        assert_eq!(results[0]["success"], serde_json::json!(true));
+        assert_eq!(results[1]["file"], "upload-big.png");
+        assert_eq!(results[1]["tooLarge"], serde_json::json!(true));
+        
+        let collection = dir.join("Media").join("upload-test");
+        assert!(collection.join("upload-small.png").exists());
+        assert!(!collection.join("upload-big.png").exists());
+        assert!(!collection.join(".upload-big.png.upload").exists());
+    }
+    
+    #[tokio::test]
+    async fn test_upload_respects_total_media_limit() {
+        let dir = test_app_dir();
+        let config = Arc::new(Mutex::new(config::Config {
+            max_total_media_bytes: 1024 * 1024 * 1024,
+            ..config::Config::default()
+        }));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        
+        let base = serve(Router::new()
+            .route("/api/media/upload", post({
+                let config = config.clone();
+                move |query, multipart| upload_media_handler(config.clone(), app_handle.clone(), query, multipart)
+            }))).await;
+        let upload = |name: &'static str, data: &'static [u8]| reqwest::Client::new()
+            .post(format!("{}/api/media/upload?collection=quota-test", base))
+            .multipart(reqwest::multipart::Form::new()
+                .part("file", reqwest::multipart::Part::bytes(data).file_name(name)))
+            .send();
+        
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0\x1f\x15\xc4\x89\0\0\0\0IEND\xae\x42\x60\x82";
+        let under = upload("under.png", png).await.unwrap();
+        assert_eq!(under.status(), StatusCode::OK);
+        
+        // Whatever else the tests stored, a single byte is always too little
+        config.lock().unwrap().max_total_media_bytes = 1;
+        let over = upload("over.svg", b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>").await.unwrap();
+        assert_eq!(over.status(), StatusCode::INSUFFICIENT_STORAGE);
+        let body: serde_json::Value = over.json().await.unwrap();
+        let error = body["results"][0]["error"].as_str().unwrap();
+        assert!(error.contains("Not enough media storage"), "{}", error);
+        
+        let collection = dir.join("Media").join("quota-test");
+        assert!(collection.join("under.png").exists());
+        assert!(!collection.join("over.svg").exists());
+        assert!(!collection.join(".over.svg.upload").exists());
+    }
+    
+    #[tokio::test]
+    async fn test_resumable_upload_in_two_pieces() {
+        let dir = test_app_dir();
+        let config = Arc::new(Mutex::new(config::Config {
+            max_upload_bytes: 1024,
+            ..config::Config::default()
+        }));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        
+        let base = serve(Router::new()
+            .route("/api/media/upload/init", post({
+                let config = config.clone();
+                move |query, body| init_resumable_upload_handler(config, query, body)
+            }))
+            .route("/api/media/upload/:id", axum::routing::patch(move |path, headers, body| {
+                append_resumable_upload_handler(config.clone(), app_handle.clone(), path, headers, body)
+            }).head(resumable_upload_offset_handler))).await;
+        let client = reqwest::Client::new();
+        
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0\x1f\x15\xc4\x89\0\0\0\0IEND\xae\x42\x60\x82".to_vec();
+        let init = |name: &str, size: usize| client
+            .post(format!("{}/api/media/upload/init?collection=resume-test", base))
+            .json(&serde_json::json!({ "filename": name, "size": size }))
+            .send();
+        
+        let too_big = init("huge.mp4", 4096).await.unwrap();
+        assert_eq!(too_big.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        
+        let escape = init("../resumed.png", png.len()).await.unwrap();
+        assert_eq!(escape.status(), StatusCode::BAD_REQUEST);
+        
+        let created = init("resumed.png", png.len()).await.unwrap();
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let created: serde_json::Value = created.json().await.unwrap();
+        let url = format!("{}/api/media/upload/{}", base, created["id"].as_str().unwrap());
+        
+        // The connection "drops" after the first piece
+        let first = client.patch(&url)
+            .header(UPLOAD_OFFSET, "0")
+            .body(png[..20].to_vec())
+            .send().await.unwrap();
+        assert_eq!(first.status(), StatusCode::NO_CONTENT);
+        
+        let head = client.head(&url).send().await.unwrap();
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(head.headers()["upload-length"], png.len().to_string().as_str());
+        let offset = head.headers()[UPLOAD_OFFSET].to_str().unwrap().to_string();
+        assert_eq!(offset, "20");
+        
+        // Resending from the start would duplicate bytes
+        let stale = client.patch(&url)
+            .header(UPLOAD_OFFSET, "0")
+            .body(png.clone())
+            .send().await.unwrap();
+        assert_eq!(stale.status(), StatusCode::CONFLICT);
+        let stale: serde_json::Value = stale.json().await.unwrap();
+        assert_eq!(stale["offset"], 20);
+        
+        let second = client.patch(&url)
+            .header(UPLOAD_OFFSET, &offset)
+            .body(png[20..].to_vec())
+            .send().await.unwrap();
+        assert_eq!(second.status(), StatusCode::OK);
+        let second: serde_json::Value = second.json().await.unwrap();
+        assert_eq!(second["storedAs"], "resumed.png");
+        
+        let collection = dir.join("Media").join("resume-test");
+        assert_eq!(std::fs::read(collection.join("resumed.png")).unwrap(), png);
+        assert_eq!(client.head(&url).send().await.unwrap().status(), StatusCode::NOT_FOUND);
+        
+        let leftovers = std::fs::read_dir(dir.join("Media").join(".uploads")).unwrap()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(created["id"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+        
+        // A file cut short still fails the upload checks once all announced bytes are in
+        let truncated = &png[..png.len() - 12];
+        let created = init("cut.png", truncated.len()).await.unwrap();
+        let created: serde_json::Value = created.json().await.unwrap();
+        let cut = client.patch(format!("{}/api/media/upload/{}", base, created["id"].as_str().unwrap()))
+            .header(UPLOAD_OFFSET, "0")
+            .body(truncated.to_vec())
+            .send().await.unwrap();
+        assert_eq!(cut.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let cut: serde_json::Value = cut.json().await.unwrap();
+        assert!(cut["error"].as_str().unwrap().contains("truncated"), "{}", cut);
+        assert!(!collection.join("cut.png").exists());
+    }
+    
+    #[tokio::test]
+    async fn test_raw_media_range_requests() {
+        let collection = test_app_dir().join("Media").join("range-test");
+        std::fs::create_dir_all(&collection).unwrap();
+        let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        std::fs::write(collection.join("clip.mp4"), &data).unwrap();
+        
+        let base = serve(Router::new().route("/api/media/raw/:filename", get(get_raw_media_handler))).await;
+        let url = format!("{}/api/media/raw/clip.mp4?collection=range-test", base);
+        let client = reqwest::Client::new();
+        
+        let full = client.get(&url).send().await.unwrap();
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(full.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(full.headers()[header::CONTENT_TYPE], "video/mp4");
+        assert_eq!(full.bytes().await.unwrap(), data);
+        
+        let partial = client.get(&url).header(header::RANGE, "bytes=100-199").send().await.unwrap();
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(partial.headers()[header::CONTENT_RANGE], "bytes 100-199/1000");
+        assert_eq!(partial.bytes().await.unwrap(), data[100..200]);
+        
+        let tail = client.get(&url).header(header::RANGE, "bytes=-10").send().await.unwrap();
+        assert_eq!(tail.headers()[header::CONTENT_RANGE], "bytes 990-999/1000");
+        assert_eq!(tail.bytes().await.unwrap(), data[990..]);
+        
+        let past_end = client.get(&url).header(header::RANGE, "bytes=1000-").send().await.unwrap();
+        assert_eq!(past_end.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(past_end.headers()[header::CONTENT_RANGE], "bytes */1000");
+        
+        let head = client.head(&url).header(header::RANGE, "bytes=0-9").send().await.unwrap();
+        assert_eq!(head.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(head.headers()[header::CONTENT_LENGTH], "10");
+        
+        let escape = client.get(format!("{}/api/media/raw/..%2Fconfig.json", base)).send().await.unwrap();
+        assert_eq!(escape.status(), StatusCode::BAD_REQUEST);
+        let missing = client.get(format!("{}/api/media/raw/nope.mp4", base)).send().await.unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+    
+    #[tokio::test]
+    async fn test_log_files() {
+        let logs_dir = test_app_dir().join("Logs");
+        std::fs::create_dir_all(&logs_dir).unwrap();
+        std::fs::write(logs_dir.join("server.2024-01-01.log"), "old entry\n").unwrap();
+        std::fs::write(logs_dir.join("server.2024-01-02.log"), "first line\nlast line\n").unwrap();
+        std::fs::write(logs_dir.join("notes.txt"), "not a log").unwrap();
+        
+        let config = Arc::new(Mutex::new(config::Config {
+            password: auth::hash_password("hunter2"),
+            ..config::Config::default()
+        }));
+        let base = serve(Router::new()
+            .route("/api/logs", get({
+                let config = config.clone();
+                move |headers| list_logs_handler(config, headers)
+            }))
+            .route("/api/logs/:name", get({
+                let config = config.clone();
+                move |path, headers| get_log_handler(config, path, headers)
+            }))).await;
+        let client = reqwest::Client::new();
+        
+        let anonymous = client.get(format!("{}/api/logs", base)).send().await.unwrap();
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+        
+        let logs: serde_json::Value = client.get(format!("{}/api/logs", base))
+            .bearer_auth("hunter2")
+            .send().await.unwrap()
+            .json().await.unwrap();
+        let listed: Vec<(&str, u64)> = logs.as_array().unwrap().iter()
+            .map(|log| (log["name"].as_str().unwrap(), log["size"].as_u64().unwrap()))
+            .filter(|(name, _)| name.starts_with("server.2024-01-0"))
+            .collect();
+        assert_eq!(listed, vec![("server.2024-01-02.log", 21), ("server.2024-01-01.log", 10)]);
+        assert!(logs.as_array().unwrap().iter().all(|log| log["name"] != "notes.txt"));
+        
+        let url = format!("{}/api/logs/server.2024-01-02.log", base);
+        let full = client.get(&url).bearer_auth("hunter2").send().await.unwrap();
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(full.text().await.unwrap(), "first line\nlast line\n");
+        
+        let tail = client.get(&url).bearer_auth("hunter2").header(header::RANGE, "bytes=-10").send().await.unwrap();
+        assert_eq!(tail.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(tail.text().await.unwrap(), "last line\n");
+        
+        for escape in ["..%2Fconfig.json", "notes.txt"] {
+            let response = client.get(format!("{}/api/logs/{}", base, escape)).bearer_auth("hunter2").send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", escape);
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_media_download_headers() {
+        let collection = test_app_dir().join("Media").join("download-test");
+        std::fs::create_dir_all(&collection).unwrap();
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        std::fs::write(collection.join("Summer Menü \"v2\".png"), png).unwrap();
+        std::fs::write(collection.join("poster.bin"), png).unwrap();
+        
+        let base = serve(Router::new().route("/api/media/:filename/download", get(download_media_handler))).await;
+        let client = reqwest::Client::new();
+        
+        let response = client.get(format!("{}/api/media/Summer%20Men%C3%BC%20%22v2%22.png/download?collection=download-test", base))
+            .send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"Summer Men_ _v2_.png\"; filename*=UTF-8''Summer%20Men%C3%BC%20%22v2%22.png"
+        );
+        assert_eq!(response.bytes().await.unwrap(), png.as_slice());
+        
+        // An extension we don't know falls back to the file's contents
+        let response = client.get(format!("{}/api/media/poster.bin/download?collection=download-test", base))
+            .send().await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(response.headers()[header::CONTENT_DISPOSITION], "attachment; filename=\"poster.bin\"; filename*=UTF-8''poster.bin");
+        
+        let escape = client.get(format!("{}/api/media/..%2Fconfig.json/download", base)).send().await.unwrap();
+        assert_eq!(escape.status(), StatusCode::BAD_REQUEST);
+        
+        let _ = std::fs::remove_dir_all(&collection);
+    }
+    
+    #[tokio::test]
+    async fn test_media_metadata() {
+        let collection = test_app_dir().join("Media").join("metadata-test");
+        std::fs::create_dir_all(&collection).unwrap();
+        image::RgbaImage::new(40, 30).save(collection.join("badge.png")).unwrap();
+        
+        let base = serve(Router::new().route("/api/media/:filename/metadata", get(media_metadata_handler))).await;
+        let client = reqwest::Client::new();
+        
+        let metadata: serde_json::Value = client.get(format!("{}/api/media/badge.png/metadata?collection=metadata-test", base))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        assert_eq!(metadata["type"], "image");
+        assert_eq!((metadata["width"].as_u64(), metadata["height"].as_u64()), (Some(40), Some(30)));
+        assert_eq!(metadata["format"], "png");
+        assert_eq!(metadata["hasAlpha"], true);
+        
+        // A changed file is probed again rather than served from the cache
+        image::RgbImage::new(20, 10).save(collection.join("badge.png")).unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options().write(true).open(collection.join("badge.png")).unwrap().set_modified(later).unwrap();
+        let metadata: serde_json::Value = client.get(format!("{}/api/media/badge.png/metadata?collection=metadata-test", base))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        assert_eq!(metadata["width"], 20);
+        assert_eq!(metadata["hasAlpha"], false);
+        
+        let missing = client.get(format!("{}/api/media/gone.png/metadata?collection=metadata-test", base)).send().await.unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        let escape = client.get(format!("{}/api/media/..%2Fconfig.json/metadata", base)).send().await.unwrap();
+        assert_eq!(escape.status(), StatusCode::BAD_REQUEST);
+        
+        let _ = std::fs::remove_dir_all(&collection);
+    }
+    
+    #[tokio::test]
+    async fn test_font_conditional_requests() {
+        let fonts_dir = test_app_dir().join("Fonts");
+        std::fs::create_dir_all(&fonts_dir).unwrap();
+        let font = include_bytes!("../tests/fixtures/FixtureSans-Bold.ttf");
+        std::fs::write(fonts_dir.join("CacheTest.ttf"), font).unwrap();
+        
+        let base = serve(Router::new().route("/api/fonts/:name", get(get_font_handler))).await;
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/fonts/CacheTest.ttf", base);
+        
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "font/ttf");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=86400");
+        let etag = response.headers()[header::ETAG].clone();
+        let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+        assert!(last_modified.to_str().unwrap().ends_with(" GMT"));
+        assert_eq!(response.bytes().await.unwrap(), font.as_slice());
+        
+        let response = client.get(&url).header(header::IF_NONE_MATCH, etag.clone()).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+        assert!(response.bytes().await.unwrap().is_empty());
+        
+        let response = client.get(&url).header(header::IF_MODIFIED_SINCE, last_modified.clone()).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        
+        // A stale ETag wins over a current date
+        let response = client.get(&url)
+            .header(header::IF_NONE_MATCH, "\"0-0\"")
+            .header(header::IF_MODIFIED_SINCE, last_modified)
+            .send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        
+        let escape = client.get(format!("{}/api/fonts/..%2Fconfig.json", base)).send().await.unwrap();
+        assert_eq!(escape.status(), StatusCode::BAD_REQUEST);
+        let missing = client.get(format!("{}/api/fonts/Missing.ttf", base)).send().await.unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        
+        let _ = std::fs::remove_file(fonts_dir.join("CacheTest.ttf"));
+    }
+    
+    #[tokio::test]
+    async fn test_upload_deduplicates_identical_files() {
+        let dir = test_app_dir();
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        
+        let base = serve(Router::new()
+            .route("/api/media/upload", post(move |query, multipart| {
+                upload_media_handler(config.clone(), app_handle.clone(), query, multipart)
+            }))).await;
+        
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0\x1f\x15\xc4\x89\0\0\0\0IEND\xae\x42\x60\x82".to_vec();
+        let upload = |name: &'static str| {
+            let form = reqwest::multipart::Form::new()
+                .part("file", reqwest::multipart::Part::bytes(png.clone()).file_name(name));
+            reqwest::Client::new()
+                .post(format!("{}/api/media/upload?collection=dedup-test", base))
+                .multipart(form)
+                .send()
+        };
+        
+        let first: serde_json::Value = upload("original.png").await.unwrap().json().await.unwrap();
+        assert_eq!(first["results"][0]["storedAs"], "original.png");
+        assert_eq!(first["results"][0]["deduplicated"], serde_json::json!(false));
+        
+        let second: serde_json::Value = upload("copy.png").await.unwrap().json().await.unwrap();
+        assert_eq!(second["results"][0]["success"], serde_json::json!(true));
+        assert_eq!(second["results"][0]["storedAs"], "original.png");
+        assert_eq!(second["results"][0]["deduplicated"], serde_json::json!(true));
+        
+        let collection = dir.join("Media").join("dedup-test");
+        let files: Vec<String> = std::fs::read_dir(&collection).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        assert_eq!(files, vec!["original.png"]);
+    }
+    
+    #[tokio::test]
+    async fn test_upload_transcodes_jpeg_to_webp() {
+        let dir = test_app_dir();
+        let config = Arc::new(Mutex::new(config::Config {
+            auto_transcode: true,
+            transcode_format: "webp".to_string(),
+            ..config::Config::default()
+        }));
+        let app_handle: Arc<Mutex<Option<AppHandle>>> = Arc::new(Mutex::new(None));
+        
+        let base = serve(Router::new()
+            .route("/api/media/upload", post(move |query, multipart| {
+                upload_media_handler(config.clone(), app_handle.clone(), query, multipart)
+            }))).await;
+        
+        let source = image::RgbImage::from_fn(256, 256, |x, y| image::Rgb([x as u8, y as u8, 128]));
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 95)
+            .encode_image(&source)
+            .unwrap();
+        let jpeg_len = jpeg.len() as u64;
+        
+        let form = reqwest::multipart::Form::new()
+            .part("file", reqwest::multipart::Part::bytes(jpeg).file_name("gradient.jpg"));
+        let body: serde_json::Value = reqwest::Client::new()
+            .post(format!("{}/api/media/upload?collection=transcode-test", base))
+            .multipart(form)
+            .send().await.unwrap()
+            .json().await.unwrap();
+        
+        assert_eq!(body["results"][0]["file"], "gradient.jpg");
+        assert_eq!(body["results"][0]["storedAs"], "gradient.webp");
+        
+        let collection = dir.join("Media").join("transcode-test");
+        let webp = collection.join("gradient.webp");
+        assert!(!collection.join("gradient.jpg").exists());
+        assert!(!collection.join(".gradient.jpg.upload").exists());
+        assert!(std::fs::metadata(&webp).unwrap().len() < jpeg_len);
+        assert_eq!(image::image_dimensions(&webp).unwrap(), (256, 256));
+    }
+    
+    #[tokio::test]
+    async fn test_cors_allowlist() {
+        let app = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .layer(cors_layer(&["https://signage.example.com".to_string()]));
+        let base = serve(app).await;
+        let client = reqwest::Client::new();
+        
+        let allowed = client.get(format!("{}/ping", base))
+            .header(header::ORIGIN, "https://signage.example.com")
+            .send().await.unwrap();
+        assert_eq!(allowed.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://signage.example.com");
+        
+        let denied = client.get(format!("{}/ping", base))
+            .header(header::ORIGIN, "https://evil.example.com")
+            .send().await.unwrap();
+        assert!(denied.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        
+        // Without an allowlist any origin goes, as before
+        let base = serve(Router::new().route("/ping", get(|| async { "pong" })).layer(cors_layer(&[]))).await;
+        let open = client.get(format!("{}/ping", base))
+            .header(header::ORIGIN, "https://evil.example.com")
+            .send().await.unwrap();
+        assert_eq!(open.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        
+        assert!(validate_config_updates(&serde_json::json!({ "allowedOrigins": ["https://ok.example"] })).is_ok());
+        assert!(validate_config_updates(&serde_json::json!({ "allowedOrigins": ["bad\norigin"] })).is_err());
+        assert!(validate_config_updates(&serde_json::json!({ "allowedOrigins": "https://ok.example" })).is_err());
+    }
+    
+    #[test]
+    fn test_find_available_port_skips_taken_port() {
+        let taken = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+        
+        let chosen = find_available_port(port).unwrap();
+        assert_ne!(chosen, port);
+        assert!(chosen > port && chosen <= port.saturating_add(PORT_SEARCH_RANGE));
+        
+        drop(taken);
+        assert_eq!(find_available_port(port), Some(port));
+    }
+    
+    #[tokio::test]
+    async fn test_serves_on_every_bind_address() {
+        assert!(bind_addrs(&["127.0.0.1".to_string(), "not-an-ip".to_string()], 0).is_err());
+        assert_eq!(bind_addrs(&[], 3006).unwrap(), vec![
+            SocketAddr::from(([0, 0, 0, 0], 3006)),
+            SocketAddr::from((std::net::Ipv6Addr::UNSPECIFIED, 3006)),
+        ]);
+        
+        let addrs = bind_addrs(&["127.0.0.1".to_string(), " 127.0.0.2 ".to_string(), "::1".to_string()], 0).unwrap();
+        let listeners = bind_listeners(&addrs).await.unwrap();
+        let bound: Vec<SocketAddr> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+        
+        let app = Router::new().route("/ping", get(|| async { "pong" }));
+        let _server = serve_with_shutdown(listeners, app);
+        
+        for addr in bound {
+            let body = reqwest::get(format!("http://{}/ping", addr)).await.unwrap().text().await.unwrap();
+            assert_eq!(body, "pong", "no answer on {}", addr);
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_supervisor_restarts_failed_server() {
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let runs = std::sync::atomic::AtomicUsize::new(0);
+        
+        let supervised = supervise("Test server", shutdown_rx, std::time::Duration::from_millis(10), || {
+            let run = runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            let shutdown_tx = &shutdown_tx;
+            async move {
+                match run {
+                    1 => Err("listener closed"),
+                    2 => Ok(()),
+                    // Third time round, stop as the exit handler would
+                    _ => {
+                        let _ = shutdown_tx.send(true);
+                        Ok(())
+                    }
+                }
+            }
+        });
+        tokio::time::timeout(std::time::Duration::from_secs(5), supervised).await
+            .expect("supervisor kept running after shutdown");
+        
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+    
+    #[tokio::test]
+    async fn test_graceful_shutdown() {
+        let app = Router::new().route("/ping", get(|| async { "pong" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        
+        let server = serve_with_shutdown(vec![listener], app);
+        let body = reqwest::get(format!("{}/ping", base)).await.unwrap().text().await.unwrap();
+        assert_eq!(body, "pong");
+        
+        // shutdown() blocks, as it does in the exit handler
+        let stopped = tokio::task::spawn_blocking(move || server.shutdown()).await.unwrap();
+        assert!(stopped);
+        assert!(reqwest::get(format!("{}/ping", base)).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_reshuffle_advances_once_per_cycle() {
+        let _guard = CONFIG_FILE.lock().await;
+        let _broadcasts = ws::BROADCASTS.lock().await;
+        test_app_dir();
+        
+        let config = Mutex::new(config::Config {
+            shuffle: true,
+            shuffle_seed: 7,
+            ..config::Config::default()
+        });
+        
+        let next = reshuffle(&config, None, 7).unwrap();
+        assert_eq!(next, playback::next_seed(7));
+        
+        // Another display finishing the same cycle picks up the order already chosen
+        assert_eq!(reshuffle(&config, None, 7).unwrap(), next);
+        assert_eq!(config.lock().unwrap().shuffle_seed, next);
+    }
+    
+    #[tokio::test]
+    async fn test_change_password_reports_errors() {
+        let _guard = CONFIG_FILE.lock().await;
+        test_app_dir();
+        let saved = config::load_config().unwrap_or_default();
+        
+        let config = Arc::new(Mutex::new(config::Config::default()));
+        let base = serve(Router::new().route("/api/password", post({
+            let config = config.clone();
+            move |body| change_password_handler(config, body)
+        }))).await;
+        let client = reqwest::Client::new();
+        
+        let missing = client.post(format!("{}/api/password", base))
+            .json(&serde_json::json!({ "password": "" }))
+            .send().await.unwrap();
+        assert_eq!(missing.status().as_u16(), 400);
+        let body: serde_json::Value = missing.json().await.unwrap();
+        assert!(body["error"].is_string());
+        
+        let changed = client.post(format!("{}/api/password", base))
+            .json(&serde_json::json!({ "newPassword": "hunter2" }))
+            .send().await.unwrap();
+        assert_eq!(changed.status().as_u16(), 200);
+        assert!(auth::verify_password(&config.lock().unwrap().password, "hunter2"));
+        
+        config::save_config(&saved).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_bundle_export_requires_password() {
+        test_app_dir();
+        let config = Arc::new(Mutex::new(config::Config {
+            password: auth::hash_password("secret"),
+            ..config::Config::default()
+        }));
+        let base = serve(Router::new()
+            .route("/api/bundle/export", get(move |headers| export_bundle_handler(config.clone(), headers)))).await;
+        let client = reqwest::Client::new();
+        
+        let refused = client.get(format!("{}/api/bundle/export", base)).send().await.unwrap();
+        assert_eq!(refused.status().as_u16(), 401);
+        
+        let exported = client.get(format!("{}/api/bundle/export", base))
+            .bearer_auth("secret")
+            .send().await.unwrap();
+        assert_eq!(exported.status().as_u16(), 200);
+        let length = exported.content_length().unwrap();
+        let data = exported.bytes().await.unwrap();
+        assert_eq!(data.len() as u64, length);
+        assert!(data.starts_with(b"PK"));
+    }
+    
+    #[tokio::test]
+    async fn test_sync_sends_peer_password() {
+        let media_dir = test_app_dir().join("Media");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("synced.txt"), "hello").unwrap();
+        
+        let peer_config = Arc::new(Mutex::new(config::Config {
+            password: auth::hash_password("secret"),
+            ..config::Config::default()
+        }));
+        let base = serve(Router::new()
+            .route("/api/media/upload", post(|| async { Json(serde_json::json!({ "success": true })) }))
+            .layer(axum::middleware::from_fn(move |request, next| {
+                auth::require_auth(peer_config.clone(), request, next)
+            }))).await;
+        let address: SocketAddr = base.trim_start_matches("http://").parse().unwrap();
+        let peer = network::Peer {
+            id: "peer".to_string(),
+            name: "Peer".to_string(),
+            ip: address.ip(),
+            port: address.port(),
+            manual: true,
+            online: true,
+            last_seen: None,
+            alias: None,
+            order: None,
+        };
+        let files = ["synced.txt".to_string()];
+        
+        let refused = network::sync_media_to_peer(&peer, &files, None).await.unwrap();
+        assert_eq!(refused.succeeded, 0);
+        assert!(refused.failed[0].error.contains("401"));
+        
+        let synced = network::sync_media_to_peer(&peer, &files, Some("secret")).await.unwrap();
+        assert_eq!(synced.succeeded, 1);
+        assert!(synced.failed.is_empty());
+    }
+}
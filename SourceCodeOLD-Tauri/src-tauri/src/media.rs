@@ -1,5 +1,27 @@
+use crate::error::AppError;
+use image::codecs::jpeg::JpegEncoder;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+pub mod index;
+pub mod metadata;
+pub mod probe;
+pub mod quota;
+pub mod resumable;
+pub mod thumbnail;
+pub mod video;
+
+pub use thumbnail::generate_thumbnail;
+
+/// Quality used when a photo has to be re-encoded to fix its orientation
+const REENCODE_JPEG_QUALITY: u8 = 92;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,28 +32,164 @@ pub struct MediaFile {
     pub file_type: String,
     pub size: u64,
     pub modified: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Length of a video, when its container says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
-pub fn get_media_dir() -> Result<std::path::PathBuf, String> {
-    crate::paths::get_media_dir()
+pub fn get_media_dir() -> Result<std::path::PathBuf, AppError> {
+    crate::paths::get_media_dir(None)
 }
 
-pub async fn get_files() -> Result<Vec<MediaFile>, String> {
-    let media_dir = get_media_dir()?;
+/// List media files in playlist order; files missing from `order` follow alphabetically
+pub async fn get_files(collection: Option<&str>, order: &[String]) -> Result<Vec<MediaFile>, AppError> {
+    get_files_in(&crate::paths::get_media_dir(collection)?, order).await
+}
+
+/// Narrowing, sorting and paging for a media listing; every field is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MediaFilter {
+    /// "image" or "video"
+    #[serde(rename = "type")]
+    pub file_type: Option<String>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    /// "name", "modified" or "size"; playlist order when unset
+    pub sort: Option<String>,
+    /// "asc" or "desc"
+    pub order: Option<String>,
+    /// Only files carrying this tag
+    pub tag: Option<String>,
+}
+
+impl MediaFilter {
+    pub fn is_empty(&self) -> bool {
+        self.file_type.is_none()
+            && self.tag.is_none()
+            && self.offset.is_none()
+            && self.limit.is_none()
+            && self.sort.is_none()
+            && self.order.is_none()
+    }
+    
+    /// Reject values the listing doesn't understand
+    pub fn validate(&self) -> Result<(), AppError> {
+        if let Some(file_type) = self.file_type.as_deref().filter(|t| !matches!(*t, "image" | "video")) {
+            return Err(AppError::Validation(format!("Invalid type '{}': expected image or video", file_type)));
+        }
+        if let Some(sort) = self.sort.as_deref().filter(|s| !matches!(*s, "name" | "modified" | "size")) {
+            return Err(AppError::Validation(format!("Invalid sort '{}': expected name, modified or size", sort)));
+        }
+        if let Some(order) = self.order.as_deref().filter(|o| !matches!(*o, "asc" | "desc")) {
+            return Err(AppError::Validation(format!("Invalid order '{}': expected asc or desc", order)));
+        }
+        Ok(())
+    }
+}
+
+/// One window of a filtered listing, with the number of matches before paging
+#[derive(Debug, Serialize)]
+pub struct MediaPage {
+    pub total: usize,
+    pub items: Vec<MediaFile>,
+}
+
+/// Like `get_files`, narrowed and paged by `filter`
+pub async fn get_files_filtered(
+    collection: Option<&str>,
+    playlist: &[String],
+    tags: &HashMap<String, Vec<String>>,
+    filter: &MediaFilter,
+) -> Result<MediaPage, AppError> {
+    let mut files = get_files(collection, playlist).await?;
+    attach_tags(&mut files, tags);
+    filter_files(files, filter)
+}
+
+/// Fill in each file's tags from the config's filename-to-tags map
+pub fn attach_tags(files: &mut [MediaFile], tags: &HashMap<String, Vec<String>>) {
+    for file in files {
+        file.tags = tags.get(&file.name).cloned().unwrap_or_default();
+    }
+}
+
+fn filter_files(mut files: Vec<MediaFile>, filter: &MediaFilter) -> Result<MediaPage, AppError> {
+    filter.validate()?;
     
+    if let Some(file_type) = filter.file_type.as_deref() {
+        files.retain(|f| f.file_type == file_type);
+    }
+    if let Some(tag) = filter.tag.as_deref() {
+        files.retain(|f| f.tags.iter().any(|t| t == tag));
+    }
+    
+    // Ties fall back to the name, so "desc" is an exact reverse of "asc"
+    match filter.sort.as_deref() {
+        Some("name") => files.sort_by(|a, b| a.name.cmp(&b.name)),
+        Some("modified") => files.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.name.cmp(&b.name))),
+        Some("size") => files.sort_by(|a, b| a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name))),
+        _ => {}
+    }
+    if filter.order.as_deref() == Some("desc") {
+        files.reverse();
+    }
+    
+    let total = files.len();
+    let items = files.into_iter()
+        .skip(filter.offset.unwrap_or(0))
+        .take(filter.limit.unwrap_or(usize::MAX))
+        .collect();
+    
+    Ok(MediaPage { total, items })
+}
+
+/// Names of the collections (sub-folders) inside the Media directory
+pub async fn list_collections() -> Result<Vec<String>, AppError> {
+    list_collections_in(&get_media_dir()?).await
+}
+
+async fn list_collections_in(media_dir: &Path) -> Result<Vec<String>, AppError> {
     if !media_dir.exists() {
-        println!("DEBUG: Creating Media directory...");
-        fs::create_dir_all(&media_dir).await
-            .map_err(|e| format!("Failed to create Media directory: {}", e))?;
-        println!("DEBUG: Media directory created");
         return Ok(Vec::new());
     }
     
-    let mut entries = fs::read_dir(&media_dir).await
-        .map_err(|e| format!("Failed to read Media directory: {}", e))?;
+    let mut entries = fs::read_dir(media_dir).await
+        .map_err(|e| AppError::Io(format!("Failed to read Media directory: {}", e)))?;
+    let mut collections = Vec::new();
+    
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        
+        // Hidden folders hold caches like thumbnails
+        if entry.path().is_dir() && !name.starts_with('.') {
+            collections.push(name);
+        }
+    }
+    
+    collections.sort();
+    Ok(collections)
+}
+
+async fn get_files_in(media_dir: &Path, order: &[String]) -> Result<Vec<MediaFile>, AppError> {
+    if !media_dir.exists() {
+        debug!("Creating Media directory...");
+        fs::create_dir_all(media_dir).await
+            .map_err(|e| AppError::Io(format!("Failed to create Media directory: {}", e)))?;
+        debug!("Media directory created");
+        return Ok(Vec::new());
+    }
+    
+    let mut entries = fs::read_dir(media_dir).await
+        .map_err(|e| AppError::Io(format!("Failed to read Media directory: {}", e)))?;
     let mut files = Vec::new();
     
-    while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+    while let Some(entry) = entries.next_entry().await? {
         let path = entry.path();
         
         if path.is_dir() {
@@ -41,65 +199,1244 @@ pub async fn get_files() -> Result<Vec<MediaFile>, String> {
         if let Some(ext) = path.extension() {
             let ext_str = ext.to_string_lossy().to_lowercase();
             
-            let file_type = match ext_str.as_str() {
-                "svg" | "png" | "jpg" | "jpeg" => "image",
-                "mp4" => "video",
-                _ => continue,
+            let Some(file_type) = media_type(&ext_str) else {
+                continue;
             };
             
-            let metadata = entry.metadata().await.map_err(|e| e.to_string())?;
-            let modified = metadata.modified().map_err(|e| e.to_string())?;
+            let metadata = entry.metadata().await?;
+            let modified = metadata.modified()?;
+            
+            let probe_path = path.clone();
+            let info = tokio::task::spawn_blocking(move || probe::probe(&probe_path)).await
+                .unwrap_or_default();
             
             files.push(MediaFile {
                 name: entry.file_name().to_string_lossy().to_string(),
                 path: path.to_string_lossy().to_string(),
                 file_type: file_type.to_string(),
                 size: metadata.len(),
-                modified: format!("{:?}", modified),
+                modified: format_modified(modified),
+                width: info.width,
+                height: info.height,
+                duration_secs: info.duration_secs,
+                tags: Vec::new(),
             });
         }
     }
     
-    files.sort_by(|a, b| a.name.cmp(&b.name));
+    apply_order(&mut files, order);
     
-    println!("DEBUG: Found {} media files", files.len());
+    debug!("Found {} media files", files.len());
     
     Ok(files)
 }
 
-pub async fn delete_file(filename: &str) -> Result<(), String> {
+/// "image" or "video" for extensions the player can show, by lowercase extension
+pub fn media_type(ext: &str) -> Option<&'static str> {
+    match ext {
+        "svg" | "png" | "jpg" | "jpeg" | "gif" | "webp" => Some("image"),
+        "mp4" | "webm" | "mov" | "mkv" => Some("video"),
+        _ => None,
+    }
+}
+
+/// MIME type for serving a media file, by lowercase extension
+pub fn content_type(ext: &str) -> &'static str {
+    match ext {
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        _ => "application/octet-stream",
+    }
+}
+
+/// MIME type for downloading a file: by extension, or by its first bytes when the extension
+/// isn't one we know
+pub fn download_content_type(filename: &str, head: &[u8]) -> &'static str {
+    let ext = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    
+    match content_type(&ext) {
+        "application/octet-stream" => match sniff_media_type(head) {
+            Some("quicktime") => content_type("mov"),
+            Some(kind) => content_type(kind),
+            None => "application/octet-stream",
+        },
+        known => known,
+    }
+}
+
+/// `Content-Disposition` value that saves the file under its own name. The quoted name is an
+/// ASCII stand-in; `filename*` carries the real one percent-encoded (RFC 6266).
+pub fn attachment_disposition(filename: &str) -> String {
+    let fallback: String = filename.chars()
+        .map(|c| if c == ' ' || (c.is_ascii_graphic() && c != '"' && c != '\\') { c } else { '_' })
+        .collect();
+    
+    let mut encoded = String::new();
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+/// What a `Range` header asks for out of a file of known length
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    /// No usable range; send the whole file
+    Full,
+    /// Inclusive start and end offsets
+    Partial(u64, u64),
+    /// The range lies past the end of the file
+    Unsatisfiable,
+}
+
+/// Interpret a single `bytes=` range. Malformed or multi-part ranges fall back to the
+/// whole file, which the spec allows and every player copes with.
+pub fn parse_byte_range(header: Option<&str>, len: u64) -> ByteRange {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    
+    let (start, end) = match (start.trim(), end.trim()) {
+        // "bytes=-500" is the last 500 bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (len.saturating_sub(suffix), len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            _ => return ByteRange::Full,
+        },
+    };
+    
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start, end)
+}
+
+/// RFC 3339 timestamp in UTC, e.g. `2024-01-02T15:04:05Z`
+pub fn format_modified(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Sort files by their position in `order`, with unlisted files alphabetically at the end
+pub fn apply_order(files: &mut [MediaFile], order: &[String]) {
+    let position = |name: &str| order.iter().position(|n| n == name).unwrap_or(usize::MAX);
+    
+    files.sort_by(|a, b| {
+        position(&a.name)
+            .cmp(&position(&b.name))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// Accept only a plain file name, so nothing can escape or hide inside the Media directory
+pub fn sanitize_filename(name: &str) -> Result<String, AppError> {
+    let invalid = |message: String| Err(AppError::Validation(message));
+    
+    if name.is_empty() {
+        return invalid("File name is empty".to_string());
+    }
+    
+    if name.contains('\0') {
+        return invalid("File name contains a null byte".to_string());
+    }
+    
+    // ':' covers Windows drive prefixes like C:foo.png
+    if name.contains(['/', '\\', ':']) {
+        return invalid(format!("File name must not contain a path: {}", name));
+    }
+    
+    // With separators gone, ".." can only be the whole name; "my..photo.jpg" is fine
+    if name == ".." {
+        return invalid(format!("File name must not be '..': {}", name));
+    }
+    
+    if name.starts_with('.') {
+        return invalid(format!("Hidden files are not allowed: {}", name));
+    }
+    
+    Path::new(name)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| AppError::Validation(format!("Invalid file name: {}", name)))
+}
+
+pub async fn delete_file(filename: &str) -> Result<(), AppError> {
+    let filename = &sanitize_filename(filename)?;
     let media_dir = get_media_dir()?;
     let file_path = media_dir.join(filename);
     
     if !file_path.starts_with(&media_dir) {
-        return Err("Invalid file path".to_string());
+        return Err(AppError::Validation("Invalid file path".to_string()));
     }
     
     if !file_path.exists() {
-        return Err("File not found".to_string());
+        return Err(AppError::NotFound("File not found".to_string()));
     }
     
-    fs::remove_file(&file_path).await
-        .map_err(|e| e.to_string())?;
+    let size = fs::metadata(&file_path).await.map(|m| m.len()).unwrap_or(0);
+    fs::remove_file(&file_path).await?;
+    quota::release(&media_dir, size);
+    
+    // Drop the cached thumbnail along with the file
+    let _ = fs::remove_file(thumbnail::thumbnail_path(&media_dir, filename)).await;
+    
+    if let Err(e) = index::forget(&media_dir, filename).await {
+        warn!("{}", e);
+    }
     
     Ok(())
 }
 
-pub async fn save_file(filename: &str, data: &[u8]) -> Result<(), String> {
-    let media_dir = get_media_dir()?;
-    let file_path = media_dir.join(filename);
+/// Rename a file in the default collection; the extension has to stay the same
+pub async fn rename_file(old_name: &str, new_name: &str) -> Result<(), AppError> {
+    rename_file_in(&get_media_dir()?, old_name, new_name).await
+}
+
+async fn rename_file_in(media_dir: &Path, old_name: &str, new_name: &str) -> Result<(), AppError> {
+    let old_name = sanitize_filename(old_name)?;
+    let new_name = sanitize_filename(new_name)?;
+    
+    // A different extension would no longer match the file's contents
+    let extension = |name: &str| Path::new(name).extension().map(|e| e.to_string_lossy().to_lowercase());
+    if extension(&old_name) != extension(&new_name) {
+        return Err(AppError::Validation(format!("{} must keep the extension of {}", new_name, old_name)));
+    }
+    
+    let old_path = media_dir.join(&old_name);
+    let new_path = media_dir.join(&new_name);
+    
+    if !old_path.exists() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    
+    if new_path.exists() {
+        return Err(AppError::Conflict(format!("A file named {} already exists", new_name)));
+    }
+    
+    fs::rename(&old_path, &new_path).await
+        .map_err(|e| AppError::Io(format!("Failed to rename {}: {}", old_name, e)))?;
+    
+    // Carry the cached thumbnail over; if that fails it is simply regenerated
+    let _ = fs::rename(
+        thumbnail::thumbnail_path(media_dir, &old_name),
+        thumbnail::thumbnail_path(media_dir, &new_name),
+    ).await;
+    
+    if let Err(e) = index::rename(media_dir, &old_name, &new_name).await {
+        warn!("{}", e);
+    }
+    
+    Ok(())
+}
+
+/// Save an upload that is already fully in memory
+pub async fn save_file(
+    collection: Option<&str>,
+    filename: &str,
+    data: &[u8],
+    transcode: Option<Transcode>,
+    video: Option<video::VideoTranscode>,
+    max_total_bytes: u64,
+) -> Result<SavedUpload, AppError> {
+    let mut upload = PendingUpload::begin(collection, filename).await?
+        .transcode(transcode)
+        .transcode_video(video)
+        .max_total_bytes(max_total_bytes);
+    
+    if let Err(e) = upload.write(data).await {
+        upload.abort().await;
+        return Err(e);
+    }
+    
+    upload.finish().await
+}
+
+/// Format uploaded images are re-encoded to before they are stored
+#[derive(Debug, Clone)]
+pub struct Transcode {
+    /// "webp" or "avif"
+    pub format: String,
+    /// 1 to 100
+    pub quality: u8,
+}
+
+impl Transcode {
+    /// What the config asks uploads to be transcoded to, if anything
+    pub fn from_config(cfg: &crate::config::Config) -> Option<Self> {
+        (cfg.auto_transcode && cfg.transcode_format != "none").then(|| Self {
+            format: cfg.transcode_format.clone(),
+            quality: cfg.image_quality.clamp(1, 100),
+        })
+    }
+    
+    /// Only still photos and graphics are worth it; GIFs may be animated
+    fn applies_to(&self, filename: &str) -> bool {
+        matches!(expected_media_type(filename), Some("jpeg" | "png"))
+    }
+}
+
+/// Slowest AVIF encoder speeds take minutes per photo, which is too long to hold an upload
+const AVIF_SPEED: u8 = 8;
+
+/// Decode an image upright and encode it in the transcode format
+fn transcode_image(path: &Path, transcode: &Transcode) -> Result<Vec<u8>, String> {
+    use image::{ExtendedColorType, ImageEncoder};
+    
+    let (image, _) = decode_upright(path)?;
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    
+    match transcode.format.as_str() {
+        "webp" => webp::Encoder::from_rgba(&rgba, width, height)
+            .encode_simple(false, transcode.quality as f32)
+            .map(|data| data.to_vec())
+            .map_err(|e| format!("Failed to encode WebP: {:?}", e)),
+        "avif" => {
+            let mut data = Vec::new();
+            image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut data, AVIF_SPEED, transcode.quality)
+                .write_image(&rgba, width, height, ExtendedColorType::Rgba8)
+                .map_err(|e| format!("Failed to encode AVIF: {}", e))?;
+            Ok(data)
+        }
+        other => Err(format!("Unsupported transcode format: {}", other)),
+    }
+}
+
+/// Transcode a finished upload into a temp file next to it, returning the new file name
+/// and temp path. Failures are only logged, since the original is still a good upload.
+async fn transcode_upload(temp_path: &Path, filename: &str, transcode: Transcode) -> Option<(String, PathBuf)> {
+    let format = transcode.format.clone();
+    let source = temp_path.to_path_buf();
+    let encoded = tokio::task::spawn_blocking(move || transcode_image(&source, &transcode)).await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+    let original_len = fs::metadata(temp_path).await
+        .map(|meta| meta.len())
+        .unwrap_or(u64::MAX);
+    
+    let data = match encoded {
+        Ok(data) if (data.len() as u64) < original_len => data,
+        Ok(_) => {
+            info!("Keeping {} as uploaded: {} would not be smaller", filename, format);
+            return None;
+        }
+        Err(e) => {
+            warn!("Failed to transcode {} to {}, keeping the original: {}", filename, format, e);
+            return None;
+        }
+    };
+    
+    let new_name = Path::new(filename).with_extension(&format).to_string_lossy().to_string();
+    let new_temp = temp_path.with_file_name(format!(".{}.upload", new_name));
+    if let Err(e) = fs::write(&new_temp, &data).await {
+        warn!("Failed to write transcoded {}, keeping the original: {}", new_name, e);
+        let _ = fs::remove_file(&new_temp).await;
+        return None;
+    }
+    
+    info!("Transcoded {} to {} ({} -> {} bytes)", filename, new_name, original_len, data.len());
+    Some((new_name, new_temp))
+}
+
+/// How much of the start of an upload is kept in memory for content sniffing
+const SNIFF_LEN: usize = 4096;
+
+/// Where a finished upload ended up
+#[derive(Debug, Clone)]
+pub struct SavedUpload {
+    pub filename: String,
+    /// The same bytes were already in the folder under `filename`, so nothing was written
+    pub deduplicated: bool,
+}
+
+/// An upload being streamed into a temp file in the Media directory
+pub struct PendingUpload {
+    filename: String,
+    file_path: PathBuf,
+    temp_path: PathBuf,
+    file: fs::File,
+    head: Vec<u8>,
+    written: u64,
+    hasher: Sha256,
+    transcode: Option<Transcode>,
+    video: Option<video::VideoTranscode>,
+    /// Largest the Media folder may grow to, 0 for unlimited
+    max_total_bytes: u64,
+}
+
+impl PendingUpload {
+    pub async fn begin(collection: Option<&str>, filename: &str) -> Result<Self, AppError> {
+        let (filename, file_path) = upload_target(collection, filename).await?;
+        
+        // Write to a temp file first so a broken upload never lands in the rotation
+        let temp_path = file_path.with_file_name(format!(".{}.upload", filename));
+        let file = fs::File::create(&temp_path).await?;
+        
+        Ok(Self {
+            filename,
+            file_path,
+            temp_path,
+            file,
+            head: Vec::new(),
+            written: 0,
+            hasher: Sha256::new(),
+            transcode: None,
+            video: None,
+            max_total_bytes: 0,
+        })
+    }
+    
+    /// Take over bytes already received into `part_path`, e.g. by a resumable upload.
+    /// Finishing moves that file into place; aborting deletes it.
+    pub async fn adopt(collection: Option<&str>, filename: &str, part_path: &Path) -> Result<Self, AppError> {
+        let (filename, file_path) = upload_target(collection, filename).await?;
+        
+        let file = fs::OpenOptions::new().read(true).append(true).open(part_path).await?;
+        
+        let mut upload = Self {
+            filename,
+            file_path,
+            temp_path: part_path.to_path_buf(),
+            file,
+            head: Vec::new(),
+            written: 0,
+            hasher: Sha256::new(),
+            transcode: None,
+            video: None,
+            max_total_bytes: 0,
+        };
+        
+        // The hash and sniffed header are built up as bytes arrive, so replay them
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = upload.file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            upload.track(&buf[..n]);
+        }
+        
+        Ok(upload)
+    }
+    
+    /// Re-encode the upload once it's finished, if it's an image worth transcoding
+    pub fn transcode(mut self, transcode: Option<Transcode>) -> Self {
+        self.transcode = transcode;
+        self
+    }
+    
+    /// Convert the upload to MP4 once it's finished, if it's a video the display may not play
+    pub fn transcode_video(mut self, video: Option<video::VideoTranscode>) -> Self {
+        self.video = video;
+        self
+    }
+    
+    /// Refuse the upload if storing it would take the Media folder past this many bytes
+    pub fn max_total_bytes(mut self, max_total_bytes: u64) -> Self {
+        self.max_total_bytes = max_total_bytes;
+        self
+    }
+    
+    /// Bytes written so far
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+    
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), AppError> {
+        self.file.write_all(chunk).await?;
+        self.track(chunk);
+        
+        Ok(())
+    }
+    
+    fn track(&mut self, chunk: &[u8]) {
+        if self.head.len() < SNIFF_LEN {
+            let take = (SNIFF_LEN - self.head.len()).min(chunk.len());
+            self.head.extend_from_slice(&chunk[..take]);
+        }
+        
+        self.hasher.update(chunk);
+        self.written += chunk.len() as u64;
+    }
+    
+    /// Throw away a partial upload
+    pub async fn abort(self) {
+        drop(self.file);
+        let _ = fs::remove_file(&self.temp_path).await;
+    }
+    
+    /// Check the finished upload and move it into place, unless the folder already has the same bytes
+    pub async fn finish(mut self) -> Result<SavedUpload, AppError> {
+        if let Err(e) = self.check().await {
+            self.abort().await;
+            return Err(e);
+        }
+        
+        let media_dir = self.file_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let hash = format!("{:x}", self.hasher.clone().finalize());
+        if let Some(existing) = index::find(&media_dir, &hash).await {
+            info!("{} has the same content as {}; keeping the existing file", self.filename, existing);
+            self.abort().await;
+            return Ok(SavedUpload { filename: existing, deduplicated: true });
+        }
+        
+        let filename = self.filename.clone();
+        let temp_path = self.temp_path.clone();
+        drop(self.file);
+        
+        // SVGs end up in the display webview, so strip anything that could run there
+        if expected_media_type(&filename) == Some("svg") {
+            if let Err(e) = sanitize_svg_file(&temp_path).await {
+                let _ = fs::remove_file(&temp_path).await;
+                return Err(AppError::Validation(format!("Rejected SVG: {}", e)));
+            }
+        }
+        
+        // Transcoding decodes the image upright, which takes care of EXIF orientation too
+        let transcoded = match self.transcode.take().filter(|t| t.applies_to(&filename)) {
+            Some(transcode) => transcode_upload(&temp_path, &filename, transcode).await,
+            None => match self.video.take().filter(|_| video::needs_transcode(&filename)) {
+                Some(video) => video::transcode_upload(&temp_path, &filename, video).await,
+                None => None,
+            },
+        };
+        
+        let (filename, temp_path, file_path) = match transcoded {
+            Some((new_name, new_temp)) => {
+                let _ = fs::remove_file(&temp_path).await;
+                let file_path = self.file_path.with_file_name(&new_name);
+                (new_name, new_temp, file_path)
+            }
+            None => {
+                // Phone photos are often stored sideways with an EXIF hint to rotate them
+                if matches!(expected_media_type(&filename), Some("jpeg" | "webp")) {
+                    let upload = temp_path.clone();
+                    let rotated = tokio::task::spawn_blocking(move || apply_exif_orientation(&upload)).await;
+                    if let Ok(Err(e)) = rotated {
+                        warn!("Failed to apply EXIF orientation to {}: {}", filename, e);
+                    }
+                }
+                (filename, temp_path, self.file_path.clone())
+            }
+        };
+        
+        // Checked on the final file, since transcoding changes its size
+        let media_root = crate::paths::get_media_dir(None)?;
+        let size = fs::metadata(&temp_path).await.map(|m| m.len()).unwrap_or(0);
+        let replaced = fs::metadata(&file_path).await.map(|m| m.len()).unwrap_or(0);
+        let growth = size.saturating_sub(replaced);
+        if let Err(e) = quota::reserve(&media_root, growth, self.max_total_bytes).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        
+        fs::rename(&temp_path, &file_path).await
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&temp_path);
+                quota::release(&media_root, growth);
+                AppError::from(e)
+            })?;
+        quota::release(&media_root, replaced.saturating_sub(size));
+        
+        if let Err(e) = index::record(&media_dir, &hash, &filename).await {
+            warn!("{}", e);
+        }
+        
+        Ok(SavedUpload { filename, deduplicated: false })
+    }
+    
+    async fn check(&mut self) -> Result<(), AppError> {
+        if self.written == 0 {
+            return Err(AppError::Validation("Corrupt or truncated upload: file is empty".to_string()));
+        }
+        
+        self.file.flush().await?;
+        
+        // Don't trust the extension the client picked
+        if let Some(expected) = expected_media_type(&self.filename) {
+            match sniff_media_type(&self.head) {
+                Some(actual) if actual == expected => {}
+                Some(actual) => {
+                    return Err(AppError::Validation(format!(
+                        "File content is {} but the extension says {}",
+                        actual, expected
+                    )));
+                }
+                None => {
+                    return Err(AppError::Validation(format!("File content is not a valid {}", expected)));
+                }
+            }
+        }
+        
+        validate_upload(&self.temp_path, &self.filename).await
+            .map_err(|e| AppError::Validation(format!("Corrupt or truncated upload: {}", e)))
+    }
+}
+
+/// Check an upload's name and work out where it will be stored, creating the folder if needed
+async fn upload_target(collection: Option<&str>, filename: &str) -> Result<(String, PathBuf), AppError> {
+    let filename = check_upload_name(filename)?;
+    
+    let media_dir = crate::paths::get_media_dir(collection)?;
+    let file_path = media_dir.join(&filename);
     
     if !file_path.starts_with(&media_dir) {
-        return Err("Invalid file path".to_string());
+        return Err(AppError::Validation("Invalid file path".to_string()));
     }
     
     if !media_dir.exists() {
-        fs::create_dir_all(&media_dir).await
+        fs::create_dir_all(&media_dir).await?;
+    }
+    
+    Ok((filename, file_path))
+}
+
+/// Sanitize an upload's name, refusing file types the display can't show
+pub fn check_upload_name(filename: &str) -> Result<String, AppError> {
+    let filename = sanitize_filename(filename)?;
+    
+    if expected_media_type(&filename).is_none() {
+        return Err(AppError::Validation(format!("Unsupported file type: {}", filename)));
+    }
+    
+    Ok(filename)
+}
+
+/// Decode an image and turn it the way its EXIF orientation says it should be shown
+pub fn decode_upright(path: &Path) -> Result<(DynamicImage, Orientation), AppError> {
+    let mut decoder = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())?
+        .into_decoder()
+        .map_err(|e| AppError::Validation(format!("Failed to decode image: {}", e)))?;
+    
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder)
+        .map_err(|e| AppError::Validation(format!("Failed to decode image: {}", e)))?;
+    image.apply_orientation(orientation);
+    
+    Ok((image, orientation))
+}
+
+/// Rotate a photo's pixels upright and drop its EXIF orientation tag,
+/// since browsers only honor the tag in some contexts
+pub fn apply_exif_orientation(path: &Path) -> Result<(), AppError> {
+    let (image, orientation) = decode_upright(path)?;
+    
+    if orientation == Orientation::NoTransforms {
+        return Ok(());
+    }
+    
+    let format = ImageFormat::from_path(path)
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+    
+    // Re-encoding writes no EXIF block, so the tag goes away with it
+    let mut data = Vec::new();
+    if format == ImageFormat::Jpeg {
+        JpegEncoder::new_with_quality(&mut data, REENCODE_JPEG_QUALITY)
+            .encode_image(&image.to_rgb8())
+            .map_err(|e| AppError::Io(format!("Failed to encode image: {}", e)))?;
+    } else {
+        image.write_to(&mut std::io::Cursor::new(&mut data), format)
+            .map_err(|e| AppError::Io(format!("Failed to encode image: {}", e)))?;
+    }
+    
+    std::fs::write(path, data)?;
+    
+    info!("Applied EXIF orientation {:?} to {:?}", orientation, path);
+    Ok(())
+}
+
+/// Media type implied by a filename's extension, for the types we can sniff
+fn expected_media_type(filename: &str) -> Option<&'static str> {
+    let ext = Path::new(filename).extension()?.to_string_lossy().to_lowercase();
+    
+    match ext.as_str() {
+        "png" => Some("png"),
+        "jpg" | "jpeg" => Some("jpeg"),
+        "svg" => Some("svg"),
+        "gif" => Some("gif"),
+        "webp" => Some("webp"),
+        "mp4" => Some("mp4"),
+        "webm" => Some("webm"),
+        "mov" => Some("quicktime"),
+        // Matroska starts with the same EBML header as WebM
+        "mkv" => Some("webm"),
+        _ => None,
+    }
+}
+
+/// Detect a media type from the file's magic bytes
+pub fn sniff_media_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some("png");
+    }
+    
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some("jpeg");
+    }
+    
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return Some("gif");
+    }
+    
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        return Some("webp");
+    }
+    
+    // EBML header; Matroska and WebM share it
+    if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        return Some("webm");
+    }
+    
+    // ISO base media: box size, then "ftyp" and the major brand
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        if &data[8..12] == b"qt  " {
+            return Some("quicktime");
+        }
+        return Some("mp4");
+    }
+    
+    // Older QuickTime files start straight with a movie or data atom
+    if data.len() >= 8 && matches!(&data[4..8], b"moov" | b"mdat" | b"wide" | b"free") {
+        return Some("quicktime");
+    }
+    
+    let text = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    let start = text.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(text.len());
+    let text = &text[start..];
+    if text.starts_with(b"<?xml") || text.starts_with(b"<svg") {
+        return Some("svg");
+    }
+    
+    None
+}
+
+/// Read back a written upload and check that known image types are complete. The type
+/// comes from the name it will be stored under, since temp files are named differently.
+async fn validate_upload(path: &Path, filename: &str) -> Result<(), String> {
+    let ext = match Path::new(filename).extension() {
+        Some(ext) => ext.to_string_lossy().to_lowercase(),
+        None => return Ok(()),
+    };
+    
+    let data = fs::read(path).await
+        .map_err(|e| format!("failed to read back file: {}", e))?;
+    
+    validate_media_bytes(&ext, &data)
+}
+
+/// Check that the contents of a media file with the given extension are complete
+pub fn validate_media_bytes(ext: &str, data: &[u8]) -> Result<(), String> {
+    if data.is_empty() {
+        return Err("file is empty".to_string());
+    }
+    
+    match ext.to_lowercase().as_str() {
+        "png" => validate_png(data),
+        "jpg" | "jpeg" => validate_jpeg(data),
+        "svg" => validate_svg(data),
+        "gif" => validate_gif(data),
+        "webp" => validate_webp(data),
+        _ => Ok(()),
+    }
+}
+
+fn validate_png(data: &[u8]) -> Result<(), String> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const IEND: &[u8] = b"IEND\xae\x42\x60\x82";
+    
+    if !data.starts_with(SIGNATURE) {
+        return Err("missing PNG signature".to_string());
+    }
+    
+    // IHDR must be the first chunk, right after the signature and its length field
+    if data.len() < 33 || &data[12..16] != b"IHDR" {
+        return Err("missing PNG header chunk".to_string());
+    }
+    
+    if !data.ends_with(IEND) {
+        return Err("PNG data ends before IEND chunk".to_string());
+    }
+    
+    Ok(())
+}
+
+fn validate_jpeg(data: &[u8]) -> Result<(), String> {
+    if !data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Err("missing JPEG start marker".to_string());
+    }
+    
+    // Some encoders pad the file after the end marker, so ignore trailing zeros
+    let end = data.iter().rposition(|&b| b != 0x00).map_or(0, |i| i + 1);
+    
+    if !data[..end].ends_with(&[0xFF, 0xD9]) {
+        return Err("JPEG data ends before end marker".to_string());
+    }
+    
+    Ok(())
+}
+
+fn validate_svg(data: &[u8]) -> Result<(), String> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| "SVG is not valid UTF-8".to_string())?;
+    
+    if !text.contains("<svg") {
+        return Err("missing <svg> element".to_string());
+    }
+    
+    if !text.trim_end().ends_with('>') || !text.contains("</svg>") {
+        return Err("SVG document is not closed".to_string());
+    }
+    
+    Ok(())
+}
+
+async fn sanitize_svg_file(path: &Path) -> Result<(), String> {
+    let data = fs::read(path).await
+        .map_err(|e| format!("failed to read back file: {}", e))?;
+    let clean = sanitize_svg(&data)?;
+    
+    if clean != data {
+        info!("Removed scripts or external references from {}", path.display());
+        fs::write(path, clean).await
             .map_err(|e| e.to_string())?;
     }
     
-    fs::write(&file_path, data).await
-        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Elements dropped along with everything inside them
+const SVG_BLOCKED_ELEMENTS: &[&[u8]] = &[b"script", b"foreignObject"];
+
+/// Strip scripts, event handler attributes and external references from an SVG.
+/// Documents that aren't well-formed XML are rejected rather than guessed at.
+pub fn sanitize_svg(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    use quick_xml::events::{BytesStart, Event};
+    use quick_xml::{Reader, Writer};
+    
+    let invalid = |e: &dyn std::fmt::Display| format!("SVG is not valid XML: {}", e);
+    let mut reader = Reader::from_reader(bytes);
+    let mut writer = Writer::new(Vec::with_capacity(bytes.len()));
+    let mut depth = 0usize;
+    
+    // Rebuild a tag without the attributes that can run script or load something
+    let clean_tag = |tag: &BytesStart| -> Result<Option<BytesStart<'static>>, String> {
+        let mut clean = tag.to_owned();
+        clean.clear_attributes();
+        let mut removed = false;
+        
+        for attr in tag.attributes() {
+            let attr = attr.map_err(|e| invalid(&e))?;
+            let name = attr.key.local_name();
+            let name = name.as_ref();
+            let value = attr.value.trim_ascii_start();
+            
+            let handler = name.len() > 2 && name[..2].eq_ignore_ascii_case(b"on");
+            let external = name.eq_ignore_ascii_case(b"href")
+                && !value.starts_with(b"#")
+                && !value.starts_with(b"data:image/");
+            
+            if handler || external {
+                removed = true;
+            } else {
+                clean.push_attribute(attr);
+            }
+        }
+        
+        Ok(removed.then_some(clean))
+    };
+    let blocked = |tag: &BytesStart| {
+        SVG_BLOCKED_ELEMENTS.iter().any(|name| tag.local_name().as_ref().eq_ignore_ascii_case(name))
+    };
+    
+    loop {
+        let event = reader.read_event().map_err(|e| invalid(&e))?;
+        let event = match event {
+            Event::Eof => break,
+            // Entity declarations can pull in local files or other URLs
+            Event::DocType(_) | Event::PI(_) => continue,
+            Event::Start(tag) if blocked(&tag) => {
+                reader.read_to_end(tag.name()).map_err(|e| invalid(&e))?;
+                continue;
+            }
+            Event::Empty(tag) if blocked(&tag) => continue,
+            Event::Start(tag) => {
+                depth += 1;
+                match clean_tag(&tag)? {
+                    Some(clean) => Event::Start(clean),
+                    None => Event::Start(tag),
+                }
+            }
+            Event::Empty(tag) => match clean_tag(&tag)? {
+                Some(clean) => Event::Empty(clean),
+                None => Event::Empty(tag),
+            },
+            Event::End(tag) => {
+                depth = depth.checked_sub(1)
+                    .ok_or_else(|| invalid(&"unexpected closing tag"))?;
+                Event::End(tag)
+            }
+            event => event,
+        };
+        
+        writer.write_event(event).map_err(|e| e.to_string())?;
+    }
+    
+    if depth != 0 {
+        return Err(invalid(&"document is not closed"));
+    }
+    
+    Ok(writer.into_inner())
+}
+
+fn validate_gif(data: &[u8]) -> Result<(), String> {
+    if !data.starts_with(b"GIF87a") && !data.starts_with(b"GIF89a") {
+        return Err("missing GIF signature".to_string());
+    }
+    
+    // Header plus logical screen descriptor
+    if data.len() < 13 {
+        return Err("missing GIF screen descriptor".to_string());
+    }
+    
+    if data.last() != Some(&0x3B) {
+        return Err("GIF data ends before trailer".to_string());
+    }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+fn validate_webp(data: &[u8]) -> Result<(), String> {
+    if data.len() < 12 || !data.starts_with(b"RIFF") || &data[8..12] != b"WEBP" {
+        return Err("missing WebP header".to_string());
+    }
+    
+    // The RIFF size covers everything after the size field itself
+    let riff_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    
+    if data.len() < riff_size + 8 {
+        return Err("WebP data is shorter than its RIFF header says".to_string());
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_truncated_images_rejected() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0\x1f\x15\xc4\x89\0\0\0\0IEND\xae\x42\x60\x82";
+        assert!(validate_png(png).is_ok());
+        assert!(validate_png(&png[..png.len() - 4]).is_err());
+        
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0xFF, 0xD9];
+        assert!(validate_jpeg(&jpeg).is_ok());
+        assert!(validate_jpeg(&jpeg[..6]).is_err());
+        
+        assert!(validate_svg(b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>").is_ok());
+        assert!(validate_svg(b"<svg xmlns=\"http://www.w3.org/2000/svg\"><rect").is_err());
+    }
+    
+    #[test]
+    fn test_sanitize_svg() {
+        let benign = br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="10" height="10">
+  <!-- a comment -->
+  <defs><linearGradient id="g"><stop offset="0" stop-color="#fff"/></linearGradient></defs>
+  <rect width="10" height="10" fill="url(#g)"/>
+  <use xlink:href="#g"/>
+  <text x="1" y="5">Tom &amp; Jerry</text>
+</svg>"##;
+        assert_eq!(sanitize_svg(benign).unwrap(), benign.to_vec());
+        
+        let hostile = br#"<!DOCTYPE svg [<!ENTITY xxe SYSTEM "file:///etc/passwd">]>
+<svg xmlns="http://www.w3.org/2000/svg" onload="alert(1)">
+  <script>alert(document.cookie)</script>
+  <SCRIPT/>
+  <foreignObject><iframe src="https://example.com"/></foreignObject>
+  <image href="https://example.com/track.png" width="1" height="1"/>
+  <a href="javascript:alert(1)"><rect width="5" height="5" onclick="alert(2)"/></a>
+</svg>"#;
+        let clean = String::from_utf8(sanitize_svg(hostile).unwrap()).unwrap();
+        for removed in ["script", "SCRIPT", "alert", "ENTITY", "foreignObject", "iframe", "example.com", "onload"] {
+            assert!(!clean.contains(removed), "{} left in {}", removed, clean);
+        }
+        assert!(clean.contains("<rect width=\"5\" height=\"5\"/>"));
+        
+        assert!(sanitize_svg(b"<svg><rect></svg>").is_err());
+        assert!(sanitize_svg(b"<svg><rect>").is_err());
+    }
+    
+    #[test]
+    fn test_sniff_media_type() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR";
+        assert_eq!(sniff_media_type(png), Some("png"));
+        assert_eq!(sniff_media_type(&png[..4]), None);
+        assert_eq!(sniff_media_type(b"  \n<svg></svg>"), Some("svg"));
+        assert_eq!(sniff_media_type(b"\0\0\0\x18ftypmp42\0\0\0\0"), Some("mp4"));
+        assert_eq!(sniff_media_type(b"\0\0\0\x14ftypqt  \0\0\0\0"), Some("quicktime"));
+        assert_eq!(sniff_media_type(b"GIF89a\x01\0\x01\0"), Some("gif"));
+        assert_eq!(sniff_media_type(b"RIFF\x04\0\0\0WEBP"), Some("webp"));
+        assert_eq!(sniff_media_type(&[0x1A, 0x45, 0xDF, 0xA3, 0x9F]), Some("webm"));
+        
+        // An executable renamed to .png
+        assert_eq!(expected_media_type("evil.png"), Some("png"));
+        assert_eq!(sniff_media_type(b"MZ\x90\0\x03\0\0\0"), None);
+        assert_ne!(sniff_media_type(&[0xFF, 0xD8, 0xFF, 0xE0]), expected_media_type("photo.PNG"));
+    }
+    
+    #[test]
+    fn test_modified_is_rfc3339() {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_704_207_845);
+        assert_eq!(format_modified(time), "2024-01-02T15:04:05Z");
+        
+        let now = format_modified(std::time::SystemTime::now());
+        assert!(chrono::DateTime::parse_from_rfc3339(&now).is_ok());
+    }
+    
+    #[test]
+    fn test_filter_files() {
+        let file = |name: &str, file_type: &str, size: u64| MediaFile {
+            name: name.to_string(),
+            path: name.to_string(),
+            file_type: file_type.to_string(),
+            size,
+            modified: String::new(),
+            width: None,
+            height: None,
+            duration_secs: None,
+            tags: Vec::new(),
+        };
+        let mut files = vec![
+            file("a.png", "image", 30),
+            file("b.mp4", "video", 500),
+            file("c.jpg", "image", 10),
+            file("d.webm", "video", 200),
+            file("e.gif", "image", 20),
+        ];
+        let tags = HashMap::from([
+            ("b.mp4".to_string(), vec!["promos".to_string()]),
+            ("e.gif".to_string(), vec!["menus".to_string(), "promos".to_string()]),
+        ]);
+        attach_tags(&mut files, &tags);
+        let names = |page: MediaPage| page.items.into_iter().map(|f| f.name).collect::<Vec<_>>();
+        
+        let videos = filter_files(files.clone(), &MediaFilter {
+            file_type: Some("video".to_string()),
+            ..MediaFilter::default()
+        }).unwrap();
+        assert_eq!(videos.total, 2);
+        assert_eq!(names(videos), vec!["b.mp4", "d.webm"]);
+        
+        let window = filter_files(files.clone(), &MediaFilter {
+            offset: Some(1),
+            limit: Some(2),
+            ..MediaFilter::default()
+        }).unwrap();
+        assert_eq!(window.total, 5);
+        assert_eq!(names(window), vec!["b.mp4", "c.jpg"]);
+        
+        let largest_images = filter_files(files.clone(), &MediaFilter {
+            file_type: Some("image".to_string()),
+            sort: Some("size".to_string()),
+            order: Some("desc".to_string()),
+            ..MediaFilter::default()
+        }).unwrap();
+        assert_eq!(names(largest_images), vec!["a.png", "e.gif", "c.jpg"]);
+        
+        let promos = filter_files(files.clone(), &MediaFilter {
+            tag: Some("promos".to_string()),
+            ..MediaFilter::default()
+        }).unwrap();
+        assert_eq!(names(promos), vec!["b.mp4", "e.gif"]);
+        
+        assert!(filter_files(files.clone(), &MediaFilter { sort: Some("colour".to_string()), ..MediaFilter::default() }).is_err());
+        assert!(filter_files(files, &MediaFilter { file_type: Some("audio".to_string()), ..MediaFilter::default() }).is_err());
+    }
+    
+    #[test]
+    fn test_apply_order() {
+        let file = |name: &str| MediaFile {
+            name: name.to_string(),
+            path: name.to_string(),
+            file_type: "image".to_string(),
+            size: 0,
+            modified: String::new(),
+            width: None,
+            height: None,
+            duration_secs: None,
+            tags: Vec::new(),
+        };
+        
+        let mut files = vec![file("a.png"), file("b.png"), file("c.png"), file("d.png")];
+        let order = vec!["c.png".to_string(), "gone.png".to_string(), "a.png".to_string()];
+        apply_order(&mut files, &order);
+        
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["c.png", "a.png", "b.png", "d.png"]);
+    }
+    
+    #[test]
+    fn test_sanitize_filename() {
+        assert!(sanitize_filename("../../etc/passwd").is_err());
+        assert!(sanitize_filename("..").is_err());
+        assert!(sanitize_filename("foo/bar.png").is_err());
+        assert!(sanitize_filename("C:\\win.png").is_err());
+        assert!(sanitize_filename(".hidden.png").is_err());
+        assert!(sanitize_filename("photo\0.jpg").is_err());
+        assert!(sanitize_filename("").is_err());
+        assert_eq!(sanitize_filename("photo.jpg").unwrap(), "photo.jpg");
+        assert_eq!(sanitize_filename("my..photo.jpg").unwrap(), "my..photo.jpg");
+    }
+    
+    #[tokio::test]
+    async fn test_rename_file() {
+        let dir = std::env::temp_dir().join(format!("media-rename-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join(".thumbnails")).unwrap();
+        
+        std::fs::write(dir.join("luanch.png"), b"slide").unwrap();
+        std::fs::write(dir.join(".thumbnails").join("luanch.png.jpg"), b"thumb").unwrap();
+        std::fs::write(dir.join("agenda.png"), b"other").unwrap();
+        
+        rename_file_in(&dir, "luanch.png", "launch.png").await.unwrap();
+        assert!(!dir.join("luanch.png").exists());
+        assert_eq!(std::fs::read(dir.join("launch.png")).unwrap(), b"slide");
+        assert!(dir.join(".thumbnails").join("launch.png.jpg").exists());
+        
+        // Never overwrite another file
+        let err = rename_file_in(&dir, "launch.png", "agenda.png").await.unwrap_err();
+        assert!(matches!(err, AppError::Conflict(_)), "{:?}", err);
+        assert_eq!(std::fs::read(dir.join("agenda.png")).unwrap(), b"other");
+        
+        // A missing file is NotFound, so the API can answer 404
+        let err = rename_file_in(&dir, "missing.png", "found.png").await.unwrap_err();
+        assert_eq!(err, AppError::NotFound("File not found".to_string()));
+        assert!(matches!(
+            rename_file_in(&dir, "launch.png", "launch.mp4").await,
+            Err(AppError::Validation(_))
+        ));
+        
+        // Neither name may point outside the Media folder
+        assert!(rename_file_in(&dir, "launch.png", "../launch.png").await.is_err());
+        assert!(rename_file_in(&dir, "../config.json", "config.png").await.is_err());
+        assert!(dir.join("launch.png").exists());
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[tokio::test]
+    async fn test_new_formats_listed() {
+        let dir = std::env::temp_dir().join(format!("media-formats-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        for name in ["anim.gif", "photo.webp", "clip.webm", "clip.mov"] {
+            std::fs::write(dir.join(name), b"data").unwrap();
+        }
+        
+        let files = get_files_in(&dir, &[]).await.unwrap();
+        let types: Vec<(&str, &str)> = files.iter()
+            .map(|f| (f.name.as_str(), f.file_type.as_str()))
+            .collect();
+        
+        assert_eq!(types, vec![
+            ("anim.gif", "image"),
+            ("clip.mov", "video"),
+            ("clip.webm", "video"),
+            ("photo.webp", "image"),
+        ]);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_apply_exif_orientation() {
+        use image::ImageEncoder;
+        
+        let dir = std::env::temp_dir().join(format!("exif-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("phone.jpg");
+        
+        // Big-endian TIFF header with a single Orientation entry set to 6 (rotate 90° clockwise)
+        let exif = vec![
+            b'M', b'M', 0, 42, 0, 0, 0, 8,
+            0, 1,
+            0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0,
+            0, 0, 0, 0,
+        ];
+        
+        let source = image::RgbImage::from_pixel(40, 20, image::Rgb([200, 100, 50]));
+        let mut data = Vec::new();
+        let mut encoder = JpegEncoder::new(&mut data);
+        encoder.set_exif_metadata(exif).unwrap();
+        encoder.write_image(&source, 40, 20, image::ExtendedColorType::Rgb8).unwrap();
+        std::fs::write(&path, data).unwrap();
+        
+        apply_exif_orientation(&path).unwrap();
+        
+        assert_eq!(image::image_dimensions(&path).unwrap(), (20, 40));
+        let (_, orientation) = decode_upright(&path).unwrap();
+        assert_eq!(orientation, Orientation::NoTransforms);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[tokio::test]
+    async fn test_collection_listing() {
+        let root = std::env::temp_dir().join(format!("collections-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("lobby")).unwrap();
+        std::fs::create_dir_all(root.join(".thumbnails")).unwrap();
+        std::fs::write(root.join("welcome.png"), b"").unwrap();
+        std::fs::write(root.join("lobby").join("menu.jpg"), b"").unwrap();
+        
+        let default_dir = crate::paths::collection_dir(&root, None).unwrap();
+        let names = |files: Vec<MediaFile>| files.into_iter().map(|f| f.name).collect::<Vec<_>>();
+        assert_eq!(names(get_files_in(&default_dir, &[]).await.unwrap()), vec!["welcome.png"]);
+        
+        let lobby_dir = crate::paths::collection_dir(&root, Some("lobby")).unwrap();
+        assert_eq!(names(get_files_in(&lobby_dir, &[]).await.unwrap()), vec!["menu.jpg"]);
+        
+        assert_eq!(list_collections_in(&root).await.unwrap(), vec!["lobby"]);
+        
+        // A collection must stay inside the Media root
+        assert!(crate::paths::collection_dir(&root, Some("../elsewhere")).is_err());
+        assert!(crate::paths::collection_dir(&root, Some("/etc")).is_err());
+        assert!(crate::paths::collection_dir(&root, Some(".thumbnails")).is_err());
+        
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
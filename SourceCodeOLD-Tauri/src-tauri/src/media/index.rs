@@ -0,0 +1,128 @@
+use crate::error::AppError;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// File in each Media folder mapping the SHA-256 of uploaded bytes to the file holding them
+pub const INDEX_FILE: &str = ".index.json";
+
+/// Uploads finish concurrently, so every read-modify-write of an index holds this
+static INDEX_LOCK: Mutex<()> = Mutex::const_new(());
+
+type Index = HashMap<String, String>;
+
+fn index_path(media_dir: &Path) -> PathBuf {
+    media_dir.join(INDEX_FILE)
+}
+
+async fn load(media_dir: &Path) -> Index {
+    let data = match fs::read(index_path(media_dir)).await {
+        Ok(data) => data,
+        Err(_) => return Index::new(),
+    };
+    
+    // A broken index only costs deduplication, so start over rather than fail uploads
+    serde_json::from_slice(&data).unwrap_or_else(|e| {
+        warn!("Ignoring corrupt media index in {}: {}", media_dir.display(), e);
+        Index::new()
+    })
+}
+
+async fn save(media_dir: &Path, index: &Index) -> Result<(), AppError> {
+    let data = serde_json::to_vec_pretty(index)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    
+    let temp_path = media_dir.join(format!("{}.tmp", INDEX_FILE));
+    fs::write(&temp_path, data).await
+        .map_err(|e| AppError::Io(format!("Failed to write media index: {}", e)))?;
+    fs::rename(&temp_path, index_path(media_dir)).await
+        .map_err(|e| AppError::Io(format!("Failed to write media index: {}", e)))
+}
+
+/// The file already holding content with this hash, if it is still there
+pub async fn find(media_dir: &Path, hash: &str) -> Option<String> {
+    let _lock = INDEX_LOCK.lock().await;
+    
+    load(media_dir).await
+        .remove(hash)
+        .filter(|name| media_dir.join(name).is_file())
+}
+
+/// Remember that `filename` now holds content with `hash`, replacing what it held before
+pub async fn record(media_dir: &Path, hash: &str, filename: &str) -> Result<(), AppError> {
+    let _lock = INDEX_LOCK.lock().await;
+    
+    let mut index = load(media_dir).await;
+    index.retain(|_, name| name != filename);
+    index.insert(hash.to_string(), filename.to_string());
+    save(media_dir, &index).await
+}
+
+/// Drop the entry for a deleted file
+pub async fn forget(media_dir: &Path, filename: &str) -> Result<(), AppError> {
+    let _lock = INDEX_LOCK.lock().await;
+    
+    let mut index = load(media_dir).await;
+    let len = index.len();
+    index.retain(|_, name| name != filename);
+    
+    if index.len() == len {
+        return Ok(());
+    }
+    save(media_dir, &index).await
+}
+
+/// Point the entry for a renamed file at its new name
+pub async fn rename(media_dir: &Path, old_name: &str, new_name: &str) -> Result<(), AppError> {
+    let _lock = INDEX_LOCK.lock().await;
+    
+    let mut index = load(media_dir).await;
+    let mut changed = false;
+    for name in index.values_mut().filter(|name| *name == old_name) {
+        *name = new_name.to_string();
+        changed = true;
+    }
+    
+    if !changed {
+        return Ok(());
+    }
+    save(media_dir, &index).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[tokio::test]
+    async fn test_index_follows_files() {
+        let dir = std::env::temp_dir().join(format!("media-index-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.png"), b"a").unwrap();
+        
+        record(&dir, "hash-a", "a.png").await.unwrap();
+        assert_eq!(find(&dir, "hash-a").await.as_deref(), Some("a.png"));
+        assert_eq!(find(&dir, "hash-b").await, None);
+        
+        // Overwriting a file with new content replaces its old hash
+        record(&dir, "hash-a2", "a.png").await.unwrap();
+        assert_eq!(find(&dir, "hash-a").await, None);
+        
+        std::fs::rename(dir.join("a.png"), dir.join("b.png")).unwrap();
+        rename(&dir, "a.png", "b.png").await.unwrap();
+        assert_eq!(find(&dir, "hash-a2").await.as_deref(), Some("b.png"));
+        
+        forget(&dir, "b.png").await.unwrap();
+        assert_eq!(find(&dir, "hash-a2").await, None);
+        
+        // Files removed behind the index's back are not handed out
+        std::fs::write(dir.join("c.png"), b"c").unwrap();
+        record(&dir, "hash-c", "c.png").await.unwrap();
+        std::fs::remove_file(dir.join("c.png")).unwrap();
+        assert_eq!(find(&dir, "hash-c").await, None);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::error::AppError;
+use image::{ImageDecoder, ImageReader};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// Most files whose details are remembered; the cache starts over past this
+const MAX_CACHED: usize = 1024;
+
+/// Details already probed, with the modification time they were probed at
+static CACHE: Mutex<BTreeMap<PathBuf, (SystemTime, Metadata)>> = Mutex::new(BTreeMap::new());
+
+/// Everything we can tell about one media file; fields that couldn't be read are left out
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub file_type: String,
+    pub size: u64,
+    pub modified: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Image format or video container, e.g. "png" or "mov,mp4,m4a,3gp,3g2,mj2"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Pixel layout of an image, e.g. "Rgba8"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_alpha: Option<bool>,
+    /// Whether an image embeds an ICC color profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_color_profile: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_codec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_codec: Option<String>,
+    /// Bits per second across all streams
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bit_rate: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_rate: Option<f64>,
+}
+
+/// Probe a media file, reusing the last result while the file is unchanged.
+/// Videos are read with ffprobe when it is installed, otherwise from MP4 headers.
+pub async fn read(path: &Path) -> Result<Metadata, AppError> {
+    let file_metadata = tokio::fs::metadata(path).await?;
+    if !file_metadata.is_file() {
+        return Err(AppError::NotFound(format!("File not found: {}", path.display())));
+    }
+    let modified = file_metadata.modified()?;
+    
+    if let Some((probed_at, metadata)) = CACHE.lock().unwrap().get(path) {
+        if *probed_at == modified {
+            return Ok(metadata.clone());
+        }
+    }
+    
+    let ext = path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let file_type = super::media_type(&ext)
+        .ok_or_else(|| AppError::Validation(format!("Not a media file: {}", path.display())))?;
+    
+    let mut metadata = Metadata {
+        name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        file_type: file_type.to_string(),
+        size: file_metadata.len(),
+        modified: super::format_modified(modified),
+        ..Metadata::default()
+    };
+    
+    if file_type == "image" {
+        let image_path = path.to_path_buf();
+        metadata = tokio::task::spawn_blocking(move || {
+            if let Err(e) = probe_image(&image_path, &mut metadata) {
+                debug!("Could not read image details of {}: {}", image_path.display(), e);
+            }
+            metadata
+        }).await
+            .map_err(|e| AppError::Io(format!("Image probe failed: {}", e)))?;
+    } else {
+        probe_video(path, &mut metadata).await;
+    }
+    
+    let mut cache = CACHE.lock().unwrap();
+    if cache.len() >= MAX_CACHED {
+        cache.clear();
+    }
+    cache.insert(path.to_path_buf(), (modified, metadata.clone()));
+    
+    Ok(metadata)
+}
+
+/// Fill in size, format and color details from an image's header, without decoding the pixels
+fn probe_image(path: &Path, metadata: &mut Metadata) -> Result<(), String> {
+    let reader = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| e.to_string())?;
+    let format = reader.format()
+        .and_then(|format| format.extensions_str().first().copied())
+        .map(str::to_string);
+    
+    let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+    let (width, height) = decoder.dimensions();
+    let color_type = decoder.color_type();
+    
+    metadata.width = Some(width);
+    metadata.height = Some(height);
+    metadata.format = format;
+    metadata.color_type = Some(format!("{:?}", color_type));
+    metadata.has_alpha = Some(color_type.has_alpha());
+    metadata.has_color_profile = decoder.icc_profile().ok().map(|profile| profile.is_some());
+    Ok(())
+}
+
+/// Fill in a video's details from ffprobe, falling back to what the MP4 headers say
+async fn probe_video(path: &Path, metadata: &mut Metadata) {
+    if let Some(ffprobe) = crate::capabilities::ffprobe_path() {
+        match run_ffprobe(&ffprobe, path).await {
+            Ok(output) => {
+                apply_ffprobe(metadata, &output);
+                return;
+            }
+            Err(e) => warn!("ffprobe failed on {}: {}", path.display(), e),
+        }
+    }
+    
+    let header_path = path.to_path_buf();
+    let info = tokio::task::spawn_blocking(move || super::probe::probe(&header_path)).await
+        .unwrap_or_default();
+    metadata.width = info.width;
+    metadata.height = info.height;
+    metadata.duration_secs = info.duration_secs;
+}
+
+async fn run_ffprobe(ffprobe: &Path, path: &Path) -> Result<serde_json::Value, String> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-print_format", "json", "-show_format", "-show_streams"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe exited with {}: {}", output.status, stderr.trim()));
+    }
+    
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("Unreadable ffprobe output: {}", e))
+}
+
+/// Copy the interesting parts of `ffprobe -show_format -show_streams` JSON into `metadata`.
+/// ffprobe prints most numbers as strings, and frame rates as fractions like "30000/1001".
+fn apply_ffprobe(metadata: &mut Metadata, output: &serde_json::Value) {
+    let number = |value: &serde_json::Value| -> Option<f64> {
+        value.as_f64().or_else(|| value.as_str()?.parse().ok())
+    };
+    let streams = output["streams"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let stream = |kind: &str| streams.iter().find(|s| s["codec_type"] == kind);
+    
+    metadata.format = output["format"]["format_name"].as_str().map(str::to_string);
+    metadata.duration_secs = number(&output["format"]["duration"]);
+    metadata.bit_rate = number(&output["format"]["bit_rate"]).map(|rate| rate as u64);
+    
+    if let Some(video) = stream("video") {
+        metadata.video_codec = video["codec_name"].as_str().map(str::to_string);
+        metadata.width = video["width"].as_u64().and_then(|w| u32::try_from(w).ok());
+        metadata.height = video["height"].as_u64().and_then(|h| u32::try_from(h).ok());
+        metadata.frame_rate = video["avg_frame_rate"].as_str()
+            .and_then(|rate| rate.split_once('/'))
+            .and_then(|(num, den)| Some((num.parse::<f64>().ok()?, den.parse::<f64>().ok()?)))
+            .filter(|(_, den)| *den > 0.0)
+            .map(|(num, den)| (num / den * 1000.0).round() / 1000.0);
+    }
+    
+    metadata.audio_codec = stream("audio")
+        .and_then(|audio| audio["codec_name"].as_str())
+        .map(str::to_string);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_apply_ffprobe() {
+        let output = serde_json::json!({
+            "streams": [
+                { "codec_type": "audio", "codec_name": "aac" },
+                { "codec_type": "video", "codec_name": "h264", "width": 1920, "height": 1080, "avg_frame_rate": "30000/1001" }
+            ],
+            "format": { "format_name": "mov,mp4,m4a,3gp,3g2,mj2", "duration": "12.500000", "bit_rate": "4800000" }
+        });
+        
+        let mut metadata = Metadata::default();
+        apply_ffprobe(&mut metadata, &output);
+        assert_eq!((metadata.width, metadata.height), (Some(1920), Some(1080)));
+        assert_eq!(metadata.video_codec.as_deref(), Some("h264"));
+        assert_eq!(metadata.audio_codec.as_deref(), Some("aac"));
+        assert_eq!(metadata.duration_secs, Some(12.5));
+        assert_eq!(metadata.bit_rate, Some(4_800_000));
+        assert_eq!(metadata.frame_rate, Some(29.97));
+    }
+}
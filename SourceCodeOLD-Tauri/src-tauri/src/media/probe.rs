@@ -0,0 +1,200 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Largest `moov` box we are willing to read into memory
+const MAX_MOOV_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Intrinsic size and length of a media file, where they could be read cheaply
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MediaInfo {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub duration_secs: Option<f64>,
+}
+
+/// Read dimensions (and for MP4/MOV, the duration) from file headers without decoding
+pub fn probe(path: &Path) -> MediaInfo {
+    let ext = path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    
+    match ext.as_str() {
+        "png" | "jpg" | "jpeg" | "gif" | "webp" => match image::image_dimensions(path) {
+            Ok((width, height)) => MediaInfo {
+                width: Some(width),
+                height: Some(height),
+                duration_secs: None,
+            },
+            Err(_) => MediaInfo::default(),
+        },
+        "mp4" | "mov" => probe_mp4(path).unwrap_or_default(),
+        _ => MediaInfo::default(),
+    }
+}
+
+/// Find the `moov` box and read the movie header and first video track header from it
+fn probe_mp4(path: &Path) -> Option<MediaInfo> {
+    let mut file = File::open(path).ok()?;
+    let file_len = file.metadata().ok()?.len();
+    let mut offset = 0;
+    
+    // Top-level boxes can be huge (mdat), so seek past them instead of reading
+    while offset + 8 <= file_len {
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8]).ok()?;
+        
+        let mut size = u32::from_be_bytes(header[0..4].try_into().ok()?) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            file.read_exact(&mut header[8..16]).ok()?;
+            size = u64::from_be_bytes(header[8..16].try_into().ok()?);
+            header_len = 16;
+        } else if size == 0 {
+            size = file_len - offset;
+        }
+        
+        if size < header_len {
+            return None;
+        }
+        
+        if &header[4..8] == b"moov" {
+            if size > MAX_MOOV_SIZE {
+                return None;
+            }
+            let mut moov = vec![0u8; (size - header_len) as usize];
+            file.read_exact(&mut moov).ok()?;
+            return Some(parse_moov(&moov));
+        }
+        
+        offset += size;
+    }
+    
+    None
+}
+
+/// Iterate the child boxes of a box's payload as (type, payload) pairs
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 8 {
+            return None;
+        }
+        
+        let size = u32::from_be_bytes(data[0..4].try_into().ok()?) as usize;
+        let size = if size == 0 { data.len() } else { size };
+        if size < 8 || size > data.len() {
+            return None;
+        }
+        
+        let (current, rest) = data.split_at(size);
+        data = rest;
+        Some((&current[4..8], &current[8..]))
+    })
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn parse_moov(moov: &[u8]) -> MediaInfo {
+    let mut info = MediaInfo::default();
+    
+    for (kind, payload) in boxes(moov) {
+        match kind {
+            b"mvhd" => info.duration_secs = parse_mvhd(payload),
+            b"trak" if info.width.is_none() => {
+                let tkhd = boxes(payload).find(|(kind, _)| *kind == b"tkhd");
+                
+                // Audio tracks report a size of zero
+                if let Some((width, height)) = tkhd.and_then(|(_, tkhd)| parse_tkhd(tkhd)) {
+                    if width > 0 && height > 0 {
+                        info.width = Some(width);
+                        info.height = Some(height);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    info
+}
+
+fn parse_mvhd(mvhd: &[u8]) -> Option<f64> {
+    let (timescale, duration) = match mvhd.first()? {
+        0 => (read_u32(mvhd, 12)?, read_u32(mvhd, 16)? as u64),
+        1 => (read_u32(mvhd, 20)?, read_u64(mvhd, 24)?),
+        _ => return None,
+    };
+    
+    if timescale == 0 {
+        return None;
+    }
+    
+    Some(duration as f64 / timescale as f64)
+}
+
+/// Track width and height, stored as 16.16 fixed point
+fn parse_tkhd(tkhd: &[u8]) -> Option<(u32, u32)> {
+    let at = match tkhd.first()? {
+        0 => 76,
+        1 => 88,
+        _ => return None,
+    };
+    
+    Some((read_u32(tkhd, at)? >> 16, read_u32(tkhd, at + 4)? >> 16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+    
+    #[test]
+    fn test_probe() {
+        let dir = std::env::temp_dir().join(format!("probe-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        image::RgbImage::new(64, 48).save(dir.join("still.png")).unwrap();
+        let info = probe(&dir.join("still.png"));
+        assert_eq!((info.width, info.height, info.duration_secs), (Some(64), Some(48), None));
+        
+        // Unsupported or unreadable files report nothing
+        std::fs::write(dir.join("notes.txt"), "hello").unwrap();
+        assert_eq!(probe(&dir.join("notes.txt")), MediaInfo::default());
+        std::fs::write(dir.join("broken.png"), "not a png").unwrap();
+        assert_eq!(probe(&dir.join("broken.png")), MediaInfo::default());
+        
+        // A 12.5 second 1920x1080 movie: mvhd with timescale 1000, one video tkhd
+        let mut mvhd = vec![0u8; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&12500u32.to_be_bytes());
+        let mut tkhd = vec![0u8; 84];
+        tkhd[76..80].copy_from_slice(&(1920u32 << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(1080u32 << 16).to_be_bytes());
+        
+        let mut moov = mp4_box(b"mvhd", &mvhd);
+        moov.extend(mp4_box(b"trak", &mp4_box(b"tkhd", &tkhd)));
+        let mut movie = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        movie.extend(mp4_box(b"mdat", &[0u8; 32]));
+        movie.extend(mp4_box(b"moov", &moov));
+        std::fs::write(dir.join("clip.mp4"), movie).unwrap();
+        
+        let info = probe(&dir.join("clip.mp4"));
+        assert_eq!((info.width, info.height), (Some(1920), Some(1080)));
+        assert_eq!(info.duration_secs, Some(12.5));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
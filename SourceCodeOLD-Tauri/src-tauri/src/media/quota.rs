@@ -0,0 +1,83 @@
+use crate::error::AppError;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::debug;
+
+/// Bytes of media stored under each Media folder, collections included. Scanned on first
+/// use, then kept up to date as uploads land and files are deleted.
+static USED_BYTES: Mutex<BTreeMap<PathBuf, u64>> = Mutex::new(BTreeMap::new());
+
+/// Bytes of media currently stored under `media_root`
+pub async fn used_bytes(media_root: &Path) -> Result<u64, AppError> {
+    if let Some(used) = USED_BYTES.lock().unwrap().get(media_root) {
+        return Ok(*used);
+    }
+    
+    let root = media_root.to_path_buf();
+    let (_, scanned) = tokio::task::spawn_blocking(move || crate::system::media_totals(&root)).await
+        .map_err(|e| AppError::Io(format!("Media size scan failed: {}", e)))?
+        .map_err(AppError::Io)?;
+    debug!("Media folder {} holds {} bytes", media_root.display(), scanned);
+    
+    // Another upload may have scanned and counted itself in the meantime
+    Ok(*USED_BYTES.lock().unwrap().entry(media_root.to_path_buf()).or_insert(scanned))
+}
+
+/// Refuse `bytes` up front if they can't fit under `max_total_bytes`, without counting them yet
+pub async fn check(media_root: &Path, bytes: u64, max_total_bytes: u64) -> Result<(), AppError> {
+    if max_total_bytes == 0 {
+        return Ok(());
+    }
+    
+    let used = used_bytes(media_root).await?;
+    if used + bytes > max_total_bytes {
+        return Err(no_room(bytes, used, max_total_bytes));
+    }
+    
+    Ok(())
+}
+
+/// Count `bytes` about to be stored, refusing them if that would pass `max_total_bytes`.
+/// A limit of 0 means unlimited.
+pub(super) async fn reserve(media_root: &Path, bytes: u64, max_total_bytes: u64) -> Result<(), AppError> {
+    if max_total_bytes == 0 {
+        // Only keep an existing count current; nothing needs the total yet
+        if let Some(used) = USED_BYTES.lock().unwrap().get_mut(media_root) {
+            *used += bytes;
+        }
+        return Ok(());
+    }
+    
+    used_bytes(media_root).await?;
+    
+    let mut sizes = USED_BYTES.lock().unwrap();
+    let used = sizes.entry(media_root.to_path_buf()).or_default();
+    if *used + bytes > max_total_bytes {
+        return Err(no_room(bytes, *used, max_total_bytes));
+    }
+    
+    *used += bytes;
+    Ok(())
+}
+
+fn no_room(bytes: u64, used: u64, max_total_bytes: u64) -> AppError {
+    AppError::QuotaExceeded(format!(
+        "Not enough media storage: the file needs {} bytes but only {} of the {} byte limit are free",
+        bytes,
+        max_total_bytes.saturating_sub(used),
+        max_total_bytes
+    ))
+}
+
+/// Stop counting `bytes` that were deleted or never stored after all
+pub(super) fn release(media_root: &Path, bytes: u64) {
+    if let Some(used) = USED_BYTES.lock().unwrap().get_mut(media_root) {
+        *used = used.saturating_sub(bytes);
+    }
+}
+
+/// Forget every count, e.g. after files were written without going through an upload
+pub fn invalidate() {
+    USED_BYTES.lock().unwrap().clear();
+}
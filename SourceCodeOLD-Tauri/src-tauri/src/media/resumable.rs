@@ -0,0 +1,273 @@
+use crate::error::AppError;
+use futures_util::{Stream, StreamExt};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
+
+use super::video::VideoTranscode;
+use super::{PendingUpload, SavedUpload, Transcode};
+
+/// Folder inside Media holding uploads that are still arriving; hidden, so it never shows up as a collection
+const UPLOADS_DIR: &str = ".uploads";
+
+/// Unfinished uploads nobody has added to for this long are thrown away
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Uploads a request is currently writing to, so two requests never append to the same one
+static BUSY: std::sync::Mutex<BTreeSet<String>> = std::sync::Mutex::new(BTreeSet::new());
+
+/// An upload sent in pieces, each one appended to `<id>.part`. This is saved beside it
+/// as `<id>.json` so the upload outlives the request that started it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumableUpload {
+    pub id: String,
+    pub filename: String,
+    pub collection: Option<String>,
+    /// Size the client announced; the upload is finished once this many bytes have arrived
+    pub size: u64,
+}
+
+/// What appending a piece led to
+#[derive(Debug)]
+pub enum Appended {
+    /// More bytes are expected, starting at this offset
+    Partial(u64),
+    /// The last piece arrived and the file was moved into its Media folder
+    Complete(SavedUpload),
+}
+
+/// Why a piece was not appended
+#[derive(Debug)]
+pub enum AppendRejection {
+    NotFound,
+    /// Another request is already sending this upload
+    Busy,
+    /// The piece doesn't start where the upload left off, which is this offset
+    OffsetMismatch(u64),
+    /// The piece would run past the announced size
+    TooLarge,
+    /// The finished file failed the usual upload checks, or couldn't be moved into place,
+    /// and was discarded
+    Rejected(AppError),
+    Failed(String),
+}
+
+/// Marks an upload busy until dropped
+struct Claim(String);
+
+impl Claim {
+    fn take(id: &str) -> Option<Self> {
+        BUSY.lock().unwrap()
+            .insert(id.to_string())
+            .then(|| Claim(id.to_string()))
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        BUSY.lock().unwrap().remove(&self.0);
+    }
+}
+
+fn uploads_dir() -> Result<PathBuf, AppError> {
+    Ok(crate::paths::get_media_dir(None)?.join(UPLOADS_DIR))
+}
+
+/// The `.part` and `.json` files for an upload id, or None if the id can't be one of ours
+fn upload_paths(id: &str) -> Result<Option<(PathBuf, PathBuf)>, AppError> {
+    if id.is_empty() || id.len() > 64 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(None);
+    }
+    
+    let dir = uploads_dir()?;
+    Ok(Some((dir.join(format!("{}.part", id)), dir.join(format!("{}.json", id)))))
+}
+
+/// Start an upload of `size` bytes. The name, collection and storage limit are checked now,
+/// so a bad upload is refused before any data is sent.
+pub async fn create(
+    collection: Option<&str>,
+    filename: &str,
+    size: u64,
+    max_total_bytes: u64,
+) -> Result<ResumableUpload, AppError> {
+    let filename = super::check_upload_name(filename)?;
+    // Only resolved to refuse collection names that would leave the Media folder
+    crate::paths::get_media_dir(collection)?;
+    
+    if size == 0 {
+        return Err(AppError::Validation("Corrupt or truncated upload: file is empty".to_string()));
+    }
+    
+    super::quota::check(&crate::paths::get_media_dir(None)?, size, max_total_bytes).await?;
+    
+    prune_stale().await;
+    
+    let dir = uploads_dir()?;
+    fs::create_dir_all(&dir).await
+        .map_err(|e| AppError::Io(format!("Failed to create {:?}: {}", dir, e)))?;
+    
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let id: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    
+    let upload = ResumableUpload {
+        id: id.clone(),
+        filename,
+        collection: collection.filter(|name| !name.is_empty()).map(str::to_string),
+        size,
+    };
+    
+    let (part_path, meta_path) = upload_paths(&id)?
+        .ok_or_else(|| AppError::Validation("Invalid upload id".to_string()))?;
+    let meta = serde_json::to_vec(&upload)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    fs::write(&part_path, b"").await?;
+    fs::write(&meta_path, meta).await?;
+    
+    info!("Started resumable upload {} for {} ({} bytes)", id, upload.filename, size);
+    Ok(upload)
+}
+
+/// An unfinished upload and how many of its bytes have arrived so far
+pub async fn find(id: &str) -> Result<Option<(ResumableUpload, u64)>, AppError> {
+    let Some((part_path, meta_path)) = upload_paths(id)? else {
+        return Ok(None);
+    };
+    
+    let (Ok(meta), Ok(part)) = (fs::read(&meta_path).await, fs::metadata(&part_path).await) else {
+        return Ok(None);
+    };
+    
+    let upload = serde_json::from_slice(&meta)
+        .map_err(|e| AppError::Io(format!("Corrupt upload record {}: {}", id, e)))?;
+    Ok(Some((upload, part.len())))
+}
+
+/// Append a piece that must start at `offset`. Whatever arrives before the connection
+/// drops is kept, so the client can ask for the offset again and carry on from there.
+/// Once the announced size is reached the file goes through the same checks as a
+/// normal upload and is moved into place.
+pub async fn append<S, B, E>(
+    id: &str,
+    offset: u64,
+    mut body: S,
+    transcode: Option<Transcode>,
+    video: Option<VideoTranscode>,
+    max_total_bytes: u64,
+) -> Result<Appended, AppendRejection>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    let _claim = Claim::take(id).ok_or(AppendRejection::Busy)?;
+    
+    let (upload, current) = find(id).await
+        .map_err(|e| AppendRejection::Failed(e.to_string()))?
+        .ok_or(AppendRejection::NotFound)?;
+    if offset != current {
+        return Err(AppendRejection::OffsetMismatch(current));
+    }
+    
+    let (part_path, meta_path) = upload_paths(id)
+        .map_err(|e| AppendRejection::Failed(e.to_string()))?
+        .ok_or(AppendRejection::NotFound)?;
+    let mut file = fs::OpenOptions::new().append(true).open(&part_path).await
+        .map_err(|e| AppendRejection::Failed(e.to_string()))?;
+    
+    let mut written = current;
+    let mut outcome = Ok(());
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                outcome = Err(AppendRejection::Failed(format!("Upload interrupted: {}", e)));
+                break;
+            }
+        };
+        let chunk = chunk.as_ref();
+        
+        if written + chunk.len() as u64 > upload.size {
+            outcome = Err(AppendRejection::TooLarge);
+            break;
+        }
+        
+        if let Err(e) = file.write_all(chunk).await {
+            outcome = Err(AppendRejection::Failed(e.to_string()));
+            break;
+        }
+        written += chunk.len() as u64;
+    }
+    
+    // Keep what made it to disk even if the piece was cut short
+    if let Err(e) = file.flush().await {
+        warn!("Failed to flush upload {}: {}", id, e);
+    }
+    drop(file);
+    outcome?;
+    debug!("Upload {} at {} of {} bytes", id, written, upload.size);
+    
+    if written < upload.size {
+        return Ok(Appended::Partial(written));
+    }
+    
+    // The upload is used up from here on, whether or not the file passes
+    let _ = fs::remove_file(&meta_path).await;
+    
+    let pending = match PendingUpload::adopt(upload.collection.as_deref(), &upload.filename, &part_path).await {
+        Ok(pending) => pending,
+        Err(e) => {
+            let _ = fs::remove_file(&part_path).await;
+            return Err(AppendRejection::Rejected(e));
+        }
+    };
+    
+    pending.transcode(transcode)
+        .transcode_video(video)
+        .max_total_bytes(max_total_bytes)
+        .finish().await
+        .map(Appended::Complete)
+        .map_err(AppendRejection::Rejected)
+}
+
+/// Throw away uploads that were started but not added to in a long while
+async fn prune_stale() {
+    let Ok(dir) = uploads_dir() else {
+        return;
+    };
+    let Ok(mut entries) = fs::read_dir(&dir).await else {
+        return;
+    };
+    
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("part") {
+            continue;
+        }
+        
+        let stale = entry.metadata().await.ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_AFTER);
+        if !stale {
+            continue;
+        }
+        
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let Some(_claim) = Claim::take(id) else {
+            continue;
+        };
+        
+        info!("Discarding abandoned upload {}", id);
+        let _ = fs::remove_file(&path).await;
+        let _ = fs::remove_file(path.with_extension("json")).await;
+    }
+}
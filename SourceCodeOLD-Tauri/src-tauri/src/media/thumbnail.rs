@@ -0,0 +1,130 @@
+use crate::error::AppError;
+use image::codecs::jpeg::JpegEncoder;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Folder inside the Media directory where thumbnails are cached
+pub const THUMBNAIL_DIR: &str = ".thumbnails";
+
+/// Default longest side for grid previews in the web UI
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+
+/// Get (or create) a JPEG thumbnail for an image in the Media directory
+pub async fn generate_thumbnail(filename: &str, max_dim: u32, quality: u8) -> Result<PathBuf, AppError> {
+    let media_dir = super::get_media_dir()?;
+    let filename = filename.to_string();
+    
+    tokio::task::spawn_blocking(move || generate_thumbnail_in(&media_dir, &filename, max_dim, quality))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?
+}
+
+/// Drop every cached thumbnail, e.g. after the quality changes; they are remade on request
+pub async fn clear_thumbnails() {
+    let Ok(media_dir) = super::get_media_dir() else {
+        return;
+    };
+    
+    match tokio::fs::remove_dir_all(media_dir.join(THUMBNAIL_DIR)).await {
+        Ok(()) => debug!("Cleared cached thumbnails"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to clear cached thumbnails: {}", e),
+    }
+}
+
+/// Path of the cached thumbnail for a media file
+pub fn thumbnail_path(media_dir: &Path, filename: &str) -> PathBuf {
+    media_dir.join(THUMBNAIL_DIR).join(format!("{}.jpg", filename))
+}
+
+fn generate_thumbnail_in(media_dir: &Path, filename: &str, max_dim: u32, quality: u8) -> Result<PathBuf, AppError> {
+    let filename = &super::sanitize_filename(filename)?;
+    let source_path = media_dir.join(filename);
+    
+    if !source_path.starts_with(media_dir) {
+        return Err(AppError::Validation("Invalid file path".to_string()));
+    }
+    
+    if !source_path.is_file() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    
+    let ext = source_path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    
+    // SVGs scale on their own and videos aren't handled yet
+    if !matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "gif" | "webp") {
+        return Err(AppError::NotFound("Thumbnails are not available for this file type".to_string()));
+    }
+    
+    let thumb_path = thumbnail_path(media_dir, filename);
+    
+    if is_up_to_date(&source_path, &thumb_path) {
+        return Ok(thumb_path);
+    }
+    
+    // Files added before uploads were normalized may still carry an orientation tag
+    let (image, _) = super::decode_upright(&source_path)?;
+    let thumb = image.thumbnail(max_dim, max_dim).to_rgb8();
+    
+    if let Some(parent) = thumb_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::Io(format!("Failed to create thumbnail directory: {}", e)))?;
+    }
+    
+    let file = std::fs::File::create(&thumb_path)
+        .map_err(|e| AppError::Io(format!("Failed to create thumbnail: {}", e)))?;
+    let mut writer = std::io::BufWriter::new(file);
+    
+    JpegEncoder::new_with_quality(&mut writer, quality.clamp(1, 100))
+        .encode_image(&thumb)
+        .map_err(|e| AppError::Io(format!("Failed to encode thumbnail: {}", e)))?;
+    
+    debug!("Generated thumbnail for {}", filename);
+    Ok(thumb_path)
+}
+
+/// A thumbnail is reused while it is newer than its source
+fn is_up_to_date(source: &Path, thumb: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    
+    match (modified(source), modified(thumb)) {
+        (Some(source_time), Some(thumb_time)) => thumb_time >= source_time,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_generate_thumbnail() {
+        let dir = std::env::temp_dir().join(format!("thumb-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        // Noisy content so the source doesn't compress down to nothing
+        let source = image::RgbImage::from_fn(1024, 768, |x, y| {
+            image::Rgb([((x * 7) ^ (y * 13)) as u8, (x * y) as u8, (x + y * 3) as u8])
+        });
+        source.save(dir.join("photo.png")).unwrap();
+        
+        let thumb = generate_thumbnail_in(&dir, "photo.png", DEFAULT_THUMBNAIL_SIZE, 80).unwrap();
+        assert!(thumb.exists());
+        
+        let original_size = std::fs::metadata(dir.join("photo.png")).unwrap().len();
+        let thumb_size = std::fs::metadata(&thumb).unwrap().len();
+        assert!(thumb_size < original_size);
+        
+        let (width, height) = image::image_dimensions(&thumb).unwrap();
+        assert_eq!((width, height), (256, 192));
+        
+        // A lower quality makes a smaller thumbnail once the cached one is cleared
+        std::fs::remove_file(&thumb).unwrap();
+        let low = generate_thumbnail_in(&dir, "photo.png", DEFAULT_THUMBNAIL_SIZE, 20).unwrap();
+        assert!(std::fs::metadata(&low).unwrap().len() < thumb_size);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Converting uploaded videos the display can't play into H.264 MP4
+#[derive(Debug, Clone)]
+pub struct VideoTranscode {
+    /// None when no ffmpeg was found; such videos are kept as uploaded
+    pub ffmpeg: Option<PathBuf>,
+}
+
+impl VideoTranscode {
+    /// Whether the config asks for video transcoding, with the ffmpeg found at startup
+    pub fn from_config(cfg: &crate::config::Config) -> Option<Self> {
+        cfg.transcode_video.then(|| Self {
+            ffmpeg: crate::capabilities::ffmpeg_path(),
+        })
+    }
+}
+
+/// QuickTime and Matroska files often hold codecs the webview can't decode
+pub fn needs_transcode(filename: &str) -> bool {
+    let ext = Path::new(filename).extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    
+    matches!(ext.as_str(), "mov" | "mkv")
+}
+
+/// Convert a finished upload into an MP4 temp file next to it, returning the new file name
+/// and temp path. Failures are only logged, since the original is still a good upload.
+pub(super) async fn transcode_upload(temp_path: &Path, filename: &str, video: VideoTranscode) -> Option<(String, PathBuf)> {
+    let Some(ffmpeg) = video.ffmpeg else {
+        warn!("Keeping {} as uploaded: video transcoding is on but ffmpeg was not found", filename);
+        return None;
+    };
+    
+    let new_name = Path::new(filename).with_extension("mp4").to_string_lossy().to_string();
+    let new_temp = temp_path.with_file_name(format!(".{}.upload", new_name));
+    
+    match run_ffmpeg(&ffmpeg, temp_path, &new_temp).await {
+        Ok(()) => {
+            info!("Transcoded {} to {}", filename, new_name);
+            Some((new_name, new_temp))
+        }
+        Err(e) => {
+            warn!("Failed to transcode {} to MP4, keeping the original: {}", filename, e);
+            let _ = tokio::fs::remove_file(&new_temp).await;
+            None
+        }
+    }
+}
+
+async fn run_ffmpeg(ffmpeg: &Path, source: &Path, dest: &Path) -> Result<(), String> {
+    let output = Command::new(ffmpeg)
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-i"])
+        .arg(source)
+        .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "23", "-pix_fmt", "yuv420p"])
+        // Move the index to the front so playback can start before the whole file loads
+        .args(["-c:a", "aac", "-movflags", "+faststart", "-f", "mp4"])
+        .arg(dest)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        // A dropped upload request shouldn't leave ffmpeg running
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffmpeg exited with {}: {}", output.status, stderr.trim()));
+    }
+    
+    if !dest.is_file() {
+        return Err("ffmpeg produced no output".to_string());
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_transcode_upload_with_stub_ffmpeg() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let dir = std::env::temp_dir().join(format!("video-transcode-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        // Stands in for ffmpeg: copies the -i input to the last argument
+        let stub = dir.join("ffmpeg");
+        std::fs::write(&stub, "#!/bin/sh\nwhile [ $# -gt 1 ]; do [ \"$1\" = \"-i\" ] && src=\"$2\"; shift; done\ncp \"$src\" \"$1\"\n").unwrap();
+        std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+        
+        let upload = dir.join(".clip.MOV.upload");
+        std::fs::write(&upload, b"movie").unwrap();
+        
+        let video = VideoTranscode { ffmpeg: Some(stub) };
+        let (name, temp) = transcode_upload(&upload, "clip.MOV", video).await.unwrap();
+        assert_eq!(name, "clip.mp4");
+        assert_eq!(std::fs::read(&temp).unwrap(), b"movie");
+        
+        // Without ffmpeg the original is kept
+        assert!(transcode_upload(&upload, "clip.MOV", VideoTranscode { ffmpeg: None }).await.is_none());
+        
+        assert!(needs_transcode("clip.mkv"));
+        assert!(!needs_transcode("clip.mp4"));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
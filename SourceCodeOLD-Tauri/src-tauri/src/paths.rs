@@ -1,29 +1,44 @@
-use std::path::PathBuf;
+use crate::error::AppError;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Environment variable pointing at the config file to use instead of `config.json` in the app dir
+pub const CONFIG_PATH_VAR: &str = "SIGNAGE_CONFIG_PATH";
+
+/// Environment variable pointing at the folder holding Media, Fonts and Addons
+pub const DATA_DIR_VAR: &str = "SIGNAGE_DATA_DIR";
 
 /// Get the base application directory
+/// IMAGE_PRESENTER_DIR, if set, takes precedence
 /// In dev mode: project root (parent of src-tauri)
 /// In production: directory containing the executable (for portable deployment)
-pub fn get_app_dir() -> Result<PathBuf, String> {
+pub fn get_app_dir() -> Result<PathBuf, AppError> {
+    // Explicit override, e.g. to keep data outside the install folder
+    if let Some(dir) = std::env::var_os("IMAGE_PRESENTER_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    
     if cfg!(debug_assertions) {
         // Development mode
-        let current = std::env::current_dir().map_err(|e| e.to_string())?;
+        let current = std::env::current_dir()?;
         
         // If we're in src-tauri, go up one level to project root
         if current.ends_with("src-tauri") {
             let parent = current.parent()
-                .ok_or("No parent directory")?
+                .ok_or_else(|| AppError::Io("No parent directory".to_string()))?
                 .to_path_buf();
-            println!("DEBUG Dev: App dir = {:?}", parent);
+            debug!("Dev: App dir = {:?}", parent);
             Ok(parent)
         } else {
-            println!("DEBUG Dev: App dir = {:?}", current);
+            debug!("Dev: App dir = {:?}", current);
             Ok(current)
         }
     } else {
         // Production mode - use directory containing the executable
-        let exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
+        let exe_path = std::env::current_exe()?;
         let exe_dir = exe_path.parent()
-            .ok_or("Failed to get parent directory")?
+            .ok_or_else(|| AppError::Io("Failed to get parent directory".to_string()))?
             .to_path_buf();
         
         // Remove the \\?\ prefix if present (Windows UNC path)
@@ -38,41 +53,137 @@ pub fn get_app_dir() -> Result<PathBuf, String> {
             exe_dir
         };
         
-        println!("DEBUG Prod: Exe path = {:?}", exe_path);
-        println!("DEBUG Prod: App dir = {:?}", clean_path);
+        debug!("Prod: Exe path = {:?}", exe_path);
+        debug!("Prod: App dir = {:?}", clean_path);
         Ok(clean_path)
     }
 }
 
-/// Get the Media directory path
-pub fn get_media_dir() -> Result<PathBuf, String> {
-    let base = get_app_dir()?;
-    let media = base.join("Media");
-    println!("DEBUG: Media dir = {:?}", media);
+/// Get the folder holding Media, Fonts and Addons
+/// SIGNAGE_DATA_DIR, if set, takes precedence; otherwise it is the app dir
+pub fn get_data_dir() -> Result<PathBuf, AppError> {
+    data_dir_from(|name| std::env::var_os(name))
+}
+
+fn data_dir_from(env: impl Fn(&str) -> Option<OsString>) -> Result<PathBuf, AppError> {
+    match env_path(env(DATA_DIR_VAR))? {
+        Some(dir) => Ok(dir),
+        None => get_app_dir(),
+    }
+}
+
+/// A path taken from the environment, resolved against the working directory if relative.
+/// Unset and empty both mean no override.
+fn env_path(value: Option<OsString>) -> Result<Option<PathBuf>, AppError> {
+    let Some(path) = value.filter(|v| !v.is_empty()).map(PathBuf::from) else {
+        return Ok(None);
+    };
+    
+    if path.is_absolute() {
+        Ok(Some(path))
+    } else {
+        Ok(Some(std::env::current_dir()?.join(path)))
+    }
+}
+
+/// Get the Media directory path, or a named collection inside it
+/// The default collection (None) is the Media folder itself
+pub fn get_media_dir(collection: Option<&str>) -> Result<PathBuf, AppError> {
+    let base = get_data_dir()?;
+    let media = collection_dir(&base.join("Media"), collection)?;
+    debug!("Media dir = {:?}", media);
     Ok(media)
 }
 
+/// Resolve a collection inside a Media root, refusing names that would leave it
+pub fn collection_dir(media_root: &Path, collection: Option<&str>) -> Result<PathBuf, AppError> {
+    match collection.filter(|name| !name.is_empty()) {
+        None => Ok(media_root.to_path_buf()),
+        Some(name) => {
+            let name = crate::media::sanitize_filename(name)
+                .map_err(|e| AppError::Validation(format!("Invalid collection name: {}", e)))?;
+            Ok(media_root.join(name))
+        }
+    }
+}
+
 /// Get the Addons directory path
-pub fn get_addons_dir() -> Result<PathBuf, String> {
-    let base = get_app_dir()?;
+pub fn get_addons_dir() -> Result<PathBuf, AppError> {
+    let base = get_data_dir()?;
     let addons = base.join("Addons");
-    println!("DEBUG: Addons dir = {:?}", addons);
+    debug!("Addons dir = {:?}", addons);
     Ok(addons)
 }
 
 /// Get the Fonts directory path
-pub fn get_fonts_dir() -> Result<PathBuf, String> {
-    let base = get_app_dir()?;
+pub fn get_fonts_dir() -> Result<PathBuf, AppError> {
+    let base = get_data_dir()?;
     let fonts = base.join("Fonts");
-    println!("DEBUG: Fonts dir = {:?}", fonts);
+    debug!("Fonts dir = {:?}", fonts);
     Ok(fonts)
 }
 
+/// Get the Logs directory path, where the server writes its daily log files
+pub fn get_logs_dir() -> Result<PathBuf, AppError> {
+    let base = get_data_dir()?;
+    let logs = base.join("Logs");
+    debug!("Logs dir = {:?}", logs);
+    Ok(logs)
+}
+
+/// Folders in the data dir that have to exist and be writable for the app to work
+const REQUIRED_DIRS: [&str; 3] = ["Media", "Fonts", "Addons"];
+
+/// Create the Media, Fonts and Addons folders and check each one can be written to, so a
+/// permission problem shows up at startup instead of as a confusing error on first use.
+/// Returns the folders that are ready, or a message for each one that isn't.
+pub fn ensure_all_dirs() -> Result<Vec<String>, Vec<String>> {
+    let base = get_data_dir()
+        .map_err(|e| vec![format!("Failed to find the data folder: {}", e)])?;
+    ensure_dirs_in(&base)
+}
+
+fn ensure_dirs_in(base: &Path) -> Result<Vec<String>, Vec<String>> {
+    let mut ready = Vec::new();
+    let mut problems = Vec::new();
+    
+    for name in REQUIRED_DIRS {
+        let dir = base.join(name);
+        match ensure_writable(&dir) {
+            Ok(()) => ready.push(dir.display().to_string()),
+            Err(e) => problems.push(format!("{} folder {} is not usable: {}", name, dir.display(), e)),
+        }
+    }
+    
+    if problems.is_empty() {
+        Ok(ready)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Create `dir` if needed and prove it is writable by creating and removing a file in it
+fn ensure_writable(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    
+    // Hidden, so nothing listing the folder picks it up if removing it fails
+    let probe = dir.join(format!(".write-check-{}", std::process::id()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
 /// Get the config file path
-pub fn get_config_path() -> Result<PathBuf, String> {
-    let base = get_app_dir()?;
-    let config = base.join("config.json");
-    println!("DEBUG: Config path = {:?}", config);
+/// SIGNAGE_CONFIG_PATH, if set, takes precedence; otherwise it is config.json in the app dir
+pub fn get_config_path() -> Result<PathBuf, AppError> {
+    config_path_from(|name| std::env::var_os(name))
+}
+
+fn config_path_from(env: impl Fn(&str) -> Option<OsString>) -> Result<PathBuf, AppError> {
+    let config = match env_path(env(CONFIG_PATH_VAR))? {
+        Some(path) => path,
+        None => get_app_dir()?.join("config.json"),
+    };
+    debug!("Config path = {:?}", config);
     Ok(config)
 }
 
@@ -83,9 +194,68 @@ mod tests {
     #[test]
     fn test_paths() {
         println!("App dir: {:?}", get_app_dir());
-        println!("Media dir: {:?}", get_media_dir());
+        println!("Media dir: {:?}", get_media_dir(None));
         println!("Addons dir: {:?}", get_addons_dir());
         println!("Fonts dir: {:?}", get_fonts_dir());
+        println!("Logs dir: {:?}", get_logs_dir());
         println!("Config path: {:?}", get_config_path());
     }
+    
+    // Other tests resolve paths at the same time, so the variables are passed in
+    // rather than set on the process
+    #[test]
+    fn test_env_overrides() {
+        let cwd = std::env::current_dir().unwrap();
+        let absolute = cwd.join("kiosk-data");
+        let env = |name: &str| match name {
+            CONFIG_PATH_VAR => Some(OsString::from("managed/signage.json")),
+            DATA_DIR_VAR => Some(absolute.clone().into_os_string()),
+            _ => None,
+        };
+        
+        // Relative paths are taken from the working directory
+        assert_eq!(config_path_from(env).unwrap(), cwd.join("managed").join("signage.json"));
+        assert_eq!(data_dir_from(env).unwrap(), absolute);
+        
+        // Unset or empty falls back to the app dir
+        let unset = |_: &str| None;
+        let empty = |_: &str| Some(OsString::new());
+        assert_eq!(config_path_from(unset).unwrap(), get_app_dir().unwrap().join("config.json"));
+        assert_eq!(data_dir_from(empty).unwrap(), get_app_dir().unwrap());
+    }
+    
+    #[test]
+    fn test_unwritable_data_dir_is_reported() {
+        let base = std::env::temp_dir().join(format!("image-presenter-dirs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        
+        let ready = ensure_dirs_in(&base).unwrap();
+        assert_eq!(ready.len(), REQUIRED_DIRS.len());
+        assert!(base.join("Fonts").is_dir());
+        assert_eq!(std::fs::read_dir(base.join("Media")).unwrap().count(), 0);
+        
+        // A file where a folder should be can't be fixed by creating it
+        std::fs::remove_dir(base.join("Addons")).unwrap();
+        std::fs::write(base.join("Addons"), b"").unwrap();
+        let problems = ensure_dirs_in(&base).unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("Addons folder"));
+        
+        // Root ignores permissions, so this part only means something for other users
+        let read_only = base.join("read-only");
+        std::fs::create_dir(&read_only).unwrap();
+        let mut permissions = std::fs::metadata(&read_only).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&read_only, permissions.clone()).unwrap();
+        if std::fs::write(read_only.join("probe"), b"").is_err() {
+            let problems = ensure_dirs_in(&read_only).unwrap_err();
+            assert_eq!(problems.len(), REQUIRED_DIRS.len());
+        }
+        
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        let _ = std::fs::set_permissions(&read_only, permissions);
+        let _ = std::fs::remove_dir_all(&base);
+    }
 }
\ No newline at end of file
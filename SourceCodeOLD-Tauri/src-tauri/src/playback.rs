@@ -0,0 +1,190 @@
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// How often the current slide is written to the config. Slides change every few seconds,
+/// so saving on each one would mean constant disk writes.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where the display is in its playlist. The display window reports each slide it shows,
+/// and remote control requests move it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PlaybackState {
+    pub index: usize,
+    pub paused: bool,
+}
+
+pub type SharedPlayback = Arc<Mutex<PlaybackState>>;
+
+/// A remote control request, e.g. `{"action": "goto", "index": 3}`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum PlaybackAction {
+    Next,
+    Prev,
+    Goto { index: usize },
+    Pause,
+    Play,
+}
+
+impl PlaybackState {
+    /// Apply an action to a playlist of `total` slides. Next and prev wrap around;
+    /// goto past the end lands on the last slide.
+    pub fn apply(&mut self, action: PlaybackAction, total: usize) {
+        let last = total.saturating_sub(1);
+        let index = self.index.min(last);
+        
+        match action {
+            PlaybackAction::Next => self.index = if index >= last { 0 } else { index + 1 },
+            PlaybackAction::Prev => self.index = if index == 0 { last } else { index - 1 },
+            PlaybackAction::Goto { index } => self.index = index.min(last),
+            PlaybackAction::Pause => self.paused = true,
+            PlaybackAction::Play => self.paused = false,
+        }
+    }
+    
+    /// The slide after the current one in a playlist of `total` slides, wrapping back to
+    /// the first after the last. None when the playlist is empty.
+    pub fn next_index(self, total: usize) -> Option<usize> {
+        if total == 0 {
+            return None;
+        }
+        Some((self.index.min(total - 1) + 1) % total)
+    }
+    
+    /// The state as the API reports it; the playlist may have shrunk since the last report
+    pub fn to_api_json(self, total: usize) -> serde_json::Value {
+        serde_json::json!({
+            "index": self.index.min(total.saturating_sub(1)),
+            "total": total,
+            "paused": self.paused,
+        })
+    }
+}
+
+/// The order to play `items` in: as given, or shuffled by `seed` when `shuffle` is on.
+/// ChaCha gives the same stream on every platform, so peers sharing a seed agree.
+pub fn sequence<T: Clone>(items: &[T], shuffle: bool, seed: u64) -> Vec<T> {
+    let mut sequence = items.to_vec();
+    if shuffle {
+        sequence.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+    }
+    sequence
+}
+
+/// Largest shuffle seed; the display and web UI hold seeds as JavaScript numbers
+pub const MAX_SEED: u64 = (1 << 53) - 1;
+
+/// Seed for the cycle after one played with `seed`. Derived rather than random,
+/// so every device that finishes a cycle reshuffles the same way.
+pub fn next_seed(seed: u64) -> u64 {
+    // SplitMix64: consecutive seeds give unrelated orders
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) & MAX_SEED
+}
+
+/// Save the current slide to the config whenever it has moved, so a restart resumes near it
+pub async fn persist_periodically(config: Arc<Mutex<crate::config::Config>>, playback: SharedPlayback) {
+    let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+    loop {
+        interval.tick().await;
+        
+        let index = playback.lock().unwrap().index;
+        if let Err(e) = persist_index(&config, index) {
+            warn!("Failed to save slide position: {}", e);
+        }
+    }
+}
+
+fn persist_index(config: &Mutex<crate::config::Config>, index: usize) -> Result<(), String> {
+    let mut cfg = config.lock().unwrap();
+    if cfg.last_slide_index == index {
+        return Ok(());
+    }
+    
+    let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    
+    // Addon settings are written straight to the file, so update it rather than overwrite it
+    let mut saved = crate::config::load_config()?;
+    saved.last_slide_index = index;
+    saved.last_slide_timestamp = Some(timestamp.clone());
+    crate::config::save_config(&saved)?;
+    
+    cfg.last_slide_index = index;
+    cfg.last_slide_timestamp = Some(timestamp);
+    debug!("Saved slide position {}", index);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_apply_wraps_and_clamps() {
+        let mut state = PlaybackState { index: 2, paused: false };
+        state.apply(PlaybackAction::Next, 3);
+        assert_eq!(state.index, 0);
+        state.apply(PlaybackAction::Prev, 3);
+        assert_eq!(state.index, 2);
+        state.apply(PlaybackAction::Prev, 3);
+        assert_eq!(state.index, 1);
+        
+        state.apply(PlaybackAction::Goto { index: 99 }, 3);
+        assert_eq!(state.index, 2);
+        
+        state.apply(PlaybackAction::Pause, 3);
+        assert!(state.paused);
+        state.apply(PlaybackAction::Play, 3);
+        assert!(!state.paused);
+        
+        // An empty playlist has nowhere to go
+        state.apply(PlaybackAction::Next, 0);
+        assert_eq!(state.index, 0);
+        
+        let action: PlaybackAction = serde_json::from_str(r#"{"action": "goto", "index": 1}"#).unwrap();
+        state.apply(action, 3);
+        assert_eq!(state.to_api_json(3), serde_json::json!({ "index": 1, "total": 3, "paused": false }));
+        assert!(serde_json::from_str::<PlaybackAction>(r#"{"action": "rewind"}"#).is_err());
+    }
+    
+    #[test]
+    fn test_next_index() {
+        let state = PlaybackState { index: 0, paused: false };
+        assert_eq!(state.next_index(3), Some(1));
+        
+        // After the last slide comes the first again
+        let last = PlaybackState { index: 2, paused: false };
+        assert_eq!(last.next_index(3), Some(0));
+        assert_eq!(last.next_index(1), Some(0));
+        
+        // The playlist shrank under the display, or is empty
+        assert_eq!(PlaybackState { index: 7, paused: false }.next_index(3), Some(0));
+        assert_eq!(state.next_index(0), None);
+    }
+    
+    #[test]
+    fn test_shuffled_sequence() {
+        let files: Vec<String> = (0..20).map(|i| format!("slide-{:02}.png", i)).collect();
+        
+        let shuffled = sequence(&files, true, 42);
+        assert_ne!(shuffled, files);
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, files);
+        
+        // Every device with the same seed plays the same order
+        assert_eq!(sequence(&files, true, 42), shuffled);
+        assert_ne!(sequence(&files, true, next_seed(42)), shuffled);
+        assert!(next_seed(u64::MAX) <= MAX_SEED);
+        
+        assert_eq!(sequence(&files, false, 42), files);
+        assert!(sequence::<String>(&[], true, 42).is_empty());
+    }
+}
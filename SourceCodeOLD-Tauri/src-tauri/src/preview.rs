@@ -0,0 +1,133 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use crate::ws::PasswordQuery;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{broadcast, watch};
+use tracing::info;
+
+/// Fastest allowed capture rate, to protect the device CPU
+pub const MIN_PREVIEW_INTERVAL_MS: u64 = 500;
+
+/// Longest side of the frames the display window captures
+const PREVIEW_MAX_WIDTH: u32 = 480;
+
+/// Shares captured display frames with connected preview clients
+pub struct PreviewHub {
+    frames: broadcast::Sender<Arc<Vec<u8>>>,
+    clients: watch::Sender<usize>,
+}
+
+impl PreviewHub {
+    pub fn new() -> Self {
+        let (frames, _) = broadcast::channel(4);
+        let (clients, _) = watch::channel(0);
+        Self { frames, clients }
+    }
+    
+    /// Hand a JPEG frame from the display window to every connected client
+    pub fn publish(&self, frame: Vec<u8>) {
+        // No receivers just means the last client left after the capture was requested
+        let _ = self.frames.send(Arc::new(frame));
+    }
+    
+    fn connect(&self) -> broadcast::Receiver<Arc<Vec<u8>>> {
+        self.clients.send_modify(|n| *n += 1);
+        self.frames.subscribe()
+    }
+    
+    fn disconnect(&self) {
+        self.clients.send_modify(|n| *n = n.saturating_sub(1));
+    }
+}
+
+/// Route for the live preview stream, served by the ws module's server
+pub fn router(config: Arc<Mutex<crate::config::Config>>, hub: Arc<PreviewHub>) -> Router {
+    Router::new()
+        .route("/preview", get(move |query, ws| preview_handler(config, hub, query, ws)))
+}
+
+async fn preview_handler(
+    config: Arc<Mutex<crate::config::Config>>,
+    hub: Arc<PreviewHub>,
+    Query(query): Query<PasswordQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if !crate::ws::is_authorized(&config, &query) {
+        return (StatusCode::UNAUTHORIZED, "Invalid password").into_response();
+    }
+    
+    ws.on_upgrade(move |socket| stream_frames(socket, hub))
+}
+
+async fn stream_frames(mut socket: WebSocket, hub: Arc<PreviewHub>) {
+    let mut frames = hub.connect();
+    info!("Preview client connected");
+    
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    if socket.send(Message::Binary(frame.to_vec())).await.is_err() {
+                        break;
+                    }
+                }
+                // A slow client just skips the frames it missed
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    
+    hub.disconnect();
+    info!("Preview client disconnected");
+}
+
+/// Ask the display window for frames only while someone is watching
+pub async fn capture_loop(
+    config: Arc<Mutex<crate::config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    hub: Arc<PreviewHub>,
+) {
+    let mut clients = hub.clients.subscribe();
+    
+    loop {
+        if clients.wait_for(|n| *n > 0).await.is_err() {
+            return;
+        }
+        
+        info!("Starting preview capture");
+        
+        while *clients.borrow() > 0 {
+            if let Some(handle) = app_handle.lock().unwrap().as_ref() {
+                let _ = handle.emit("preview-capture", serde_json::json!({
+                    "maxWidth": PREVIEW_MAX_WIDTH
+                }));
+            }
+            
+            let interval = {
+                let cfg = config.lock().unwrap();
+                cfg.preview_interval_ms.max(MIN_PREVIEW_INTERVAL_MS)
+            };
+            
+            tokio::select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_millis(interval)) => {}
+                _ = clients.changed() => {}
+            }
+        }
+        
+        info!("Stopping preview capture");
+    }
+}
@@ -0,0 +1,137 @@
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::warn;
+
+/// Clients tracked before idle ones are forgotten
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket per client IP for mutating API requests
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Take a token for `client`, or return the seconds until one is available
+    fn take(&self, client: IpAddr, per_min: u32, now: Instant) -> Result<(), u64> {
+        let capacity = per_min as f64;
+        let per_sec = capacity / 60.0;
+        let refilled = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            (bucket.tokens + elapsed * per_sec).min(capacity)
+        };
+        
+        let mut buckets = self.buckets.lock().unwrap();
+        
+        // A full bucket is the same as no bucket, so those are safe to drop
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, bucket| refilled(bucket) < capacity);
+        }
+        
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: capacity, updated: now });
+        bucket.tokens = refilled(bucket);
+        bucket.updated = now;
+        
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / per_sec).ceil() as u64)
+        }
+    }
+}
+
+/// Middleware limiting each client to `rate_limit_per_min` mutating /api requests
+pub async fn limit_mutations(
+    config: Arc<Mutex<crate::config::Config>>,
+    limiter: RateLimiter,
+    request: Request,
+    next: Next,
+) -> Response {
+    let per_min = config.lock().unwrap().rate_limit_per_min;
+    let mutating = matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+    
+    if per_min == 0 || !mutating || !request.uri().path().starts_with("/api/") {
+        return next.run(request).await;
+    }
+    
+    let client = request.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    
+    if let Err(retry_after) = limiter.take(client, per_min, Instant::now()) {
+        warn!("Rate limit exceeded by {} on {} {}", client, request.method(), request.uri().path());
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(serde_json::json!({
+                "error": "Too many requests, try again later"
+            })),
+        ).into_response();
+    }
+    
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    
+    #[tokio::test]
+    async fn test_requests_over_limit_are_rejected() {
+        let config = Arc::new(Mutex::new(crate::config::Config {
+            rate_limit_per_min: 3,
+            ..crate::config::Config::default()
+        }));
+        let limiter = RateLimiter::new();
+        
+        let app = Router::new()
+            .route("/api/media/order", post(|| async { "ok" }).get(|| async { "ok" }))
+            .layer(middleware::from_fn(move |request, next| {
+                limit_mutations(config.clone(), limiter.clone(), request, next)
+            }));
+        
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        
+        let url = format!("http://{}/api/media/order", addr);
+        let client = reqwest::Client::new();
+        
+        for _ in 0..3 {
+            assert_eq!(client.post(&url).send().await.unwrap().status().as_u16(), 200);
+        }
+        
+        let rejected = client.post(&url).send().await.unwrap();
+        assert_eq!(rejected.status().as_u16(), 429);
+        let retry_after: u64 = rejected.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+        assert!((1..=20).contains(&retry_after));
+        
+        // Reads are never limited
+        assert_eq!(client.get(&url).send().await.unwrap().status().as_u16(), 200);
+    }
+}
@@ -0,0 +1,157 @@
+use mlua::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Shortest interval an addon may schedule a backend task at
+pub const MIN_SCHEDULE_INTERVAL_SECS: f64 = 1.0;
+
+/// A backend function an addon asked to have called periodically
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledCall {
+    pub interval: Duration,
+    pub function_name: String,
+}
+
+struct RunningSchedule {
+    calls: Vec<ScheduledCall>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+static RUNNING: OnceLock<Mutex<HashMap<String, RunningSchedule>>> = OnceLock::new();
+
+fn running() -> &'static Mutex<HashMap<String, RunningSchedule>> {
+    RUNNING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+static EVENT_SINK: OnceLock<crate::addon::EventSink> = OnceLock::new();
+
+/// Where addon.emit calls from scheduled tasks and config hooks go; set once the app handle exists
+pub fn set_event_sink(sink: crate::addon::EventSink) {
+    let _ = EVENT_SINK.set(sink);
+}
+
+pub fn event_sink() -> crate::addon::EventSink {
+    EVENT_SINK.get().cloned().unwrap_or_else(|| Arc::new(|_| {}))
+}
+
+/// Add `addon.schedule(interval_secs, function_name)` to an addon API table.
+/// Registrations are only collected here; `sync_schedules` decides what runs.
+pub fn register_lua_api(lua: &Lua, addon_api: &LuaTable) -> Result<(), String> {
+    lua.set_app_data(Vec::<ScheduledCall>::new());
+    
+    let schedule_fn = lua.create_function(|lua, (interval_secs, function_name): (f64, String)| {
+        if !interval_secs.is_finite() || interval_secs < MIN_SCHEDULE_INTERVAL_SECS {
+            return Err(LuaError::RuntimeError(format!(
+                "Schedule interval must be at least {} second(s)",
+                MIN_SCHEDULE_INTERVAL_SECS
+            )));
+        }
+        
+        if let Some(mut calls) = lua.app_data_mut::<Vec<ScheduledCall>>() {
+            calls.push(ScheduledCall {
+                interval: Duration::from_secs_f64(interval_secs),
+                function_name,
+            });
+        }
+        
+        Ok(())
+    }).map_err(|e| format!("Failed to create schedule function: {}", e))?;
+    
+    addon_api.set("schedule", schedule_fn)
+        .map_err(|e| format!("Failed to set schedule: {}", e))?;
+    
+    Ok(())
+}
+
+/// What a Lua instance's script registered with `addon.schedule` so far
+pub fn registered_calls(lua: &Lua) -> Vec<ScheduledCall> {
+    lua.app_data_ref::<Vec<ScheduledCall>>()
+        .map(|calls| calls.clone())
+        .unwrap_or_default()
+}
+
+/// Start scheduled tasks for enabled addons and stop those of disabled or removed ones.
+/// Registrations come from the scan's backend init run, so backend.lua isn't run again here.
+pub async fn sync_schedules() {
+    let mut addons = match crate::addon::scan_addons(false).await {
+        Ok(addons) => addons,
+        Err(e) => {
+            warn!("[Scheduler] Failed to scan addons: {}", e);
+            return;
+        }
+    };
+    
+    let config = crate::config::load_config().unwrap_or_default();
+    let mut desired = HashMap::new();
+    
+    for addon_item in &mut addons {
+        crate::addon::merge_addon_config(addon_item, config.addons.get(&addon_item.id));
+        
+        if !addon_item.enabled || addon_item.schedules.is_empty() {
+            continue;
+        }
+        
+        desired.insert(addon_item.id.clone(), std::mem::take(&mut addon_item.schedules));
+    }
+    
+    let mut running = running().lock().unwrap();
+    
+    // Stop anything no longer wanted or whose registrations changed
+    running.retain(|addon_id, schedule| {
+        let keep = desired.get(addon_id) == Some(&schedule.calls);
+        if !keep {
+            for task in &schedule.tasks {
+                task.abort();
+            }
+            info!("[Scheduler] Stopped {} task(s) for {}", schedule.tasks.len(), addon_id);
+        }
+        keep
+    });
+    
+    for (addon_id, calls) in desired {
+        if running.contains_key(&addon_id) {
+            continue;
+        }
+        
+        let tasks = calls.iter()
+            .map(|call| spawn_task(addon_id.clone(), call.clone()))
+            .collect::<Vec<_>>();
+        
+        info!("[Scheduler] Started {} task(s) for {}", tasks.len(), addon_id);
+        running.insert(addon_id, RunningSchedule { calls, tasks });
+    }
+}
+
+fn spawn_task(addon_id: String, call: ScheduledCall) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(call.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        
+        // The first tick completes immediately; wait a full interval before the first run
+        interval.tick().await;
+        
+        loop {
+            interval.tick().await;
+            
+            let sink = event_sink();
+            match crate::run_addon_function(addon_id.clone(), call.function_name.clone(), Vec::new(), sink.clone()).await {
+                Ok(result) => {
+                    debug!("[Scheduler] {}.{} -> {}", addon_id, call.function_name, result);
+                    
+                    // Whatever the function returns goes to the display as an event named after it
+                    if !result.is_null() {
+                        sink(serde_json::json!({
+                            "addon_id": addon_id,
+                            "event_name": call.function_name,
+                            "payload": result,
+                        }));
+                    }
+                }
+                Err(e) => warn!("[Scheduler] {}.{} failed: {}", addon_id, call.function_name, e),
+            }
+        }
+    })
+}
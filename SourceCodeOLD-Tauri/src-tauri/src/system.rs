@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// How long a report is reused before the Media directory is scanned again
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Storage and runtime figures for the /api/system/info endpoint
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemInfo {
+    pub media_count: u64,
+    pub media_bytes: u64,
+    pub disk_free_bytes: u64,
+    pub disk_total_bytes: u64,
+    pub uptime_secs: u64,
+    pub version: String,
+    /// Whether uploaded videos can be transcoded
+    pub ffmpeg_available: bool,
+}
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static CACHE: Mutex<Option<(Instant, SystemInfo)>> = Mutex::new(None);
+
+/// When the app started; called early in main so uptime counts from launch
+pub fn started_at() -> Instant {
+    *STARTED.get_or_init(Instant::now)
+}
+
+/// Get the current report, scanning the Media directory at most once per CACHE_TTL
+pub async fn get_info() -> Result<SystemInfo, String> {
+    if let Some((at, info)) = CACHE.lock().unwrap().as_ref() {
+        if at.elapsed() < CACHE_TTL {
+            // Uptime is free to compute, so keep it current
+            return Ok(SystemInfo { uptime_secs: started_at().elapsed().as_secs(), ..info.clone() });
+        }
+    }
+    
+    let media_dir = crate::paths::get_media_dir(None)?;
+    let info = tokio::task::spawn_blocking(move || gather(&media_dir)).await
+        .map_err(|e| format!("System info task failed: {}", e))??;
+    
+    *CACHE.lock().unwrap() = Some((Instant::now(), info.clone()));
+    Ok(info)
+}
+
+fn gather(media_dir: &Path) -> Result<SystemInfo, String> {
+    std::fs::create_dir_all(media_dir)
+        .map_err(|e| format!("Failed to create Media directory: {}", e))?;
+    
+    let (media_count, media_bytes) = media_totals(media_dir)?;
+    let disk_free_bytes = fs2::available_space(media_dir)
+        .map_err(|e| format!("Failed to read free disk space: {}", e))?;
+    let disk_total_bytes = fs2::total_space(media_dir)
+        .map_err(|e| format!("Failed to read disk size: {}", e))?;
+    
+    Ok(SystemInfo {
+        media_count,
+        media_bytes,
+        disk_free_bytes,
+        disk_total_bytes,
+        uptime_secs: started_at().elapsed().as_secs(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ffmpeg_available: crate::capabilities::ffmpeg_path().is_some(),
+    })
+}
+
+/// Names of the Media, Fonts and Addons folders that are missing or can't be listed
+pub fn inaccessible_dirs() -> Result<Vec<String>, String> {
+    Ok(inaccessible(&[
+        ("Media", crate::paths::get_media_dir(None)?),
+        ("Fonts", crate::paths::get_fonts_dir()?),
+        ("Addons", crate::paths::get_addons_dir()?),
+    ]))
+}
+
+fn inaccessible(dirs: &[(&str, PathBuf)]) -> Vec<String> {
+    dirs.iter()
+        .filter(|(_, path)| std::fs::read_dir(path).is_err())
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// Count media files and their total size in the Media directory and its collections
+pub fn media_totals(media_dir: &Path) -> Result<(u64, u64), String> {
+    let mut count = 0;
+    let mut bytes = 0;
+    let mut dirs = vec![media_dir.to_path_buf()];
+    
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read Media directory: {}", e))?;
+        
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            
+            // Collections are one level deep; hidden folders hold caches like thumbnails
+            if path.is_dir() {
+                if dir == media_dir && !name.starts_with('.') {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            
+            let ext = path.extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if crate::media::media_type(&ext).is_none() {
+                continue;
+            }
+            
+            if let Ok(metadata) = entry.metadata() {
+                count += 1;
+                bytes += metadata.len();
+            }
+        }
+    }
+    
+    Ok((count, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_gather() {
+        let dir = std::env::temp_dir().join(format!("system-info-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("Lobby")).unwrap();
+        std::fs::create_dir_all(dir.join(".thumbnails")).unwrap();
+        
+        std::fs::write(dir.join("a.png"), vec![0u8; 1234]).unwrap();
+        std::fs::write(dir.join("Lobby").join("b.mp4"), vec![0u8; 100]).unwrap();
+        // Neither counts: not media, and a cached thumbnail
+        std::fs::write(dir.join("notes.txt"), "hello").unwrap();
+        std::fs::write(dir.join(".thumbnails").join("a.png.jpg"), vec![0u8; 50]).unwrap();
+        
+        let info = gather(&dir).unwrap();
+        assert_eq!(info.media_count, 2);
+        assert_eq!(info.media_bytes, 1334);
+        assert!(info.disk_total_bytes >= info.disk_free_bytes);
+        
+        let json = serde_json::to_value(&info).unwrap();
+        for key in ["mediaCount", "mediaBytes", "diskFreeBytes", "diskTotalBytes", "uptimeSecs", "version"] {
+            assert!(json.get(key).is_some(), "missing {}", key);
+        }
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_inaccessible_dirs() {
+        let dir = std::env::temp_dir().join(format!("system-ready-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dirs = ["Media", "Fonts", "Addons"].map(|name| (name, dir.join(name)));
+        for (_, path) in &dirs {
+            std::fs::create_dir_all(path).unwrap();
+        }
+        assert!(inaccessible(&dirs).is_empty());
+        
+        std::fs::remove_dir_all(dir.join("Media")).unwrap();
+        assert_eq!(inaccessible(&dirs), vec!["Media".to_string()]);
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,213 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::AppHandle;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+/// Events a slow client can fall behind by before it starts missing some
+const EVENT_BUFFER: usize = 16;
+
+static EVENTS: OnceLock<broadcast::Sender<String>> = OnceLock::new();
+
+fn events() -> &'static broadcast::Sender<String> {
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_BUFFER).0)
+}
+
+/// Held by tests that count broadcasts, so they don't see each other's events
+#[cfg(test)]
+pub static BROADCASTS: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[cfg(test)]
+pub fn subscribe() -> broadcast::Receiver<String> {
+    events().subscribe()
+}
+
+/// Push a JSON event to every connected WebSocket client
+pub fn broadcast(event: serde_json::Value) {
+    // No receivers just means nobody is connected
+    let _ = events().send(event.to_string());
+}
+
+pub fn broadcast_media_update() {
+    broadcast(serde_json::json!({ "event": "media-update" }));
+}
+
+pub fn broadcast_config_update(cfg: &crate::config::Config) {
+    broadcast(config_event(cfg));
+}
+
+fn config_event(cfg: &crate::config::Config) -> serde_json::Value {
+    serde_json::json!({
+        "event": "config-update",
+        "config": crate::config::to_api_json(cfg)
+    })
+}
+
+#[derive(Deserialize)]
+pub struct PasswordQuery {
+    password: Option<String>,
+}
+
+/// WebSocket clients pass the display password as a query parameter
+pub fn is_authorized(config: &Mutex<crate::config::Config>, query: &PasswordQuery) -> bool {
+    let cfg = config.lock().unwrap();
+    cfg.password.is_empty()
+        || query.password.as_deref().is_some_and(|p| crate::auth::verify_password(&cfg.password, p))
+}
+
+/// Run the WebSocket server on the configured ws_port, serving events and the live preview.
+/// It listens on the same addresses and admits the same clients as the web server.
+pub async fn start_ws_server(
+    config: Arc<Mutex<crate::config::Config>>,
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+    preview_hub: Arc<crate::preview::PreviewHub>,
+) {
+    let (ws_port, bind_addresses) = {
+        let cfg = config.lock().unwrap();
+        (cfg.ws_port, cfg.bind_addresses.clone())
+    };
+    
+    tokio::spawn(crate::preview::capture_loop(config.clone(), app_handle, preview_hub.clone()));
+    
+    let app = router(config.clone())
+        .merge(crate::preview::router(config.clone(), preview_hub))
+        .layer(axum::middleware::from_fn(move |request, next| {
+            crate::ipfilter::restrict_clients(config.clone(), request, next)
+        }));
+    
+    let listeners = match crate::bind_addrs(&bind_addresses, ws_port) {
+        Ok(addrs) => crate::bind_listeners(&addrs).await,
+        Err(e) => Err(e),
+    };
+    let listeners = match listeners {
+        Ok(listeners) => listeners,
+        Err(e) => {
+            error!("Failed to start WebSocket server on port {}: {}", ws_port, e);
+            return;
+        }
+    };
+    
+    let servers: Vec<_> = listeners.into_iter()
+        .map(|listener| {
+            if let Ok(addr) = listener.local_addr() {
+                info!("WebSocket server started on ws://{}", addr);
+            }
+            // The IP filter needs to know who is connecting
+            let service = app.clone().into_make_service_with_connect_info::<SocketAddr>();
+            tokio::spawn(async move { axum::serve(listener, service).await })
+        })
+        .collect();
+    
+    for server in servers {
+        if let Ok(Err(e)) = server.await {
+            error!("WebSocket server stopped: {}", e);
+        }
+    }
+}
+
+fn router(config: Arc<Mutex<crate::config::Config>>) -> Router {
+    Router::new()
+        .route("/", get(move |query, ws| events_handler(config, query, ws)))
+}
+
+async fn events_handler(
+    config: Arc<Mutex<crate::config::Config>>,
+    Query(query): Query<PasswordQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if !is_authorized(&config, &query) {
+        return (StatusCode::UNAUTHORIZED, "Invalid password").into_response();
+    }
+    
+    ws.on_upgrade(move |socket| stream_events(socket, config))
+}
+
+async fn stream_events(mut socket: WebSocket, config: Arc<Mutex<crate::config::Config>>) {
+    // Subscribe first so nothing sent while the initial config goes out is lost
+    let mut events = events().subscribe();
+    
+    let initial = config_event(&config.lock().unwrap()).to_string();
+    if socket.send(Message::Text(initial)).await.is_err() {
+        return;
+    }
+    
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if socket.send(Message::Text(event)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("WebSocket client missed {} events", missed);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite;
+    
+    async fn next_event<S>(client: &mut S) -> serde_json::Value
+    where
+        S: StreamExt<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin,
+    {
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next())
+            .await
+            .expect("timed out waiting for event")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_broadcast_reaches_all_clients() {
+        let _guard = BROADCASTS.lock().await;
+        let config = Arc::new(Mutex::new(crate::config::Config::default()));
+        
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router(config)).await.unwrap();
+        });
+        
+        let url = format!("ws://{}/", addr);
+        let (mut first, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+        let (mut second, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+        
+        // Each client gets the current config as soon as it connects
+        for client in [&mut first, &mut second] {
+            let event = next_event(client).await;
+            assert_eq!(event["event"], "config-update");
+            assert!(event["config"]["displayName"].is_string());
+        }
+        
+        broadcast_media_update();
+        
+        for client in [&mut first, &mut second] {
+            assert_eq!(next_event(client).await["event"], "media-update");
+        }
+    }
+}
@@ -13,6 +13,15 @@ function getApiUrl(endpoint) {
     return currentDeviceUrl + endpoint;
 }
 
+// fetch() that sends the entered password so protected routes accept the request
+function apiFetch(url, options = {}) {
+    const headers = new Headers(options.headers || {});
+    if (authPassword) {
+        headers.set('Authorization', `Bearer ${authPassword}`);
+    }
+    return fetch(url, { ...options, headers });
+}
+
 // Initialize
 async function init() {
     await loadConfig();
@@ -60,11 +69,32 @@ async function loadTabContent(tabName) {
 // Tab initialization functions - SIMPLIFIED (no manual resolution)
 function initDisplayTab() {
     // Set current values
-    document.getElementById('rotation').value = config.rotation || 0;
+    // Older configs stored counter-clockwise as -90
+    document.getElementById('rotation').value = config.rotation === -90 ? 270 : (config.rotation || 0);
     document.getElementById('image-duration').value = (config.imageDuration || 5000) / 1000;
     document.getElementById('duration-value').textContent = `${(config.imageDuration || 5000) / 1000} seconds`;
-    document.getElementById('video-position').value = config.videoPosition || 'after';
+    document.getElementById('video-position').value = config.videoPosition === 'between' ? 'interleave' : (config.videoPosition || 'after');
+    document.getElementById('shuffle').checked = !!config.shuffle;
     document.getElementById('image-scaling').value = config.imageScaling || 'contain';
+    document.getElementById('transition').value = config.transition || 'none';
+    document.getElementById('transition-duration').value = config.transitionDurationMs ?? 300;
+    document.getElementById('fallback-message').value = config.fallbackMessage ?? 'No media uploaded';
+    document.getElementById('fallback-background').value = config.fallbackBackground || '#000000';
+    document.getElementById('auto-transcode').checked = !!config.autoTranscode;
+    document.getElementById('transcode-format').value = config.transcodeFormat || 'webp';
+    document.getElementById('image-quality').value = config.imageQuality ?? 80;
+    document.getElementById('transcode-video').checked = !!config.transcodeVideo;
+    document.getElementById('transcode-video-hint').textContent = config.capabilities?.tools?.ffmpeg
+        ? 'Uses ffmpeg to produce H.264 video the display can play.'
+        : 'ffmpeg was not found on this device, so videos are kept as uploaded.';
+    
+    const schedule = config.schedule || { enabled: false, startTime: '08:00', endTime: '18:00', days: [1, 2, 3, 4, 5] };
+    document.getElementById('schedule-enabled').checked = schedule.enabled;
+    document.getElementById('schedule-start').value = schedule.startTime;
+    document.getElementById('schedule-end').value = schedule.endTime;
+    document.querySelectorAll('.schedule-day').forEach(box => {
+        box.checked = schedule.days.includes(parseInt(box.value));
+    });
     
     // Add event listeners
     document.getElementById('image-duration').addEventListener('input', (e) => {
@@ -105,6 +135,15 @@ async function initAddonsTab() {
     document.getElementById('reload-addons').addEventListener('click', reloadAddons);
     document.getElementById('open-addons-folder').addEventListener('click', openAddonsFolder);
     
+    const installInput = document.getElementById('install-addon-file');
+    document.getElementById('install-addon').addEventListener('click', () => installInput.click());
+    installInput.addEventListener('change', async () => {
+        if (installInput.files.length > 0) {
+            await installAddon(installInput.files[0]);
+        }
+        installInput.value = '';
+    });
+    
     await loadAddonsList();
 }
 
@@ -117,6 +156,8 @@ function initNetworkTab() {
         // Set current values with proper config data
         document.getElementById('device-name').value = config.displayName || '';
         document.getElementById('static-ip').value = config.staticIp || '';
+        document.getElementById('bind-addresses').value = (config.bindAddresses || []).join(', ');
+        document.getElementById('allowed-origins').value = (config.allowedOrigins || []).join(', ');
         
         // FIXED: Explicit boolean check with logging for debugging
         const localhostOnlyValue = config.localhostOnly === true;
@@ -126,6 +167,11 @@ function initNetworkTab() {
         document.getElementById('app-port').value = config.port || 3000;
         document.getElementById('ws-port').value = config.wsPort || 3001;
         document.getElementById('discovery-port').value = config.discoveryPort || 3002;
+        document.getElementById('discovery-mode').value = config.discoveryMode || 'both';
+        document.getElementById('discovery-interval').value = config.discoveryIntervalSecs || 5;
+        document.getElementById('peer-ttl').value = config.peerTtlSecs || 30;
+        document.getElementById('log-level').value = config.logLevel || 'info';
+        document.getElementById('rate-limit').value = config.rateLimitPerMin || 0;
     }, 100);
     
     // Add event listeners
@@ -186,11 +232,11 @@ function createDeviceElement(device) {
                ${!device.online && !device.isCurrent ? 'disabled' : ''}>
         <div class="device-info" data-device-id="${device.id}">
             <div class="device-name">
-                ${device.name}
+                ${device.isCurrent ? device.name : peerDisplayName(device)}
                 <span class="device-status ${device.online || device.isCurrent ? 'online' : ''}"></span>
                 <span class="device-edit-icon">✏️ Editing</span>
             </div>
-            <div class="device-ip">${device.ip}:${device.port}</div>
+            <div class="device-ip">${peerAddress(device)}</div>
         </div>
     `;
     
@@ -258,13 +304,23 @@ function getDeviceName(deviceId) {
         return 'Current Device';
     }
     const peer = peers.find(p => p.id === deviceId);
-    return peer ? peer.name : 'Unknown Device';
+    return peer ? peerDisplayName(peer) : 'Unknown Device';
+}
+
+function peerDisplayName(peer) {
+    return peer.alias || peer.name;
+}
+
+// host:port, with IPv6 addresses in brackets so the result also works in URLs
+function peerAddress(peer) {
+    const host = String(peer.ip).includes(':') ? `[${peer.ip}]` : peer.ip;
+    return `${host}:${peer.port}`;
 }
 
 // Configuration functions
 async function loadConfig() {
     try {
-        const response = await fetch(getApiUrl('/api/config'));
+        const response = await apiFetch(getApiUrl('/api/config'));
         config = await response.json();
         
         // Update version display
@@ -282,7 +338,7 @@ async function loadConfig() {
 
 async function loadPeers() {
     try {
-        const response = await fetch('/api/peers');
+        const response = await apiFetch('/api/peers');
         peers = await response.json();
         updateDevicePanel();
     } catch (err) {
@@ -292,7 +348,7 @@ async function loadPeers() {
 
 async function loadAddonsList() {
     try {
-        const response = await fetch('/api/addons');
+        const response = await apiFetch('/api/addons');
         addons = await response.json();
         updateAddonsList();
     } catch (err) {
@@ -369,7 +425,7 @@ function setupEventListeners() {
                 } else {
                     const peer = peers.find(p => p.id === deviceId);
                     if (peer) {
-                        currentDeviceUrl = `http://${peer.ip}:${peer.port}`;
+                        currentDeviceUrl = `http://${peerAddress(peer)}`;
                     }
                 }
                 
@@ -397,7 +453,22 @@ async function saveDisplaySettings() {
         rotation: parseInt(document.getElementById('rotation').value),
         imageDuration: parseInt(document.getElementById('image-duration').value) * 1000,
         videoPosition: document.getElementById('video-position').value,
+        shuffle: document.getElementById('shuffle').checked,
         imageScaling: document.getElementById('image-scaling').value,
+        transition: document.getElementById('transition').value,
+        transitionDurationMs: parseInt(document.getElementById('transition-duration').value) || 0,
+        fallbackMessage: document.getElementById('fallback-message').value,
+        fallbackBackground: document.getElementById('fallback-background').value,
+        autoTranscode: document.getElementById('auto-transcode').checked,
+        transcodeFormat: document.getElementById('transcode-format').value,
+        imageQuality: Math.min(100, Math.max(1, parseInt(document.getElementById('image-quality').value) || 80)),
+        transcodeVideo: document.getElementById('transcode-video').checked,
+        schedule: {
+            enabled: document.getElementById('schedule-enabled').checked,
+            startTime: document.getElementById('schedule-start').value,
+            endTime: document.getElementById('schedule-end').value,
+            days: [...document.querySelectorAll('.schedule-day:checked')].map(box => parseInt(box.value))
+        },
         password: authPassword
     };
     
@@ -409,15 +480,24 @@ async function saveNetworkSettings() {
     const data = {
         displayName: document.getElementById('device-name').value,
         staticIp: document.getElementById('static-ip').value,
+        bindAddresses: document.getElementById('bind-addresses').value
+            .split(',').map(s => s.trim()).filter(Boolean),
+        allowedOrigins: document.getElementById('allowed-origins').value
+            .split(',').map(s => s.trim()).filter(Boolean),
         localhostOnly: document.getElementById('localhost-only').checked,
         port: parseInt(document.getElementById('app-port').value) || 3000,
         wsPort: parseInt(document.getElementById('ws-port').value) || 3001,
         discoveryPort: parseInt(document.getElementById('discovery-port').value) || 3002,
+        discoveryMode: document.getElementById('discovery-mode').value,
+        discoveryIntervalSecs: parseInt(document.getElementById('discovery-interval').value) || 5,
+        peerTtlSecs: parseInt(document.getElementById('peer-ttl').value) || 30,
+        logLevel: document.getElementById('log-level').value,
+        rateLimitPerMin: parseInt(document.getElementById('rate-limit').value) || 0,
         password: authPassword
     };
     
     try {
-        const response = await fetch(getApiUrl('/api/config'), {
+        const response = await apiFetch(getApiUrl('/api/config'), {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify(data)
@@ -427,11 +507,14 @@ async function saveNetworkSettings() {
             // Handle password change
             const newPassword = document.getElementById('password').value;
             if (newPassword !== '') {
-                await fetch(getApiUrl('/api/password'), {
+                const passwordResponse = await apiFetch(getApiUrl('/api/password'), {
                     method: 'POST',
                     headers: { 'Content-Type': 'application/json' },
                     body: JSON.stringify({ password: authPassword, newPassword })
                 });
+                if (!passwordResponse.ok) {
+                    throw new Error('Failed to change password');
+                }
                 authPassword = newPassword;
             }
             
@@ -447,7 +530,7 @@ async function saveNetworkSettings() {
 
 async function reloadAddons() {
     try {
-        const response = await fetch('/api/addons/reload', {
+        const response = await apiFetch('/api/addons/reload', {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ password: authPassword })
@@ -465,6 +548,36 @@ async function reloadAddons() {
     }
 }
 
+async function installAddon(file, overwrite = false) {
+    const formData = new FormData();
+    formData.append('addon', file);
+    formData.append('password', authPassword);
+    
+    try {
+        const response = await apiFetch(`/api/addons/install${overwrite ? '?overwrite=true' : ''}`, {
+            method: 'POST',
+            body: formData
+        });
+        const result = await response.json();
+        
+        if (response.status === 409 && !overwrite) {
+            if (confirm(`${result.error}\n\nReplace the installed version?`)) {
+                await installAddon(file, true);
+            }
+            return;
+        }
+        if (!response.ok) {
+            throw new Error(result.problems ? result.problems.join('\n') : result.error);
+        }
+        
+        showToast(`Addon ${result.id} installed`, 'success');
+        await loadAddonsList();
+    } catch (err) {
+        showToast(`Failed to install addon: ${err.message}`, 'error');
+        console.error('Addon install error:', err);
+    }
+}
+
 function openAddonsFolder() {
     showToast('Addons folder: [App Directory]/Addons', 'success');
 }
@@ -479,11 +592,13 @@ function updatePeerList() {
         item.className = 'peer-item';
         item.innerHTML = `
             <div class="peer-info">
-                <div class="peer-name">${peer.name}</div>
-                <div class="peer-ip">${peer.ip}:${peer.port}</div>
+                <div class="peer-name">${peerDisplayName(peer)}</div>
+                <div class="peer-ip">${peerAddress(peer)}</div>
             </div>
             <div class="peer-actions">
                 <span class="peer-status ${peer.online ? 'online' : ''}" title="${peer.online ? 'Online' : 'Offline'}"></span>
+                <button class="btn-small" onclick="renamePeer('${peer.id}')">Rename</button>
+                <button class="btn-small" onclick="setPeerPassword('${peer.id}')">Password</button>
                 ${peer.manual ? `<button class="btn-danger btn-small" onclick="deletePeer('${peer.id}')">Delete</button>` : ''}
             </div>
         `;
@@ -502,7 +617,7 @@ async function addDevice() {
     }
     
     try {
-        const response = await fetch('/api/peers/add', {
+        const response = await apiFetch('/api/peers/add', {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ ip, port: parseInt(port), name, password: authPassword })
@@ -522,11 +637,59 @@ async function addDevice() {
     }
 }
 
+async function renamePeer(peerId) {
+    const peer = peers.find(p => p.id === peerId);
+    if (!peer) return;
+    
+    const alias = prompt(`Label for ${peer.name} (leave empty to use its own name):`, peer.alias || '');
+    if (alias === null) return;
+    
+    try {
+        const response = await apiFetch(`/api/peers/${encodeURIComponent(peerId)}`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ alias, order: peer.order ?? null })
+        });
+        
+        if (response.ok) {
+            await loadPeers();
+        } else {
+            throw new Error('Failed to rename device');
+        }
+    } catch (err) {
+        showToast('Failed to rename device', 'error');
+    }
+}
+
+async function setPeerPassword(peerId) {
+    const peer = peers.find(p => p.id === peerId);
+    if (!peer) return;
+    
+    const password = prompt(`Password for syncing to ${peerDisplayName(peer)} (leave empty if it has none):`, '');
+    if (password === null) return;
+    
+    try {
+        const response = await apiFetch(`/api/peers/${encodeURIComponent(peerId)}/password`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ password })
+        });
+        
+        if (response.ok) {
+            showToast('Device password saved', 'success');
+        } else {
+            throw new Error('Failed to save device password');
+        }
+    } catch (err) {
+        showToast('Failed to save device password', 'error');
+    }
+}
+
 async function deletePeer(peerId) {
     if (!confirm('Are you sure you want to remove this device?')) return;
     
     try {
-        const response = await fetch(`/api/peers/${encodeURIComponent(peerId)}`, {
+        const response = await apiFetch(`/api/peers/${encodeURIComponent(peerId)}`, {
             method: 'DELETE',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ password: authPassword })
@@ -567,6 +730,9 @@ async function uploadUpdate() {
     
     showToast('Uploading update... Please wait', 'success');
     
+    // Devices report the checksum of what they received; only matching uploads get installed
+    const localHash = await sha256Hex(file);
+    
     // Upload to each selected device
     for (const deviceId of selectedDevices) {
         let uploadUrl = '/api/update';
@@ -577,7 +743,7 @@ async function uploadUpdate() {
                 failCount++;
                 continue;
             }
-            uploadUrl = `http://${peer.ip}:${peer.port}/api/update`;
+            uploadUrl = `http://${peerAddress(peer)}/api/update`;
         }
         
         const formData = new FormData();
@@ -590,7 +756,7 @@ async function uploadUpdate() {
             const controller = new AbortController();
             const timeoutId = setTimeout(() => controller.abort(), 30000);
             
-            const response = await fetch(uploadUrl, {
+            const response = await apiFetch(uploadUrl, {
                 method: 'POST',
                 body: formData,
                 signal: controller.signal
@@ -598,7 +764,29 @@ async function uploadUpdate() {
             
             clearTimeout(timeoutId);
             
-            if (response.ok) {
+            if (!response.ok) {
+                failCount++;
+                continue;
+            }
+            
+            const { staged } = await response.json();
+            const matches = localHash
+                ? staged.sha256 === localHash
+                : confirm(`${getDeviceName(deviceId)} received an update with SHA-256 ${staged.sha256}. Install it?`);
+            
+            if (!matches) {
+                await apiFetch(uploadUrl, { method: 'DELETE' });
+                failCount++;
+                continue;
+            }
+            
+            const confirmResponse = await apiFetch(`${uploadUrl}/confirm`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ sha256: staged.sha256, password: authPassword })
+            });
+            
+            if (confirmResponse.ok) {
                 successCount++;
             } else {
                 failCount++;
@@ -621,6 +809,18 @@ async function uploadUpdate() {
     }
 }
 
+// Hex SHA-256 of a file, or null where the browser only allows hashing on secure origins
+async function sha256Hex(file) {
+    if (!window.crypto || !window.crypto.subtle) {
+        return null;
+    }
+    
+    const digest = await window.crypto.subtle.digest('SHA-256', await file.arrayBuffer());
+    return Array.from(new Uint8Array(digest))
+        .map(b => b.toString(16).padStart(2, '0'))
+        .join('');
+}
+
 // Generic save to devices function
 async function saveToDevices(endpoint, data, settingName) {
     let successCount = 0;
@@ -629,7 +829,7 @@ async function saveToDevices(endpoint, data, settingName) {
     // If editing a specific device, only save to that device
     if (editingDeviceId) {
         try {
-            const response = await fetch(getApiUrl(endpoint), {
+            const response = await apiFetch(getApiUrl(endpoint), {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify(data)
@@ -663,11 +863,11 @@ async function saveToDevices(endpoint, data, settingName) {
                 failCount++;
                 continue;
             }
-            apiUrl = `http://${peer.ip}:${peer.port}${endpoint}`;
+            apiUrl = `http://${peerAddress(peer)}${endpoint}`;
         }
         
         try {
-            const response = await fetch(apiUrl, {
+            const response = await apiFetch(apiUrl, {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify(data)
@@ -702,7 +902,7 @@ async function saveToDevices(endpoint, data, settingName) {
 // Media functions (called from media tab)
 async function loadMediaList() {
     try {
-        const response = await fetch(getApiUrl('/api/media'));
+        const response = await apiFetch(getApiUrl('/api/media'));
         const files = await response.json();
         
         const mediaList = document.getElementById('media-list');
@@ -717,6 +917,7 @@ async function loadMediaList() {
                     ${file.type === 'video' ? '🎥' : '🖼️'} 
                     ${formatFileSize(file.size)}
                 </div>
+                <button class="btn-small" onclick="renameMedia('${file.name}')">Rename</button>
                 <button class="delete-btn" onclick="deleteMedia('${file.name}')">Delete</button>
             `;
             mediaList.appendChild(item);
@@ -745,7 +946,7 @@ async function handleFiles(files) {
                 failCount++;
                 continue;
             }
-            uploadUrl = `http://${peer.ip}:${peer.port}/api/media/upload`;
+            uploadUrl = `http://${peerAddress(peer)}/api/media/upload`;
         }
         
         // Create FormData for each request
@@ -757,7 +958,7 @@ async function handleFiles(files) {
         deviceFormData.append('password', authPassword);
         
         try {
-            const response = await fetch(uploadUrl, {
+            const response = await apiFetch(uploadUrl, {
                 method: 'POST',
                 body: deviceFormData
             });
@@ -791,7 +992,7 @@ async function deleteMedia(filename) {
     if (!confirm(`Are you sure you want to delete ${filename}?`)) return;
     
     try {
-        const response = await fetch(getApiUrl(`/api/media/${encodeURIComponent(filename)}`), {
+        const response = await apiFetch(getApiUrl(`/api/media/${encodeURIComponent(filename)}`), {
             method: 'DELETE',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ password: authPassword })
@@ -808,6 +1009,29 @@ async function deleteMedia(filename) {
     }
 }
 
+async function renameMedia(filename) {
+    const newName = prompt('New file name:', filename);
+    if (!newName || newName === filename) return;
+    
+    try {
+        const response = await apiFetch(getApiUrl(`/api/media/${encodeURIComponent(filename)}/rename`), {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ newName })
+        });
+        
+        if (response.ok) {
+            showToast('File renamed successfully', 'success');
+            await loadMediaList();
+        } else {
+            const body = await response.json().catch(() => ({}));
+            throw new Error(body.error || 'Failed to rename file');
+        }
+    } catch (err) {
+        showToast(err.message, 'error');
+    }
+}
+
 // Addon functions (called from addons tab)
 function updateAddonsList() {
     const addonsList = document.getElementById('addons-list');
@@ -843,6 +1067,7 @@ function updateAddonsList() {
     }
     
     addonsList.innerHTML = html;
+    Object.keys(addons).forEach(updateSettingVisibility);
     setupAddonEventListeners();
 }
 
@@ -853,7 +1078,7 @@ function createAddonElement(id, addon) {
     let settingsHtml = '';
     if (addon.settings && addon.settings.length > 0) {
         settingsHtml = `
-            <div class="addon-settings" id="settings-${id}">
+            <div class="addon-settings" id="settings-${id}" onchange="updateSettingVisibility('${id}')" oninput="updateSettingVisibility('${id}')">
                 <h4>Settings</h4>
                 ${addon.settings.map(setting => createSettingElement(id, setting, addon.config[setting.id])).join('')}
                 <button class="btn-success" onclick="saveAddonSettings('${id}')">Save Settings</button>
@@ -872,6 +1097,7 @@ function createAddonElement(id, addon) {
                         ${addon.info.category ? `• ${addon.info.category}` : ''}
                     </div>
                     ${addon.info.description ? `<div class="addon-description">${addon.info.description}</div>` : ''}
+                    ${addon.lastError ? `<div class="addon-error">${addon.lastError.replace(/</g, '&lt;')}</div>` : ''}
                 </div>
                 <div class="addon-controls">
                     <span class="addon-status ${statusClass}">${statusText}</span>
@@ -885,6 +1111,7 @@ function createAddonElement(id, addon) {
                     ${addon.settings && addon.settings.length > 0 ? 
                         `<button class="btn-small" onclick="toggleAddonSettings('${id}')">Settings</button>` : 
                         ''}
+                    <button class="btn-small btn-danger" onclick="uninstallAddon('${id}')">Uninstall</button>
                 </div>
             </div>
             ${settingsHtml}
@@ -954,7 +1181,7 @@ function createSettingElement(addonId, setting, value) {
     }
     
     return `
-        <div class="addon-setting">
+        <div class="addon-setting" data-setting-id="${setting.id}">
             <label for="${addonId}-${setting.id}">${setting.name}</label>
             ${inputHtml}
             ${setting.description ? `<small>${setting.description}</small>` : ''}
@@ -962,6 +1189,44 @@ function createSettingElement(addonId, setting, value) {
     `;
 }
 
+// Hide settings whose depends_on setting doesn't currently hold the value they need
+function updateSettingVisibility(addonId) {
+    const addon = addons[addonId];
+    if (!addon || !addon.settings) return;
+    
+    // Settings listed after the one they depend on also hide when it is hidden
+    const hidden = new Set();
+    for (const setting of addon.settings) {
+        if (!setting.depends_on) continue;
+        
+        const row = document.querySelector(`#settings-${addonId} .addon-setting[data-setting-id="${setting.id}"]`);
+        const source = addon.settings.find(s => s.id === setting.depends_on && s.id !== setting.id);
+        const element = document.getElementById(`${addonId}-${setting.depends_on}`);
+        if (!row || !source || !element) continue;
+        
+        const value = readSettingValue(source, element);
+        const wanted = setting.depends_value;
+        const matches = wanted === undefined || wanted === null
+            ? Boolean(value)
+            : value === wanted || String(value) === String(wanted);
+        const visible = matches && !hidden.has(source.id);
+        
+        row.style.display = visible ? '' : 'none';
+        if (!visible) hidden.add(setting.id);
+    }
+}
+
+function readSettingValue(setting, element) {
+    switch (setting.type) {
+        case 'boolean':
+            return element.checked;
+        case 'range':
+            return parseInt(element.value);
+        default:
+            return element.value;
+    }
+}
+
 function toggleAddonSettings(addonId) {
     const settings = document.getElementById(`settings-${addonId}`);
     if (settings) {
@@ -969,6 +1234,32 @@ function toggleAddonSettings(addonId) {
     }
 }
 
+async function uninstallAddon(addonId) {
+    const name = addons[addonId] ? addons[addonId].info.name : addonId;
+    if (!confirm(`Uninstall ${name}? Its folder and saved settings will be deleted.`)) {
+        return;
+    }
+    
+    try {
+        const response = await apiFetch(`/api/addons/${encodeURIComponent(addonId)}`, {
+            method: 'DELETE',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ password: authPassword })
+        });
+        
+        if (!response.ok) {
+            const result = await response.json();
+            throw new Error(result.error);
+        }
+        
+        showToast(`${name} uninstalled`, 'success');
+        await loadAddonsList();
+    } catch (err) {
+        showToast(`Failed to uninstall addon: ${err.message}`, 'error');
+        console.error('Addon uninstall error:', err);
+    }
+}
+
 function updateRangeDisplay(elementId, value, unit) {
     const display = document.getElementById(`${elementId}-display`);
     if (display) {
@@ -987,16 +1278,7 @@ async function saveAddonSettings(addonId) {
         const element = document.getElementById(`${addonId}-${setting.id}`);
         if (!element) continue;
         
-        switch (setting.type) {
-            case 'boolean':
-                config[setting.id] = element.checked;
-                break;
-            case 'range':
-                config[setting.id] = parseInt(element.value);
-                break;
-            default:
-                config[setting.id] = element.value;
-        }
+        config[setting.id] = readSettingValue(setting, element);
     }
     
     await saveToDevices(`/api/addons/${addonId}/config`, { ...config, password: authPassword }, 'Addon settings');
@@ -1015,8 +1297,7 @@ function setupAddonEventListeners() {
             const enabled = e.target.checked;
             
             try {
-                const config = { enabled: enabled };
-                await saveToDevices(`/api/addons/${addonId}/config`, { ...config, password: authPassword }, 'Addon toggle');
+                await saveToDevices(`/api/addons/${addonId}/enabled`, { enabled, password: authPassword }, 'Addon toggle');
                 
                 // Update local state
                 if (addons[addonId]) {